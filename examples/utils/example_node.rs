@@ -106,9 +106,8 @@ impl ExampleNode {
             Request::Put(data, id) => {
                 self.handle_put_request(data, id, src, dst);
             }
-            Request::Post(..) => {
-                warn!("{:?} ExampleNode: Post unimplemented.",
-                      self.get_debug_name());
+            Request::Post(data, id) => {
+                self.handle_post_request(data, id, src, dst);
             }
             Request::Delete(..) => {
                 warn!("{:?} ExampleNode: Delete unimplemented.",
@@ -125,6 +124,33 @@ impl ExampleNode {
                 warn!("{:?} ExampleNode: Append unimplemented.",
                       self.get_debug_name());
             }
+            Request::GetBatch(..) |
+            Request::PutBatch(..) => {
+                warn!("{:?} ExampleNode: Batches unimplemented.",
+                      self.get_debug_name());
+            }
+            Request::MutateEntries(..) |
+            Request::SetUserPermissions(..) => {
+                warn!("{:?} ExampleNode: MutableData unimplemented.",
+                      self.get_debug_name());
+            }
+            Request::GetWithProof(..) => {
+                warn!("{:?} ExampleNode: GetWithProof unimplemented.",
+                      self.get_debug_name());
+            }
+            Request::GetAppendedData(..) => {
+                warn!("{:?} ExampleNode: GetAppendedData unimplemented.",
+                      self.get_debug_name());
+            }
+            Request::ConditionalPost(..) |
+            Request::ConditionalDelete(..) => {
+                warn!("{:?} ExampleNode: Conditional mutations unimplemented.",
+                      self.get_debug_name());
+            }
+            Request::Messaging(..) => {
+                warn!("{:?} ExampleNode: Messaging unimplemented.",
+                      self.get_debug_name());
+            }
         }
     }
 
@@ -197,6 +223,37 @@ impl ExampleNode {
         }
     }
 
+    fn handle_post_request(&mut self,
+                           data: Data,
+                           id: MessageId,
+                           src: Authority<XorName>,
+                           dst: Authority<XorName>) {
+        let data_id = data.identifier();
+        let result = match self.db.get(&data_id) {
+            Some(current) => {
+                self.node.validate_successor(current, &data).map_err(|error| format!("{:?}", error))
+            }
+            None => Err("Data not found".to_owned()),
+        };
+        match result {
+            Ok(()) => {
+                trace!("{:?} Updating : key {:?}, value {:?}",
+                       self.get_debug_name(),
+                       data.name(),
+                       data);
+                let _ = self.node.send_post_success(dst, src, data_id, id);
+                let _ = self.db.insert(data_id, data);
+            }
+            Err(error) => {
+                trace!("{:?} PostDataRequest failed for {:?}: {}",
+                       self.get_debug_name(),
+                       data_id.name(),
+                       error);
+                let _ = self.node.send_post_failure(dst, src, data_id, error.into_bytes(), id);
+            }
+        }
+    }
+
     fn handle_node_added(&mut self, name: XorName) {
         self.send_refresh(MessageId::from_added_node(name));
     }
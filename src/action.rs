@@ -15,11 +15,15 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
+use data::{DataIdentifier, MerkleHash};
 use error::InterfaceError;
+use id::PublicId;
 use messages::{Request, UserMessage};
 use routing_table::Authority;
 use std::fmt::{self, Debug, Formatter};
 use std::sync::mpsc::Sender;
+use types::MessageId;
+use utils;
 use xor_name::XorName;
 
 /// An Action initiates a message flow < A | B > where we are (a part of) A.
@@ -35,12 +39,36 @@ pub enum Action {
         dst: Authority<XorName>,
         content: UserMessage,
         priority: u8,
+        /// If set, the message is encrypted so that only this recipient can read it.
+        recipient: Option<PublicId>,
         result_tx: Sender<Result<(), InterfaceError>>,
     },
     ClientSendRequest {
         content: Request,
         dst: Authority<XorName>,
         priority: u8,
+        /// If set, the request is encrypted so that only this recipient can read it.
+        recipient: Option<PublicId>,
+        result_tx: Sender<Result<(), InterfaceError>>,
+    },
+    NodeSendBroadcast {
+        content: Vec<u8>,
+        result_tx: Sender<Result<(), InterfaceError>>,
+    },
+    NodeSendStream {
+        dst: XorName,
+        stream_id: MessageId,
+        content: Vec<u8>,
+        result_tx: Sender<Result<(), InterfaceError>>,
+    },
+    NodeSignDataRoot {
+        version: u64,
+        root: MerkleHash,
+        result_tx: Sender<Result<(), InterfaceError>>,
+    },
+    NodeSetExpiry {
+        data_id: DataIdentifier,
+        expiry: Option<u64>,
         result_tx: Sender<Result<(), InterfaceError>>,
     },
     Name { result_tx: Sender<XorName> },
@@ -62,6 +90,30 @@ impl Debug for Action {
                        content,
                        dst)
             }
+            Action::NodeSendBroadcast { ref content, .. } => {
+                write!(formatter,
+                       "Action::NodeSendBroadcast {{ {} bytes, result_tx }}",
+                       content.len())
+            }
+            Action::NodeSendStream { ref dst, ref stream_id, ref content, .. } => {
+                write!(formatter,
+                       "Action::NodeSendStream {{ {:?}, {:?}, {} bytes, result_tx }}",
+                       dst,
+                       stream_id,
+                       content.len())
+            }
+            Action::NodeSignDataRoot { version, ref root, .. } => {
+                write!(formatter,
+                       "Action::NodeSignDataRoot {{ {}, {}, result_tx }}",
+                       version,
+                       utils::format_binary_array(root))
+            }
+            Action::NodeSetExpiry { ref data_id, ref expiry, .. } => {
+                write!(formatter,
+                       "Action::NodeSetExpiry {{ {:?}, {:?}, result_tx }}",
+                       data_id,
+                       expiry)
+            }
             Action::Name { .. } => write!(formatter, "Action::Name"),
             Action::Timeout(token) => write!(formatter, "Action::Timeout({})", token),
             Action::Terminate => write!(formatter, "Action::Terminate"),
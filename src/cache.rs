@@ -15,7 +15,12 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
+use data::{Data, DataIdentifier};
+use maidsafe_utilities::serialisation::serialised_size;
 use messages::{Request, Response};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
 
 /// A cache that stores `Response`s keyed by `Requests`. Should be implemented
 /// by layers above routing.
@@ -23,8 +28,27 @@ pub trait Cache: Send {
     /// Retrieve cached response for the given request.
     fn get(&self, request: &Request) -> Option<Response>;
 
-    /// Cache the given response.
-    fn put(&self, response: Response);
+    /// Cache the given response. Returns `true` if it has been stored.
+    fn put(&self, response: Response) -> bool;
+
+    /// Drop any cached response containing the given data, because it has been modified.
+    fn invalidate(&self, _data_id: &DataIdentifier) {}
+
+    /// Whether `Get` requests and `GetSuccess` responses for structured data should be passed to
+    /// this cache, too, even though they are not
+    /// [cacheable](../enum.Response.html#method.is_cacheable).
+    ///
+    /// Cached structured data can become stale, so a cache opting in should only serve it for a
+    /// limited time, and drop it when it is [invalidated](#method.invalidate).
+    fn caches_structured_data(&self) -> bool {
+        false
+    }
+
+    /// Whether this cache stores anything at all. If not, relayed messages aren't passed to it,
+    /// and its misses aren't counted in the node's statistics.
+    fn is_enabled(&self) -> bool {
+        true
+    }
 }
 
 /// A no-op implementation of the `Cache` trait. Throws everything away on put
@@ -35,5 +59,338 @@ impl Cache for NullCache {
     fn get(&self, _: &Request) -> Option<Response> {
         None
     }
-    fn put(&self, _: Response) {}
+    fn put(&self, _: Response) -> bool {
+        false
+    }
+
+    fn is_enabled(&self) -> bool {
+        false
+    }
+}
+
+/// Counters of the operations performed by a cache.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// The number of requests answered from the cache.
+    pub hits: u64,
+    /// The number of cacheable requests not found in the cache.
+    pub misses: u64,
+    /// The number of entries removed to stay within the byte budget.
+    pub evictions: u64,
+    /// The number of entries removed because they were older than the time to live.
+    pub expirations: u64,
+    /// The number of entries removed because the data was modified.
+    pub invalidations: u64,
+}
+
+struct Entry {
+    data: Data,
+    size: usize,
+    inserted: Instant,
+    /// The value of `Inner::clock` when the entry was last used.
+    last_used: u64,
+}
+
+struct Inner {
+    entries: HashMap<DataIdentifier, Entry>,
+    /// The cached identifiers, least recently used first.
+    lru: BTreeMap<u64, DataIdentifier>,
+    clock: u64,
+    size: usize,
+    stats: CacheStats,
+}
+
+impl Inner {
+    fn touch(&mut self, data_id: &DataIdentifier) {
+        self.clock += 1;
+        if let Some(entry) = self.entries.get_mut(data_id) {
+            let _ = self.lru.remove(&entry.last_used);
+            entry.last_used = self.clock;
+            let _ = self.lru.insert(self.clock, *data_id);
+        }
+    }
+
+    fn remove(&mut self, data_id: &DataIdentifier) -> Option<Entry> {
+        let entry = self.entries.remove(data_id);
+        if let Some(ref entry) = entry {
+            let _ = self.lru.remove(&entry.last_used);
+            self.size -= entry.size;
+        }
+        entry
+    }
+
+    fn remove_lru(&mut self) -> bool {
+        let data_id = match self.lru.values().next() {
+            Some(data_id) => *data_id,
+            None => return false,
+        };
+        self.remove(&data_id).is_some()
+    }
+}
+
+/// A bounded, thread-safe `Cache` of `GetSuccess` responses, keyed by the identifier of the
+/// requested data.
+///
+/// The serialised size of the cached data is limited to a byte budget, with the least recently
+/// used entries evicted first, and entries are dropped once they are older than the time to live.
+///
+/// Structured data is only cached if [enabled](#method.with_structured_data).
+pub struct MemoryCache {
+    inner: Mutex<Inner>,
+    max_bytes: usize,
+    ttl: Duration,
+    /// The maximum age of cached structured data, if it is cached at all.
+    structured_max_age: Option<Duration>,
+}
+
+impl MemoryCache {
+    /// Creates a cache holding at most `max_bytes` of serialised data, each entry for at most
+    /// `ttl`.
+    pub fn new(max_bytes: usize, ttl: Duration) -> MemoryCache {
+        MemoryCache {
+            inner: Mutex::new(Inner {
+                entries: HashMap::new(),
+                lru: BTreeMap::new(),
+                clock: 0,
+                size: 0,
+                stats: Default::default(),
+            }),
+            max_bytes: max_bytes,
+            ttl: ttl,
+            structured_max_age: None,
+        }
+    }
+
+    /// Enables caching structured data, serving each version for at most `max_age` after it was
+    /// cached.  A cached version is never replaced by an older one.
+    pub fn with_structured_data(mut self, max_age: Duration) -> MemoryCache {
+        self.structured_max_age = Some(max_age);
+        self
+    }
+
+    /// Returns the counters of the operations performed so far.
+    pub fn stats(&self) -> CacheStats {
+        self.lock().stats
+    }
+
+    /// Returns the number of cached entries.
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    /// Returns `true` if nothing is cached.
+    pub fn is_empty(&self) -> bool {
+        self.lock().entries.is_empty()
+    }
+
+    /// Returns the total serialised size of the cached data.
+    pub fn size_in_bytes(&self) -> usize {
+        self.lock().size
+    }
+
+    fn lock(&self) -> MutexGuard<Inner> {
+        self.inner.lock().expect("Failed to lock.")
+    }
+
+    fn is_expired(&self, entry: &Entry) -> bool {
+        let age = entry.inserted.elapsed();
+        match (&entry.data, self.structured_max_age) {
+            (&Data::Structured(..), Some(max_age)) if age > max_age => true,
+            _ => age > self.ttl,
+        }
+    }
+}
+
+impl Cache for MemoryCache {
+    fn get(&self, request: &Request) -> Option<Response> {
+        let (data_id, message_id) = match *request {
+            Request::Get(data_id, message_id) => (data_id, message_id),
+            _ => return None,
+        };
+        let mut inner = self.lock();
+        let expired = inner.entries.get(&data_id).map(|entry| self.is_expired(entry));
+        match expired {
+            Some(false) => (),
+            Some(true) => {
+                let _ = inner.remove(&data_id);
+                inner.stats.expirations += 1;
+                inner.stats.misses += 1;
+                return None;
+            }
+            None => {
+                inner.stats.misses += 1;
+                return None;
+            }
+        }
+        inner.touch(&data_id);
+        inner.stats.hits += 1;
+        inner.entries
+            .get(&data_id)
+            .map(|entry| Response::GetSuccess(entry.data.clone(), message_id))
+    }
+
+    fn put(&self, response: Response) -> bool {
+        let data = match response {
+            Response::GetSuccess(data, _) => data,
+            _ => return false,
+        };
+        if let Data::Structured(..) = data {
+            if self.structured_max_age.is_none() {
+                return false;
+            }
+        }
+        let size = serialised_size(&data) as usize;
+        if size > self.max_bytes {
+            return false;
+        }
+        let data_id = data.identifier();
+        let mut inner = self.lock();
+        let is_older = inner.entries
+            .get(&data_id)
+            .map_or(false, |entry| data.version() < entry.data.version());
+        if is_older {
+            return false;
+        }
+        let _ = inner.remove(&data_id);
+        while inner.size + size > self.max_bytes && inner.remove_lru() {
+            inner.stats.evictions += 1;
+        }
+        inner.size += size;
+        let _ = inner.entries.insert(data_id,
+                                     Entry {
+                                         data: data,
+                                         size: size,
+                                         inserted: Instant::now(),
+                                         last_used: 0,
+                                     });
+        inner.touch(&data_id);
+        true
+    }
+
+    fn invalidate(&self, data_id: &DataIdentifier) {
+        let mut inner = self.lock();
+        if inner.remove(data_id).is_some() {
+            inner.stats.invalidations += 1;
+        }
+    }
+
+    fn caches_structured_data(&self) -> bool {
+        self.structured_max_age.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data::{Data, ImmutableData, StructuredData};
+    use maidsafe_utilities::serialisation::serialised_size;
+    use messages::{Request, Response};
+    use rand;
+    use std::collections::BTreeSet;
+    use std::thread;
+    use std::time::Duration;
+    use super::*;
+    use types::MessageId;
+
+    fn get(cache: &MemoryCache, data: &Data) -> Option<Data> {
+        match cache.get(&Request::Get(data.identifier(), MessageId::new())) {
+            Some(Response::GetSuccess(data, _)) => Some(data),
+            Some(response) => panic!("Unexpected response {:?}", response),
+            None => None,
+        }
+    }
+
+    #[test]
+    fn byte_budget() {
+        let data: Vec<_> = (0..3u8)
+            .map(|i| Data::Immutable(ImmutableData::new(vec![i; 100])))
+            .collect();
+        let size = serialised_size(&data[0]) as usize;
+        let cache = MemoryCache::new(2 * size, Duration::from_secs(60));
+
+        cache.put(Response::GetSuccess(data[0].clone(), MessageId::new()));
+        cache.put(Response::GetSuccess(data[1].clone(), MessageId::new()));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.size_in_bytes(), 2 * size);
+
+        // Using the first entry makes the second one the least recently used.
+        assert_eq!(get(&cache, &data[0]), Some(data[0].clone()));
+        cache.put(Response::GetSuccess(data[2].clone(), MessageId::new()));
+        assert_eq!(get(&cache, &data[1]), None);
+        assert_eq!(get(&cache, &data[0]), Some(data[0].clone()));
+        assert_eq!(get(&cache, &data[2]), Some(data[2].clone()));
+        assert_eq!(cache.size_in_bytes(), 2 * size);
+
+        // Data larger than the budget isn't cached.
+        let large = Data::Immutable(ImmutableData::new(vec![0; 300]));
+        assert!(!cache.put(Response::GetSuccess(large.clone(), MessageId::new())));
+        assert_eq!(get(&cache, &large), None);
+
+        assert_eq!(cache.stats(),
+                   CacheStats {
+                       hits: 3,
+                       misses: 2,
+                       evictions: 1,
+                       expirations: 0,
+                       invalidations: 0,
+                   });
+    }
+
+    #[test]
+    fn time_to_live() {
+        let data = Data::Immutable(ImmutableData::new(vec![1, 2, 3]));
+        let cache = MemoryCache::new(1000, Duration::from_millis(100));
+        cache.put(Response::GetSuccess(data.clone(), MessageId::new()));
+        assert_eq!(get(&cache, &data), Some(data.clone()));
+
+        thread::sleep(Duration::from_millis(150));
+        assert_eq!(get(&cache, &data), None);
+        assert!(cache.is_empty());
+        assert_eq!(cache.size_in_bytes(), 0);
+        assert_eq!(cache.stats().expirations, 1);
+    }
+
+    #[test]
+    fn invalidation() {
+        let data = Data::Immutable(ImmutableData::new(vec![1, 2, 3]));
+        let cache = MemoryCache::new(1000, Duration::from_secs(60));
+        cache.put(Response::GetSuccess(data.clone(), MessageId::new()));
+        cache.invalidate(&data.identifier());
+        assert_eq!(get(&cache, &data), None);
+        assert!(cache.is_empty());
+        assert_eq!(cache.stats().invalidations, 1);
+    }
+
+    #[test]
+    fn structured_data() {
+        let name = rand::random();
+        let sd = |version, value| {
+            let sd = unwrap!(StructuredData::new(0, name, version, vec![value], BTreeSet::new()));
+            Data::Structured(sd)
+        };
+
+        // Structured data isn't cached unless enabled.
+        let cache = MemoryCache::new(1000, Duration::from_secs(60));
+        assert!(!cache.caches_structured_data());
+        cache.put(Response::GetSuccess(sd(0, 0), MessageId::new()));
+        assert!(cache.is_empty());
+
+        let cache = MemoryCache::new(1000, Duration::from_secs(60))
+            .with_structured_data(Duration::from_millis(100));
+        assert!(cache.caches_structured_data());
+        cache.put(Response::GetSuccess(sd(1, 1), MessageId::new()));
+        assert_eq!(get(&cache, &sd(1, 1)), Some(sd(1, 1)));
+
+        // An older version doesn't replace a newer one.
+        cache.put(Response::GetSuccess(sd(0, 0), MessageId::new()));
+        assert_eq!(get(&cache, &sd(1, 1)), Some(sd(1, 1)));
+        cache.put(Response::GetSuccess(sd(2, 2), MessageId::new()));
+        assert_eq!(get(&cache, &sd(2, 2)), Some(sd(2, 2)));
+
+        // Structured data is served only up to its maximum age, immutable data up to the TTL.
+        let immutable = Data::Immutable(ImmutableData::new(vec![1, 2, 3]));
+        cache.put(Response::GetSuccess(immutable.clone(), MessageId::new()));
+        thread::sleep(Duration::from_millis(150));
+        assert_eq!(get(&cache, &sd(2, 2)), None);
+        assert_eq!(get(&cache, &immutable), Some(immutable));
+    }
 }
@@ -17,21 +17,31 @@
 
 use action::Action;
 use cache::NullCache;
-use data::{AppendWrapper, Data, DataIdentifier};
+use compression::Compression;
+use data::{AppendWrapper, AppendedRange, Condition, Data, DataIdentifier, DataValidators,
+           EntryAction, ExpiryTracker, ImmutableData, MAX_MUTABLE_DATA_SIZE_IN_BYTES,
+           PermissionSet};
+use data::chunked::{self, DataMap, FetchStep, FileFetch};
 use error::{InterfaceError, RoutingError};
 use event::Event;
 use evented::{Evented, ToEvented};
-use id::FullId;
+use id::{FullId, PublicId};
+use maidsafe_utilities::serialisation;
 #[cfg(not(feature = "use-mock-crust"))]
 use maidsafe_utilities::thread::{self, Joiner};
-use messages::{CLIENT_GET_PRIORITY, DEFAULT_PRIORITY, Request};
+use messages::{CLIENT_GET_PRIORITY, DEFAULT_PRIORITY, MAX_BATCH_LEN, MAX_BATCH_SIZE_IN_BYTES,
+               Request};
+use messaging::MpidMessageWrapper;
 use routing_table::Authority;
 #[cfg(not(feature = "use-mock-crust"))]
 use rust_sodium;
+use rust_sodium::crypto::sign;
+use rustc_serialize::Encodable;
 use state_machine::{State, StateMachine};
 use states;
 #[cfg(feature = "use-mock-crust")]
 use std::cell::RefCell;
+use std::collections::BTreeMap;
 #[cfg(feature = "use-mock-crust")]
 use std::collections::VecDeque;
 use std::sync::mpsc::{Receiver, Sender, channel};
@@ -39,6 +49,7 @@ use std::sync::mpsc::{Receiver, Sender, channel};
 use std::sync::mpsc::TryRecvError;
 use types::MessageId;
 use types::RoutingActionSender;
+use utils;
 use xor_name::XorName;
 
 /// Interface for sending and receiving messages to and from a network of nodes in the role of a
@@ -118,7 +129,16 @@ impl Client {
         let full_id = keys.unwrap_or_else(FullId::new);
 
         StateMachine::new(move |crust_service, timer| {
-            states::Bootstrapping::new(cache, true, crust_service, full_id, min_section_size, timer)
+            states::Bootstrapping::new(cache,
+                                       true,
+                                       Compression::None,
+                                       None,
+                                       DataValidators::new(),
+                                       ExpiryTracker::new(),
+                                       crust_service,
+                                       full_id,
+                                       min_section_size,
+                                       timer)
                 .map_or(State::Terminated, State::Bootstrapping)
                 .to_evented()
         })
@@ -130,7 +150,23 @@ impl Client {
                             data_id: DataIdentifier,
                             message_id: MessageId)
                             -> Result<(), InterfaceError> {
-        self.send_action(Request::Get(data_id, message_id), dst, CLIENT_GET_PRIORITY)
+        self.send_action(Request::Get(data_id, message_id), dst, CLIENT_GET_PRIORITY, None)
+    }
+
+    /// Send a `GetWithProof` message with a `DataIdentifier` to an `Authority`.
+    ///
+    /// The response includes a `DataProof`, whose `verify` method checks that the data is held by
+    /// the section responsible for it, given a `SectionKey` of that section that the client
+    /// trusts, independently of the proxy node.
+    pub fn send_get_with_proof_request(&self,
+                                       dst: Authority<XorName>,
+                                       data_id: DataIdentifier,
+                                       message_id: MessageId)
+                                       -> Result<(), InterfaceError> {
+        self.send_action(Request::GetWithProof(data_id, message_id),
+                         dst,
+                         CLIENT_GET_PRIORITY,
+                         None)
     }
 
     /// Add something to the network
@@ -139,16 +175,22 @@ impl Client {
                             data: Data,
                             message_id: MessageId)
                             -> Result<(), InterfaceError> {
-        self.send_action(Request::Put(data, message_id), dst, DEFAULT_PRIORITY)
+        self.send_action(Request::Put(data, message_id), dst, DEFAULT_PRIORITY, None)
     }
 
     /// Change something already on the network
+    ///
+    /// The retained previous versions of `StructuredData` are not sent: the update only needs the
+    /// new version.
     pub fn send_post_request(&self,
                              dst: Authority<XorName>,
                              data: Data,
                              message_id: MessageId)
                              -> Result<(), InterfaceError> {
-        self.send_action(Request::Post(data, message_id), dst, DEFAULT_PRIORITY)
+        self.send_action(Request::Post(data.without_history(), message_id),
+                         dst,
+                         DEFAULT_PRIORITY,
+                         None)
     }
 
     /// Remove something from the network
@@ -157,7 +199,42 @@ impl Client {
                                data: Data,
                                message_id: MessageId)
                                -> Result<(), InterfaceError> {
-        self.send_action(Request::Delete(data, message_id), dst, DEFAULT_PRIORITY)
+        self.send_action(Request::Delete(data.without_history(), message_id),
+                         dst,
+                         DEFAULT_PRIORITY,
+                         None)
+    }
+
+    /// Change something already on the network, but only if it currently satisfies `condition`.
+    ///
+    /// If it doesn't, the response is a `ConditionNotMet` with its current version.
+    pub fn send_conditional_post_request(&self,
+                                         dst: Authority<XorName>,
+                                         data: Data,
+                                         condition: Condition,
+                                         message_id: MessageId)
+                                         -> Result<(), InterfaceError> {
+        let request = Request::ConditionalPost(data.without_history(), condition, message_id);
+        self.send_action(request,
+                         dst,
+                         DEFAULT_PRIORITY,
+                         None)
+    }
+
+    /// Remove something from the network, but only if it currently satisfies `condition`.
+    ///
+    /// If it doesn't, the response is a `ConditionNotMet` with its current version.
+    pub fn send_conditional_delete_request(&self,
+                                           dst: Authority<XorName>,
+                                           data: Data,
+                                           condition: Condition,
+                                           message_id: MessageId)
+                                           -> Result<(), InterfaceError> {
+        let request = Request::ConditionalDelete(data.without_history(), condition, message_id);
+        self.send_action(request,
+                         dst,
+                         DEFAULT_PRIORITY,
+                         None)
     }
 
     /// Append an item to appendable data.
@@ -166,9 +243,138 @@ impl Client {
                                wrapper: AppendWrapper,
                                message_id: MessageId)
                                -> Result<(), InterfaceError> {
-        self.send_action(Request::Append(wrapper, message_id), dst, DEFAULT_PRIORITY)
+        self.send_action(Request::Append(wrapper, message_id), dst, DEFAULT_PRIORITY, None)
     }
 
+    /// Insert, update or delete entries of a `MutableData`.
+    ///
+    /// Fails with `PayloadTooLarge` if the actions exceed `MAX_MUTABLE_DATA_SIZE_IN_BYTES`.
+    pub fn send_mutate_entries_request(&self,
+                                       dst: Authority<XorName>,
+                                       data_id: DataIdentifier,
+                                       actions: BTreeMap<Vec<u8>, EntryAction>,
+                                       message_id: MessageId)
+                                       -> Result<(), InterfaceError> {
+        if serialisation::serialised_size(&actions) > MAX_MUTABLE_DATA_SIZE_IN_BYTES {
+            return Err(InterfaceError::PayloadTooLarge);
+        }
+        self.send_action(Request::MutateEntries(data_id, actions, message_id),
+                         dst,
+                         DEFAULT_PRIORITY,
+                         None)
+    }
+
+    /// Set the permissions of `user` for a `MutableData`, or remove them if `permissions` is
+    /// `None`. The `version` must be the data's current permissions version plus one.
+    pub fn send_set_user_permissions_request(&self,
+                                             dst: Authority<XorName>,
+                                             data_id: DataIdentifier,
+                                             user: sign::PublicKey,
+                                             permissions: Option<PermissionSet>,
+                                             version: u64,
+                                             message_id: MessageId)
+                                             -> Result<(), InterfaceError> {
+        let request =
+            Request::SetUserPermissions(data_id, user, permissions, version, message_id);
+        self.send_action(request, dst, DEFAULT_PRIORITY, None)
+    }
+
+    /// Get a range of the items of the appendable data `data_id`, in the order they were appended.
+    ///
+    /// The response is a `GetAppendedDataSuccess` with the items and the total number of items.
+    pub fn send_get_appended_data_request(&self,
+                                          dst: Authority<XorName>,
+                                          data_id: DataIdentifier,
+                                          range: AppendedRange,
+                                          message_id: MessageId)
+                                          -> Result<(), InterfaceError> {
+        self.send_action(Request::GetAppendedData(data_id, range, message_id),
+                         dst,
+                         CLIENT_GET_PRIORITY,
+                         None)
+    }
+
+    /// Send an MPID messaging operation to the `ClientManager`s of our own account, or a
+    /// `DeleteHeader` to those of a message's sender.
+    pub fn send_messaging_request(&self,
+                                  dst: Authority<XorName>,
+                                  wrapper: MpidMessageWrapper,
+                                  message_id: MessageId)
+                                  -> Result<(), InterfaceError> {
+        self.send_action(Request::Messaging(wrapper, message_id), dst, DEFAULT_PRIORITY, None)
+    }
+
+    /// Get several data items managed by `dst` with a single message.
+    ///
+    /// The response is a `GetBatchResponse` with the result for each item.
+    pub fn send_get_batch_request(&self,
+                                  dst: Authority<XorName>,
+                                  data_ids: Vec<DataIdentifier>,
+                                  message_id: MessageId)
+                                  -> Result<(), InterfaceError> {
+        self.send_action(Request::GetBatch(data_ids, message_id),
+                         dst,
+                         CLIENT_GET_PRIORITY,
+                         None)
+    }
+
+    /// Put several data items managed by `dst` with a single message.
+    ///
+    /// The response is a `PutBatchResponse` with the result for each item.
+    pub fn send_put_batch_request(&self,
+                                  dst: Authority<XorName>,
+                                  data: Vec<Data>,
+                                  message_id: MessageId)
+                                  -> Result<(), InterfaceError> {
+        self.send_action(Request::PutBatch(data, message_id), dst, DEFAULT_PRIORITY, None)
+    }
+
+    /// Get the given data items, each from its destination authority, grouping them into as few
+    /// `GetBatch` requests as possible. Returns the message ID, destination and items of each
+    /// request that was sent.
+    ///
+    /// If sending a request fails, the error is returned together with the requests that had
+    /// already been sent, whose responses will still arrive.
+    pub fn send_get_requests_batched(&self,
+                                     requests: Vec<(Authority<XorName>, DataIdentifier)>)
+                                     -> Result<Vec<(MessageId, Authority<XorName>,
+                                                    Vec<DataIdentifier>)>,
+                                               (InterfaceError,
+                                                Vec<(MessageId, Authority<XorName>,
+                                                     Vec<DataIdentifier>)>)> {
+        let mut sent = vec![];
+        for (dst, data_ids) in batch_by_authority(requests, serialised_size) {
+            let message_id = MessageId::new();
+            if let Err(error) = self.send_get_batch_request(dst, data_ids.clone(), message_id) {
+                return Err((error, sent));
+            }
+            sent.push((message_id, dst, data_ids));
+        }
+        Ok(sent)
+    }
+
+    /// Put the given data items, each to its destination authority, grouping them into as few
+    /// `PutBatch` requests as possible. Returns the message ID, destination and number of items
+    /// of each request that was sent.
+    ///
+    /// If sending a request fails, the error is returned together with the requests that had
+    /// already been sent, whose responses will still arrive.
+    pub fn send_put_requests_batched(&self,
+                                     requests: Vec<(Authority<XorName>, Data)>)
+                                     -> Result<Vec<(MessageId, Authority<XorName>, usize)>,
+                                               (InterfaceError,
+                                                Vec<(MessageId, Authority<XorName>, usize)>)> {
+        let mut sent = vec![];
+        for (dst, data) in batch_by_authority(requests, serialised_size) {
+            let message_id = MessageId::new();
+            let count = data.len();
+            if let Err(error) = self.send_put_batch_request(dst, data, message_id) {
+                return Err((error, sent));
+            }
+            sent.push((message_id, dst, count));
+        }
+        Ok(sent)
+    }
 
     /// Request account information for the Client calling this function
     pub fn send_get_account_info_request(&self,
@@ -177,7 +383,108 @@ impl Client {
                                          -> Result<(), InterfaceError> {
         self.send_action(Request::GetAccountInfo(message_id),
                          dst,
-                         CLIENT_GET_PRIORITY)
+                         CLIENT_GET_PRIORITY,
+                         None)
+    }
+
+    /// Send `request` to the single node `dst`, encrypted with the `box_` public key of
+    /// `recipient`, so that only `dst` and none of the nodes relaying it (including our proxy)
+    /// can read it.
+    ///
+    /// `dst` must be a `ManagedNode` or `Client` authority whose public ID is `recipient`.
+    pub fn send_encrypted_request(&self,
+                                  dst: Authority<XorName>,
+                                  recipient: &PublicId,
+                                  request: Request)
+                                  -> Result<(), InterfaceError> {
+        if !utils::is_single_node_authority_of(&dst, recipient) {
+            return Err(InterfaceError::InvalidRecipient);
+        }
+        let priority = request.priority();
+        self.send_action(request, dst, priority, Some(*recipient))
+    }
+
+    /// Split `payload` into `ImmutableData` chunks, optionally encrypted convergently, and put
+    /// all of them to our `ClientManager` in parallel.
+    ///
+    /// Returns the identifier of the data map chunk, from which the payload can be retrieved
+    /// again, and the IDs of the put requests. The data map chunk is put last.
+    ///
+    /// If sending a request fails, the error is returned together with the IDs of the requests
+    /// that had already been sent, whose responses will still arrive. As splitting is
+    /// deterministic, the whole payload can be put again later.
+    pub fn send_put_file(&self,
+                         payload: &[u8],
+                         encrypt: bool)
+                         -> Result<(DataIdentifier, Vec<MessageId>),
+                                   (RoutingError, Vec<MessageId>)> {
+        let (data_map_chunk, chunks) = chunked::split(payload, encrypt)
+            .map_err(|error| (error, vec![]))?;
+        let name = self.name().map_err(|error| (RoutingError::from(error), vec![]))?;
+        let dst = Authority::ClientManager(name);
+        let data_map_id = data_map_chunk.identifier();
+        let mut message_ids = Vec::with_capacity(chunks.len() + 1);
+        for chunk in chunks.into_iter().chain(Some(data_map_chunk)) {
+            let message_id = MessageId::new();
+            if let Err(error) = self.send_put_request(dst, Data::Immutable(chunk), message_id) {
+                return Err((RoutingError::from(error), message_ids));
+            }
+            message_ids.push(message_id);
+        }
+        Ok((data_map_id, message_ids))
+    }
+
+    /// Start getting the payload whose data map chunk has the given name, by getting the data
+    /// map.
+    ///
+    /// Every `ImmutableData` received in a `GetSuccess` response then needs to be passed to
+    /// `handle_file_chunk`, until it returns the reassembled payload.
+    pub fn send_get_file(&self, data_map_name: XorName) -> Result<FileFetch, InterfaceError> {
+        self.send_get_request(Authority::NaeManager(data_map_name),
+                              DataIdentifier::Immutable(data_map_name),
+                              MessageId::new())?;
+        Ok(FileFetch::new(data_map_name))
+    }
+
+    /// Add a chunk received while getting a payload with `send_get_file`. Once the data map has
+    /// arrived, this gets all chunks listed in it in parallel. Returns the reassembled payload
+    /// when all of them have arrived.
+    pub fn handle_file_chunk(&self,
+                             fetch: &mut FileFetch,
+                             chunk: ImmutableData)
+                             -> Result<Option<Vec<u8>>, RoutingError> {
+        match fetch.add(chunk)? {
+            FetchStep::GetChunks(names) => {
+                for name in names {
+                    self.send_get_request(Authority::NaeManager(name),
+                                          DataIdentifier::Immutable(name),
+                                          MessageId::new())?;
+                }
+                Ok(None)
+            }
+            FetchStep::Pending => Ok(None),
+            FetchStep::Complete(payload) => Ok(Some(payload)),
+        }
+    }
+
+    /// Get all chunks listed in `data_map` in parallel.
+    ///
+    /// Once all `GetSuccess` responses have been received, the payload can be reassembled with
+    /// `chunked::reassemble`. The data map itself is obtained with a `Get` request for the data
+    /// map chunk and `chunked::parse_data_map`.
+    pub fn send_get_file_chunks(&self,
+                                data_map: &DataMap)
+                                -> Result<Vec<MessageId>, InterfaceError> {
+        data_map.chunks
+            .iter()
+            .map(|details| {
+                let message_id = MessageId::new();
+                self.send_get_request(Authority::NaeManager(details.name),
+                                      DataIdentifier::Immutable(details.name),
+                                      message_id)
+                    .map(|()| message_id)
+            })
+            .collect()
     }
 
     /// Returns the name of this node.
@@ -191,12 +498,14 @@ impl Client {
     fn send_action(&self,
                    content: Request,
                    dst: Authority<XorName>,
-                   priority: u8)
+                   priority: u8,
+                   recipient: Option<PublicId>)
                    -> Result<(), InterfaceError> {
         let action = Action::ClientSendRequest {
             content: content,
             dst: dst,
             priority: priority,
+            recipient: recipient,
             result_tx: self.interface_result_tx.clone(),
         };
 
@@ -281,3 +590,77 @@ impl Drop for Client {
         }
     }
 }
+
+/// Returns the serialised size of `item`, in bytes.
+fn serialised_size<T: Encodable>(item: &T) -> usize {
+    serialisation::serialised_size(item) as usize
+}
+
+/// Groups the items by their destination authority, in batches of at most `MAX_BATCH_LEN` items
+/// and `MAX_BATCH_SIZE_IN_BYTES` bytes, as measured by `size`. An item that exceeds the size limit
+/// on its own is sent in a batch of its own.
+fn batch_by_authority<T, F>(items: Vec<(Authority<XorName>, T)>,
+                            size: F)
+                            -> Vec<(Authority<XorName>, Vec<T>)>
+    where F: Fn(&T) -> usize
+{
+    let mut groups: BTreeMap<Authority<XorName>, Vec<(Vec<T>, usize)>> = BTreeMap::new();
+    for (dst, item) in items {
+        let item_size = size(&item);
+        let batches = groups.entry(dst).or_insert_with(Vec::new);
+        let is_full = |&(ref batch, batch_size): &(Vec<T>, usize)| {
+            batch.len() == MAX_BATCH_LEN ||
+            (!batch.is_empty() && batch_size + item_size > MAX_BATCH_SIZE_IN_BYTES)
+        };
+        if batches.last().map_or(true, is_full) {
+            batches.push((vec![], 0));
+        }
+        if let Some(&mut (ref mut batch, ref mut batch_size)) = batches.last_mut() {
+            batch.push(item);
+            *batch_size += item_size;
+        }
+    }
+    groups.into_iter()
+        .flat_map(|(dst, batches)| batches.into_iter().map(move |(batch, _)| (dst, batch)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use messages::{MAX_BATCH_LEN, MAX_BATCH_SIZE_IN_BYTES};
+    use rand;
+    use routing_table::Authority;
+    use super::batch_by_authority;
+
+    #[test]
+    fn batches_grouped_per_authority() {
+        let (name0, name1) = (rand::random(), rand::random());
+        let (dst0, dst1) = (Authority::NaeManager(name0), Authority::NaeManager(name1));
+        let items = (0..(MAX_BATCH_LEN + 1))
+            .map(|i| (dst0, i))
+            .chain(Some((dst1, MAX_BATCH_LEN + 1)))
+            .collect();
+        let batches = batch_by_authority(items, |_| 1);
+
+        let mut expected = vec![(dst0, (0..MAX_BATCH_LEN).collect()),
+                                (dst0, vec![MAX_BATCH_LEN]),
+                                (dst1, vec![MAX_BATCH_LEN + 1])];
+        expected.sort_by_key(|&(dst, _)| dst);
+        assert_eq!(batches, expected);
+    }
+
+    #[test]
+    fn batches_limited_by_size() {
+        let dst = Authority::NaeManager(rand::random());
+        let size = MAX_BATCH_SIZE_IN_BYTES / 3;
+        let items = vec![(dst, size), (dst, size), (dst, size), (dst, size + 1),
+                         (dst, 2 * MAX_BATCH_SIZE_IN_BYTES), (dst, 1)];
+        let batches = batch_by_authority(items, |&item| item);
+
+        assert_eq!(batches,
+                   vec![(dst, vec![size, size, size]),
+                        (dst, vec![size + 1]),
+                        (dst, vec![2 * MAX_BATCH_SIZE_IN_BYTES]),
+                        (dst, vec![1])]);
+    }
+}
@@ -15,6 +15,7 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
+use data::ValidationError;
 use std::error::Error;
 use std::fmt::{self, Display, Formatter};
 
@@ -86,6 +87,8 @@ pub enum MutationError {
     /// Network error occurring at Vault level which has no bearing on clients, e.g. serialisation
     /// failure or database failure
     NetworkOther(String),
+    /// Invalid successor or data item, with the rule it violates
+    ValidationFailed(ValidationError),
 }
 
 impl<T: Into<String>> From<T> for MutationError {
@@ -94,6 +97,12 @@ impl<T: Into<String>> From<T> for MutationError {
     }
 }
 
+impl From<ValidationError> for MutationError {
+    fn from(error: ValidationError) -> Self {
+        MutationError::ValidationFailed(error)
+    }
+}
+
 impl Display for MutationError {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         match *self {
@@ -118,6 +127,9 @@ impl Display for MutationError {
             MutationError::NetworkOther(ref error) => {
                 write!(formatter, "Error on Vault network: {}", error)
             }
+            MutationError::ValidationFailed(ref error) => {
+                write!(formatter, "Data given failed validation: {}", error)
+            }
         }
     }
 }
@@ -135,6 +147,7 @@ impl Error for MutationError {
             MutationError::InvalidOperation => "Invalid operation",
             MutationError::NetworkFull => "Network full",
             MutationError::NetworkOther(ref error) => error,
+            MutationError::ValidationFailed(_) => "Validation failed",
         }
     }
 }
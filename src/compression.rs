@@ -0,0 +1,213 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.1.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use crust::PeerId;
+use error::RoutingError;
+use flate2;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+/// The maximum length a compressed message is allowed to expand to. This prevents a peer from
+/// making us inflate a "compression bomb". Larger messages are never compressed, so they are
+/// always sent as is instead of being rejected by the receiver.
+pub const MAX_DECOMPRESSED_LEN: u64 = 2 * 1024 * 1024;
+/// Messages shorter than this are never compressed: they wouldn't shrink by enough to be worth it.
+pub const MIN_COMPRESSED_LEN: usize = 1024;
+
+/// A codec used to compress serialised messages sent to a directly connected peer.
+///
+/// Compression is applied to each message after it has been signed, just before it is sent, and
+/// reversed by the peer as soon as it is received. Signed content is therefore always the same,
+/// whichever codecs the nodes along the route use.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, RustcEncodable,
+         RustcDecodable)]
+pub enum Compression {
+    /// The payload is sent as is.
+    None,
+    /// The payload is compressed using DEFLATE.
+    Deflate,
+}
+
+/// Compresses `payload` using `compression`. Returns the codec that was actually applied together
+/// with the resulting bytes: if the payload is too short or too long to be compressed, or if
+/// compressing doesn't make it smaller, it is returned unchanged with `Compression::None`.
+pub fn compress(compression: Compression,
+                payload: Vec<u8>)
+                -> Result<(Compression, Vec<u8>), RoutingError> {
+    if payload.len() < MIN_COMPRESSED_LEN || payload.len() as u64 > MAX_DECOMPRESSED_LEN {
+        return Ok((Compression::None, payload));
+    }
+    match compression {
+        Compression::None => Ok((Compression::None, payload)),
+        Compression::Deflate => {
+            let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::Default);
+            encoder.write_all(&payload)?;
+            let compressed = encoder.finish()?;
+            if compressed.len() < payload.len() {
+                Ok((Compression::Deflate, compressed))
+            } else {
+                Ok((Compression::None, payload))
+            }
+        }
+    }
+}
+
+/// Reverses `compress`, failing if the result would exceed `MAX_DECOMPRESSED_LEN`.
+pub fn decompress(compression: Compression, payload: Vec<u8>) -> Result<Vec<u8>, RoutingError> {
+    match compression {
+        Compression::None => Ok(payload),
+        Compression::Deflate => {
+            let mut decompressed = Vec::new();
+            let mut decoder = DeflateDecoder::new(&payload[..]).take(MAX_DECOMPRESSED_LEN + 1);
+            let _ = decoder.read_to_end(&mut decompressed)?;
+            if decompressed.len() as u64 > MAX_DECOMPRESSED_LEN {
+                return Err(RoutingError::DecompressedSizeExceeded);
+            }
+            Ok(decompressed)
+        }
+    }
+}
+
+/// Keeps track of the codecs our peers can decompress, and decides which one to use for each of
+/// them.
+///
+/// Every peer announces the codecs it supports in a `DirectMessage::Capabilities` when it
+/// connects. Peers that haven't done so, e.g. because they run an older version, are only ever
+/// sent uncompressed messages.
+pub struct PeerCompression {
+    /// The codec we compress messages with, if the peer supports it.
+    compression: Compression,
+    peers: HashMap<PeerId, Vec<Compression>>,
+}
+
+impl PeerCompression {
+    pub fn new(compression: Compression) -> Self {
+        PeerCompression {
+            compression: compression,
+            peers: HashMap::new(),
+        }
+    }
+
+    /// Returns the codecs we can decompress, to be announced to our peers.
+    pub fn supported() -> Vec<Compression> {
+        vec![Compression::Deflate]
+    }
+
+    /// Records the codecs announced by the given peer.
+    pub fn insert_peer(&mut self, peer_id: PeerId, supported: Vec<Compression>) {
+        let _ = self.peers.insert(peer_id, supported);
+    }
+
+    /// Forgets about the given peer.
+    pub fn remove_peer(&mut self, peer_id: &PeerId) {
+        let _ = self.peers.remove(peer_id);
+    }
+
+    /// Returns the codec to compress messages to the given peer with.
+    pub fn codec_for(&self, peer_id: &PeerId) -> Compression {
+        match self.peers.get(peer_id) {
+            Some(supported) if supported.contains(&self.compression) => self.compression,
+            _ => Compression::None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "use-mock-crust"))]
+    use crust::PeerId;
+    use error::RoutingError;
+    use flate2;
+    use flate2::write::DeflateEncoder;
+    #[cfg(not(feature = "use-mock-crust"))]
+    use id::FullId;
+    #[cfg(feature = "use-mock-crust")]
+    use mock_crust::crust::PeerId;
+    use rand;
+    use std::io::Write;
+    use super::*;
+
+    #[cfg(not(feature = "use-mock-crust"))]
+    fn make_peer_id(_: usize) -> PeerId {
+        PeerId(*FullId::new().public_id().encrypting_public_key())
+    }
+
+    #[cfg(feature = "use-mock-crust")]
+    fn make_peer_id(index: usize) -> PeerId {
+        PeerId(index)
+    }
+
+    #[test]
+    fn round_trip() {
+        let payload: Vec<u8> = (0..10000).map(|i| (i % 7) as u8).collect();
+        let (compression, compressed) = unwrap!(compress(Compression::Deflate, payload.clone()));
+        assert_eq!(Compression::Deflate, compression);
+        assert!(compressed.len() < payload.len());
+        assert_eq!(payload, unwrap!(decompress(compression, compressed)));
+
+        // Random bytes don't compress, so they should be sent uncompressed.
+        let payload: Vec<u8> = (0..1000).map(|_| rand::random()).collect();
+        let (compression, compressed) = unwrap!(compress(Compression::Deflate, payload.clone()));
+        assert_eq!(Compression::None, compression);
+        assert_eq!(payload, compressed);
+
+        // Short payloads aren't worth compressing.
+        let payload = vec![0u8; MIN_COMPRESSED_LEN - 1];
+        let (compression, compressed) = unwrap!(compress(Compression::Deflate, payload.clone()));
+        assert_eq!(Compression::None, compression);
+        assert_eq!(payload, compressed);
+    }
+
+    #[test]
+    fn decompression_limit() {
+        // We never produce anything the receiver would refuse to decompress.
+        let payload = vec![0u8; MAX_DECOMPRESSED_LEN as usize + 1];
+        let (compression, compressed) = unwrap!(compress(Compression::Deflate, payload.clone()));
+        assert_eq!(Compression::None, compression);
+        assert_eq!(payload, compressed);
+
+        // But a compression bomb from a peer is rejected with an error.
+        let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::Default);
+        unwrap!(encoder.write_all(&payload));
+        let bomb = unwrap!(encoder.finish());
+        match decompress(Compression::Deflate, bomb) {
+            Err(RoutingError::DecompressedSizeExceeded) => (),
+            result => panic!("Unexpected result {:?}", result.map(|bytes| bytes.len())),
+        }
+    }
+
+    #[test]
+    fn peer_compression() {
+        let old_peer = make_peer_id(0);
+        let new_peer = make_peer_id(1);
+
+        let mut peers = PeerCompression::new(Compression::Deflate);
+        peers.insert_peer(new_peer, PeerCompression::supported());
+        assert_eq!(Compression::None, peers.codec_for(&old_peer));
+        assert_eq!(Compression::Deflate, peers.codec_for(&new_peer));
+
+        peers.remove_peer(&new_peer);
+        assert_eq!(Compression::None, peers.codec_for(&new_peer));
+
+        // A node that has compression disabled never compresses, even if the peer supports it.
+        let mut peers = PeerCompression::new(Compression::None);
+        peers.insert_peer(new_peer, PeerCompression::supported());
+        assert_eq!(Compression::None, peers.codec_for(&new_peer));
+    }
+}
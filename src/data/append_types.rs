@@ -192,6 +192,135 @@ impl AppendWrapper {
     }
 }
 
+/// What happens when appending an item would make an appendable data exceed its size limit.
+#[derive(Hash, Eq, PartialEq, PartialOrd, Ord, Clone, Copy, RustcDecodable, RustcEncodable,
+         Debug)]
+pub enum EvictionPolicy {
+    /// The append fails. The owners need to remove items by adding them to the `deleted_data`.
+    OwnerManaged,
+    /// The oldest items with an append sequence number below `before` are evicted to make room
+    /// for the new one. The owners raise `before` in a `Post` once they have read the items, so
+    /// that appenders can't evict any items the owners haven't seen yet.
+    OldestFirst {
+        /// The append sequence number of the oldest item that must not be evicted.
+        before: u64,
+    },
+}
+
+impl Default for EvictionPolicy {
+    fn default() -> EvictionPolicy {
+        EvictionPolicy::OwnerManaged
+    }
+}
+
+/// A range of the items in an appendable data, in the order they were appended.
+#[derive(Hash, Eq, PartialEq, PartialOrd, Ord, Clone, Copy, RustcDecodable, RustcEncodable,
+         Debug)]
+pub enum AppendedRange {
+    /// At most `count` items, starting at the given position.
+    Index {
+        /// The position of the first item, where 0 is the oldest one.
+        start: u64,
+        /// The maximum number of items.
+        count: u64,
+    },
+    /// At most `count` items with an append sequence number not below the given one.
+    Since {
+        /// The append sequence number of the first item.
+        seq: u64,
+        /// The maximum number of items.
+        count: u64,
+    },
+}
+
+/// A page of the items in an appendable data, each with its append sequence number.
+#[derive(Hash, Eq, PartialEq, PartialOrd, Ord, Clone, RustcDecodable, RustcEncodable, Debug)]
+pub enum AppendedItems {
+    /// Items of a public appendable data.
+    Pub(Vec<(u64, AppendedData)>),
+    /// Items of a private appendable data.
+    Priv(Vec<(u64, PrivAppendedData)>),
+}
+
+// The append sequence numbers are kept in a vector `seqs`, in the same order as the items in the
+// set `data`, so that the items themselves are stored exactly as before. Items without a
+// sequence number count as the oldest ones.
+
+fn seq_at(seqs: &[u64], index: usize) -> u64 {
+    seqs.get(index).cloned().unwrap_or(0)
+}
+
+/// Inserts `item` with the append sequence number `seq`, or returns `false` if it is already
+/// present.
+pub fn insert_item<T: Ord>(data: &mut BTreeSet<T>,
+                           seqs: &mut Vec<u64>,
+                           item: T,
+                           seq: u64)
+                           -> bool {
+    if data.contains(&item) {
+        return false;
+    }
+    let index = data.iter().take_while(|existing| **existing < item).count();
+    seqs.resize(data.len(), 0);
+    seqs.insert(index, seq);
+    data.insert(item)
+}
+
+/// Removes `item` and its append sequence number, or returns `false` if it is not present.
+pub fn remove_item<T: Ord>(data: &mut BTreeSet<T>, seqs: &mut Vec<u64>, item: &T) -> bool {
+    let index = match data.iter().position(|existing| existing == item) {
+        Some(index) => index,
+        None => return false,
+    };
+    seqs.resize(data.len(), 0);
+    let _ = seqs.remove(index);
+    data.remove(item)
+}
+
+/// Returns the items with their append sequence numbers, in the order they were appended.
+pub fn ordered_items<T: Ord + Clone>(data: &BTreeSet<T>, seqs: &[u64]) -> Vec<(u64, T)> {
+    let mut items: Vec<_> = data.iter()
+        .enumerate()
+        .map(|(index, item)| (seq_at(seqs, index), item.clone()))
+        .collect();
+    items.sort();
+    items
+}
+
+/// Returns the items in the given `range`.
+pub fn page<T: Ord + Clone>(data: &BTreeSet<T>,
+                            seqs: &[u64],
+                            range: &AppendedRange)
+                            -> Vec<(u64, T)> {
+    let items = ordered_items(data, seqs).into_iter();
+    match *range {
+        AppendedRange::Index { start, count } => {
+            items.skip(start as usize).take(count as usize).collect()
+        }
+        AppendedRange::Since { seq, count } => {
+            items.skip_while(|&(item_seq, _)| item_seq < seq).take(count as usize).collect()
+        }
+    }
+}
+
+/// Returns the oldest item other than `except` that the eviction `policy` allows to evict.
+pub fn evictable_item<T: Ord + Clone>(data: &BTreeSet<T>,
+                                      seqs: &[u64],
+                                      policy: EvictionPolicy,
+                                      except: &T)
+                                      -> Option<T> {
+    let before = match policy {
+        EvictionPolicy::OwnerManaged => return None,
+        EvictionPolicy::OldestFirst { before } => before,
+    };
+    data.iter()
+        .enumerate()
+        .map(|(index, item)| (seq_at(seqs, index), item))
+        .filter(|&(seq, item)| seq < before && item != except)
+        .min()
+        .map(|(_, item)| item.clone())
+}
+
 #[cfg(test)]
 mod tests {
     use data::{DataIdentifier, PrivAppendedData};
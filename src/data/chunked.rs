@@ -0,0 +1,278 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.1.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+//! Splitting of large payloads into `ImmutableData` chunks, and their reassembly.
+//!
+//! A payload is split into chunks of at most `MAX_CHUNK_SIZE` bytes, each stored as a separate,
+//! content-addressed `ImmutableData`. A _data map_ lists the chunks in order; it is itself stored
+//! as an `ImmutableData`, whose name identifies the whole payload.
+//!
+//! Chunks can optionally be encrypted _convergently_: each chunk's key is the hash of its
+//! plaintext, so identical payloads still result in identical chunks, but only holders of the
+//! data map can decrypt them.
+//!
+//! A `FileFetch` keeps track of retrieving a whole payload: first its data map, then its chunks.
+
+use error::RoutingError;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use rust_sodium::crypto::hash::sha256;
+use rust_sodium::crypto::secretbox;
+use std::collections::BTreeMap;
+use super::ImmutableData;
+use xor_name::XorName;
+
+/// The maximal size of a chunk's plaintext, in bytes.
+pub const MAX_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// The location and size of a single chunk of a payload.
+#[derive(Clone, Debug, Eq, PartialEq, RustcEncodable, RustcDecodable)]
+pub struct ChunkDetails {
+    /// The name of the chunk's `ImmutableData`.
+    pub name: XorName,
+    /// The size of the chunk's plaintext.
+    pub size: u64,
+    /// The key the chunk is encrypted with, if any. This is the SHA-256 hash of the plaintext.
+    pub key: Option<[u8; secretbox::KEYBYTES]>,
+}
+
+/// The list of chunks a payload was split into, in order.
+#[derive(Clone, Debug, Eq, PartialEq, RustcEncodable, RustcDecodable)]
+pub struct DataMap {
+    /// The total size of the payload.
+    pub size: u64,
+    /// The chunks, in order.
+    pub chunks: Vec<ChunkDetails>,
+}
+
+/// Splits `payload` into chunks, optionally encrypting them convergently.
+///
+/// Returns the data map chunk, whose name identifies the payload, and the content chunks. All of
+/// them need to be stored to be able to reassemble the payload.
+pub fn split(payload: &[u8],
+             encrypt: bool)
+             -> Result<(ImmutableData, Vec<ImmutableData>), RoutingError> {
+    let mut details = Vec::new();
+    let mut chunks = Vec::new();
+    for plaintext in payload.chunks(MAX_CHUNK_SIZE) {
+        let (chunk, key) = if encrypt {
+            let key = secretbox::Key(sha256::hash(plaintext).0);
+            let ciphertext = secretbox::seal(plaintext, &nonce_for(&key), &key);
+            (ImmutableData::new(ciphertext), Some(key.0))
+        } else {
+            (ImmutableData::new(plaintext.to_vec()), None)
+        };
+        details.push(ChunkDetails {
+            name: *chunk.name(),
+            size: plaintext.len() as u64,
+            key: key,
+        });
+        chunks.push(chunk);
+    }
+    let data_map = DataMap {
+        size: payload.len() as u64,
+        chunks: details,
+    };
+    let data_map_chunk = ImmutableData::new(serialise(&data_map)?);
+    if !data_map_chunk.validate_size() {
+        return Err(RoutingError::ExceededSizeLimit);
+    }
+    Ok((data_map_chunk, chunks))
+}
+
+/// Parses the data map from its chunk.
+pub fn parse_data_map(data_map_chunk: &ImmutableData) -> Result<DataMap, RoutingError> {
+    Ok(deserialise(data_map_chunk.value())?)
+}
+
+/// Reassembles the payload from the chunks listed in `data_map`, verifying each of them.
+pub fn reassemble(data_map: &DataMap,
+                  chunks: &BTreeMap<XorName, ImmutableData>)
+                  -> Result<Vec<u8>, RoutingError> {
+    let mut payload = Vec::with_capacity(data_map.size as usize);
+    for details in &data_map.chunks {
+        let chunk = chunks.get(&details.name).ok_or(RoutingError::MissingChunk(details.name))?;
+        // The name of a received chunk is computed from its value, so this verifies its content.
+        if *chunk.name() != details.name {
+            return Err(RoutingError::HashMismatch);
+        }
+        let plaintext = match details.key {
+            Some(key) => {
+                let key = secretbox::Key(key);
+                let plaintext = secretbox::open(chunk.value(), &nonce_for(&key), &key)
+                    .map_err(|()| RoutingError::HashMismatch)?;
+                if sha256::hash(&plaintext).0 != key.0 {
+                    return Err(RoutingError::HashMismatch);
+                }
+                plaintext
+            }
+            None => chunk.value().clone(),
+        };
+        if plaintext.len() as u64 != details.size {
+            return Err(RoutingError::HashMismatch);
+        }
+        payload.extend_from_slice(&plaintext);
+    }
+    if payload.len() as u64 != data_map.size {
+        return Err(RoutingError::HashMismatch);
+    }
+    Ok(payload)
+}
+
+/// The next step of a `FileFetch`, after a chunk has been added to it.
+#[derive(Debug, Eq, PartialEq)]
+pub enum FetchStep {
+    /// The data map has arrived: the chunks with these names need to be fetched.
+    GetChunks(Vec<XorName>),
+    /// More chunks are still missing.
+    Pending,
+    /// All chunks have arrived: this is the reassembled payload.
+    Complete(Vec<u8>),
+}
+
+/// Collects the data map and the chunks of a payload as they arrive, and reassembles it once all
+/// of them are there.
+pub struct FileFetch {
+    data_map_name: XorName,
+    data_map: Option<DataMap>,
+    chunks: BTreeMap<XorName, ImmutableData>,
+}
+
+impl FileFetch {
+    /// Starts fetching the payload whose data map chunk has the given name.
+    pub fn new(data_map_name: XorName) -> FileFetch {
+        FileFetch {
+            data_map_name: data_map_name,
+            data_map: None,
+            chunks: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the name of the payload's data map chunk.
+    pub fn data_map_name(&self) -> &XorName {
+        &self.data_map_name
+    }
+
+    /// Adds a received chunk, which is either the data map or one of the chunks it lists. Other
+    /// chunks are ignored.
+    pub fn add(&mut self, chunk: ImmutableData) -> Result<FetchStep, RoutingError> {
+        if let Some(ref data_map) = self.data_map {
+            if data_map.chunks.iter().any(|details| details.name == *chunk.name()) {
+                let _ = self.chunks.insert(*chunk.name(), chunk);
+            }
+            if data_map.chunks.iter().all(|details| self.chunks.contains_key(&details.name)) {
+                return Ok(FetchStep::Complete(reassemble(data_map, &self.chunks)?));
+            }
+            return Ok(FetchStep::Pending);
+        }
+        if *chunk.name() != self.data_map_name {
+            return Ok(FetchStep::Pending);
+        }
+        let data_map = parse_data_map(&chunk)?;
+        if data_map.chunks.is_empty() {
+            return Ok(FetchStep::Complete(reassemble(&data_map, &self.chunks)?));
+        }
+        let names = data_map.chunks.iter().map(|details| details.name).collect();
+        self.data_map = Some(data_map);
+        Ok(FetchStep::GetChunks(names))
+    }
+}
+
+/// Returns the nonce for the given convergent key. As every key is only ever used to encrypt the
+/// same plaintext, the nonce can be derived from it.
+fn nonce_for(key: &secretbox::Key) -> secretbox::Nonce {
+    let hash = sha256::hash(&key.0);
+    let mut nonce = [0; secretbox::NONCEBYTES];
+    nonce.copy_from_slice(&hash.0[..secretbox::NONCEBYTES]);
+    secretbox::Nonce(nonce)
+}
+
+#[cfg(test)]
+mod tests {
+    use data::ImmutableData;
+    use error::RoutingError;
+    use rand::{self, Rng};
+    use std::collections::BTreeMap;
+    use super::*;
+    use xor_name::XorName;
+
+    fn chunk_map(chunks: Vec<ImmutableData>) -> BTreeMap<XorName, ImmutableData> {
+        chunks.into_iter().map(|chunk| (*chunk.name(), chunk)).collect()
+    }
+
+    #[test]
+    fn split_and_reassemble() {
+        let mut rng = rand::thread_rng();
+        let payload: Vec<u8> = rng.gen_iter().take(2 * MAX_CHUNK_SIZE + 100).collect();
+
+        for &encrypt in &[false, true] {
+            let (data_map_chunk, chunks) = unwrap!(split(&payload, encrypt));
+            assert_eq!(chunks.len(), 3);
+            assert!(chunks.iter().all(ImmutableData::validate_size));
+            assert_eq!(!encrypt, chunks[0].value()[..] == payload[..MAX_CHUNK_SIZE]);
+
+            let data_map = unwrap!(parse_data_map(&data_map_chunk));
+            assert_eq!(unwrap!(reassemble(&data_map, &chunk_map(chunks.clone()))), payload);
+
+            // Splitting is deterministic, even with encryption.
+            let (other_data_map_chunk, other_chunks) = unwrap!(split(&payload, encrypt));
+            assert_eq!(data_map_chunk, other_data_map_chunk);
+            assert_eq!(chunks, other_chunks);
+
+            let mut missing = chunk_map(chunks);
+            let name = data_map.chunks[1].name;
+            let _ = missing.remove(&name);
+            match reassemble(&data_map, &missing) {
+                Err(RoutingError::MissingChunk(missing_name)) => assert_eq!(name, missing_name),
+                result => panic!("Unexpected result {:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    fn tampered_chunk() {
+        let payload = b"tampered".to_vec();
+        let (data_map_chunk, _) = unwrap!(split(&payload, true));
+        let mut data_map = unwrap!(parse_data_map(&data_map_chunk));
+        let tampered = ImmutableData::new(b"other".to_vec());
+        data_map.chunks[0].name = *tampered.name();
+        match reassemble(&data_map, &chunk_map(vec![tampered])) {
+            Err(RoutingError::HashMismatch) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn fetch() {
+        let mut rng = rand::thread_rng();
+        let payload: Vec<u8> = rng.gen_iter().take(MAX_CHUNK_SIZE + 100).collect();
+        let (data_map_chunk, chunks) = unwrap!(split(&payload, true));
+        let mut fetch = FileFetch::new(*data_map_chunk.name());
+
+        // Chunks are only accepted once the data map has arrived.
+        assert_eq!(unwrap!(fetch.add(chunks[0].clone())), FetchStep::Pending);
+        let names = chunks.iter().map(|chunk| *chunk.name()).collect();
+        assert_eq!(unwrap!(fetch.add(data_map_chunk)), FetchStep::GetChunks(names));
+        assert_eq!(unwrap!(fetch.add(chunks[1].clone())), FetchStep::Pending);
+        assert_eq!(unwrap!(fetch.add(ImmutableData::new(b"other".to_vec()))),
+                   FetchStep::Pending);
+        assert_eq!(unwrap!(fetch.add(chunks[0].clone())), FetchStep::Complete(payload));
+
+        // An empty payload is complete as soon as the data map arrives.
+        let (data_map_chunk, _) = unwrap!(split(&[], false));
+        let mut fetch = FileFetch::new(*data_map_chunk.name());
+        assert_eq!(unwrap!(fetch.add(data_map_chunk)), FetchStep::Complete(vec![]));
+    }
+}
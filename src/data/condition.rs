@@ -0,0 +1,77 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.1.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use super::Data;
+use maidsafe_utilities::serialisation::{SerialisationError, serialise};
+use rust_sodium::crypto::hash::sha256;
+
+/// The SHA-256 hash of a serialised data item.
+pub type ContentHash = [u8; sha256::DIGESTBYTES];
+
+/// Returns the hash of the serialised `data`, as used by `Condition::ContentHash`.
+pub fn content_hash(data: &Data) -> Result<ContentHash, SerialisationError> {
+    Ok(sha256::hash(&serialise(data)?).0)
+}
+
+/// The expected state of a stored data item, which a conditional `Post` or `Delete` requires in
+/// order to be applied.
+#[derive(Hash, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, RustcEncodable, RustcDecodable)]
+pub enum Condition {
+    /// The stored item has the given version.
+    Version(u64),
+    /// The hash of the stored item equals the given one.
+    ContentHash(ContentHash),
+}
+
+impl Condition {
+    /// Returns `true` if the currently stored item `current` satisfies this condition.
+    ///
+    /// A version condition is never met by data types without a version.
+    pub fn is_met_by(&self, current: &Data) -> bool {
+        match *self {
+            Condition::Version(version) => current.version() == Some(version),
+            Condition::ContentHash(ref hash) => content_hash(current).ok() == Some(*hash),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data::{Data, ImmutableData, StructuredData};
+    use rand;
+    use std::collections::BTreeSet;
+    use super::*;
+
+    #[test]
+    fn conditions() {
+        let name = rand::random();
+        let sd = unwrap!(StructuredData::new(0, name, 3, vec![1], BTreeSet::new()));
+        let data = Data::Structured(sd);
+        assert!(Condition::Version(3).is_met_by(&data));
+        assert!(!Condition::Version(2).is_met_by(&data));
+        assert!(Condition::ContentHash(unwrap!(content_hash(&data))).is_met_by(&data));
+
+        let sd = unwrap!(StructuredData::new(0, name, 3, vec![2], BTreeSet::new()));
+        let changed = Data::Structured(sd);
+        assert!(Condition::Version(3).is_met_by(&changed));
+        assert!(!Condition::ContentHash(unwrap!(content_hash(&data))).is_met_by(&changed));
+
+        let immutable = Data::Immutable(ImmutableData::new(vec![1, 2, 3]));
+        assert!(!Condition::Version(0).is_met_by(&immutable));
+        assert!(Condition::ContentHash(unwrap!(content_hash(&immutable))).is_met_by(&immutable));
+    }
+}
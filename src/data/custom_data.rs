@@ -0,0 +1,243 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.1.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+use maidsafe_utilities::serialisation::serialised_size;
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug, Formatter};
+use super::{Data, DataIdentifier, ValidationError};
+use utils;
+use xor_name::XorName;
+
+/// Maximum allowed size for a serialised `CustomData`, regardless of its type's own limit.
+pub const MAX_CUSTOM_DATA_SIZE_IN_BYTES: u64 = 1024 * 1024;
+
+/// Application-defined data, whose rules are defined by the `DataValidator` registered for its
+/// type ID.
+///
+/// Routing treats the content as opaque and only interprets it through the validator.
+#[derive(Hash, Clone, Eq, PartialEq, Ord, PartialOrd, RustcEncodable, RustcDecodable)]
+pub struct CustomData {
+    type_id: u64,
+    name: XorName,
+    content: Vec<u8>,
+}
+
+impl CustomData {
+    /// Creates a new data item of the given type.
+    pub fn new(type_id: u64, name: XorName, content: Vec<u8>) -> CustomData {
+        CustomData {
+            type_id: type_id,
+            name: name,
+            content: content,
+        }
+    }
+
+    /// Returns the type ID.
+    pub fn type_id(&self) -> u64 {
+        self.type_id
+    }
+
+    /// Returns the name.
+    pub fn name(&self) -> &XorName {
+        &self.name
+    }
+
+    /// Returns the content.
+    pub fn content(&self) -> &[u8] {
+        &self.content
+    }
+
+    /// Returns `DataIdentifier` for this data element.
+    pub fn identifier(&self) -> DataIdentifier {
+        DataIdentifier::Custom(self.name, self.type_id)
+    }
+
+    /// Returns `true` if the serialised size is within `MAX_CUSTOM_DATA_SIZE_IN_BYTES`.
+    pub fn validate_size(&self) -> bool {
+        serialised_size(self) <= MAX_CUSTOM_DATA_SIZE_IN_BYTES
+    }
+}
+
+impl Debug for CustomData {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter,
+               "CustomData {{ type_id: {}, name: {}, content: {} }}",
+               self.type_id,
+               self.name,
+               utils::format_binary_array(&self.content))
+    }
+}
+
+/// The rules for an application-defined data type, implemented by upper layers.
+///
+/// Nodes need to register the same validators, e.g. via `NodeBuilder::data_validator`, so that
+/// every member of a section accepts or rejects the same requests.
+pub trait DataValidator: Send {
+    /// The type ID this validator is responsible for.
+    fn type_id(&self) -> u64;
+
+    /// The maximum serialised size of a data item of this type, in bytes. The effective limit is
+    /// at most `MAX_CUSTOM_DATA_SIZE_IN_BYTES`.
+    fn max_size(&self) -> u64;
+
+    /// Validates a data item on its own, e.g. its ownership rules, when it is stored.
+    fn validate(&self, data: &CustomData) -> Result<(), ValidationError>;
+
+    /// Validates `successor` as the new version of `current`, e.g. that it is signed by the
+    /// current owners.
+    fn validate_successor(&self,
+                          current: &CustomData,
+                          successor: &CustomData)
+                          -> Result<(), ValidationError>;
+}
+
+/// The registered `DataValidator`s, by type ID.
+#[derive(Default)]
+pub struct DataValidators {
+    validators: BTreeMap<u64, Box<DataValidator>>,
+}
+
+impl DataValidators {
+    /// Returns a registry without any validators.
+    pub fn new() -> DataValidators {
+        Default::default()
+    }
+
+    /// Registers `validator` for its type ID, replacing any previously registered one.
+    pub fn register(&mut self, validator: Box<DataValidator>) {
+        let _ = self.validators.insert(validator.type_id(), validator);
+    }
+
+    /// Returns `true` if a validator is registered for `type_id`.
+    pub fn is_registered(&self, type_id: u64) -> bool {
+        self.validators.contains_key(&type_id)
+    }
+
+    /// Validates `CustomData` against the size limits and the rules of its registered type.
+    ///
+    /// Built-in data types are always accepted here: their size limits are left to the upper
+    /// layers, via their `validate_size` methods.
+    pub fn validate(&self, data: &Data) -> Result<(), ValidationError> {
+        match *data {
+            Data::Custom(ref data) => {
+                let validator = self.validator(data.type_id())?;
+                if !data.validate_size() || serialised_size(data) > validator.max_size() {
+                    return Err(ValidationError::SizeExceeded);
+                }
+                validator.validate(data)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Validates `successor` as the new version of `current`, using the built-in rules for
+    /// `StructuredData` and appendable data, and the registered rules for `CustomData`.
+    ///
+    /// Upper layers call this via `Node::validate_successor` when handling a `Post`.
+    pub fn validate_successor(&self,
+                              current: &Data,
+                              successor: &Data)
+                              -> Result<(), ValidationError> {
+        if current.identifier() != successor.identifier() {
+            return Err(ValidationError::IdentifierChanged);
+        }
+        self.validate(successor)?;
+        match (current, successor) {
+            (&Data::Structured(ref current), &Data::Structured(ref successor)) => {
+                current.validate_successor(successor)
+            }
+            (&Data::PubAppendable(ref current), &Data::PubAppendable(ref successor)) => {
+                current.validate_successor(successor)
+            }
+            (&Data::PrivAppendable(ref current), &Data::PrivAppendable(ref successor)) => {
+                current.validate_successor(successor)
+            }
+            (&Data::Custom(ref current), &Data::Custom(ref successor)) => {
+                self.validator(current.type_id())?.validate_successor(current, successor)
+            }
+            _ => Err(ValidationError::SuccessorNotSupported),
+        }
+    }
+
+    fn validator(&self, type_id: u64) -> Result<&DataValidator, ValidationError> {
+        self.validators
+            .get(&type_id)
+            .map(|validator| &**validator)
+            .ok_or(ValidationError::UnregisteredType(type_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data::{Data, ValidationError};
+    use rand;
+    use super::*;
+
+    const TYPE_ID: u64 = 1000;
+
+    /// Accepts contents of at most 10 bytes, whose successors must start with the current content.
+    struct PrefixValidator;
+
+    impl DataValidator for PrefixValidator {
+        fn type_id(&self) -> u64 {
+            TYPE_ID
+        }
+
+        fn max_size(&self) -> u64 {
+            100
+        }
+
+        fn validate(&self, data: &CustomData) -> Result<(), ValidationError> {
+            if data.content().len() > 10 {
+                return Err(ValidationError::Rejected("Content too long".to_owned()));
+            }
+            Ok(())
+        }
+
+        fn validate_successor(&self,
+                              current: &CustomData,
+                              successor: &CustomData)
+                              -> Result<(), ValidationError> {
+            if successor.content().starts_with(current.content()) {
+                Ok(())
+            } else {
+                Err(ValidationError::Rejected("Content not extended".to_owned()))
+            }
+        }
+    }
+
+    #[test]
+    fn registered_rules() {
+        let mut validators = DataValidators::new();
+        let name = rand::random();
+        let data = Data::Custom(CustomData::new(TYPE_ID, name, vec![1, 2]));
+        assert_eq!(validators.validate(&data),
+                   Err(ValidationError::UnregisteredType(TYPE_ID)));
+
+        validators.register(Box::new(PrefixValidator));
+        assert!(validators.is_registered(TYPE_ID));
+        assert_eq!(validators.validate(&data), Ok(()));
+        let too_long = Data::Custom(CustomData::new(TYPE_ID, name, vec![0; 11]));
+        assert!(validators.validate(&too_long).is_err());
+
+        let successor = Data::Custom(CustomData::new(TYPE_ID, name, vec![1, 2, 3]));
+        assert_eq!(validators.validate_successor(&data, &successor), Ok(()));
+        assert!(validators.validate_successor(&successor, &data).is_err());
+        let other_type = Data::Custom(CustomData::new(TYPE_ID + 1, name, vec![1, 2, 3]));
+        assert_eq!(validators.validate_successor(&data, &other_type),
+                   Err(ValidationError::IdentifierChanged));
+    }
+}
@@ -0,0 +1,171 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.1.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use super::{Data, DataIdentifier};
+use std::collections::{BTreeMap, BTreeSet};
+use std::mem;
+
+/// Keeps track of the expiry times of stored data items, so that storage can be reclaimed once
+/// they have expired.
+///
+/// Times are in seconds since the UNIX epoch. The tracker is serialisable, so that the upper layer
+/// can persist it and restore it with `NodeBuilder::expiry_tracker` after a restart or relocation.
+#[derive(Clone, Debug, Default, PartialEq, Eq, RustcEncodable, RustcDecodable)]
+pub struct ExpiryTracker {
+    /// The tracked items, by expiry time.
+    by_time: BTreeMap<u64, BTreeSet<DataIdentifier>>,
+    /// The expiry time of each tracked item.
+    expiries: BTreeMap<DataIdentifier, u64>,
+}
+
+impl ExpiryTracker {
+    /// Creates a new, empty tracker.
+    pub fn new() -> ExpiryTracker {
+        Default::default()
+    }
+
+    /// Tracks the expiry time of `data`, replacing any previous one, or stops tracking it if it
+    /// doesn't expire.
+    pub fn track(&mut self, data: &Data) {
+        self.set_expiry(data.identifier(), data.expiry());
+    }
+
+    /// Sets the expiry time of the item `data_id`, or stops tracking it if `expiry` is `None`.
+    pub fn set_expiry(&mut self, data_id: DataIdentifier, expiry: Option<u64>) {
+        self.untrack(&data_id);
+        if let Some(expiry) = expiry {
+            let _ = self.by_time.entry(expiry).or_insert_with(BTreeSet::new).insert(data_id);
+            let _ = self.expiries.insert(data_id, expiry);
+        }
+    }
+
+    /// Stops tracking the item `data_id`, e.g. because it has been deleted.
+    pub fn untrack(&mut self, data_id: &DataIdentifier) {
+        if let Some(expiry) = self.expiries.remove(data_id) {
+            let now_empty = self.by_time.get_mut(&expiry).map_or(false, |ids| {
+                let _ = ids.remove(data_id);
+                ids.is_empty()
+            });
+            if now_empty {
+                let _ = self.by_time.remove(&expiry);
+            }
+        }
+    }
+
+    /// Returns the expiry time of the item `data_id`, if it is tracked.
+    pub fn expiry(&self, data_id: &DataIdentifier) -> Option<u64> {
+        self.expiries.get(data_id).cloned()
+    }
+
+    /// Returns the earliest expiry time of all tracked items.
+    pub fn next_expiry(&self) -> Option<u64> {
+        self.by_time.keys().next().cloned()
+    }
+
+    /// Returns the items that have expired at time `now`, earliest first.
+    pub fn expired(&self, now: u64) -> Vec<DataIdentifier> {
+        self.by_time
+            .iter()
+            .take_while(|&(&expiry, _)| expiry <= now)
+            .flat_map(|(_, ids)| ids.iter().cloned())
+            .collect()
+    }
+
+    /// Removes and returns the items that have expired at time `now`, earliest first.
+    pub fn remove_expired(&mut self, now: u64) -> Vec<DataIdentifier> {
+        let unexpired = self.by_time.split_off(&now.saturating_add(1));
+        let expired = mem::replace(&mut self.by_time, unexpired);
+        let ids: Vec<_> = expired.into_iter().flat_map(|(_, ids)| ids).collect();
+        for data_id in &ids {
+            let _ = self.expiries.remove(data_id);
+        }
+        ids
+    }
+
+    /// Returns the number of tracked items.
+    pub fn len(&self) -> usize {
+        self.expiries.len()
+    }
+
+    /// Returns `true` if no items are tracked.
+    pub fn is_empty(&self) -> bool {
+        self.expiries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use data::{Data, DataIdentifier, ImmutableData, StructuredData};
+    use maidsafe_utilities::serialisation::{deserialise, serialise};
+    use rand;
+    use std::collections::BTreeSet;
+    use super::*;
+
+    #[test]
+    fn track_and_expire() {
+        let mut tracker = ExpiryTracker::new();
+        let ids: Vec<_> = (0..3).map(|_| DataIdentifier::Immutable(rand::random())).collect();
+        tracker.set_expiry(ids[0], Some(20));
+        tracker.set_expiry(ids[1], Some(10));
+        tracker.set_expiry(ids[2], Some(20));
+        assert_eq!(tracker.len(), 3);
+        assert_eq!(tracker.next_expiry(), Some(10));
+        assert!(tracker.expired(9).is_empty());
+        assert_eq!(tracker.expired(10), vec![ids[1]]);
+
+        // Updating the expiry time replaces the previous one.
+        tracker.set_expiry(ids[1], Some(30));
+        assert!(tracker.expired(10).is_empty());
+        assert_eq!(tracker.expiry(&ids[1]), Some(30));
+
+        tracker.untrack(&ids[0]);
+        assert_eq!(tracker.remove_expired(25), vec![ids[2]]);
+        assert_eq!(tracker.len(), 1);
+        assert_eq!(tracker.remove_expired(u64::max_value()), vec![ids[1]]);
+        assert!(tracker.is_empty());
+        assert_eq!(tracker.next_expiry(), None);
+    }
+
+    #[test]
+    fn track_data() {
+        let mut tracker = ExpiryTracker::new();
+        let immutable = Data::Immutable(ImmutableData::new(vec![1, 2, 3]));
+        tracker.track(&immutable);
+        assert!(tracker.is_empty());
+
+        let mut sd = unwrap!(StructuredData::new(0, rand::random(), 0, vec![], BTreeSet::new()));
+        sd.set_expiry(Some(5));
+        let data = Data::Structured(sd.clone());
+        tracker.track(&data);
+        assert_eq!(tracker.expiry(&data.identifier()), Some(5));
+
+        // Data that no longer expires is no longer tracked.
+        sd.set_expiry(None);
+        tracker.track(&Data::Structured(sd));
+        assert!(tracker.is_empty());
+    }
+
+    #[test]
+    fn serialisation() {
+        let mut tracker = ExpiryTracker::new();
+        tracker.set_expiry(DataIdentifier::Immutable(rand::random()), Some(10));
+        tracker.set_expiry(DataIdentifier::Structured(rand::random(), 1), Some(20));
+        let restored: ExpiryTracker = unwrap!(deserialise(&unwrap!(serialise(&tracker))));
+        assert_eq!(restored, tracker);
+        assert_eq!(restored.next_expiry(), Some(10));
+    }
+}
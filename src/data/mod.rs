@@ -16,19 +16,39 @@
 // relating to use of the SAFE Network Software.
 
 mod append_types;
+pub mod chunked;
+mod condition;
+mod custom_data;
+mod expiry;
 mod immutable_data;
+mod mutable_data;
 mod priv_appendable_data;
+mod proof;
 mod pub_appendable_data;
 mod structured_data;
+mod validation_error;
 
 use error::RoutingError;
+use maidsafe_utilities::serialisation::serialise;
 use rust_sodium::crypto::sign::{self, PublicKey, Signature};
-pub use self::append_types::{AppendWrapper, AppendedData, Filter};
+pub use self::append_types::{AppendWrapper, AppendedData, AppendedItems, AppendedRange,
+                             EvictionPolicy, Filter};
+pub use self::condition::{Condition, ContentHash, content_hash};
+pub use self::custom_data::{CustomData, DataValidator, DataValidators,
+                             MAX_CUSTOM_DATA_SIZE_IN_BYTES};
+pub use self::expiry::ExpiryTracker;
 pub use self::immutable_data::{ImmutableData, MAX_IMMUTABLE_DATA_SIZE_IN_BYTES};
+pub use self::mutable_data::{EntryAction, MAX_MUTABLE_DATA_ENTRIES,
+                             MAX_MUTABLE_DATA_SIZE_IN_BYTES, MutableData, Permission,
+                             PermissionSet, Value};
 pub use self::priv_appendable_data::{MAX_PRIV_APPENDABLE_DATA_SIZE_IN_BYTES, PrivAppendableData,
                                      PrivAppendedData};
+pub use self::proof::{DataProof, MerkleHash, MerkleProof, MerkleTree, SectionKey,
+                      root_signed_bytes};
 pub use self::pub_appendable_data::{MAX_PUB_APPENDABLE_DATA_SIZE_IN_BYTES, PubAppendableData};
-pub use self::structured_data::{MAX_STRUCTURED_DATA_SIZE_IN_BYTES, StructuredData};
+pub use self::structured_data::{MAX_STRUCTURED_DATA_HISTORY, MAX_STRUCTURED_DATA_SIZE_IN_BYTES,
+                                StructuredData};
+pub use self::validation_error::ValidationError;
 use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::{self, Debug, Formatter};
 use xor_name::XorName;
@@ -37,24 +57,81 @@ use xor_name::XorName;
 /// effectively immutable.
 pub const NO_OWNER_PUB_KEY: PublicKey = PublicKey([0; sign::PUBLICKEYBYTES]);
 
-/// Confirms *unique and valid* signatures are more than 50% of total owners.
+/// The maximum number of owners of a data item.
+pub const MAX_DATA_OWNERS: usize = 32;
+
+/// Returns the number of owner signatures required to update a data item with `owner_count`
+/// owners: `threshold` if the item declares one, otherwise at least half of the owners.
+pub fn required_signatures(owner_count: usize, threshold: Option<u64>) -> usize {
+    threshold.map_or((owner_count + 1) / 2, |threshold| threshold as usize)
+}
+
+/// Verifies that a declared signature `threshold` is at least 1 and can be met by the owners.
+pub fn validate_threshold(owner_count: usize,
+                          threshold: Option<u64>)
+                          -> Result<(), ValidationError> {
+    match threshold {
+        Some(threshold) if threshold == 0 || threshold > owner_count as u64 => {
+            Err(ValidationError::InvalidThreshold {
+                threshold: threshold,
+                owner_count: owner_count,
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Confirms *unique and valid* signatures meet the required number of owners: `threshold` if
+/// given, otherwise at least 50% of total owners.
 pub fn verify_signatures(owners: &BTreeSet<PublicKey>,
+                         threshold: Option<u64>,
                          data: &[u8],
                          signatures: &BTreeMap<PublicKey, Signature>)
-                         -> Result<(), RoutingError> {
+                         -> Result<(), ValidationError> {
     // Refuse when not enough signatures found
-    if signatures.len() < (owners.len() + 1) / 2 {
-        return Err(RoutingError::NotEnoughSignatures);
+    let required = required_signatures(owners.len(), threshold);
+    if signatures.len() < required {
+        return Err(ValidationError::NotEnoughSignatures {
+            required: required,
+            actual: signatures.len(),
+        });
     }
 
     // Refuse if there is any invalid signature
-    if !signatures.iter()
-        .all(|(pub_key, sig)| owners.contains(pub_key) && verify_detached(sig, data, pub_key)) {
-        return Err(RoutingError::FailedSignature);
+    for (pub_key, sig) in signatures {
+        if !owners.contains(pub_key) {
+            return Err(ValidationError::SignatureByNonOwner(*pub_key));
+        }
+        if !verify_detached(sig, data, pub_key) {
+            return Err(ValidationError::InvalidSignature(*pub_key));
+        }
     }
     Ok(())
 }
 
+/// An optional field of a data item's signed content.
+///
+/// Only the fields that are set are appended to the signed bytes, so that items which don't use
+/// them are signed exactly as before the fields were introduced.
+#[derive(RustcEncodable)]
+enum SignedField {
+    MaxHistory(u64),
+    Threshold(u64),
+    EvictionPolicy(EvictionPolicy),
+    Expiry(u64),
+}
+
+/// Appends the given optional fields to the serialised signed content `data`, unless there are
+/// none.
+fn append_signed_fields(mut data: Vec<u8>,
+                        fields: Vec<SignedField>)
+                        -> Result<Vec<u8>, RoutingError> {
+    if !fields.is_empty() {
+        data.extend(serialise(&fields)?);
+    }
+    Ok(data)
+}
+
 // Returns whether the signature is valid. It explicitly considers any signature for
 // `NO_OWNER_PUB_KEY` invalid.
 fn verify_detached(sig: &Signature, data: &[u8], pub_key: &PublicKey) -> bool {
@@ -72,6 +149,10 @@ pub enum Data {
     PubAppendable(PubAppendableData),
     /// `PrivAppendableData` data type.
     PrivAppendable(PrivAppendableData),
+    /// `MutableData` data type.
+    Mutable(MutableData),
+    /// Application-defined data type.
+    Custom(CustomData),
 }
 
 impl Data {
@@ -82,6 +163,8 @@ impl Data {
             Data::Immutable(ref data) => data.name(),
             Data::PubAppendable(ref data) => data.name(),
             Data::PrivAppendable(ref data) => data.name(),
+            Data::Mutable(ref data) => data.name(),
+            Data::Custom(ref data) => data.name(),
         }
     }
 
@@ -92,6 +175,45 @@ impl Data {
             Data::Immutable(ref data) => data.identifier(),
             Data::PubAppendable(ref data) => data.identifier(),
             Data::PrivAppendable(ref data) => data.identifier(),
+            Data::Mutable(ref data) => data.identifier(),
+            Data::Custom(ref data) => data.identifier(),
+        }
+    }
+
+    /// Returns the version of versioned data types, or `None` for immutable, mutable and custom
+    /// data.
+    pub fn version(&self) -> Option<u64> {
+        match *self {
+            Data::Structured(ref data) => Some(data.get_version()),
+            Data::PubAppendable(ref data) => Some(data.get_version()),
+            Data::PrivAppendable(ref data) => Some(data.get_version()),
+            Data::Immutable(_) |
+            Data::Mutable(_) |
+            Data::Custom(_) => None,
+        }
+    }
+
+    /// Returns the time (in seconds since the UNIX epoch) after which the data may be removed, if
+    /// it has one.
+    pub fn expiry(&self) -> Option<u64> {
+        match *self {
+            Data::Structured(ref data) => data.get_expiry(),
+            Data::PubAppendable(ref data) => data.expiry,
+            Data::PrivAppendable(ref data) => data.expiry,
+            Data::Immutable(_) |
+            Data::Mutable(_) |
+            Data::Custom(_) => None,
+        }
+    }
+
+    /// Returns the data without the retained previous versions of `StructuredData`. Other types
+    /// are returned unchanged.
+    pub fn without_history(self) -> Data {
+        match self {
+            Data::Structured(ref data) if !data.get_history().is_empty() => {
+                Data::Structured(data.without_history())
+            }
+            data => data,
         }
     }
 
@@ -102,6 +224,8 @@ impl Data {
             Data::PrivAppendable(ref data) => data.validate_size(),
             Data::PubAppendable(ref data) => data.validate_size(),
             Data::Structured(ref data) => data.validate_size(),
+            Data::Mutable(ref data) => data.validate_size(),
+            Data::Custom(ref data) => data.validate_size(),
         }
     }
 }
@@ -117,6 +241,13 @@ pub enum DataIdentifier {
     PubAppendable(XorName),
     /// Request for private appendable data.
     PrivAppendable(XorName),
+    /// Data request, (Identifier, TypeTag) pair for name resolution, for `MutableData`.
+    Mutable(XorName, u64),
+    /// Data request, (Identifier, TypeId) pair for name resolution, for `CustomData`.
+    Custom(XorName, u64),
+    /// Data request, (Identifier, TypeTag, Version) for a specific current or retained previous
+    /// version of a StructuredData.
+    StructuredVersion(XorName, u64, u64),
 }
 
 impl Debug for Data {
@@ -126,6 +257,8 @@ impl Debug for Data {
             Data::Immutable(ref data) => data.fmt(formatter),
             Data::PubAppendable(ref data) => data.fmt(formatter),
             Data::PrivAppendable(ref data) => data.fmt(formatter),
+            Data::Mutable(ref data) => data.fmt(formatter),
+            Data::Custom(ref data) => data.fmt(formatter),
         }
     }
 }
@@ -135,6 +268,9 @@ impl DataIdentifier {
     pub fn name(&self) -> &XorName {
         match *self {
             DataIdentifier::Structured(ref name, _) |
+            DataIdentifier::StructuredVersion(ref name, _, _) |
+            DataIdentifier::Mutable(ref name, _) |
+            DataIdentifier::Custom(ref name, _) |
             DataIdentifier::Immutable(ref name) |
             DataIdentifier::PubAppendable(ref name) |
             DataIdentifier::PrivAppendable(ref name) => name,
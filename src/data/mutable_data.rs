@@ -0,0 +1,421 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.1.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+use error::RoutingError;
+use maidsafe_utilities::serialisation::serialised_size;
+use rust_sodium::crypto::sign::PublicKey;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Debug, Formatter};
+use std::mem;
+use super::DataIdentifier;
+use xor_name::XorName;
+
+/// Maximum allowed size for a Mutable Data to grow to
+pub const MAX_MUTABLE_DATA_SIZE_IN_BYTES: u64 = 1024 * 1024;
+/// Maximum allowed number of entries in a Mutable Data
+pub const MAX_MUTABLE_DATA_ENTRIES: u64 = 1000;
+
+/// A value in a `MutableData`, together with its version.
+#[derive(Hash, Eq, PartialEq, PartialOrd, Ord, Clone, Debug, RustcDecodable, RustcEncodable)]
+pub struct Value {
+    /// The actual content of the entry.
+    pub content: Vec<u8>,
+    /// The version of the entry, incremented by one with every update.
+    pub entry_version: u64,
+}
+
+/// A kind of mutation a key can be permitted to perform on a `MutableData`.
+#[derive(Hash, Eq, PartialEq, PartialOrd, Ord, Clone, Copy, Debug, RustcDecodable,
+         RustcEncodable)]
+pub enum Permission {
+    /// Inserting new entries.
+    Insert,
+    /// Updating existing entries.
+    Update,
+    /// Deleting existing entries.
+    Delete,
+    /// Changing the permissions of any key.
+    ManagePermissions,
+}
+
+/// The set of mutations a key is permitted to perform on a `MutableData`.
+#[derive(Hash, Eq, PartialEq, PartialOrd, Ord, Clone, Copy, Debug, Default, RustcDecodable,
+         RustcEncodable)]
+pub struct PermissionSet {
+    insert: bool,
+    update: bool,
+    delete: bool,
+    manage_permissions: bool,
+}
+
+impl PermissionSet {
+    /// Creates a set that doesn't permit anything.
+    pub fn new() -> PermissionSet {
+        Default::default()
+    }
+
+    /// Returns the set with the given permission added.
+    pub fn allow(mut self, permission: Permission) -> PermissionSet {
+        match permission {
+            Permission::Insert => self.insert = true,
+            Permission::Update => self.update = true,
+            Permission::Delete => self.delete = true,
+            Permission::ManagePermissions => self.manage_permissions = true,
+        }
+        self
+    }
+
+    /// Returns whether the set contains the given permission.
+    pub fn is_allowed(&self, permission: Permission) -> bool {
+        match permission {
+            Permission::Insert => self.insert,
+            Permission::Update => self.update,
+            Permission::Delete => self.delete,
+            Permission::ManagePermissions => self.manage_permissions,
+        }
+    }
+}
+
+/// A mutation of a single entry of a `MutableData`.
+#[derive(Hash, Eq, PartialEq, PartialOrd, Ord, Clone, Debug, RustcDecodable, RustcEncodable)]
+pub enum EntryAction {
+    /// Inserts a new entry. Its version must be 0, or if the entry has been deleted before, the
+    /// version of the deletion plus one.
+    Insert(Value),
+    /// Updates an existing entry. The version must be the current one plus one.
+    Update(Value),
+    /// Deletes an existing entry. The argument must be the current version plus one.
+    Delete(u64),
+}
+
+impl EntryAction {
+    fn permission(&self) -> Permission {
+        match *self {
+            EntryAction::Insert(_) => Permission::Insert,
+            EntryAction::Update(_) => Permission::Update,
+            EntryAction::Delete(_) => Permission::Delete,
+        }
+    }
+}
+
+/// Mutable data consisting of individually versioned key/value entries.
+///
+/// Unlike `StructuredData`, this is not replaced as a whole: entries are inserted, updated and
+/// deleted one at a time by any key that has the corresponding permission. The owner is
+/// permitted everything.
+///
+/// Deleted entries leave a tombstone with their last version, so that their versions keep
+/// increasing if they are inserted again, and old signed mutations can't be replayed.
+#[derive(Hash, Eq, PartialEq, PartialOrd, Ord, Clone, RustcDecodable, RustcEncodable)]
+pub struct MutableData {
+    name: XorName,
+    tag: u64,
+    entries: BTreeMap<Vec<u8>, Value>,
+    /// The versions of the deleted entries.
+    tombstones: BTreeMap<Vec<u8>, u64>,
+    permissions: BTreeMap<PublicKey, PermissionSet>,
+    permissions_version: u64,
+    owners: BTreeSet<PublicKey>,
+}
+
+impl MutableData {
+    /// Creates a new `MutableData`.
+    pub fn new(name: XorName,
+               tag: u64,
+               permissions: BTreeMap<PublicKey, PermissionSet>,
+               entries: BTreeMap<Vec<u8>, Value>,
+               owners: BTreeSet<PublicKey>)
+               -> Result<MutableData, RoutingError> {
+        if owners.len() > 1 {
+            return Err(RoutingError::InvalidOwners);
+        }
+
+        let data = MutableData {
+            name: name,
+            tag: tag,
+            entries: entries,
+            tombstones: BTreeMap::new(),
+            permissions: permissions,
+            permissions_version: 0,
+            owners: owners,
+        };
+        if !data.validate_size() {
+            return Err(RoutingError::ExceededSizeLimit);
+        }
+        Ok(data)
+    }
+
+    /// Returns the name.
+    pub fn name(&self) -> &XorName {
+        &self.name
+    }
+
+    /// Returns the type tag.
+    pub fn tag(&self) -> u64 {
+        self.tag
+    }
+
+    /// Returns `DataIdentifier` for this data element.
+    pub fn identifier(&self) -> DataIdentifier {
+        DataIdentifier::Mutable(self.name, self.tag)
+    }
+
+    /// Returns the value of the entry with the given key, if any.
+    pub fn get(&self, key: &[u8]) -> Option<&Value> {
+        self.entries.get(key)
+    }
+
+    /// Returns all entries.
+    pub fn entries(&self) -> &BTreeMap<Vec<u8>, Value> {
+        &self.entries
+    }
+
+    /// Returns the keys of the deleted entries, with the version of their deletion.
+    pub fn tombstones(&self) -> &BTreeMap<Vec<u8>, u64> {
+        &self.tombstones
+    }
+
+    /// Returns the permissions of all keys other than the owner.
+    pub fn permissions(&self) -> &BTreeMap<PublicKey, PermissionSet> {
+        &self.permissions
+    }
+
+    /// Returns the version of the permissions, incremented by one with every change.
+    pub fn permissions_version(&self) -> u64 {
+        self.permissions_version
+    }
+
+    /// Returns the current owner keys.
+    pub fn owners(&self) -> &BTreeSet<PublicKey> {
+        &self.owners
+    }
+
+    /// Returns whether `key` is permitted to perform the given kind of mutation.
+    pub fn is_allowed(&self, key: &PublicKey, permission: Permission) -> bool {
+        self.owners.contains(key) ||
+        self.permissions.get(key).map_or(false, |set| set.is_allowed(permission))
+    }
+
+    /// Applies the given entry mutations on behalf of `requester`, if all of them are permitted
+    /// and valid and the data doesn't exceed the size limits afterwards. Otherwise returns an
+    /// error and leaves the data unchanged.
+    pub fn mutate_entries(&mut self,
+                          actions: BTreeMap<Vec<u8>, EntryAction>,
+                          requester: &PublicKey)
+                          -> Result<(), RoutingError> {
+        if !actions.values().all(|action| self.is_allowed(requester, action.permission())) {
+            return Err(RoutingError::AccessDenied);
+        }
+
+        let mut entries = self.entries.clone();
+        let mut tombstones = self.tombstones.clone();
+        for (key, action) in actions {
+            let current_version = entries.get(&key).map(|value| value.entry_version);
+            let valid = match (&action, current_version) {
+                (&EntryAction::Insert(ref value), None) => {
+                    let deleted_version = tombstones.get(&key).cloned();
+                    value.entry_version == deleted_version.map_or(0, |version| version + 1)
+                }
+                (&EntryAction::Update(ref value), Some(version)) => {
+                    value.entry_version == version + 1
+                }
+                (&EntryAction::Delete(new_version), Some(version)) => new_version == version + 1,
+                _ => false,
+            };
+            if !valid {
+                return Err(RoutingError::InvalidEntryActions);
+            }
+            match action {
+                EntryAction::Insert(value) |
+                EntryAction::Update(value) => {
+                    let _ = tombstones.remove(&key);
+                    let _ = entries.insert(key, value);
+                }
+                EntryAction::Delete(version) => {
+                    let _ = entries.remove(&key);
+                    let _ = tombstones.insert(key, version);
+                }
+            }
+        }
+
+        let old_entries = mem::replace(&mut self.entries, entries);
+        let old_tombstones = mem::replace(&mut self.tombstones, tombstones);
+        if !self.validate_size() {
+            self.entries = old_entries;
+            self.tombstones = old_tombstones;
+            return Err(RoutingError::ExceededSizeLimit);
+        }
+        Ok(())
+    }
+
+    /// Sets the permissions of `user` on behalf of `requester`, or removes them if `permissions`
+    /// is `None`. The `version` must be the current permissions version plus one.
+    pub fn set_user_permissions(&mut self,
+                                user: PublicKey,
+                                permissions: Option<PermissionSet>,
+                                version: u64,
+                                requester: &PublicKey)
+                                -> Result<(), RoutingError> {
+        if !self.is_allowed(requester, Permission::ManagePermissions) {
+            return Err(RoutingError::AccessDenied);
+        }
+        if version != self.permissions_version + 1 {
+            return Err(RoutingError::InvalidPermissionsVersion);
+        }
+        let _ = match permissions {
+            Some(permissions) => self.permissions.insert(user, permissions),
+            None => self.permissions.remove(&user),
+        };
+        self.permissions_version = version;
+        Ok(())
+    }
+
+    /// Return true if the size and the number of entries are valid
+    pub fn validate_size(&self) -> bool {
+        self.entries.len() as u64 <= MAX_MUTABLE_DATA_ENTRIES &&
+        serialised_size(self) <= MAX_MUTABLE_DATA_SIZE_IN_BYTES
+    }
+}
+
+impl Debug for MutableData {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter,
+               "MutableData {{ name: {}, tag: {}, entries: {}, tombstones: {}, permissions: {:?}, \
+                permissions_version: {}, owners: {:?} }}",
+               self.name(),
+               self.tag,
+               self.entries.len(),
+               self.tombstones.len(),
+               self.permissions,
+               self.permissions_version,
+               self.owners)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use error::RoutingError;
+    use rand;
+    use rust_sodium::crypto::sign;
+    use std::collections::{BTreeMap, BTreeSet};
+    use super::*;
+
+    fn value(content: &[u8], entry_version: u64) -> Value {
+        Value {
+            content: content.to_vec(),
+            entry_version: entry_version,
+        }
+    }
+
+    fn single_action(key: &[u8], action: EntryAction) -> BTreeMap<Vec<u8>, EntryAction> {
+        let mut actions = BTreeMap::new();
+        let _ = actions.insert(key.to_vec(), action);
+        actions
+    }
+
+    #[test]
+    fn entry_mutations() {
+        let (owner, _) = sign::gen_keypair();
+        let mut owners = BTreeSet::new();
+        let _ = owners.insert(owner);
+        let mut data =
+            unwrap!(MutableData::new(rand::random(), 0, BTreeMap::new(), BTreeMap::new(), owners));
+
+        let insert = single_action(b"key", EntryAction::Insert(value(b"a", 0)));
+        unwrap!(data.mutate_entries(insert.clone(), &owner));
+        assert_eq!(data.get(b"key"), Some(&value(b"a", 0)));
+
+        // Inserting an existing key or skipping a version fails and changes nothing.
+        match data.mutate_entries(insert, &owner) {
+            Err(RoutingError::InvalidEntryActions) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        let update = single_action(b"key", EntryAction::Update(value(b"b", 2)));
+        assert!(data.mutate_entries(update, &owner).is_err());
+        assert_eq!(data.get(b"key"), Some(&value(b"a", 0)));
+
+        let update = single_action(b"key", EntryAction::Update(value(b"b", 1)));
+        unwrap!(data.mutate_entries(update, &owner));
+        assert_eq!(data.get(b"key"), Some(&value(b"b", 1)));
+
+        let delete = single_action(b"key", EntryAction::Delete(2));
+        unwrap!(data.mutate_entries(delete, &owner));
+        assert!(data.entries().is_empty());
+        assert_eq!(data.tombstones().get(&b"key"[..]), Some(&2));
+
+        // The original insert can't be replayed: the key's versions continue after the deletion.
+        let insert = single_action(b"key", EntryAction::Insert(value(b"a", 0)));
+        match data.mutate_entries(insert, &owner) {
+            Err(RoutingError::InvalidEntryActions) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        let insert = single_action(b"key", EntryAction::Insert(value(b"c", 3)));
+        unwrap!(data.mutate_entries(insert, &owner));
+        assert_eq!(data.get(b"key"), Some(&value(b"c", 3)));
+        assert!(data.tombstones().is_empty());
+    }
+
+    #[test]
+    fn permissions() {
+        let (owner, _) = sign::gen_keypair();
+        let (user, _) = sign::gen_keypair();
+        let mut owners = BTreeSet::new();
+        let _ = owners.insert(owner);
+        let mut data =
+            unwrap!(MutableData::new(rand::random(), 0, BTreeMap::new(), BTreeMap::new(), owners));
+
+        let insert = single_action(b"key", EntryAction::Insert(value(b"a", 0)));
+        match data.mutate_entries(insert.clone(), &user) {
+            Err(RoutingError::AccessDenied) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+
+        let permissions = PermissionSet::new().allow(Permission::Insert);
+        assert!(data.set_user_permissions(user, Some(permissions), 1, &user).is_err());
+        match data.set_user_permissions(user, Some(permissions), 2, &owner) {
+            Err(RoutingError::InvalidPermissionsVersion) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        unwrap!(data.set_user_permissions(user, Some(permissions), 1, &owner));
+        assert_eq!(data.permissions_version(), 1);
+
+        unwrap!(data.mutate_entries(insert, &user));
+        let delete = single_action(b"key", EntryAction::Delete(1));
+        assert!(data.mutate_entries(delete.clone(), &user).is_err());
+
+        unwrap!(data.set_user_permissions(user, None, 2, &owner));
+        assert!(!data.is_allowed(&user, Permission::Insert));
+        unwrap!(data.mutate_entries(delete, &owner));
+    }
+
+    #[test]
+    fn size_limits() {
+        let (owner, _) = sign::gen_keypair();
+        let mut owners = BTreeSet::new();
+        let _ = owners.insert(owner);
+        let mut data =
+            unwrap!(MutableData::new(rand::random(), 0, BTreeMap::new(), BTreeMap::new(), owners));
+
+        let actions = (0..MAX_MUTABLE_DATA_ENTRIES + 1)
+            .map(|i| (i.to_string().into_bytes(), EntryAction::Insert(value(b"a", 0))))
+            .collect();
+        match data.mutate_entries(actions, &owner) {
+            Err(RoutingError::ExceededSizeLimit) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert!(data.entries().is_empty());
+    }
+}
@@ -0,0 +1,359 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.1.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+//! Proofs that a data item is held by the section responsible for it.
+//!
+//! The members of a section compute a Merkle tree over all data items they hold as `NaeManager`
+//! and sign its root, together with a version that increases whenever the held data changes. A
+//! `DataProof` ties a single item to that root, and the root to a quorum of a `SectionKey`, i.e.
+//! the section's members as known to the client. A client can thus check a
+//! `GetSuccessWithProof` response without trusting the proxy that relayed it, and reject proofs
+//! for an older version than one it has already seen.
+
+use super::{Data, DataIdentifier};
+use QUORUM;
+use error::RoutingError;
+use id::PublicId;
+use maidsafe_utilities::serialisation::serialise;
+use routing_table::Prefix;
+use rust_sodium::crypto::hash::sha256;
+use rust_sodium::crypto::sign::{self, Signature};
+use std::collections::{BTreeMap, BTreeSet};
+use xor_name::XorName;
+
+/// The hash of a Merkle tree node.
+pub type MerkleHash = [u8; sha256::DIGESTBYTES];
+
+const LEAF_TAG: u8 = 0;
+const NODE_TAG: u8 = 1;
+
+/// A Merkle tree over a set of data items, ordered by their identifiers.
+pub struct MerkleTree {
+    ids: Vec<DataIdentifier>,
+    // The hashes of each level, starting with the leaves.
+    levels: Vec<Vec<MerkleHash>>,
+}
+
+impl MerkleTree {
+    /// Builds the tree over the given data items.
+    pub fn new<'a, I: IntoIterator<Item = &'a Data>>(data: I) -> Result<MerkleTree, RoutingError> {
+        let mut leaves = Vec::new();
+        for item in data {
+            leaves.push((item.identifier(), leaf_hash(item)?));
+        }
+        leaves.sort_by(|lhs, rhs| lhs.0.cmp(&rhs.0));
+        let (ids, hashes): (Vec<_>, Vec<_>) = leaves.into_iter().unzip();
+        let mut levels = vec![hashes];
+        while levels[levels.len() - 1].len() > 1 {
+            let next = levels[levels.len() - 1]
+                .chunks(2)
+                .map(|pair| if pair.len() == 2 {
+                    node_hash(&pair[0], &pair[1])
+                } else {
+                    pair[0]
+                })
+                .collect();
+            levels.push(next);
+        }
+        Ok(MerkleTree {
+            ids: ids,
+            levels: levels,
+        })
+    }
+
+    /// Returns the root hash. The root of an empty tree is the hash of the empty string.
+    pub fn root(&self) -> MerkleHash {
+        self.levels[self.levels.len() - 1].first().cloned().unwrap_or_else(|| sha256::hash(&[]).0)
+    }
+
+    /// Returns the proof for the item with the given identifier, or `None` if it is not in the
+    /// tree.
+    pub fn proof(&self, data_id: &DataIdentifier) -> Option<MerkleProof> {
+        let leaf_index = match self.ids.binary_search(data_id) {
+            Ok(index) => index,
+            Err(_) => return None,
+        };
+        let mut index = leaf_index;
+        let mut path = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(sibling) = level.get(index ^ 1) {
+                path.push(*sibling);
+            }
+            index /= 2;
+        }
+        Some(MerkleProof {
+            index: leaf_index as u64,
+            leaf_count: self.ids.len() as u64,
+            path: path,
+        })
+    }
+}
+
+/// The path from a data item's leaf to the root of a `MerkleTree`.
+#[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Hash, RustcEncodable, RustcDecodable, Debug)]
+pub struct MerkleProof {
+    index: u64,
+    leaf_count: u64,
+    path: Vec<MerkleHash>,
+}
+
+impl MerkleProof {
+    /// Returns the root of the tree `data` is part of, according to this proof.
+    pub fn root(&self, data: &Data) -> Result<MerkleHash, RoutingError> {
+        if self.index >= self.leaf_count {
+            return Err(RoutingError::InvalidDataProof);
+        }
+        let mut hash = leaf_hash(data)?;
+        let mut index = self.index;
+        let mut count = self.leaf_count;
+        let mut path = self.path.iter();
+        while count > 1 {
+            if (index ^ 1) < count {
+                let sibling = path.next().ok_or(RoutingError::InvalidDataProof)?;
+                hash = if index % 2 == 0 {
+                    node_hash(&hash, sibling)
+                } else {
+                    node_hash(sibling, &hash)
+                };
+            }
+            index /= 2;
+            count = (count + 1) / 2;
+        }
+        if path.next().is_some() {
+            return Err(RoutingError::InvalidDataProof);
+        }
+        Ok(hash)
+    }
+}
+
+/// The members of a section, which a client trusts to vouch for the data the section holds.
+///
+/// The client needs to obtain it independently of the proofs it verifies with it, e.g. from a
+/// node it trusts.
+#[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Hash, RustcEncodable, RustcDecodable, Debug)]
+pub struct SectionKey {
+    prefix: Prefix<XorName>,
+    members: BTreeSet<PublicId>,
+}
+
+impl SectionKey {
+    /// Creates the key of the section with the given prefix and members.
+    pub fn new(prefix: Prefix<XorName>, members: BTreeSet<PublicId>) -> SectionKey {
+        SectionKey {
+            prefix: prefix,
+            members: members,
+        }
+    }
+
+    /// Returns the prefix of the section.
+    pub fn prefix(&self) -> &Prefix<XorName> {
+        &self.prefix
+    }
+
+    /// Returns the members of the section.
+    pub fn members(&self) -> &BTreeSet<PublicId> {
+        &self.members
+    }
+}
+
+/// A proof that a data item is held by the section with the given prefix.
+#[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Hash, RustcEncodable, RustcDecodable, Debug)]
+pub struct DataProof {
+    prefix: Prefix<XorName>,
+    version: u64,
+    merkle_proof: MerkleProof,
+    root: MerkleHash,
+    root_signatures: BTreeMap<PublicId, Signature>,
+}
+
+impl DataProof {
+    /// Creates a proof from a Merkle proof and the signatures of the section's members over
+    /// `(prefix, version, root)`.
+    pub fn new(prefix: Prefix<XorName>,
+               version: u64,
+               merkle_proof: MerkleProof,
+               root: MerkleHash,
+               root_signatures: BTreeMap<PublicId, Signature>)
+               -> DataProof {
+        DataProof {
+            prefix: prefix,
+            version: version,
+            merkle_proof: merkle_proof,
+            root: root,
+            root_signatures: root_signatures,
+        }
+    }
+
+    /// Returns the prefix of the section vouching for the data.
+    pub fn prefix(&self) -> &Prefix<XorName> {
+        &self.prefix
+    }
+
+    /// Returns the version of the section's data the proof refers to.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Returns the Merkle root signed by the section.
+    pub fn root(&self) -> &MerkleHash {
+        &self.root
+    }
+
+    /// Verifies that `data` belongs to the section's prefix, that the proof's version is at least
+    /// `min_version`, e.g. the version of the last proof accepted from this section, that the
+    /// Merkle root is signed by a quorum of `section_key`, and that `data` is part of the tree
+    /// with that root.
+    pub fn verify(&self,
+                  data: &Data,
+                  section_key: &SectionKey,
+                  min_version: u64)
+                  -> Result<(), RoutingError> {
+        if !self.prefix.matches(data.name()) || self.version < min_version {
+            return Err(RoutingError::InvalidDataProof);
+        }
+        self.verify_signatures(section_key)?;
+        if self.merkle_proof.root(data)? != self.root {
+            return Err(RoutingError::InvalidDataProof);
+        }
+        Ok(())
+    }
+
+    /// Verifies that the Merkle root is signed by a quorum of `section_key`.
+    pub fn verify_signatures(&self, section_key: &SectionKey) -> Result<(), RoutingError> {
+        if !section_key.prefix.is_compatible(&self.prefix) {
+            return Err(RoutingError::InvalidDataProof);
+        }
+        check_quorum(&section_key.members,
+                     &self.root_signatures,
+                     &root_signed_bytes(&self.prefix, self.version, &self.root)?)
+    }
+}
+
+/// Returns the bytes a section member signs to vouch for the Merkle `root` of the given
+/// `version` of its section's data.
+pub fn root_signed_bytes(prefix: &Prefix<XorName>,
+                         version: u64,
+                         root: &MerkleHash)
+                         -> Result<Vec<u8>, RoutingError> {
+    Ok(serialise(&(prefix, version, root))?)
+}
+
+fn check_quorum(members: &BTreeSet<PublicId>,
+                signatures: &BTreeMap<PublicId, Signature>,
+                signed_bytes: &[u8])
+                -> Result<(), RoutingError> {
+    let valid_count = signatures.iter()
+        .filter(|&(pub_id, sig)| {
+            members.contains(pub_id) &&
+            sign::verify_detached(sig, signed_bytes, pub_id.signing_public_key())
+        })
+        .count();
+    if members.is_empty() || valid_count * 100 < QUORUM * members.len() {
+        return Err(RoutingError::NotEnoughSignatures);
+    }
+    Ok(())
+}
+
+fn leaf_hash(data: &Data) -> Result<MerkleHash, RoutingError> {
+    let mut bytes = vec![LEAF_TAG];
+    bytes.extend_from_slice(&serialise(data)?);
+    Ok(sha256::hash(&bytes).0)
+}
+
+fn node_hash(left: &MerkleHash, right: &MerkleHash) -> MerkleHash {
+    let mut bytes = Vec::with_capacity(1 + 2 * sha256::DIGESTBYTES);
+    bytes.push(NODE_TAG);
+    bytes.extend_from_slice(left);
+    bytes.extend_from_slice(right);
+    sha256::hash(&bytes).0
+}
+
+#[cfg(test)]
+mod tests {
+    use data::{Data, ImmutableData};
+    use error::RoutingError;
+    use id::FullId;
+    use rand;
+    use routing_table::Prefix;
+    use rust_sodium::crypto::sign;
+    use std::collections::BTreeMap;
+    use super::*;
+
+    fn gen_data(count: usize) -> Vec<Data> {
+        (0..count)
+            .map(|_| Data::Immutable(ImmutableData::new(rand::random::<[u8; 16]>().to_vec())))
+            .collect()
+    }
+
+    #[test]
+    fn merkle_proofs() {
+        for count in 1..10 {
+            let data = gen_data(count);
+            let tree = unwrap!(MerkleTree::new(&data));
+            for item in &data {
+                let proof = unwrap!(tree.proof(&item.identifier()));
+                assert_eq!(unwrap!(proof.root(item)), tree.root());
+            }
+            let other = gen_data(1);
+            assert!(tree.proof(&other[0].identifier()).is_none());
+            let proof = unwrap!(tree.proof(&data[0].identifier()));
+            assert!(proof.root(&other[0]).ok() != Some(tree.root()));
+        }
+    }
+
+    #[test]
+    fn data_proof() {
+        let data = gen_data(5);
+        let tree = unwrap!(MerkleTree::new(&data));
+        let prefix = Prefix::new(0, *data[0].name());
+        let full_ids: Vec<_> = (0..5).map(|_| FullId::new()).collect();
+        let members = full_ids.iter().map(|id| *id.public_id()).collect();
+        let section_key = SectionKey::new(prefix, members);
+        let sign_all = |bytes: &[u8], count: usize| -> BTreeMap<_, _> {
+            full_ids.iter()
+                .take(count)
+                .map(|id| (*id.public_id(), sign::sign_detached(bytes, id.signing_private_key())))
+                .collect()
+        };
+        let root_bytes = unwrap!(root_signed_bytes(&prefix, 3, &tree.root()));
+
+        let merkle_proof = unwrap!(tree.proof(&data[2].identifier()));
+        let proof = DataProof::new(prefix,
+                                   3,
+                                   merkle_proof.clone(),
+                                   tree.root(),
+                                   sign_all(&root_bytes, 3));
+        unwrap!(proof.verify(&data[2], &section_key, 3));
+        assert!(proof.verify(&data[3], &section_key, 3).is_err());
+
+        // Proofs older than the given version are rejected.
+        assert!(proof.verify(&data[2], &section_key, 4).is_err());
+
+        // Signatures by members the client doesn't know are rejected.
+        let other_members = (0..5).map(|_| *FullId::new().public_id()).collect();
+        match proof.verify(&data[2], &SectionKey::new(prefix, other_members), 3) {
+            Err(RoutingError::NotEnoughSignatures) => (),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+
+        // Signatures by less than a quorum of the section are rejected.
+        let proof = DataProof::new(prefix, 3, merkle_proof, tree.root(), sign_all(&root_bytes, 2));
+        match proof.verify(&data[2], &section_key, 3) {
+            Err(RoutingError::NotEnoughSignatures) => (),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+}
@@ -18,9 +18,12 @@
 use error::RoutingError;
 use maidsafe_utilities::serialisation::{serialise, serialised_size};
 use rust_sodium::crypto::sign::{self, PublicKey, SecretKey, Signature};
+use std::cmp;
 use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::{self, Debug, Formatter};
-use super::{AppendWrapper, AppendedData, DataIdentifier, Filter, NO_OWNER_PUB_KEY};
+use super::{AppendWrapper, AppendedData, AppendedRange, DataIdentifier, EvictionPolicy, Filter,
+            MAX_DATA_OWNERS, NO_OWNER_PUB_KEY, SignedField, ValidationError};
+use super::append_types;
 use xor_name::XorName;
 
 /// Maximum allowed size for a public appendable data to grow to
@@ -45,11 +48,21 @@ pub struct PubAppendableData {
     pub deleted_data: BTreeSet<AppendedData>,
     /// The pub_keys of the current owners of the chunk's.
     pub owners: BTreeSet<PublicKey>,
+    /// The number of owners that need to sign an update. If `None`, at least half of them.
+    pub threshold: Option<u64>,
+    /// Time (in seconds since the UNIX epoch) after which the data may be removed, if any.
+    pub expiry: Option<u64>,
     /// The pub_keys and signatures of the owners of the chunk's current version.
     pub signatures: BTreeMap<PublicKey, Signature>,
     /// The collection of appended data items. These are not signed by the owners, as they change
     /// even between `Post`s.
     pub data: BTreeSet<AppendedData>,
+    /// What happens to appended items when the size limit is reached.
+    pub eviction_policy: EvictionPolicy,
+    /// The append sequence number of each item in `data`, in the same order as `data`.
+    pub append_seqs: Vec<u64>,
+    /// The append sequence number of the next appended item.
+    pub next_append_seq: u64,
 }
 
 impl PubAppendableData {
@@ -60,7 +73,7 @@ impl PubAppendableData {
                deleted_data: BTreeSet<AppendedData>,
                filter: Filter)
                -> Result<PubAppendableData, RoutingError> {
-        if owners.len() > 1 {
+        if owners.len() > MAX_DATA_OWNERS {
             return Err(RoutingError::InvalidOwners);
         }
 
@@ -70,8 +83,13 @@ impl PubAppendableData {
             filter: filter,
             deleted_data: deleted_data,
             owners: owners,
+            threshold: None,
+            expiry: None,
             signatures: BTreeMap::new(),
             data: BTreeSet::new(),
+            eviction_policy: EvictionPolicy::default(),
+            append_seqs: Vec::new(),
+            next_append_seq: 0,
         })
     }
 
@@ -90,20 +108,25 @@ impl PubAppendableData {
         self.name = other.name;
         self.version = other.version;
         self.filter = other.filter;
+        self.eviction_policy = other.eviction_policy;
         self.deleted_data = other.deleted_data;
         self.owners = other.owners;
+        self.threshold = other.threshold;
+        self.expiry = other.expiry;
         self.signatures = other.signatures;
-        self.data.extend(other.data);
+        for (seq, ad) in append_types::ordered_items(&other.data, &other.append_seqs) {
+            let _ = append_types::insert_item(&mut self.data, &mut self.append_seqs, ad, seq);
+        }
         for ad in &self.deleted_data {
-            if self.data.contains(ad) {
-                let _remove = self.data.remove(ad);
-            }
+            let _remove = append_types::remove_item(&mut self.data, &mut self.append_seqs, ad);
         }
+        self.next_append_seq = cmp::max(self.next_append_seq, other.next_append_seq);
         Ok(())
     }
 
-    /// Inserts the given data item, or returns `false` if it cannot be added because it has
-    /// recently been deleted.
+    /// Inserts the given data item with the next append sequence number, or returns `false` if
+    /// it cannot be added because it has recently been deleted, is already present, or the size
+    /// limit is reached and the eviction policy doesn't make room for it.
     pub fn append(&mut self, appended_data: AppendedData) -> bool {
         if match self.filter {
             Filter::WhiteList(ref white_list) => !white_list.contains(&appended_data.sign_key),
@@ -111,7 +134,30 @@ impl PubAppendableData {
         } || self.deleted_data.contains(&appended_data) {
             return false;
         }
-        self.data.insert(appended_data)
+        if !append_types::insert_item(&mut self.data,
+                                      &mut self.append_seqs,
+                                      appended_data.clone(),
+                                      self.next_append_seq) {
+            return false;
+        }
+        while !self.validate_size() {
+            let evicted = append_types::evictable_item(&self.data,
+                                                       &self.append_seqs,
+                                                       self.eviction_policy,
+                                                       &appended_data);
+            let item = match evicted {
+                Some(item) => item,
+                None => {
+                    let _ = append_types::remove_item(&mut self.data,
+                                                      &mut self.append_seqs,
+                                                      &appended_data);
+                    return false;
+                }
+            };
+            let _ = append_types::remove_item(&mut self.data, &mut self.append_seqs, &item);
+        }
+        self.next_append_seq += 1;
+        true
     }
 
     /// Inserts the given wrapper item, or returns `false` if cannot
@@ -145,16 +191,30 @@ impl PubAppendableData {
     pub fn validate_self_against_successor(&self,
                                            other: &PubAppendableData)
                                            -> Result<(), RoutingError> {
-        if other.owners.len() > 1 || other.signatures.len() > 1 ||
-           self.owners.contains(&NO_OWNER_PUB_KEY) {
-            return Err(RoutingError::InvalidOwners);
+        Ok(self.validate_successor(other)?)
+    }
+
+    /// Like `validate_self_against_successor`, but returns the specific rule `other` violates.
+    pub fn validate_successor(&self, other: &PubAppendableData) -> Result<(), ValidationError> {
+        if other.owners.len() > MAX_DATA_OWNERS {
+            return Err(ValidationError::TooManyOwners);
+        }
+        super::validate_threshold(other.owners.len(), other.threshold)?;
+        if self.owners.contains(&NO_OWNER_PUB_KEY) {
+            return Err(ValidationError::NoOwnerKey);
         }
 
-        if other.name != self.name || other.version != self.version + 1 {
-            return Err(RoutingError::UnknownMessageType);
+        if other.name != self.name {
+            return Err(ValidationError::IdentifierChanged);
         }
-        let data = other.data_to_sign()?;
-        super::verify_signatures(&self.owners, &data, &other.signatures)
+        if other.version != self.version + 1 {
+            return Err(ValidationError::WrongVersion {
+                expected: self.version + 1,
+                actual: other.version,
+            });
+        }
+        let data = other.data_to_sign().map_err(|_| ValidationError::Serialisation)?;
+        super::verify_signatures(&self.owners, self.threshold, &data, &other.signatures)
     }
 
     fn data_to_sign(&self) -> Result<Vec<u8>, RoutingError> {
@@ -167,26 +227,44 @@ impl PubAppendableData {
             filter: &self.filter,
             deleted_data: &self.deleted_data,
         };
+        let mut fields = vec![];
+        if let Some(threshold) = self.threshold {
+            fields.push(SignedField::Threshold(threshold));
+        }
+        if self.eviction_policy != EvictionPolicy::OwnerManaged {
+            fields.push(SignedField::EvictionPolicy(self.eviction_policy));
+        }
+        if let Some(expiry) = self.expiry {
+            fields.push(SignedField::Expiry(expiry));
+        }
 
-        serialise(&sd).map_err(From::from)
+        super::append_signed_fields(serialise(&sd)?, fields)
     }
 
     /// Adds a signature with the given `keys.1` to the `signatures` and returns
-    /// the number of signatures that are still required. If more than 50% of the owners
-    /// have signed, 0 is returned and validation is complete.
+    /// the number of signatures that are still required. If the owners' signature threshold is
+    /// met, 0 is returned and validation is complete.
     pub fn add_signature(&mut self, keys: &(PublicKey, SecretKey)) -> Result<usize, RoutingError> {
-        if !self.signatures.is_empty() {
-            return Err(RoutingError::InvalidOwners);
-        }
         let data = self.data_to_sign()?;
         let sig = sign::sign_detached(&data, &keys.1);
 
         if self.signatures.insert(keys.0, sig).is_none() {
-            return Ok(((self.owners.len() / 2) + 1).saturating_sub(self.signatures.len()));
+            let required = super::required_signatures(self.owners.len(), self.threshold);
+            return Ok(required.saturating_sub(self.signatures.len()));
         }
         Err(RoutingError::FailedSignature)
     }
 
+    /// Declares the number of owners that need to sign an update, e.g. 2 for 2-of-3 ownership.
+    /// If `None`, at least half of the owners need to sign.
+    ///
+    /// The threshold is part of the signed content, so this needs to be called before signing.
+    pub fn set_threshold(&mut self, threshold: Option<u64>) -> Result<(), ValidationError> {
+        super::validate_threshold(self.owners.len(), threshold)?;
+        self.threshold = threshold;
+        Ok(())
+    }
+
     /// Overwrite any existing signatures with the new signatures provided.
     pub fn replace_signatures(&mut self, new_signatures: BTreeMap<PublicKey, Signature>) {
         self.signatures = new_signatures;
@@ -197,6 +275,18 @@ impl PubAppendableData {
         &self.data
     }
 
+    /// Returns the items in the given `range`, with their append sequence numbers, ordered from
+    /// oldest to newest.
+    pub fn get_page(&self, range: &AppendedRange) -> Vec<(u64, AppendedData)> {
+        append_types::page(&self.data, &self.append_seqs, range)
+    }
+
+    /// Returns `true` if the data has an expiry time not later than `now` (in seconds since the
+    /// UNIX epoch).
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expiry.map_or(false, |expiry| expiry <= now)
+    }
+
     /// Get the version
     pub fn get_version(&self) -> u64 {
         self.version
@@ -241,7 +331,8 @@ struct SerialisablePubAppendableData<'a> {
 
 #[cfg(test)]
 mod test {
-    use data::{self, AppendWrapper, AppendedData, DataIdentifier, Filter};
+    use data::{self, AppendWrapper, AppendedData, AppendedRange, DataIdentifier, EvictionPolicy,
+               Filter};
     use rand;
     use rust_sodium::crypto::sign;
     use std::collections::BTreeSet;
@@ -265,11 +356,13 @@ mod test {
                     Err(error) => panic!("Error: {:?}", error),
                 };
                 assert!(data::verify_signatures(&owner_keys,
+                                                None,
                                                 &data,
                                                 pub_appendable_data.get_signatures())
                     .is_err());
                 assert!(pub_appendable_data.add_signature(&keys).is_ok());
                 assert!(data::verify_signatures(&owner_keys,
+                                                None,
                                                 &data,
                                                 pub_appendable_data.get_signatures())
                     .is_ok());
@@ -297,6 +390,7 @@ mod test {
                     Err(error) => panic!("Error: {:?}", error),
                 };
                 assert!(data::verify_signatures(&owner_keys,
+                                                None,
                                                 &data,
                                                 pub_appendable_data.get_signatures())
                     .is_err());
@@ -396,4 +490,48 @@ mod test {
         assert!(ad_fail.add_signature(&keys).is_ok());
         assert!(ad.update_with_other(ad_fail).is_err());
     }
+
+    #[test]
+    fn eviction_and_pagination() {
+        let keys = sign::gen_keypair();
+        let mut ad = unwrap!(PubAppendableData::new(rand::random(),
+                                                    0,
+                                                    BTreeSet::new(),
+                                                    BTreeSet::new(),
+                                                    Filter::black_list(None)));
+        let new_item = || {
+            let pointer = DataIdentifier::Immutable(rand::random());
+            unwrap!(AppendedData::new(pointer, keys.0, &keys.1))
+        };
+
+        // With the default policy, appends fail once the size limit is reached.
+        while ad.append(new_item()) {}
+        let count = ad.get_data().len() as u64;
+        assert_eq!(ad.next_append_seq, count);
+        assert!(ad.validate_size());
+
+        // Pages are ordered by the append sequence numbers.
+        let page = ad.get_page(&AppendedRange::Index { start: 2, count: 3 });
+        assert_eq!(page.iter().map(|&(seq, _)| seq).collect::<Vec<_>>(), vec![2, 3, 4]);
+        let page = ad.get_page(&AppendedRange::Since {
+            seq: count - 2,
+            count: 10,
+        });
+        assert_eq!(page.iter().map(|&(seq, _)| seq).collect::<Vec<_>>(),
+                   vec![count - 2, count - 1]);
+        let oldest = ad.get_page(&AppendedRange::Index { start: 0, count: 2 });
+
+        // Only items below the owners' `before` mark can be evicted, oldest first.
+        ad.eviction_policy = EvictionPolicy::OldestFirst { before: 1 };
+        let item = new_item();
+        assert!(ad.append(item.clone()));
+        assert_eq!(ad.get_data().len() as u64, count);
+        assert!(ad.get_data().contains(&item));
+        assert!(!ad.get_data().contains(&oldest[0].1));
+        assert!(ad.validate_size());
+        assert!(!ad.append(new_item()));
+        assert!(ad.get_data().contains(&oldest[1].1));
+        assert_eq!(ad.get_page(&AppendedRange::Since { seq: count, count: 10 }),
+                   vec![(count, item)]);
+    }
 }
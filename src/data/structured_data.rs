@@ -18,19 +18,27 @@
 use error::RoutingError;
 use maidsafe_utilities::serialisation::{serialise, serialised_size};
 use rust_sodium::crypto::sign::{self, PublicKey, SecretKey, Signature};
-use std::collections::{BTreeMap, BTreeSet};
+use std::{iter, mem};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::fmt::{self, Debug, Formatter};
-use super::{DataIdentifier, NO_OWNER_PUB_KEY};
+use super::{DataIdentifier, MAX_DATA_OWNERS, NO_OWNER_PUB_KEY, SignedField, ValidationError};
 use utils;
 use xor_name::XorName;
 
 /// Maximum allowed size for a Structured Data to grow to
 pub const MAX_STRUCTURED_DATA_SIZE_IN_BYTES: u64 = 102400;
+/// Maximum number of previous versions a Structured Data can retain
+pub const MAX_STRUCTURED_DATA_HISTORY: u64 = 10;
 
 /// Mutable structured data.
 ///
 /// These types may be stored unsigned with previous and current owner keys
 /// set to the same keys. Updates require a signature to validate.
+///
+/// If created with a nonzero `max_history`, up to that many previous versions are retained, each
+/// with its signatures, so that every retained version can be verified against its predecessor.
+/// The history counts towards `MAX_STRUCTURED_DATA_SIZE_IN_BYTES`: the oldest versions are dropped
+/// as needed to keep the whole data item within that limit.
 #[derive(Hash, Eq, PartialEq, PartialOrd, Ord, Clone, RustcDecodable, RustcEncodable)]
 pub struct StructuredData {
     type_tag: u64,
@@ -39,6 +47,12 @@ pub struct StructuredData {
     version: u64,
     owners: BTreeSet<PublicKey>,
     signatures: BTreeMap<PublicKey, Signature>,
+    threshold: Option<u64>,
+    /// Time (in seconds since the Unix epoch) after which the data may be removed.
+    expiry: Option<u64>,
+    max_history: u64,
+    /// Previous versions, oldest first. These have no history of their own.
+    history: VecDeque<StructuredData>,
 }
 
 impl StructuredData {
@@ -49,9 +63,26 @@ impl StructuredData {
                data: Vec<u8>,
                owners: BTreeSet<PublicKey>)
                -> Result<StructuredData, RoutingError> {
-        if owners.len() > 1 {
+        StructuredData::with_history(type_tag, name, version, data, owners, 0)
+    }
+
+    /// Creates a new `StructuredData` that retains up to `max_history` previous versions.
+    ///
+    /// A nonzero `max_history` is part of the signed content, so it can only be changed by an
+    /// update.
+    pub fn with_history(type_tag: u64,
+                        name: XorName,
+                        version: u64,
+                        data: Vec<u8>,
+                        owners: BTreeSet<PublicKey>,
+                        max_history: u64)
+                        -> Result<StructuredData, RoutingError> {
+        if owners.len() > MAX_DATA_OWNERS {
             return Err(RoutingError::InvalidOwners);
         }
+        if max_history > MAX_STRUCTURED_DATA_HISTORY {
+            return Err(RoutingError::ExceededSizeLimit);
+        }
 
         Ok(StructuredData {
             type_tag: type_tag,
@@ -60,6 +91,10 @@ impl StructuredData {
             version: version,
             owners: owners,
             signatures: BTreeMap::new(),
+            threshold: None,
+            expiry: None,
+            max_history: max_history,
+            history: VecDeque::new(),
         })
     }
 
@@ -67,15 +102,16 @@ impl StructuredData {
     /// returns an error.
     ///
     /// To transfer ownership, the current owner signs over the data and increase `version` by one.
+    ///
+    /// The replaced version is added to the history, if `other` retains any. The history of
+    /// `other` itself is ignored.
     pub fn replace_with_other(&mut self, other: StructuredData) -> Result<(), RoutingError> {
         self.validate_self_against_successor(&other)?;
 
-        self.type_tag = other.type_tag;
-        self.name = other.name;
-        self.data = other.data;
-        self.version = other.version;
-        self.owners = other.owners;
-        self.signatures = other.signatures;
+        let history = mem::replace(&mut self.history, VecDeque::new());
+        let previous = mem::replace(self, other);
+        self.history = history;
+        self.push_history(previous);
         Ok(())
     }
 
@@ -95,10 +131,58 @@ impl StructuredData {
                                      other: &StructuredData)
                                      -> Result<(), RoutingError> {
         self.validate_self_against_successor(other)?;
+        let previous = self.without_history();
         self.data.clear();
         self.version += 1;
         self.owners.clear();
         self.signatures.clear();
+        self.push_history(previous);
+        Ok(())
+    }
+
+    /// Appends `previous` to the history and drops the oldest versions beyond `max_history` or
+    /// that don't fit into the size limit.
+    fn push_history(&mut self, previous: StructuredData) {
+        self.history.push_back(previous);
+        while self.history.len() as u64 > self.max_history ||
+              (!self.history.is_empty() &&
+               serialised_size(self) > MAX_STRUCTURED_DATA_SIZE_IN_BYTES) {
+            let _ = self.history.pop_front();
+        }
+    }
+
+    /// Returns the retained previous versions, oldest first.
+    pub fn get_history(&self) -> &VecDeque<StructuredData> {
+        &self.history
+    }
+
+    /// Returns the current version without the retained previous ones, e.g. to send it in a
+    /// response or update request that doesn't need them.
+    pub fn without_history(&self) -> StructuredData {
+        StructuredData { history: VecDeque::new(), ..self.clone() }
+    }
+
+    /// Returns the given version, if it is the current one or retained in the history. The
+    /// returned data has no history of its own.
+    pub fn get_historic_version(&self, version: u64) -> Option<StructuredData> {
+        if version == self.version {
+            Some(self.without_history())
+        } else {
+            self.history.iter().find(|sd| sd.version == version).cloned()
+        }
+    }
+
+    /// Verifies that each retained version is a valid successor of the one before it, and that
+    /// the current version is a valid successor of the latest retained one, unless deleted.
+    pub fn verify_history(&self) -> Result<(), RoutingError> {
+        let successors: Vec<&StructuredData> = if self.is_deleted() {
+            self.history.iter().skip(1).collect()
+        } else {
+            self.history.iter().skip(1).chain(iter::once(self)).collect()
+        };
+        for (predecessor, successor) in self.history.iter().zip(successors) {
+            predecessor.validate_self_against_successor(successor)?;
+        }
         Ok(())
     }
 
@@ -114,18 +198,32 @@ impl StructuredData {
     pub fn validate_self_against_successor(&self,
                                            other: &StructuredData)
                                            -> Result<(), RoutingError> {
-        if other.owners.len() > 1 || other.signatures.len() > 1 ||
-           self.owners.contains(&NO_OWNER_PUB_KEY) {
-            return Err(RoutingError::InvalidOwners);
-        }
+        Ok(self.validate_successor(other)?)
+    }
 
-        // TODO(dirvine) Increase error types to be more descriptive  :07/07/2015
-        if other.type_tag != self.type_tag || other.name != self.name ||
-           other.version != self.version + 1 {
-            return Err(RoutingError::UnknownMessageType);
+    /// Like `validate_self_against_successor`, but returns the specific rule `other` violates.
+    pub fn validate_successor(&self, other: &StructuredData) -> Result<(), ValidationError> {
+        if other.owners.len() > MAX_DATA_OWNERS {
+            return Err(ValidationError::TooManyOwners);
         }
-        let data = other.data_to_sign()?;
-        super::verify_signatures(&self.owners, &data, &other.signatures)
+        super::validate_threshold(other.owners.len(), other.threshold)?;
+        if self.owners.contains(&NO_OWNER_PUB_KEY) {
+            return Err(ValidationError::NoOwnerKey);
+        }
+        if other.max_history > MAX_STRUCTURED_DATA_HISTORY {
+            return Err(ValidationError::SizeExceeded);
+        }
+        if other.type_tag != self.type_tag || other.name != self.name {
+            return Err(ValidationError::IdentifierChanged);
+        }
+        if other.version != self.version + 1 {
+            return Err(ValidationError::WrongVersion {
+                expected: self.version + 1,
+                actual: other.version,
+            });
+        }
+        let data = other.data_to_sign().map_err(|_| ValidationError::Serialisation)?;
+        super::verify_signatures(&self.owners, self.threshold, &data, &other.signatures)
     }
 
     fn data_to_sign(&self) -> Result<Vec<u8>, RoutingError> {
@@ -138,21 +236,29 @@ impl StructuredData {
             version: self.version.to_string().as_bytes().to_vec(),
             owners: &self.owners,
         };
+        let mut fields = vec![];
+        if self.max_history != 0 {
+            fields.push(SignedField::MaxHistory(self.max_history));
+        }
+        if let Some(threshold) = self.threshold {
+            fields.push(SignedField::Threshold(threshold));
+        }
+        if let Some(expiry) = self.expiry {
+            fields.push(SignedField::Expiry(expiry));
+        }
 
-        serialise(&sd).map_err(From::from)
+        super::append_signed_fields(serialise(&sd)?, fields)
     }
 
     /// Adds a signature with the given `keys.1` to the `signatures` and returns
-    /// the number of signatures that are still required. If more than 50% of the owners
-    /// have signed, 0 is returned and validation is complete.
+    /// the number of signatures that are still required. If the owners' signature threshold is
+    /// met, 0 is returned and validation is complete.
     pub fn add_signature(&mut self, keys: &(PublicKey, SecretKey)) -> Result<usize, RoutingError> {
-        if !self.signatures.is_empty() {
-            return Err(RoutingError::InvalidOwners);
-        }
         let data = self.data_to_sign()?;
         let sig = sign::sign_detached(&data, &keys.1);
         if self.signatures.insert(keys.0, sig).is_none() {
-            return Ok(((self.owners.len() / 2) + 1).saturating_sub(self.signatures.len()));
+            let required = super::required_signatures(self.owners.len(), self.threshold);
+            return Ok(required.saturating_sub(self.signatures.len()));
         }
         Err(RoutingError::FailedSignature)
     }
@@ -187,7 +293,47 @@ impl StructuredData {
         &self.signatures
     }
 
-    /// Return true if the size is valid
+    /// Get the number of owner signatures required for an update, if declared
+    pub fn get_threshold(&self) -> Option<u64> {
+        self.threshold
+    }
+
+    /// Declares the number of owners that need to sign an update, e.g. 2 for 2-of-3 ownership.
+    /// If `None`, at least half of the owners need to sign.
+    ///
+    /// The threshold is part of the signed content, so this needs to be called before signing.
+    pub fn set_threshold(&mut self, threshold: Option<u64>) -> Result<(), ValidationError> {
+        super::validate_threshold(self.owners.len(), threshold)?;
+        self.threshold = threshold;
+        Ok(())
+    }
+
+    /// Get the time (in seconds since the Unix epoch) after which the data may be removed, if any
+    pub fn get_expiry(&self) -> Option<u64> {
+        self.expiry
+    }
+
+    /// Sets the time (in seconds since the Unix epoch) after which the data may be removed, or
+    /// `None` to keep it until it is deleted.
+    ///
+    /// The expiry is part of the signed content, so this needs to be called before signing.
+    pub fn set_expiry(&mut self, expiry: Option<u64>) {
+        self.expiry = expiry;
+    }
+
+    /// Returns `true` if the data has an expiry time not later than `now` (in seconds since the
+    /// Unix epoch).
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expiry.map_or(false, |expiry| expiry <= now)
+    }
+
+    /// Get the maximum number of retained previous versions
+    pub fn get_max_history(&self) -> u64 {
+        self.max_history
+    }
+
+    /// Return true if the size is valid, i.e. the current version together with all retained
+    /// previous versions is within the size limit
     pub fn validate_size(&self) -> bool {
         serialised_size(self) <= MAX_STRUCTURED_DATA_SIZE_IN_BYTES
     }
@@ -197,13 +343,18 @@ impl Debug for StructuredData {
     fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
         write!(formatter,
                "StructuredData {{ type_tag: {}, name: {}, version: {}, data: {}, \
-                owners: {:?}, signatures: {:?} }}",
+                owners: {:?}, threshold: {:?}, expiry: {:?}, signatures: {:?}, \
+                history: {}/{} }}",
                self.type_tag,
                self.name(),
                self.version,
                utils::format_binary_array(&self.data[..]),
                self.owners,
-               self.signatures)
+               self.threshold,
+               self.expiry,
+               self.signatures,
+               self.history.len(),
+               self.max_history)
     }
 }
 
@@ -220,8 +371,9 @@ struct SerialisableStructuredData<'a> {
 mod tests {
     use data;
     use rand;
+    use data::ValidationError;
     use rust_sodium::crypto::sign;
-    use std::collections::BTreeSet;
+    use std::collections::{BTreeMap, BTreeSet};
     use super::*;
     use xor_name::XorName;
 
@@ -238,11 +390,13 @@ mod tests {
                     Err(error) => panic!("Error: {:?}", error),
                 };
                 assert!(data::verify_signatures(&owner_keys,
+                                                None,
                                                 &data,
                                                 structured_data.get_signatures())
                     .is_err());
                 assert!(structured_data.add_signature(&keys).is_ok());
                 assert!(data::verify_signatures(&owner_keys,
+                                                None,
                                                 &data,
                                                 structured_data.get_signatures())
                     .is_ok());
@@ -266,6 +420,7 @@ mod tests {
                     Err(error) => panic!("Error: {:?}", error),
                 };
                 assert!(data::verify_signatures(&owner_keys,
+                                                None,
                                                 &data,
                                                 structured_data.get_signatures())
                     .is_err());
@@ -293,4 +448,199 @@ mod tests {
         assert!(sd_fail.add_signature(&keys).is_ok());
         assert!(sd.replace_with_other(sd_fail).is_err());
     }
+
+    #[test]
+    fn history() {
+        let keys = sign::gen_keypair();
+        let mut owner = BTreeSet::new();
+        owner.insert(keys.0);
+        let name: XorName = rand::random();
+
+        let mut sd = unwrap!(StructuredData::with_history(0, name, 0, vec![0], owner.clone(), 2));
+        assert!(StructuredData::with_history(0, name, 0, vec![], owner.clone(), 11).is_err());
+
+        for version in 1..4 {
+            let data = vec![version as u8];
+            let mut sd_new =
+                unwrap!(StructuredData::with_history(0, name, version, data, owner.clone(), 2));
+            assert!(sd_new.add_signature(&keys).is_ok());
+            unwrap!(sd.replace_with_other(sd_new));
+        }
+
+        // Only the two latest previous versions are retained, and each one is verifiable.
+        let versions: Vec<u64> = sd.get_history().iter().map(|sd| sd.get_version()).collect();
+        assert_eq!(versions, vec![1, 2]);
+        assert!(sd.verify_history().is_ok());
+        assert_eq!(unwrap!(sd.get_historic_version(2)).get_data(), &vec![2]);
+        assert!(unwrap!(sd.get_historic_version(3)).get_history().is_empty());
+        assert!(sd.get_historic_version(0).is_none());
+
+        // Without history, nothing is retained.
+        let mut sd = unwrap!(StructuredData::new(0, name, 0, vec![], owner.clone()));
+        let mut sd_new = unwrap!(StructuredData::new(0, name, 1, vec![], owner));
+        assert!(sd_new.add_signature(&keys).is_ok());
+        unwrap!(sd.replace_with_other(sd_new));
+        assert!(sd.get_history().is_empty());
+    }
+
+    #[test]
+    fn history_size_limit() {
+        let keys = sign::gen_keypair();
+        let mut owner = BTreeSet::new();
+        owner.insert(keys.0);
+        let name: XorName = rand::random();
+        let data_size = MAX_STRUCTURED_DATA_SIZE_IN_BYTES as usize * 2 / 5;
+
+        let data = vec![0; data_size];
+        let mut sd = unwrap!(StructuredData::with_history(0, name, 0, data, owner.clone(), 10));
+        for version in 1..5 {
+            let data = vec![version as u8; data_size];
+            let mut sd_new =
+                unwrap!(StructuredData::with_history(0, name, version, data, owner.clone(), 10));
+            assert!(sd_new.add_signature(&keys).is_ok());
+            unwrap!(sd.replace_with_other(sd_new));
+            assert!(sd.validate_size());
+        }
+
+        // Only one previous version fits into the size limit together with the current one.
+        let versions: Vec<u64> = sd.get_history().iter().map(|sd| sd.get_version()).collect();
+        assert_eq!(versions, vec![3]);
+        assert!(sd.verify_history().is_ok());
+
+        // The history counts towards the size limit.
+        let previous = sd.without_history();
+        sd.history.push_front(previous);
+        assert!(!sd.validate_size());
+        assert!(sd.without_history().validate_size());
+    }
+
+    #[test]
+    fn validation_errors() {
+        let keys = sign::gen_keypair();
+        let other_keys = sign::gen_keypair();
+        let mut owner = BTreeSet::new();
+        owner.insert(keys.0);
+        let name: XorName = rand::random();
+        let sd = unwrap!(StructuredData::new(0, name, 0, vec![], owner.clone()));
+
+        let mut sd_new = unwrap!(StructuredData::new(0, name, 2, vec![], owner.clone()));
+        assert!(sd_new.add_signature(&keys).is_ok());
+        assert_eq!(sd.validate_successor(&sd_new),
+                   Err(ValidationError::WrongVersion {
+                       expected: 1,
+                       actual: 2,
+                   }));
+
+        let mut sd_new = unwrap!(StructuredData::new(1, name, 1, vec![], owner.clone()));
+        assert!(sd_new.add_signature(&keys).is_ok());
+        assert_eq!(sd.validate_successor(&sd_new),
+                   Err(ValidationError::IdentifierChanged));
+
+        let sd_new = unwrap!(StructuredData::new(0, name, 1, vec![], owner.clone()));
+        assert_eq!(sd.validate_successor(&sd_new),
+                   Err(ValidationError::NotEnoughSignatures {
+                       required: 1,
+                       actual: 0,
+                   }));
+
+        let mut sd_new = unwrap!(StructuredData::new(0, name, 1, vec![], owner.clone()));
+        assert!(sd_new.add_signature(&other_keys).is_ok());
+        assert_eq!(sd.validate_successor(&sd_new),
+                   Err(ValidationError::SignatureByNonOwner(other_keys.0)));
+
+        let mut sd_new = unwrap!(StructuredData::new(0, name, 1, vec![], owner));
+        let mut signatures = BTreeMap::new();
+        let _ = signatures.insert(keys.0, sign::sign_detached(b"other data", &keys.1));
+        sd_new.replace_signatures(signatures);
+        assert_eq!(sd.validate_successor(&sd_new),
+                   Err(ValidationError::InvalidSignature(keys.0)));
+
+        let mut no_owner = BTreeSet::new();
+        no_owner.insert(data::NO_OWNER_PUB_KEY);
+        let sd = unwrap!(StructuredData::new(0, name, 0, vec![], no_owner));
+        let mut sd_new = unwrap!(StructuredData::new(0, name, 1, vec![], BTreeSet::new()));
+        assert!(sd_new.add_signature(&keys).is_ok());
+        assert_eq!(sd.validate_successor(&sd_new),
+                   Err(ValidationError::NoOwnerKey));
+    }
+
+    #[test]
+    fn threshold() {
+        let keys: Vec<_> = (0..3).map(|_| sign::gen_keypair()).collect();
+        let owners: BTreeSet<_> = keys.iter().map(|&(ref public_key, _)| *public_key).collect();
+        let name: XorName = rand::random();
+
+        // Invalid thresholds are rejected.
+        let mut sd = unwrap!(StructuredData::new(0, name, 0, vec![], owners.clone()));
+        assert_eq!(sd.set_threshold(Some(0)),
+                   Err(ValidationError::InvalidThreshold {
+                       threshold: 0,
+                       owner_count: 3,
+                   }));
+        assert_eq!(sd.set_threshold(Some(4)),
+                   Err(ValidationError::InvalidThreshold {
+                       threshold: 4,
+                       owner_count: 3,
+                   }));
+
+        // 2-of-3: one signature is not enough, two are.
+        unwrap!(sd.set_threshold(Some(2)));
+        let mut sd_new = unwrap!(StructuredData::new(0, name, 1, vec![], owners.clone()));
+        unwrap!(sd_new.set_threshold(Some(1)));
+        assert_eq!(unwrap!(sd_new.add_signature(&keys[0])), 0);
+        assert_eq!(sd.validate_successor(&sd_new),
+                   Err(ValidationError::NotEnoughSignatures {
+                       required: 2,
+                       actual: 1,
+                   }));
+        assert_eq!(unwrap!(sd_new.add_signature(&keys[1])), 0);
+        assert_eq!(sd.validate_successor(&sd_new), Ok(()));
+        unwrap!(sd.replace_with_other(sd_new));
+        assert_eq!(sd.get_threshold(), Some(1));
+
+        // 1-of-3: the successor declared a threshold of 1, so any single owner can now update.
+        let mut sd_new = unwrap!(StructuredData::new(0, name, 2, vec![], owners.clone()));
+        assert!(sd_new.add_signature(&keys[2]).is_ok());
+        assert_eq!(sd.validate_successor(&sd_new), Ok(()));
+
+        // The threshold is signed, so changing it invalidates existing signatures.
+        let mut sd_new = unwrap!(StructuredData::new(0, name, 2, vec![], owners));
+        assert!(sd_new.add_signature(&keys[2]).is_ok());
+        unwrap!(sd_new.set_threshold(Some(3)));
+        assert_eq!(sd.validate_successor(&sd_new),
+                   Err(ValidationError::InvalidSignature(keys[2].0)));
+    }
+
+    #[test]
+    fn expiry() {
+        let keys = sign::gen_keypair();
+        let mut owners = BTreeSet::new();
+        owners.insert(keys.0);
+        let name: XorName = rand::random();
+
+        let sd = unwrap!(StructuredData::new(0, name, 0, vec![], owners.clone()));
+        assert_eq!(sd.get_expiry(), None);
+        assert!(!sd.is_expired(u64::max_value()));
+
+        let mut sd_new = unwrap!(StructuredData::new(0, name, 1, vec![], owners.clone()));
+        sd_new.set_expiry(Some(100));
+        assert!(sd_new.add_signature(&keys).is_ok());
+        assert_eq!(sd.validate_successor(&sd_new), Ok(()));
+        assert!(!sd_new.is_expired(99));
+        assert!(sd_new.is_expired(100));
+
+        // The expiry is signed, so extending it invalidates existing signatures.
+        sd_new.set_expiry(Some(200));
+        assert_eq!(sd.validate_successor(&sd_new),
+                   Err(ValidationError::InvalidSignature(keys.0)));
+
+        // Without an expiry, nothing is added to the signed content.
+        let mut sd_plain = unwrap!(StructuredData::new(0, name, 1, vec![], owners.clone()));
+        assert!(sd_plain.add_signature(&keys).is_ok());
+        sd_plain.set_expiry(Some(100));
+        assert_eq!(sd.validate_successor(&sd_plain),
+                   Err(ValidationError::InvalidSignature(keys.0)));
+        sd_plain.set_expiry(None);
+        assert_eq!(sd.validate_successor(&sd_plain), Ok(()));
+    }
 }
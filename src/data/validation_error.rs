@@ -0,0 +1,130 @@
+// Copyright 2015 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.1.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+use rust_sodium::crypto::sign::PublicKey;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+/// The reason why a data item is not a valid successor of another, or not valid by itself.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, RustcEncodable, RustcDecodable)]
+pub enum ValidationError {
+    /// The successor has a different name or type tag.
+    IdentifierChanged,
+    /// The successor's version is not the current version plus one.
+    WrongVersion {
+        /// The version a valid successor must have.
+        expected: u64,
+        /// The successor's actual version.
+        actual: u64,
+    },
+    /// The data has more owners than supported.
+    TooManyOwners,
+    /// The declared signature threshold is zero or exceeds the number of owners.
+    InvalidThreshold {
+        /// The declared threshold.
+        threshold: u64,
+        /// The number of owners.
+        owner_count: usize,
+    },
+    /// The data is owned by `NO_OWNER_PUB_KEY` and can't be changed.
+    NoOwnerKey,
+    /// The successor is not signed by enough of the current owners.
+    NotEnoughSignatures {
+        /// The number of signatures required.
+        required: usize,
+        /// The number of signatures given.
+        actual: usize,
+    },
+    /// The successor is signed by a key that is not a current owner.
+    SignatureByNonOwner(PublicKey),
+    /// The successor's signature by the given key is invalid.
+    InvalidSignature(PublicKey),
+    /// The data exceeds its size limit.
+    SizeExceeded,
+    /// The data could not be serialised to verify its signatures.
+    Serialisation,
+    /// No `DataValidator` is registered for the given custom type ID.
+    UnregisteredType(u64),
+    /// The data kind doesn't support replacing an item with a successor.
+    SuccessorNotSupported,
+    /// A `DataValidator` rejected the data for the given reason.
+    Rejected(String),
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        match *self {
+            ValidationError::IdentifierChanged => {
+                write!(formatter, "Successor has a different name or type tag")
+            }
+            ValidationError::WrongVersion { expected, actual } => {
+                write!(formatter,
+                       "Successor has version {}, expected {}",
+                       actual,
+                       expected)
+            }
+            ValidationError::TooManyOwners => write!(formatter, "Data has too many owners"),
+            ValidationError::InvalidThreshold { threshold, owner_count } => {
+                write!(formatter,
+                       "Signature threshold {} is invalid for {} owners",
+                       threshold,
+                       owner_count)
+            }
+            ValidationError::NoOwnerKey => write!(formatter, "Data without owner can't be changed"),
+            ValidationError::NotEnoughSignatures { required, actual } => {
+                write!(formatter,
+                       "Successor has {} owner signatures, {} required",
+                       actual,
+                       required)
+            }
+            ValidationError::SignatureByNonOwner(ref key) => {
+                write!(formatter, "Successor is signed by non-owner {:?}", key)
+            }
+            ValidationError::InvalidSignature(ref key) => {
+                write!(formatter, "Successor's signature by {:?} is invalid", key)
+            }
+            ValidationError::SizeExceeded => write!(formatter, "Data exceeds its size limit"),
+            ValidationError::Serialisation => write!(formatter, "Data could not be serialised"),
+            ValidationError::UnregisteredType(type_id) => {
+                write!(formatter, "No validator registered for type {}", type_id)
+            }
+            ValidationError::SuccessorNotSupported => {
+                write!(formatter, "Data can't be replaced with a successor")
+            }
+            ValidationError::Rejected(ref reason) => write!(formatter, "Data rejected: {}", reason),
+        }
+    }
+}
+
+impl Error for ValidationError {
+    fn description(&self) -> &str {
+        match *self {
+            ValidationError::IdentifierChanged => "Identifier changed",
+            ValidationError::WrongVersion { .. } => "Wrong version",
+            ValidationError::TooManyOwners => "Too many owners",
+            ValidationError::InvalidThreshold { .. } => "Invalid threshold",
+            ValidationError::NoOwnerKey => "Data without owner",
+            ValidationError::NotEnoughSignatures { .. } => "Not enough signatures",
+            ValidationError::SignatureByNonOwner(_) => "Signature by non-owner",
+            ValidationError::InvalidSignature(_) => "Invalid signature",
+            ValidationError::SizeExceeded => "Size exceeded",
+            ValidationError::Serialisation => "Serialisation failed",
+            ValidationError::UnregisteredType(_) => "Unregistered type",
+            ValidationError::SuccessorNotSupported => "Successor not supported",
+            ValidationError::Rejected(_) => "Rejected",
+        }
+    }
+}
@@ -0,0 +1,473 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.1.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use cache::{Cache, CacheStats};
+use data::{Data, DataIdentifier, ImmutableData};
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use maidsafe_utilities::thread::{self, Joiner};
+use messages::{Request, Response};
+use rand;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::mpsc::{self, Receiver, Sender};
+use xor_name::XorName;
+
+/// The extension of files which are still being written.
+const TEMP_EXTENSION: &'static str = "tmp";
+
+/// A file system operation performed by the worker thread.
+enum Op {
+    /// Write the given content to the chunk's file, if it is still pending with the given clock
+    /// value.
+    Write(XorName, u64, Vec<u8>),
+    /// Remove the chunk's file.
+    Remove(XorName),
+    /// Stop the worker thread.
+    Stop,
+}
+
+struct Inner {
+    /// The size of each cached chunk's file, and the value of `clock` when it was last used.
+    entries: HashMap<XorName, (u64, u64)>,
+    /// The cached names, least recently used first.
+    lru: BTreeMap<u64, XorName>,
+    /// The content of the chunks which haven't been written to disk yet, and the value of `clock`
+    /// when they were put.
+    pending: HashMap<XorName, (u64, Vec<u8>)>,
+    clock: u64,
+    size: u64,
+    stats: CacheStats,
+    ops: Sender<Op>,
+}
+
+impl Inner {
+    fn insert(&mut self, name: XorName, size: u64) {
+        self.clock += 1;
+        if let Some((old_size, last_used)) = self.entries.insert(name, (size, self.clock)) {
+            let _ = self.lru.remove(&last_used);
+            self.size -= old_size;
+        }
+        let _ = self.lru.insert(self.clock, name);
+        self.size += size;
+    }
+
+    fn touch(&mut self, name: &XorName) {
+        if let Some(&(size, _)) = self.entries.get(name) {
+            self.insert(*name, size);
+        }
+    }
+
+    /// Removes the chunk and has the worker thread remove its file.
+    fn remove(&mut self, name: &XorName) -> bool {
+        if let Some((size, last_used)) = self.entries.remove(name) {
+            let _ = self.lru.remove(&last_used);
+            let _ = self.pending.remove(name);
+            self.size -= size;
+            let _ = self.ops.send(Op::Remove(*name));
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// A `Cache` of immutable data chunks in a directory on disk, which keeps its content across
+/// restarts.
+///
+/// The total size of the files is limited, with the least recently used chunks evicted first.
+/// Files are written and removed by a worker thread, so that `put` doesn't block on the disk.
+/// Each chunk is written to a uniquely named temporary file which is renamed once complete, so
+/// that a crash never leaves a partially written chunk behind, and its content is checked
+/// against its name whenever it is read.
+pub struct DiskCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    inner: Arc<Mutex<Inner>>,
+    _worker: Joiner,
+}
+
+impl DiskCache {
+    /// Opens the cache in `dir`, creating the directory if needed, holding at most `max_bytes`.
+    ///
+    /// Existing chunks are loaded, most recently modified last, and temporary files left by an
+    /// interrupted write are removed. Only the chunks' file sizes are read here: their content is
+    /// checked when they are first requested, and they are removed if it doesn't match their
+    /// names. Any other files are skipped.
+    pub fn open<P: AsRef<Path>>(dir: P, max_bytes: u64) -> io::Result<DiskCache> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        let mut chunks = vec![];
+        for entry in fs::read_dir(&dir)? {
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(error) => {
+                    debug!("Failed to read cache directory entry: {:?}", error);
+                    continue;
+                }
+            };
+            let file_name = match path.file_name().and_then(|file_name| file_name.to_str()) {
+                Some(file_name) => file_name.to_owned(),
+                None => {
+                    debug!("Skipping unknown file {:?} in cache directory", path);
+                    continue;
+                }
+            };
+            if is_temp_file_name(&file_name) {
+                debug!("Removing temporary cache file {:?}", path);
+                if let Err(error) = fs::remove_file(&path) {
+                    debug!("Failed to remove cache file {:?}: {:?}", path, error);
+                }
+                continue;
+            }
+            let name = match XorName::from_hex(&file_name) {
+                Ok(name) => name,
+                Err(_) => {
+                    debug!("Skipping unknown file {:?} in cache directory", path);
+                    continue;
+                }
+            };
+            match fs::metadata(&path) {
+                Ok(ref metadata) if !metadata.is_file() => {
+                    debug!("Skipping unknown file {:?} in cache directory", path);
+                }
+                Ok(metadata) => {
+                    match metadata.modified() {
+                        Ok(modified) => chunks.push((modified, name, metadata.len())),
+                        Err(error) => debug!("Skipping cache file {:?}: {:?}", path, error),
+                    }
+                }
+                Err(error) => debug!("Skipping cache file {:?}: {:?}", path, error),
+            }
+        }
+        chunks.sort();
+
+        let (ops_tx, ops_rx) = mpsc::channel();
+        let inner = Arc::new(Mutex::new(Inner {
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+            pending: HashMap::new(),
+            clock: 0,
+            size: 0,
+            stats: Default::default(),
+            ops: ops_tx,
+        }));
+        let worker_dir = dir.clone();
+        let worker_inner = inner.clone();
+        let worker = thread::named("DiskCache",
+                                   move || Self::run(&worker_dir, &worker_inner, ops_rx));
+        let cache = DiskCache {
+            dir: dir,
+            max_bytes: max_bytes,
+            inner: inner,
+            _worker: worker,
+        };
+        {
+            let mut inner = cache.lock();
+            for (_, name, size) in chunks {
+                inner.insert(name, size);
+            }
+            cache.evict(&mut inner, 0);
+        }
+        Ok(cache)
+    }
+
+    /// Returns the counters of the operations performed since the cache was opened.
+    pub fn stats(&self) -> CacheStats {
+        self.lock().stats
+    }
+
+    /// Returns the number of cached chunks.
+    pub fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+
+    /// Returns `true` if nothing is cached.
+    pub fn is_empty(&self) -> bool {
+        self.lock().entries.is_empty()
+    }
+
+    /// Returns the total size of the cached chunks' files.
+    pub fn size_in_bytes(&self) -> u64 {
+        self.lock().size
+    }
+
+    fn lock(&self) -> MutexGuard<Inner> {
+        lock(&self.inner)
+    }
+
+    /// Removes least recently used chunks until `size` more bytes fit within the limit.
+    fn evict(&self, inner: &mut Inner, size: u64) {
+        while inner.size + size > self.max_bytes {
+            let name = match inner.lru.values().next() {
+                Some(name) => *name,
+                None => return,
+            };
+            let _ = inner.remove(&name);
+            inner.stats.evictions += 1;
+        }
+    }
+
+    /// Performs the file system operations until the cache is dropped.
+    fn run(dir: &Path, inner: &Mutex<Inner>, ops: Receiver<Op>) {
+        for op in ops.iter() {
+            match op {
+                Op::Write(name, clock, content) => {
+                    let is_pending = |inner: &Inner| {
+                        inner.pending.get(&name).map_or(false, |&(pending, _)| pending == clock)
+                    };
+                    // Skip chunks which have been removed in the meantime.
+                    if !is_pending(&*lock(inner)) {
+                        continue;
+                    }
+                    let result = write_chunk(dir, &name, &content);
+                    let mut inner = lock(inner);
+                    if !is_pending(&*inner) {
+                        continue;
+                    }
+                    let _ = inner.pending.remove(&name);
+                    if let Err(error) = result {
+                        debug!("Failed to write cache file for {:?}: {:?}", name, error);
+                        let _ = inner.remove(&name);
+                    }
+                }
+                Op::Remove(name) => {
+                    match fs::remove_file(chunk_path(dir, &name)) {
+                        Err(ref error) if error.kind() != io::ErrorKind::NotFound => {
+                            debug!("Failed to remove cache file for {:?}: {:?}", name, error);
+                        }
+                        _ => (),
+                    }
+                }
+                Op::Stop => return,
+            }
+        }
+    }
+}
+
+impl Drop for DiskCache {
+    fn drop(&mut self) {
+        // The worker finishes the pending operations first.
+        let _ = self.lock().ops.send(Op::Stop);
+    }
+}
+
+impl Cache for DiskCache {
+    fn get(&self, request: &Request) -> Option<Response> {
+        let (name, message_id) = match *request {
+            Request::Get(DataIdentifier::Immutable(name), message_id) => (name, message_id),
+            _ => return None,
+        };
+        let pending = {
+            let mut inner = self.lock();
+            if !inner.entries.contains_key(&name) {
+                inner.stats.misses += 1;
+                return None;
+            }
+            inner.pending
+                .get(&name)
+                .map(|&(_, ref content)| deserialise::<ImmutableData>(content).ok())
+        };
+        // Read the file without blocking the other users of the cache.
+        let data = match pending {
+            Some(data) => data,
+            None => read_data(&chunk_path(&self.dir, &name)),
+        };
+        let mut inner = self.lock();
+        match data {
+            Some(data) if *data.name() == name => {
+                inner.touch(&name);
+                inner.stats.hits += 1;
+                Some(Response::GetSuccess(Data::Immutable(data), message_id))
+            }
+            _ => {
+                debug!("Removing invalid cache file for {:?}", name);
+                let _ = inner.remove(&name);
+                inner.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    fn put(&self, response: Response) -> bool {
+        let data = match response {
+            Response::GetSuccess(Data::Immutable(data), _) => data,
+            _ => return false,
+        };
+        let mut inner = self.lock();
+        if inner.entries.contains_key(data.name()) {
+            inner.touch(data.name());
+            return true;
+        }
+        let content = match serialise(&data) {
+            Ok(content) => content,
+            Err(error) => {
+                debug!("Failed to serialise {:?}: {:?}", data, error);
+                return false;
+            }
+        };
+        let size = content.len() as u64;
+        if size > self.max_bytes {
+            return false;
+        }
+        self.evict(&mut inner, size);
+        inner.insert(*data.name(), size);
+        let clock = inner.clock;
+        let _ = inner.pending.insert(*data.name(), (clock, content.clone()));
+        let _ = inner.ops.send(Op::Write(*data.name(), clock, content));
+        true
+    }
+
+    fn invalidate(&self, data_id: &DataIdentifier) {
+        if let DataIdentifier::Immutable(ref name) = *data_id {
+            let mut inner = self.lock();
+            if inner.remove(name) {
+                inner.stats.invalidations += 1;
+            }
+        }
+    }
+}
+
+fn lock(inner: &Mutex<Inner>) -> MutexGuard<Inner> {
+    inner.lock().expect("Failed to lock.")
+}
+
+fn chunk_path(dir: &Path, name: &XorName) -> PathBuf {
+    dir.join(name.to_hex())
+}
+
+/// Writes the chunk to a temporary file unique to this write, and renames it once it is
+/// complete and synced.
+fn write_chunk(dir: &Path, name: &XorName, content: &[u8]) -> io::Result<()> {
+    let temp_name = format!("{}.{:016x}.{}", name.to_hex(), rand::random::<u64>(), TEMP_EXTENSION);
+    let temp_path = dir.join(temp_name);
+    let result = File::create(&temp_path)
+        .and_then(|mut file| {
+            file.write_all(content)?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&temp_path, chunk_path(dir, name)));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result?;
+    sync_dir(dir)
+}
+
+/// Syncs the directory, so that a renamed file survives a crash.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+fn read_data(path: &Path) -> Option<ImmutableData> {
+    let mut content = vec![];
+    match File::open(path).and_then(|mut file| file.read_to_end(&mut content)) {
+        Ok(_) => deserialise(&content).ok(),
+        Err(_) => None,
+    }
+}
+
+/// Returns `true` if `file_name` is that of a temporary file created by `write_chunk`.
+fn is_temp_file_name(file_name: &str) -> bool {
+    let parts: Vec<_> = file_name.split('.').collect();
+    parts.len() == 3 && XorName::from_hex(parts[0]).is_ok() && parts[1].len() == 16 &&
+    u64::from_str_radix(parts[1], 16).is_ok() && parts[2] == TEMP_EXTENSION
+}
+
+#[cfg(test)]
+mod tests {
+    use cache::Cache;
+    use data::{Data, ImmutableData};
+    use maidsafe_utilities::serialisation::serialised_size;
+    use messages::{Request, Response};
+    use rand;
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use super::*;
+    use types::MessageId;
+
+    fn get(cache: &DiskCache, data: &Data) -> Option<Data> {
+        match cache.get(&Request::Get(data.identifier(), MessageId::new())) {
+            Some(Response::GetSuccess(data, _)) => Some(data),
+            Some(response) => panic!("Unexpected response {:?}", response),
+            None => None,
+        }
+    }
+
+    #[test]
+    fn persistence() {
+        let dir_name = format!("routing_disk_cache_{:016x}", rand::random::<u64>());
+        let dir = env::temp_dir().join(dir_name);
+        let data: Vec<_> = (0..3u8)
+            .map(|i| Data::Immutable(ImmutableData::new(vec![i; 100])))
+            .collect();
+        let size = serialised_size(&data[0]);
+        {
+            let cache = unwrap!(DiskCache::open(&dir, 2 * size));
+            cache.put(Response::GetSuccess(data[0].clone(), MessageId::new()));
+            cache.put(Response::GetSuccess(data[1].clone(), MessageId::new()));
+            assert_eq!(get(&cache, &data[0]), Some(data[0].clone()));
+            // The least recently used chunk is evicted.
+            cache.put(Response::GetSuccess(data[2].clone(), MessageId::new()));
+            assert_eq!(get(&cache, &data[1]), None);
+            assert_eq!(cache.len(), 2);
+            assert_eq!(cache.size_in_bytes(), 2 * size);
+            assert_eq!(cache.stats().evictions, 1);
+        }
+
+        // Simulate an interrupted write, a corrupted chunk and a file that isn't ours.
+        let name = match data[2] {
+            Data::Immutable(ref data) => *data.name(),
+            _ => unreachable!(),
+        };
+        let temp_name = format!("{}.{:016x}.{}", name.to_hex(), 1, TEMP_EXTENSION);
+        unwrap!(unwrap!(File::create(dir.join(temp_name))).write_all(&[1, 2, 3]));
+        unwrap!(unwrap!(File::create(dir.join(name.to_hex()))).write_all(&[0; 20]));
+        let other_path = dir.join("other.tmp");
+        unwrap!(unwrap!(File::create(&other_path)).write_all(&[4, 5, 6]));
+
+        // After reopening, the temporary file is gone, and the corrupted chunk is only removed
+        // once it is requested.
+        let cache = unwrap!(DiskCache::open(&dir, 2 * size));
+        assert_eq!(cache.len(), 2);
+        assert_eq!(unwrap!(fs::read_dir(&dir)).count(), 3);
+        assert_eq!(get(&cache, &data[0]), Some(data[0].clone()));
+        assert_eq!(get(&cache, &data[2]), None);
+        assert_eq!(cache.len(), 1);
+
+        cache.invalidate(&data[0].identifier());
+        assert!(cache.is_empty());
+        // Dropping the cache waits for the files to be removed. Files that aren't ours are kept.
+        drop(cache);
+        let remaining: Vec<_> = unwrap!(fs::read_dir(&dir))
+            .map(|entry| unwrap!(entry).path())
+            .collect();
+        assert_eq!(remaining, vec![other_path.clone()]);
+        unwrap!(fs::remove_file(&other_path));
+        unwrap!(fs::remove_dir(&dir));
+    }
+}
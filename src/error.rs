@@ -17,11 +17,13 @@
 
 use action::Action;
 use crust::{self, PeerId};
+use data::ValidationError;
 use event::Event;
 use maidsafe_utilities::event_sender::{EventSenderError, MaidSafeEventCategory};
 use maidsafe_utilities::serialisation;
 use std::sync::mpsc::{RecvError, SendError};
 use super::routing_table::Error as RoutingTableError;
+use xor_name::XorName;
 
 #[derive(Debug)]
 /// The type of errors that can occur if routing is unable to handle a send request.
@@ -30,6 +32,17 @@ pub enum InterfaceError {
     NotConnected,
     /// We are not in a state to handle the action.
     InvalidState,
+    /// The recipient of an encrypted message is not the single node or client it is addressed to.
+    InvalidRecipient,
+    /// An encrypted message is sent from a section. Encryption is randomised, so the members'
+    /// messages would differ and never accumulate.
+    InvalidSource,
+    /// The message payload exceeds the maximum size allowed for its kind.
+    PayloadTooLarge,
+    /// We have sent too many messages of this kind recently.
+    RateLimitExceeded,
+    /// The queue of outgoing messages is full. The message should be sent again later.
+    SendQueueFull,
     /// Error while trying to receive a message from a channel
     ChannelRxError(RecvError),
     /// Error while trying to transmit an event via a channel
@@ -71,6 +84,16 @@ pub enum RoutingError {
     DuplicateSignatures,
     /// The list of owner keys is invalid
     InvalidOwners,
+    /// A data item failed validation
+    Validation(ValidationError),
+    /// The requester is not permitted to perform the mutation
+    AccessDenied,
+    /// The entry mutations don't match the existing entries or their versions
+    InvalidEntryActions,
+    /// The permissions version is not the current one plus one
+    InvalidPermissionsVersion,
+    /// The data would exceed its maximum size
+    ExceededSizeLimit,
     /// Duplicate request received
     FilterCheckFailed,
     /// Failure to bootstrap off the provided endpoints
@@ -111,6 +134,14 @@ pub enum RoutingError {
     CannotTunnelThroughTunnel,
     /// Decoded a user message with an unexpected hash.
     HashMismatch,
+    /// A compressed message expanded beyond the allowed size.
+    DecompressedSizeExceeded,
+    /// A compressed message contained another compressed message.
+    NestedCompression,
+    /// A chunk needed to reassemble a payload is missing.
+    MissingChunk(XorName),
+    /// A data proof doesn't match the data it is supposed to prove
+    InvalidDataProof,
     /// Candidate is unknown
     UnknownCandidate,
     /// Operation timed out
@@ -127,6 +158,12 @@ impl From<RoutingTableError> for RoutingError {
     }
 }
 
+impl From<ValidationError> for RoutingError {
+    fn from(error: ValidationError) -> RoutingError {
+        RoutingError::Validation(error)
+    }
+}
+
 impl From<::std::str::Utf8Error> for RoutingError {
     fn from(error: ::std::str::Utf8Error) -> RoutingError {
         RoutingError::Utf8(error)
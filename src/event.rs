@@ -15,10 +15,12 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
+use data::DataIdentifier;
 use messages::{Request, Response};
 use routing_table::{Prefix, RoutingTable};
 use routing_table::Authority;
 use std::fmt::{self, Debug, Formatter};
+use types::MessageId;
 use xor_name::XorName;
 
 /// An Event raised by a `Node` or `Client` via its event sender.
@@ -48,6 +50,53 @@ pub enum Event {
         /// The destination authority that receives the response.
         dst: Authority<XorName>,
     },
+    /// Received the responses to a request sent to an `Authority::Sections`. This is raised once
+    /// every section has responded, or after a timeout with the responses received so far.
+    SectionsResponse {
+        /// The identifier of the request.
+        msg_id: MessageId,
+        /// The response of each section that responded, by prefix.
+        responses: Vec<(Prefix<XorName>, Response)>,
+        /// The `Sections` authority the request was sent to.
+        src: Authority<XorName>,
+        /// The destination authority that receives the responses.
+        dst: Authority<XorName>,
+    },
+    /// Received a network-wide broadcast.
+    Broadcast {
+        /// The name of the node that originated the broadcast.
+        origin: XorName,
+        /// The broadcast's unique identifier.
+        msg_id: MessageId,
+        /// The broadcast's payload.
+        payload: Vec<u8>,
+    },
+    /// Received the next chunk of a stream. Chunks of a stream are raised in order, exactly once.
+    StreamChunk {
+        /// The name of the node that sent the stream.
+        src: XorName,
+        /// The stream's unique identifier.
+        stream_id: MessageId,
+        /// The index of this chunk in the stream.
+        index: u32,
+        /// The total number of chunks in the stream.
+        chunk_count: u32,
+        /// The chunk's payload.
+        payload: Vec<u8>,
+    },
+    /// All chunks of a stream we sent have been acknowledged by its recipient.
+    StreamSent(MessageId),
+    /// A stream we sent has been dropped, because some of its chunks were still not acknowledged
+    /// after being resent several times.
+    StreamFailed(MessageId),
+    /// An incoming stream has been aborted, either because one of its chunks was invalid or
+    /// because no chunks were received for too long. No further chunks of it will be raised.
+    StreamAborted {
+        /// The name of the node that sent the stream.
+        src: XorName,
+        /// The stream's unique identifier.
+        stream_id: MessageId,
+    },
     /// A node has connected to us.
     NodeAdded(XorName, RoutingTable<XorName>),
     /// A node has disconnected from us.
@@ -59,6 +108,9 @@ pub enum Event {
     SectionMerge(Prefix<XorName>),
     /// The client has successfully connected to a proxy node on the network.
     Connected,
+    /// Data items whose expiry time, as tracked with `Node::track_expiry`, has passed. They are no
+    /// longer tracked and can be removed from storage.
+    DataExpired(Vec<DataIdentifier>),
     /// Disconnected or failed to connect - restart required.
     RestartRequired,
     /// Startup failed - terminate.
@@ -85,6 +137,43 @@ impl Debug for Event {
                        src,
                        dst)
             }
+            Event::SectionsResponse { ref msg_id, ref responses, ref src, ref dst } => {
+                write!(formatter,
+                       "Event::SectionsResponse {{ msg_id: {:?}, responses: {:?}, src: {:?}, \
+                        dst: {:?} }}",
+                       msg_id,
+                       responses,
+                       src,
+                       dst)
+            }
+            Event::Broadcast { ref origin, ref msg_id, ref payload } => {
+                write!(formatter,
+                       "Event::Broadcast {{ origin: {:?}, msg_id: {:?}, {} bytes }}",
+                       origin,
+                       msg_id,
+                       payload.len())
+            }
+            Event::StreamChunk { ref src, ref stream_id, index, chunk_count, ref payload } => {
+                write!(formatter,
+                       "Event::StreamChunk {{ src: {:?}, stream_id: {:?}, {}/{}, {} bytes }}",
+                       src,
+                       stream_id,
+                       index,
+                       chunk_count,
+                       payload.len())
+            }
+            Event::StreamSent(ref stream_id) => {
+                write!(formatter, "Event::StreamSent({:?})", stream_id)
+            }
+            Event::StreamFailed(ref stream_id) => {
+                write!(formatter, "Event::StreamFailed({:?})", stream_id)
+            }
+            Event::StreamAborted { ref src, ref stream_id } => {
+                write!(formatter,
+                       "Event::StreamAborted {{ src: {:?}, stream_id: {:?} }}",
+                       src,
+                       stream_id)
+            }
             Event::NodeAdded(ref node_name, _) => {
                 write!(formatter,
                        "Event::NodeAdded({:?}, routing_table)",
@@ -99,6 +188,9 @@ impl Debug for Event {
             Event::SectionMerge(ref prefix) => {
                 write!(formatter, "Event::SectionMerge({:?})", prefix)
             }
+            Event::DataExpired(ref data_ids) => {
+                write!(formatter, "Event::DataExpired({:?})", data_ids)
+            }
             Event::Connected => write!(formatter, "Event::Connected"),
             Event::RestartRequired => write!(formatter, "Event::RestartRequired"),
             Event::Terminate => write!(formatter, "Event::Terminate"),
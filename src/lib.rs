@@ -134,6 +134,7 @@ extern crate quick_error;
 extern crate unwrap;
 #[cfg(not(feature = "use-mock-crust"))]
 extern crate crust;
+extern crate flate2;
 extern crate itertools;
 extern crate lru_time_cache;
 extern crate rand;
@@ -149,7 +150,9 @@ mod ack_manager;
 mod action;
 mod client;
 mod cache;
+mod compression;
 mod data;
+mod disk_cache;
 mod error;
 mod event;
 mod event_stream;
@@ -159,12 +162,16 @@ mod message_filter;
 mod messages;
 mod node;
 mod peer_manager;
+mod rate_limiter;
+mod response_aggregator;
 mod routing_message_filter;
 mod routing_table;
+mod send_queue;
 mod signature_accumulator;
 mod state_machine;
 mod states;
 mod stats;
+mod stream;
 mod timer;
 mod tunnels;
 mod types;
@@ -189,26 +196,35 @@ pub const TYPE_TAG_DNS_PACKET: u64 = 5;
 /// The quorum, as a percentage of the number of members of the authority.
 pub const QUORUM: usize = 60;
 
-pub use cache::{Cache, NullCache};
+pub use cache::{Cache, CacheStats, MemoryCache, NullCache};
 pub use client::Client;
-pub use data::{AppendWrapper, AppendedData, Data, DataIdentifier, Filter, ImmutableData,
-               MAX_IMMUTABLE_DATA_SIZE_IN_BYTES, MAX_PRIV_APPENDABLE_DATA_SIZE_IN_BYTES,
-               MAX_PUB_APPENDABLE_DATA_SIZE_IN_BYTES, MAX_STRUCTURED_DATA_SIZE_IN_BYTES,
-               NO_OWNER_PUB_KEY, PrivAppendableData, PrivAppendedData, PubAppendableData,
-               StructuredData};
+pub use compression::Compression;
+pub use data::chunked;
+pub use data::{AppendWrapper, AppendedData, AppendedItems, AppendedRange, Condition, ContentHash,
+               CustomData, Data, DataIdentifier, DataProof, DataValidator, DataValidators,
+               EntryAction, EvictionPolicy, ExpiryTracker, Filter, ImmutableData,
+               MAX_CUSTOM_DATA_SIZE_IN_BYTES, MAX_IMMUTABLE_DATA_SIZE_IN_BYTES,
+               MAX_MUTABLE_DATA_ENTRIES, MAX_MUTABLE_DATA_SIZE_IN_BYTES,
+               MAX_PRIV_APPENDABLE_DATA_SIZE_IN_BYTES, MAX_PUB_APPENDABLE_DATA_SIZE_IN_BYTES,
+               MAX_STRUCTURED_DATA_HISTORY, MAX_STRUCTURED_DATA_SIZE_IN_BYTES, MerkleHash,
+               MerkleProof, MerkleTree, MutableData, NO_OWNER_PUB_KEY, Permission, PermissionSet,
+               PrivAppendableData, PrivAppendedData, PubAppendableData, SectionKey, StructuredData,
+               ValidationError, Value, content_hash};
+pub use disk_cache::DiskCache;
 pub use error::{InterfaceError, RoutingError};
 pub use event::Event;
 pub use event_stream::EventStream;
 pub use evented::Evented;
 pub use id::{FullId, PublicId};
-pub use messages::{Request, Response};
+pub use messages::{BatchItemResult, MAX_BATCH_LEN, MAX_BATCH_SIZE_IN_BYTES, Request, Response};
 #[cfg(feature = "use-mock-crust")]
 pub use mock_crust::crust;
 pub use node::{Node, NodeBuilder};
-pub use routing_table::{Authority, Prefix, RoutingTable, Xorable};
+pub use routing_table::{Authority, Prefix, PrefixSet, RoutingTable, Xorable};
 pub use routing_table::Error as RoutingTableError;
 #[cfg(any(test, feature = "use-mock-crust"))]
 pub use routing_table::verify_network_invariant;
+pub use stats::{CacheCounts, CacheMetrics};
 pub use types::MessageId;
 pub use xor_name::{XOR_NAME_BITS, XOR_NAME_LEN, XorName, XorNameFromHexError};
 
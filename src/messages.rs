@@ -16,9 +16,11 @@
 // relating to use of the SAFE Network Software.
 
 use ack_manager::Ack;
+use compression::{self, Compression};
 #[cfg(not(feature = "use-mock-crust"))]
 use crust::PeerId;
-use data::{AppendWrapper, Data, DataIdentifier};
+use data::{AppendWrapper, AppendedItems, AppendedRange, Condition, ContentHash, Data,
+           DataIdentifier, DataProof, EntryAction, MerkleHash, PermissionSet};
 use error::RoutingError;
 use event::Event;
 use id::{FullId, PublicId};
@@ -26,6 +28,7 @@ use itertools::Itertools;
 use lru_time_cache::LruCache;
 use maidsafe_utilities;
 use maidsafe_utilities::serialisation::{deserialise, serialise};
+use messaging::MpidMessageWrapper;
 #[cfg(feature = "use-mock-crust")]
 use mock_crust::crust::PeerId;
 use peer_manager::SectionMap;
@@ -44,6 +47,12 @@ use xor_name::XorName;
 
 /// The maximal length of a user message part, in bytes.
 pub const MAX_PART_LEN: usize = 20 * 1024;
+/// The maximal length of a broadcast payload, in bytes. Broadcasts are not split into parts.
+pub const MAX_BROADCAST_LEN: usize = MAX_PART_LEN;
+/// The maximal number of items in a batched request.
+pub const MAX_BATCH_LEN: usize = 64;
+/// The maximal total serialised size of the items in a batched request, in bytes.
+pub const MAX_BATCH_SIZE_IN_BYTES: usize = 1024 * 1024;
 
 /// Get and refresh messages from nodes have a high priority: They relocate data under churn and are
 /// critical to prevent data loss.
@@ -81,6 +90,14 @@ pub enum Message {
         /// The receiver
         dst: PeerId,
     },
+    /// Any of the above, compressed with a codec the receiver announced in
+    /// `DirectMessage::Capabilities`.
+    Compressed {
+        /// The codec the message was compressed with.
+        compression: Compression,
+        /// The compressed serialised message.
+        payload: Vec<u8>,
+    },
 }
 
 impl Message {
@@ -90,6 +107,20 @@ impl Message {
             Message::TunnelDirect { ref content, .. } => content.priority(),
             Message::Hop(ref content) |
             Message::TunnelHop { ref content, .. } => content.content.content.priority(),
+            // Messages are only compressed once their priority is known.
+            Message::Compressed { .. } => 0,
+        }
+    }
+
+    /// Reverses the compression of a `Message::Compressed` and returns the message it contains,
+    /// together with its serialised form.
+    pub fn decompress(compression: Compression,
+                      payload: Vec<u8>)
+                      -> Result<(Message, Vec<u8>), RoutingError> {
+        let bytes = compression::decompress(compression, payload)?;
+        match deserialise(&bytes)? {
+            Message::Compressed { .. } => Err(RoutingError::NestedCompression),
+            message => Ok((message, bytes)),
         }
     }
 }
@@ -172,6 +203,15 @@ pub enum DirectMessage {
     },
     /// Receipt of a part of a ResourceProofResponse
     ResourceProofResponseReceipt,
+    /// Sent to a newly connected peer to announce the optional features we support. Older peers
+    /// can't parse this and ignore it, so they are never sent anything that relies on it.
+    Capabilities {
+        /// The codecs we can decompress a `Message::Compressed` with.
+        compression: Vec<Compression>,
+    },
+    /// A signature for the version and Merkle root of the data our section holds, sent to our
+    /// own section
+    DataRootSignature(Prefix<XorName>, u64, MerkleHash, sign::Signature),
 }
 
 impl DirectMessage {
@@ -253,6 +293,89 @@ impl SectionList {
     pub fn from<I: IntoIterator<Item = PublicId>>(prefix: Prefix<XorName>, pub_ids: I) -> Self {
         Self::new(prefix, pub_ids.into_iter().collect())
     }
+
+    /// Returns the section's members.
+    pub fn pub_ids(&self) -> &BTreeSet<PublicId> {
+        &self.pub_ids
+    }
+}
+
+/// A payload to be delivered to every node in the network, signed by the node that originated it.
+#[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Hash, RustcEncodable, RustcDecodable)]
+pub struct SignedBroadcast {
+    origin: PublicId,
+    msg_id: MessageId,
+    payload: Vec<u8>,
+    signature: sign::Signature,
+}
+
+impl SignedBroadcast {
+    /// Creates a new broadcast with the given payload, signed by `full_id`.
+    pub fn new(full_id: &FullId,
+               msg_id: MessageId,
+               payload: Vec<u8>)
+               -> Result<SignedBroadcast, RoutingError> {
+        let signed_bytes = Self::signed_bytes(&msg_id, &payload)?;
+        Ok(SignedBroadcast {
+            origin: *full_id.public_id(),
+            msg_id: msg_id,
+            signature: sign::sign_detached(&signed_bytes, full_id.signing_private_key()),
+            payload: payload,
+        })
+    }
+
+    /// Returns whether the payload is within the size limit and signed by the origin.
+    pub fn verify(&self) -> bool {
+        if self.payload.len() > MAX_BROADCAST_LEN {
+            return false;
+        }
+        match Self::signed_bytes(&self.msg_id, &self.payload) {
+            Ok(signed_bytes) => {
+                sign::verify_detached(&self.signature,
+                                      &signed_bytes,
+                                      self.origin.signing_public_key())
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Returns the public ID of the node that originated the broadcast.
+    pub fn origin(&self) -> &PublicId {
+        &self.origin
+    }
+
+    /// Returns the broadcast's unique identifier.
+    pub fn msg_id(&self) -> &MessageId {
+        &self.msg_id
+    }
+
+    /// Returns the broadcast's payload.
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// Converts the broadcast into the event raised by every node receiving it.
+    pub fn into_event(self) -> Event {
+        Event::Broadcast {
+            origin: *self.origin.name(),
+            msg_id: self.msg_id,
+            payload: self.payload,
+        }
+    }
+
+    fn signed_bytes(msg_id: &MessageId, payload: &[u8]) -> Result<Vec<u8>, RoutingError> {
+        Ok(serialise(&(msg_id, sha256::hash(payload)))?)
+    }
+}
+
+impl Debug for SignedBroadcast {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter,
+               "SignedBroadcast {{ origin: {:?}, {:?}, {} bytes }}",
+               self.origin,
+               self.msg_id,
+               self.payload.len())
+    }
 }
 
 /// Wrapper around a routing message, signed by the originator of the message.
@@ -431,6 +554,8 @@ impl SignedMessage {
                 })
             }
             ManagedNode(_) | Client { .. } => self.signatures.len() == 1,
+            // Messages are never sent from multiple sections at once.
+            Sections(_) => false,
         }
     }
 }
@@ -634,7 +759,9 @@ pub enum MessageContent {
         priority: u8,
         /// Is the message cacheable?
         cacheable: bool,
-        /// The `part_index`-th part of the serialised user message.
+        /// Is the message encrypted for the single node or client it is addressed to?
+        encrypted: bool,
+        /// The `part_index`-th part of the serialised and possibly encrypted user message.
         payload: Vec<u8>,
     },
     /// Confirm with section that the candidate is about to resource prove.
@@ -665,6 +792,40 @@ pub enum MessageContent {
         /// contacts.
         sections: SectionMap,
     },
+    /// A broadcast to be delivered to every node in the network.
+    ///
+    /// Sent from a `ManagedNode` to a `Section`. The origin sends it to its own section with
+    /// `depth` 0. Every recipient delivers it, and forwards it to one section in each subtree of
+    /// the prefix tree that is a sibling of our own prefix at a position not less than `depth`,
+    /// with `depth` set to the length of that subtree's prefix.
+    Broadcast {
+        /// The signed broadcast.
+        broadcast: SignedBroadcast,
+        /// The length of the prefix of the subtree the recipient section is responsible for.
+        depth: u16,
+    },
+    /// A chunk of a stream between two nodes.
+    ///
+    /// Sent from a `ManagedNode` to a `ManagedNode`. Only a window of chunks is unacknowledged at
+    /// any time. A chunk that is resent after all its routes failed has its `attempt` incremented,
+    /// so that it isn't discarded as a duplicate.
+    StreamChunk {
+        /// The stream's unique identifier.
+        stream_id: MessageId,
+        /// The number of chunks in the stream.
+        chunk_count: u32,
+        /// The index of this chunk.
+        index: u32,
+        /// The number of times this chunk has been resent.
+        attempt: u32,
+        /// The chunk's payload.
+        payload: Vec<u8>,
+    },
+    /// Tells the nodes relaying an encrypted response that it makes any cached copies of the given
+    /// data stale, since they can't read the response itself.
+    ///
+    /// Sent with the same source and destination authorities as the response's parts.
+    CacheInvalidation(DataIdentifier),
 }
 
 impl MessageContent {
@@ -673,9 +834,20 @@ impl MessageContent {
         match *self {
             MessageContent::Ack(_, priority) |
             MessageContent::UserMessagePart { priority, .. } => priority,
+            MessageContent::StreamChunk { .. } => DEFAULT_PRIORITY,
             _ => 0,
         }
     }
+
+    /// Returns `true` if this is bulk user data, which may be queued if the connection to the
+    /// next hop is busy, rather than a routing message that has to be sent right away.
+    pub fn is_bulk(&self) -> bool {
+        match *self {
+            MessageContent::UserMessagePart { .. } |
+            MessageContent::StreamChunk { .. } => true,
+            _ => false,
+        }
+    }
 }
 
 impl Debug for DirectMessage {
@@ -690,6 +862,13 @@ impl Debug for DirectMessage {
             SectionListSignature(ref sec_list, _) => {
                 write!(formatter, "SectionListSignature({:?}, ..)", sec_list.prefix)
             }
+            DataRootSignature(ref prefix, version, ref root, _) => {
+                write!(formatter,
+                       "DataRootSignature({:?}, {}, {}, ..)",
+                       prefix,
+                       version,
+                       utils::format_binary_array(root))
+            }
             BootstrapIdentify { ref public_id } => {
                 write!(formatter, "BootstrapIdentify {{ {:?} }}", public_id)
             }
@@ -723,6 +902,9 @@ impl Debug for DirectMessage {
                        leading_zero_bytes)
             }
             ResourceProofResponseReceipt => write!(formatter, "ResourceProofResponseReceipt"),
+            Capabilities { ref compression } => {
+                write!(formatter, "Capabilities {{ compression: {:?} }}", compression)
+            }
         }
     }
 }
@@ -804,13 +986,21 @@ impl Debug for MessageContent {
             OwnSectionMerge(ref sections) => write!(formatter, "OwnSectionMerge({:?})", sections),
             OtherSectionMerge(ref section) => write!(formatter, "OtherSectionMerge({:?})", section),
             Ack(ack, priority) => write!(formatter, "Ack({:?}, {})", ack, priority),
-            UserMessagePart { hash, part_count, part_index, priority, cacheable, .. } => {
+            UserMessagePart { hash,
+                              part_count,
+                              part_index,
+                              priority,
+                              cacheable,
+                              encrypted,
+                              .. } => {
                 write!(formatter,
-                       "UserMessagePart {{ {}/{}, priority: {}, cacheable: {}, {:x} }}",
+                       "UserMessagePart {{ {}/{}, priority: {}, cacheable: {}, encrypted: {}, \
+                        {:x} }}",
                        part_index + 1,
                        part_count,
                        priority,
                        cacheable,
+                       encrypted,
                        hash)
             }
             AcceptAsCandidate { ref expect_id, ref client_auth, ref message_id } => {
@@ -829,6 +1019,19 @@ impl Debug for MessageContent {
                        sections)
             }
             NodeApproval { ref sections } => write!(formatter, "NodeApproval {{ {:?} }}", sections),
+            Broadcast { ref broadcast, depth } => {
+                write!(formatter, "Broadcast {{ {:?}, depth: {} }}", broadcast, depth)
+            }
+            StreamChunk { ref stream_id, chunk_count, index, attempt, ref payload } => {
+                write!(formatter,
+                       "StreamChunk {{ {:?}, {}/{}, attempt: {}, {} bytes }}",
+                       stream_id,
+                       index,
+                       chunk_count,
+                       attempt,
+                       payload.len())
+            }
+            CacheInvalidation(ref data_id) => write!(formatter, "CacheInvalidation({:?})", data_id),
         }
     }
 }
@@ -845,38 +1048,72 @@ pub enum UserMessage {
 impl UserMessage {
     /// Splits up the message into smaller `MessageContent` parts, which can individually be sent
     /// and routed, and then be put back together by the receiver.
-    pub fn to_parts(&self, priority: u8) -> Result<Vec<MessageContent>, RoutingError> {
-        // TODO: This internally serialises the message - remove that duplicated work!
-        let hash = maidsafe_utilities::big_endian_sip_hash(self);
+    ///
+    /// If a `recipient` key is given, the serialised message is encrypted so that only the holder
+    /// of the corresponding secret key can read it. In that case the hash is computed over the
+    /// encrypted payload, so it doesn't reveal anything about the content.
+    ///
+    /// If an encrypted response [invalidates](enum.Response.html#method.invalidated_data_id)
+    /// cached data, a plaintext `CacheInvalidation` with only the data identifier is added after
+    /// the parts, so that the nodes relaying them can still update their caches.
+    pub fn to_parts(&self,
+                    priority: u8,
+                    recipient: Option<&box_::PublicKey>)
+                    -> Result<Vec<MessageContent>, RoutingError> {
         let payload = serialise(self)?;
+        let (hash, payload) = if let Some(recipient) = recipient {
+            let sealed = serialise(&SealedPayload::new(&payload, recipient))?;
+            (maidsafe_utilities::big_endian_sip_hash(&sealed), sealed)
+        } else {
+            // TODO: This internally serialises the message - remove that duplicated work!
+            (maidsafe_utilities::big_endian_sip_hash(self), payload)
+        };
+        let cacheable = recipient.is_none() && self.is_cacheable();
         let len = payload.len();
         let part_count = (len + MAX_PART_LEN - 1) / MAX_PART_LEN;
 
-        Ok((0..part_count)
+        let mut parts: Vec<_> = (0..part_count)
             .map(|i| {
                 MessageContent::UserMessagePart {
                     hash: hash,
                     part_count: part_count as u32,
                     part_index: i as u32,
-                    cacheable: self.is_cacheable(),
+                    cacheable: cacheable,
+                    encrypted: recipient.is_some(),
                     payload: payload[(i * len / part_count)..((i + 1) * len / part_count)].to_vec(),
                     priority: priority,
                 }
             })
-            .collect())
+            .collect();
+        if recipient.is_some() {
+            if let Some(data_id) = self.invalidated_data_id() {
+                parts.push(MessageContent::CacheInvalidation(data_id));
+            }
+        }
+        Ok(parts)
     }
 
     /// Puts the given parts of a serialised message together and verifies that it matches the
     /// given hash code. If it does, returns the `UserMessage`.
+    ///
+    /// If the message is encrypted, `secret_key` must be given to decrypt it, and the hash is
+    /// verified against the encrypted payload instead.
     pub fn from_parts<'a, I: Iterator<Item = &'a Vec<u8>>>(hash: u64,
+                                                           secret_key: Option<&box_::SecretKey>,
                                                            parts: I)
                                                            -> Result<UserMessage, RoutingError> {
         let mut payload = Vec::new();
         for part in parts {
             payload.extend_from_slice(part);
         }
+        if let Some(secret_key) = secret_key {
+            if hash != maidsafe_utilities::big_endian_sip_hash(&payload) {
+                return Err(RoutingError::HashMismatch);
+            }
+            payload = deserialise::<SealedPayload>(&payload[..])?.open(secret_key)?;
+        }
         let user_msg = deserialise(&payload[..])?;
-        if hash != maidsafe_utilities::big_endian_sip_hash(&user_msg) {
+        if secret_key.is_none() && hash != maidsafe_utilities::big_endian_sip_hash(&user_msg) {
             Err(RoutingError::HashMismatch)
         } else {
             Ok(user_msg)
@@ -904,10 +1141,32 @@ impl UserMessage {
         }
     }
 
+    /// Returns whether the nodes relaying this message should pass it to their cache: cacheable
+    /// requests and responses, `Get`s and `GetSuccess`es of structured data, which caches can opt
+    /// in to, and responses invalidating cached data.
     fn is_cacheable(&self) -> bool {
         match *self {
-            UserMessage::Request(ref request) => request.is_cacheable(),
-            UserMessage::Response(ref response) => response.is_cacheable(),
+            UserMessage::Request(ref request) => {
+                request.is_cacheable() ||
+                match *request {
+                    Request::Get(DataIdentifier::Structured(..), _) => true,
+                    _ => false,
+                }
+            }
+            UserMessage::Response(ref response) => {
+                response.is_cacheable() || response.invalidated_data_id().is_some() ||
+                match *response {
+                    Response::GetSuccess(Data::Structured(..), _) => true,
+                    _ => false,
+                }
+            }
+        }
+    }
+
+    fn invalidated_data_id(&self) -> Option<DataIdentifier> {
+        match *self {
+            UserMessage::Request(_) => None,
+            UserMessage::Response(ref response) => response.invalidated_data_id(),
         }
     }
 }
@@ -929,6 +1188,39 @@ pub enum Request {
     Append(AppendWrapper, MessageId),
     /// Get account information for Client with given ID
     GetAccountInfo(MessageId),
+    /// Ask for several data items from the same authority at once
+    GetBatch(Vec<DataIdentifier>, MessageId),
+    /// Put several data items to the same authority at once
+    PutBatch(Vec<Data>, MessageId),
+    /// Insert, update or delete entries of a `MutableData` by key
+    MutateEntries(DataIdentifier, BTreeMap<Vec<u8>, EntryAction>, MessageId),
+    /// Set the permissions of a key for a `MutableData`, or remove them if `None`. The `u64` is
+    /// the new permissions version, i.e. the current one plus one
+    SetUserPermissions(DataIdentifier, sign::PublicKey, Option<PermissionSet>, u64, MessageId),
+    /// Ask for a range of the items of an appendable data, in the order they were appended
+    GetAppendedData(DataIdentifier, AppendedRange, MessageId),
+    /// MPID messaging operation, sent to or between `ClientManager`s, see `MpidManager`
+    Messaging(MpidMessageWrapper, MessageId),
+    /// Ask for data together with a proof that the responsible section holds it
+    GetWithProof(DataIdentifier, MessageId),
+    /// Post data to network only if the stored data currently satisfies the condition
+    ConditionalPost(Data, Condition, MessageId),
+    /// Delete data from network only if the stored data currently satisfies the condition
+    ConditionalDelete(Data, Condition, MessageId),
+}
+
+/// The outcome of a single item of a batched request.
+#[derive(Ord, PartialOrd, Eq, PartialEq, Clone, Hash, Debug, RustcEncodable, RustcDecodable)]
+pub enum BatchItemResult<T> {
+    /// The item was handled successfully.
+    Success(T),
+    /// The item failed.
+    Failure {
+        /// ID of the affected data chunk
+        data_id: DataIdentifier,
+        /// Error type sent back, may be injected from upper layers
+        external_error_indicator: Vec<u8>,
+    },
 }
 
 /// Response message types
@@ -1008,6 +1300,78 @@ pub enum Response {
         /// Error type sent back, may be injected from upper layers
         external_error_indicator: Vec<u8>,
     },
+    /// Success token for entry mutations (may be ignored)
+    MutateEntriesSuccess(DataIdentifier, MessageId),
+    /// Success token for a permissions change (may be ignored)
+    SetUserPermissionsSuccess(DataIdentifier, MessageId),
+    /// Error for entry mutations, includes signed request to prevent injection attacks
+    MutateEntriesFailure {
+        /// Unique message identifier
+        id: MessageId,
+        /// ID of the affected data chunk
+        data_id: DataIdentifier,
+        /// Error type sent back, may be injected from upper layers
+        external_error_indicator: Vec<u8>,
+    },
+    /// Error for a permissions change, includes signed request to prevent injection attacks
+    SetUserPermissionsFailure {
+        /// Unique message identifier
+        id: MessageId,
+        /// ID of the affected data chunk
+        data_id: DataIdentifier,
+        /// Error type sent back, may be injected from upper layers
+        external_error_indicator: Vec<u8>,
+    },
+    /// Response to `GetBatch`, with one result per requested item, in the order of the request
+    GetBatchResponse {
+        /// Unique message identifier
+        id: MessageId,
+        /// The results for the requested items
+        results: Vec<BatchItemResult<Data>>,
+    },
+    /// Response to `PutBatch`, with one result per item, in the order of the request
+    PutBatchResponse {
+        /// Unique message identifier
+        id: MessageId,
+        /// The results for the items
+        results: Vec<BatchItemResult<DataIdentifier>>,
+    },
+    /// MPID messaging notification or reply, sent by `ClientManager`s to their client
+    Messaging(MpidMessageWrapper, MessageId),
+    /// Reply with the requested range of appended items
+    GetAppendedDataSuccess {
+        /// Unique message identifier
+        id: MessageId,
+        /// ID of the appendable data
+        data_id: DataIdentifier,
+        /// The total number of items currently held in the appendable data
+        total: u64,
+        /// The requested items, with their append sequence numbers
+        items: AppendedItems,
+    },
+    /// Error for `GetAppendedData`, includes signed request to prevent injection attacks
+    GetAppendedDataFailure {
+        /// Unique message identifier
+        id: MessageId,
+        /// ID of the appendable data
+        data_id: DataIdentifier,
+        /// Error type sent back, may be injected from upper layers
+        external_error_indicator: Vec<u8>,
+    },
+    /// Error for `ConditionalPost` or `ConditionalDelete` whose condition is not met by the stored
+    /// data, includes signed request to prevent injection attacks
+    ConditionNotMet {
+        /// Unique message identifier
+        id: MessageId,
+        /// ID of the affected data chunk
+        data_id: DataIdentifier,
+        /// The current version of the stored data, if its type is versioned
+        current_version: Option<u64>,
+        /// The hash of the stored data
+        current_hash: ContentHash,
+    },
+    /// Reply to `GetWithProof` with the requested data and the section's proof that it holds it
+    GetSuccessWithProof(Data, DataProof, MessageId),
 }
 
 impl Request {
@@ -1016,11 +1380,20 @@ impl Request {
         match *self {
             Request::Refresh(..) => 2,
             Request::Get(..) |
+            Request::GetWithProof(..) |
+            Request::GetBatch(..) |
+            Request::GetAppendedData(..) |
             Request::GetAccountInfo(..) => 3,
-            Request::Append(..) => 4,
+            Request::Append(..) |
+            Request::MutateEntries(..) |
+            Request::SetUserPermissions(..) |
+            Request::Messaging(..) => 4,
+            Request::PutBatch(..) => 5,
             Request::Put(ref data, _) |
             Request::Post(ref data, _) |
-            Request::Delete(ref data, _) => {
+            Request::Delete(ref data, _) |
+            Request::ConditionalPost(ref data, _, _) |
+            Request::ConditionalDelete(ref data, _, _) => {
                 match *data {
                     Data::Structured(..) => 4,
                     _ => 5,
@@ -1031,10 +1404,31 @@ impl Request {
 
     /// Is the response corresponding to this request cacheable?
     pub fn is_cacheable(&self) -> bool {
-        if let Request::Get(DataIdentifier::Immutable(..), _) = *self {
-            true
-        } else {
-            false
+        match *self {
+            Request::Get(DataIdentifier::Immutable(..), _) => true,
+            _ => false,
+        }
+    }
+
+    /// The unique identifier of this request.
+    pub fn message_id(&self) -> MessageId {
+        match *self {
+            Request::Refresh(_, id) |
+            Request::Get(_, id) |
+            Request::GetWithProof(_, id) |
+            Request::Put(_, id) |
+            Request::Post(_, id) |
+            Request::Delete(_, id) |
+            Request::ConditionalPost(_, _, id) |
+            Request::ConditionalDelete(_, _, id) |
+            Request::Append(_, id) |
+            Request::GetAccountInfo(id) |
+            Request::GetBatch(_, id) |
+            Request::PutBatch(_, id) |
+            Request::MutateEntries(_, _, id) |
+            Request::SetUserPermissions(_, _, _, _, id) |
+            Request::GetAppendedData(_, _, id) |
+            Request::Messaging(_, id) => id,
         }
     }
 }
@@ -1043,7 +1437,8 @@ impl Response {
     /// The priority Crust should send this message with.
     pub fn priority(&self) -> u8 {
         match *self {
-            Response::GetSuccess(ref data, _) => {
+            Response::GetSuccess(ref data, _) |
+            Response::GetSuccessWithProof(ref data, _, _) => {
                 match *data {
                     Data::Structured(..) => 4,
                     _ => 5,
@@ -1053,22 +1448,73 @@ impl Response {
             Response::PostSuccess(..) |
             Response::DeleteSuccess(..) |
             Response::AppendSuccess(..) |
+            Response::MutateEntriesSuccess(..) |
+            Response::SetUserPermissionsSuccess(..) |
             Response::GetAccountInfoSuccess { .. } |
             Response::GetFailure { .. } |
             Response::PutFailure { .. } |
             Response::PostFailure { .. } |
             Response::DeleteFailure { .. } |
+            Response::ConditionNotMet { .. } |
             Response::AppendFailure { .. } |
-            Response::GetAccountInfoFailure { .. } => 3,
+            Response::MutateEntriesFailure { .. } |
+            Response::SetUserPermissionsFailure { .. } |
+            Response::GetAppendedDataFailure { .. } |
+            Response::GetAccountInfoFailure { .. } |
+            Response::PutBatchResponse { .. } |
+            Response::Messaging(..) => 3,
+            Response::GetAppendedDataSuccess { .. } |
+            Response::GetBatchResponse { .. } => 5,
         }
     }
 
     /// Is this response cacheable?
     pub fn is_cacheable(&self) -> bool {
-        if let Response::GetSuccess(Data::Immutable(..), _) = *self {
-            true
-        } else {
-            false
+        match *self {
+            Response::GetSuccess(Data::Immutable(..), _) => true,
+            _ => false,
+        }
+    }
+
+    /// The identifier of the data of which this response makes any cached copies stale, i.e. the
+    /// data that has been successfully posted, deleted, appended to or whose entries have been
+    /// mutated.
+    pub fn invalidated_data_id(&self) -> Option<DataIdentifier> {
+        match *self {
+            Response::PostSuccess(data_id, _) |
+            Response::DeleteSuccess(data_id, _) |
+            Response::AppendSuccess(data_id, _) |
+            Response::MutateEntriesSuccess(data_id, _) => Some(data_id),
+            _ => None,
+        }
+    }
+
+    /// The unique identifier of this response, i.e. that of the request it responds to.
+    pub fn message_id(&self) -> MessageId {
+        match *self {
+            Response::GetSuccess(_, id) |
+            Response::GetSuccessWithProof(_, _, id) |
+            Response::PutSuccess(_, id) |
+            Response::PostSuccess(_, id) |
+            Response::DeleteSuccess(_, id) |
+            Response::AppendSuccess(_, id) |
+            Response::MutateEntriesSuccess(_, id) |
+            Response::SetUserPermissionsSuccess(_, id) |
+            Response::GetAccountInfoSuccess { id, .. } |
+            Response::GetFailure { id, .. } |
+            Response::PutFailure { id, .. } |
+            Response::PostFailure { id, .. } |
+            Response::DeleteFailure { id, .. } |
+            Response::ConditionNotMet { id, .. } |
+            Response::AppendFailure { id, .. } |
+            Response::MutateEntriesFailure { id, .. } |
+            Response::SetUserPermissionsFailure { id, .. } |
+            Response::GetAppendedDataSuccess { id, .. } |
+            Response::GetAppendedDataFailure { id, .. } |
+            Response::GetAccountInfoFailure { id, .. } |
+            Response::GetBatchResponse { id, .. } |
+            Response::PutBatchResponse { id, .. } |
+            Response::Messaging(_, id) => id,
         }
     }
 }
@@ -1085,6 +1531,9 @@ impl Debug for Request {
             Request::Get(ref data_request, ref message_id) => {
                 write!(formatter, "Get({:?}, {:?})", data_request, message_id)
             }
+            Request::GetWithProof(ref data_request, ref message_id) => {
+                write!(formatter, "GetWithProof({:?}, {:?})", data_request, message_id)
+            }
             Request::Put(ref data, ref message_id) => {
                 write!(formatter, "Put({:?}, {:?})", data, message_id)
             }
@@ -1094,12 +1543,62 @@ impl Debug for Request {
             Request::Delete(ref data, ref message_id) => {
                 write!(formatter, "Delete({:?}, {:?})", data, message_id)
             }
+            Request::ConditionalPost(ref data, ref condition, ref message_id) => {
+                write!(formatter,
+                       "ConditionalPost({:?}, {:?}, {:?})",
+                       data,
+                       condition,
+                       message_id)
+            }
+            Request::ConditionalDelete(ref data, ref condition, ref message_id) => {
+                write!(formatter,
+                       "ConditionalDelete({:?}, {:?}, {:?})",
+                       data,
+                       condition,
+                       message_id)
+            }
             Request::Append(ref wrapper, ref message_id) => {
                 write!(formatter, "Append({:?}, {:?})", wrapper, message_id)
             }
             Request::GetAccountInfo(ref message_id) => {
                 write!(formatter, "GetAccountInfo({:?})", message_id)
             }
+            Request::GetBatch(ref data_ids, ref message_id) => {
+                write!(formatter, "GetBatch({:?}, {:?})", data_ids, message_id)
+            }
+            Request::PutBatch(ref data, ref message_id) => {
+                write!(formatter, "PutBatch({} items, {:?})", data.len(), message_id)
+            }
+            Request::MutateEntries(ref data_id, ref actions, ref message_id) => {
+                write!(formatter,
+                       "MutateEntries({:?}, {} actions, {:?})",
+                       data_id,
+                       actions.len(),
+                       message_id)
+            }
+            Request::SetUserPermissions(ref data_id,
+                                        ref user,
+                                        ref permissions,
+                                        version,
+                                        ref message_id) => {
+                write!(formatter,
+                       "SetUserPermissions({:?}, {:?}, {:?}, {}, {:?})",
+                       data_id,
+                       user,
+                       permissions,
+                       version,
+                       message_id)
+            }
+            Request::GetAppendedData(ref data_id, ref range, ref message_id) => {
+                write!(formatter,
+                       "GetAppendedData({:?}, {:?}, {:?})",
+                       data_id,
+                       range,
+                       message_id)
+            }
+            Request::Messaging(ref wrapper, ref message_id) => {
+                write!(formatter, "Messaging({:?}, {:?})", wrapper, message_id)
+            }
         }
     }
 }
@@ -1110,6 +1609,13 @@ impl Debug for Response {
             Response::GetSuccess(ref data, ref message_id) => {
                 write!(formatter, "GetSuccess({:?}, {:?})", data, message_id)
             }
+            Response::GetSuccessWithProof(ref data, ref proof, ref message_id) => {
+                write!(formatter,
+                       "GetSuccessWithProof({:?}, {:?}, {:?})",
+                       data,
+                       proof.prefix(),
+                       message_id)
+            }
             Response::PutSuccess(ref name, ref message_id) => {
                 write!(formatter, "PutSuccess({:?}, {:?})", name, message_id)
             }
@@ -1122,6 +1628,19 @@ impl Debug for Response {
             Response::AppendSuccess(ref name, ref message_id) => {
                 write!(formatter, "AppendSuccess({:?}, {:?})", name, message_id)
             }
+            Response::MutateEntriesSuccess(ref name, ref message_id) => {
+                write!(formatter, "MutateEntriesSuccess({:?}, {:?})", name, message_id)
+            }
+            Response::SetUserPermissionsSuccess(ref name, ref message_id) => {
+                write!(formatter, "SetUserPermissionsSuccess({:?}, {:?})", name, message_id)
+            }
+            Response::GetAppendedDataSuccess { ref id, ref data_id, total, .. } => {
+                write!(formatter,
+                       "GetAppendedDataSuccess {{ {:?}, {:?}, {} total, .. }}",
+                       id,
+                       data_id,
+                       total)
+            }
             Response::GetAccountInfoSuccess { ref id, .. } => {
                 write!(formatter, "GetAccountInfoSuccess {{ {:?}, .. }}", id)
             }
@@ -1137,16 +1656,81 @@ impl Debug for Response {
             Response::DeleteFailure { ref id, ref data_id, .. } => {
                 write!(formatter, "DeleteFailure {{ {:?}, {:?}, .. }}", id, data_id)
             }
+            Response::ConditionNotMet { ref id, ref data_id, current_version, .. } => {
+                write!(formatter,
+                       "ConditionNotMet {{ {:?}, {:?}, {:?}, .. }}",
+                       id,
+                       data_id,
+                       current_version)
+            }
             Response::AppendFailure { ref id, ref data_id, .. } => {
                 write!(formatter, "AppendFailure {{ {:?}, {:?}, .. }}", id, data_id)
             }
+            Response::MutateEntriesFailure { ref id, ref data_id, .. } => {
+                write!(formatter, "MutateEntriesFailure {{ {:?}, {:?}, .. }}", id, data_id)
+            }
+            Response::SetUserPermissionsFailure { ref id, ref data_id, .. } => {
+                write!(formatter,
+                       "SetUserPermissionsFailure {{ {:?}, {:?}, .. }}",
+                       id,
+                       data_id)
+            }
+            Response::GetAppendedDataFailure { ref id, ref data_id, .. } => {
+                write!(formatter, "GetAppendedDataFailure {{ {:?}, {:?}, .. }}", id, data_id)
+            }
             Response::GetAccountInfoFailure { ref id, .. } => {
                 write!(formatter, "GetAccountInfoFailure {{ {:?}, .. }}", id)
             }
+            Response::GetBatchResponse { ref id, ref results } => {
+                write!(formatter,
+                       "GetBatchResponse {{ {:?}, {} results }}",
+                       id,
+                       results.len())
+            }
+            Response::PutBatchResponse { ref id, ref results } => {
+                write!(formatter, "PutBatchResponse {{ {:?}, {:?} }}", id, results)
+            }
+            Response::Messaging(ref wrapper, ref message_id) => {
+                write!(formatter, "Messaging({:?}, {:?})", wrapper, message_id)
+            }
         }
     }
 }
 
+/// A user message payload, encrypted with an ephemeral key pair for a single recipient.
+///
+/// As the ephemeral secret key is discarded after sealing, only the recipient can open it.
+#[derive(RustcEncodable, RustcDecodable)]
+struct SealedPayload {
+    public_key: box_::PublicKey,
+    nonce: [u8; box_::NONCEBYTES],
+    ciphertext: Vec<u8>,
+}
+
+impl SealedPayload {
+    /// Encrypts `payload` for `recipient`.
+    ///
+    /// The ephemeral key pair and the nonce are random, so sealing the same payload twice gives
+    /// unrelated bytes: nobody can confirm a guessed plaintext or link equal messages.
+    fn new(payload: &[u8], recipient: &box_::PublicKey) -> SealedPayload {
+        let (public_key, secret_key) = box_::gen_keypair();
+        let nonce = box_::gen_nonce();
+        SealedPayload {
+            public_key: public_key,
+            nonce: nonce.0,
+            ciphertext: box_::seal(payload, &nonce, recipient, &secret_key),
+        }
+    }
+
+    fn open(&self, secret_key: &box_::SecretKey) -> Result<Vec<u8>, RoutingError> {
+        box_::open(&self.ciphertext,
+                   &box_::Nonce(self.nonce),
+                   &self.public_key,
+                   secret_key)
+            .map_err(|()| RoutingError::AsymmetricDecryptionFailure)
+    }
+}
+
 /// This assembles `UserMessage`s from `UserMessagePart`s.
 /// It maps `(hash, part_count)` of an incoming `UserMessage` to the map containing
 /// all `UserMessagePart`s that have already arrived, by `part_index`.
@@ -1159,11 +1743,14 @@ impl UserMessageCache {
 
     /// Adds the given one to the cache of received message parts, returning a `UserMessage` if the
     /// given part was the last missing piece of it.
+    ///
+    /// `secret_key` must be given if and only if the message is encrypted.
     pub fn add(&mut self,
                hash: u64,
                part_count: u32,
                part_index: u32,
-               payload: Vec<u8>)
+               payload: Vec<u8>,
+               secret_key: Option<&box_::SecretKey>)
                -> Option<UserMessage> {
         {
             let entry = self.0.entry((hash, part_count)).or_insert_with(BTreeMap::new);
@@ -1181,7 +1768,7 @@ impl UserMessageCache {
 
         self.0
             .remove(&(hash, part_count))
-            .and_then(|part_map| UserMessage::from_parts(hash, part_map.values()).ok())
+            .and_then(|part_map| UserMessage::from_parts(hash, secret_key, part_map.values()).ok())
     }
 }
 
@@ -1190,7 +1777,7 @@ mod tests {
 
     #[cfg(not(feature = "use-mock-crust"))]
     use crust::PeerId;
-    use data::{Data, ImmutableData};
+    use data::{Data, DataIdentifier, ImmutableData, StructuredData};
     use id::FullId;
     use maidsafe_utilities;
     use maidsafe_utilities::serialisation::serialise;
@@ -1198,8 +1785,8 @@ mod tests {
     use mock_crust::crust::PeerId;
     use rand;
     use routing_table::{Authority, Prefix};
+    use rust_sodium::crypto::{box_, sign};
     use rust_sodium::crypto::hash::sha256;
-    use rust_sodium::crypto::sign;
     use std::collections::BTreeSet;
     use std::iter;
     use super::*;
@@ -1265,7 +1852,7 @@ mod tests {
         let data_bytes: Vec<u8> = (0..10).map(|i| i as u8).collect();
         let data = Data::Immutable(ImmutableData::new(data_bytes));
         let user_msg = UserMessage::Request(Request::Put(data, MessageId::new()));
-        let parts = unwrap!(user_msg.to_parts(1));
+        let parts = unwrap!(user_msg.to_parts(1, None));
         assert_eq!(1, parts.len());
         let part = parts[0].clone();
         let name: XorName = rand::random();
@@ -1354,7 +1941,7 @@ mod tests {
         let data = Data::Immutable(ImmutableData::new(data_bytes));
         let user_msg = UserMessage::Request(Request::Put(data, MessageId::new()));
         let msg_hash = maidsafe_utilities::big_endian_sip_hash(&user_msg);
-        let parts = unwrap!(user_msg.to_parts(42));
+        let parts = unwrap!(user_msg.to_parts(42, None));
         assert_eq!(parts.len(), 3);
         let payloads: Vec<Vec<u8>> = parts.into_iter()
             .enumerate()
@@ -1364,9 +1951,11 @@ mod tests {
                                                   part_index,
                                                   payload,
                                                   priority,
-                                                  cacheable } => {
+                                                  cacheable,
+                                                  encrypted } => {
                     assert_eq!(msg_hash, hash);
                     assert_eq!(3, part_count);
+                    assert!(!encrypted);
                     assert_eq!(i, part_index as usize);
                     assert_eq!(42, priority);
                     assert!(!cacheable);
@@ -1375,7 +1964,122 @@ mod tests {
                 msg => panic!("Unexpected message {:?}", msg),
             })
             .collect();
-        let deserialised_user_msg = unwrap!(UserMessage::from_parts(msg_hash, payloads.iter()));
+        let deserialised_user_msg =
+            unwrap!(UserMessage::from_parts(msg_hash, None, payloads.iter()));
         assert_eq!(user_msg, deserialised_user_msg);
     }
+
+    #[test]
+    fn encrypted_user_message_parts() {
+        let data_bytes: Vec<u8> = (0..(MAX_PART_LEN * 2)).map(|_| rand::random()).collect();
+        let data = Data::Immutable(ImmutableData::new(data_bytes.clone()));
+        let user_msg = UserMessage::Response(Response::GetSuccess(data, MessageId::new()));
+        let recipient = FullId::new();
+        let parts =
+            unwrap!(user_msg.to_parts(42, Some(recipient.public_id().encrypting_public_key())));
+        assert_eq!(parts.len(), 3);
+        let mut msg_hash = None;
+        let payloads: Vec<Vec<u8>> = parts.into_iter()
+            .map(|msg| match msg {
+                MessageContent::UserMessagePart { hash, cacheable, encrypted, payload, .. } => {
+                    assert!(!cacheable);
+                    assert!(encrypted);
+                    msg_hash = Some(hash);
+                    payload
+                }
+                msg => panic!("Unexpected message {:?}", msg),
+            })
+            .collect();
+        let msg_hash = unwrap!(msg_hash);
+        assert_ne!(maidsafe_utilities::big_endian_sip_hash(&user_msg), msg_hash);
+
+        // The plaintext must not be visible to relays.
+        let ciphertext: Vec<u8> = payloads.iter().flat_map(|payload| payload.clone()).collect();
+        assert!(!ciphertext.windows(32).any(|window| window == &data_bytes[..32]));
+
+        // Neither a missing nor a wrong key can be used to decrypt it.
+        assert!(UserMessage::from_parts(msg_hash, None, payloads.iter()).is_err());
+        let wrong_key = FullId::new();
+        assert!(UserMessage::from_parts(msg_hash,
+                                        Some(wrong_key.encrypting_private_key()),
+                                        payloads.iter())
+            .is_err());
+
+        let deserialised_user_msg =
+            unwrap!(UserMessage::from_parts(msg_hash,
+                                            Some(recipient.encrypting_private_key()),
+                                            payloads.iter()));
+        assert_eq!(user_msg, deserialised_user_msg);
+
+        // Sealing the same message again gives different parts, so they can't be linked.
+        let recipient_key = recipient.public_id().encrypting_public_key();
+        let other_parts = unwrap!(user_msg.to_parts(42, Some(recipient_key)));
+        let other_payloads: Vec<Vec<u8>> = other_parts.into_iter()
+            .map(|msg| match msg {
+                MessageContent::UserMessagePart { payload, .. } => payload,
+                msg => panic!("Unexpected message {:?}", msg),
+            })
+            .collect();
+        assert!(payloads != other_payloads);
+    }
+
+    #[test]
+    fn cacheable_user_message_parts() {
+        let is_cacheable = |user_msg: UserMessage, recipient: Option<&box_::PublicKey>| {
+            let parts = unwrap!(user_msg.to_parts(42, recipient));
+            parts.into_iter().all(|part| match part {
+                MessageContent::UserMessagePart { cacheable, .. } => cacheable,
+                msg => panic!("Unexpected message {:?}", msg),
+            })
+        };
+        let recipient = FullId::new();
+        let recipient_key = recipient.public_id().encrypting_public_key();
+        let name = rand::random();
+        let sd_id = DataIdentifier::Structured(name, 0);
+
+        // Structured data isn't cacheable, but relays pass it to caches which opt in to it.
+        let request = Request::Get(sd_id, MessageId::new());
+        assert!(!request.is_cacheable());
+        assert!(is_cacheable(UserMessage::Request(request), None));
+        let sd = unwrap!(StructuredData::new(0, name, 0, vec![], BTreeSet::new()));
+        let response = Response::GetSuccess(Data::Structured(sd), MessageId::new());
+        assert!(!response.is_cacheable());
+        assert!(is_cacheable(UserMessage::Response(response.clone()), None));
+        assert!(!is_cacheable(UserMessage::Response(response), Some(recipient_key)));
+
+        // Responses invalidating cached data are visible to relays. If they are encrypted, they
+        // are followed by a plaintext notice with only the data identifier.
+        let responses = vec![Response::PostSuccess(sd_id, MessageId::new()),
+                             Response::DeleteSuccess(sd_id, MessageId::new()),
+                             Response::AppendSuccess(sd_id, MessageId::new()),
+                             Response::MutateEntriesSuccess(sd_id, MessageId::new())];
+        for response in responses {
+            assert_eq!(response.invalidated_data_id(), Some(sd_id));
+            let user_msg = UserMessage::Response(response);
+            assert!(is_cacheable(user_msg.clone(), None));
+
+            let mut parts = unwrap!(user_msg.to_parts(42, Some(recipient_key)));
+            match parts.pop() {
+                Some(MessageContent::CacheInvalidation(data_id)) => assert_eq!(data_id, sd_id),
+                msg => panic!("Unexpected message {:?}", msg),
+            }
+            let mut msg_hash = None;
+            let payloads: Vec<Vec<u8>> = parts.into_iter()
+                .map(|part| match part {
+                    MessageContent::UserMessagePart { hash, cacheable, encrypted, payload, .. } => {
+                        assert!(!cacheable);
+                        assert!(encrypted);
+                        msg_hash = Some(hash);
+                        payload
+                    }
+                    msg => panic!("Unexpected message {:?}", msg),
+                })
+                .collect();
+            let secret_key = recipient.encrypting_private_key();
+            assert_eq!(user_msg,
+                       unwrap!(UserMessage::from_parts(unwrap!(msg_hash),
+                                                       Some(secret_key),
+                                                       payloads.iter())));
+        }
+    }
 }
@@ -28,6 +28,33 @@ pub enum Error {
     /// Used where the length of a [message's `body`](struct.MpidMessage.html#method.new) exceeds
     /// [`MAX_BODY_SIZE`](constant.MAX_BODY_SIZE.html).
     BodyTooLarge,
+    /// Storing a header would exceed the recipient's
+    /// [`MAX_INBOX_SIZE`](constant.MAX_INBOX_SIZE.html).
+    InboxFull,
+    /// Storing a message would exceed the sender's
+    /// [`MAX_OUTBOX_SIZE`](constant.MAX_OUTBOX_SIZE.html).
+    OutboxFull,
+    /// The message or header is not in the inbox or outbox.
+    NoSuchMessage,
+    /// The source of a message is not allowed to send it to the given account.
+    InvalidAuthority,
+    /// A message's signature doesn't match its sender.
+    InvalidSignature,
+    /// The message is a response, which managers don't handle.
+    UnexpectedMessage,
+    /// A message's body is not encrypted.
+    NotEncrypted,
+    /// A message's body could not be decrypted with the given keys.
+    DecryptionFailed,
+    /// Used where the [allow-list and block-list](struct.MpidContacts.html#method.new) together
+    /// exceed [`MAX_CONTACTS`](constant.MAX_CONTACTS.html).
+    TooManyContacts,
+    /// The lists' version is not greater than that of the currently held ones.
+    OutdatedContacts,
+    /// The recipient has blocked the message's sender.
+    SenderBlocked,
+    /// The sender, unknown to the recipient, has exceeded its rate limit.
+    RateLimited,
     /// Serialisation error.
     Serialisation(SerialisationError),
 }
@@ -37,6 +64,18 @@ impl Display for Error {
         match *self {
             Error::MetadataTooLarge => write!(formatter, "Message header too large"),
             Error::BodyTooLarge => write!(formatter, "Message body too large"),
+            Error::InboxFull => write!(formatter, "Inbox size limit exceeded"),
+            Error::OutboxFull => write!(formatter, "Outbox size limit exceeded"),
+            Error::NoSuchMessage => write!(formatter, "No such message"),
+            Error::InvalidAuthority => write!(formatter, "Invalid message source"),
+            Error::InvalidSignature => write!(formatter, "Invalid message signature"),
+            Error::UnexpectedMessage => write!(formatter, "Unexpected message type"),
+            Error::NotEncrypted => write!(formatter, "Message body is not encrypted"),
+            Error::DecryptionFailed => write!(formatter, "Failed to decrypt message body"),
+            Error::TooManyContacts => write!(formatter, "Contact lists too large"),
+            Error::OutdatedContacts => write!(formatter, "Contact lists version too low"),
+            Error::SenderBlocked => write!(formatter, "Sender blocked by recipient"),
+            Error::RateLimited => write!(formatter, "Sender rate limit exceeded"),
             Error::Serialisation(ref error) => write!(formatter, "Serialisation error: {}", error),
         }
     }
@@ -47,6 +86,18 @@ impl StdError for Error {
         match *self {
             Error::MetadataTooLarge => "Header too large",
             Error::BodyTooLarge => "Body too large",
+            Error::InboxFull => "Inbox full",
+            Error::OutboxFull => "Outbox full",
+            Error::NoSuchMessage => "No such message",
+            Error::InvalidAuthority => "Invalid authority",
+            Error::InvalidSignature => "Invalid signature",
+            Error::UnexpectedMessage => "Unexpected message",
+            Error::NotEncrypted => "Not encrypted",
+            Error::DecryptionFailed => "Decryption failed",
+            Error::TooManyContacts => "Too many contacts",
+            Error::OutdatedContacts => "Outdated contacts",
+            Error::SenderBlocked => "Sender blocked",
+            Error::RateLimited => "Rate limited",
             Error::Serialisation(ref error) => error.description(),
         }
     }
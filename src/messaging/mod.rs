@@ -21,16 +21,32 @@ pub const GUID_SIZE: usize = 16;
 pub const MAX_INBOX_SIZE: usize = 1 << 27;
 /// Maximum allowed outbox size for an account (128 MiB).
 pub const MAX_OUTBOX_SIZE: usize = 1 << 27;
+/// Maximum number of headers a sender which is not in the recipient's allow-list can send to it
+/// within each [`UNKNOWN_SENDER_WINDOW_SECS`](constant.UNKNOWN_SENDER_WINDOW_SECS.html).
+pub const MAX_HEADERS_PER_UNKNOWN_SENDER: usize = 10;
+/// Maximum number of headers all senders which are not in the recipient's allow-list together can
+/// send to it within each [`UNKNOWN_SENDER_WINDOW_SECS`](constant.UNKNOWN_SENDER_WINDOW_SECS.html).
+pub const MAX_HEADERS_FROM_UNKNOWN_SENDERS: usize = 100;
+/// The time window for `MAX_HEADERS_PER_UNKNOWN_SENDER` and `MAX_HEADERS_FROM_UNKNOWN_SENDERS`, in
+/// seconds (one hour).
+pub const UNKNOWN_SENDER_WINDOW_SECS: u64 = 60 * 60;
 
 mod error;
+mod mpid_contacts;
 mod mpid_header;
+mod mpid_manager;
 mod mpid_message;
 mod mpid_message_wrapper;
+mod mpid_receipt;
 
 pub use self::error::Error;
+pub use self::mpid_contacts::{MAX_CONTACTS, MpidContacts};
 pub use self::mpid_header::{MAX_HEADER_METADATA_SIZE, MpidHeader};
+pub use self::mpid_manager::{MemoryMpidStorage, MpidFailure, MpidManager, MpidOutgoing,
+                             MpidStorage, UnknownSenders};
 pub use self::mpid_message::{MAX_BODY_SIZE, MpidMessage};
 pub use self::mpid_message_wrapper::MpidMessageWrapper;
+pub use self::mpid_receipt::{MessageStatus, MpidReceipt, ReceiptKind};
 
 #[cfg(test)]
 fn generate_random_bytes(size: usize) -> Vec<u8> {
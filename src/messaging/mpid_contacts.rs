@@ -0,0 +1,179 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.1.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+/// Maximum allowed total number of names in the allow-list and block-list of an account.
+pub const MAX_CONTACTS: usize = 1000;
+
+use maidsafe_utilities::serialisation::serialise;
+use rust_sodium::crypto::hash::sha256;
+use rust_sodium::crypto::sign::{self, PublicKey, SecretKey, Signature};
+use std::collections::BTreeSet;
+use std::fmt::{self, Debug, Formatter};
+use super::Error;
+use utils;
+use xor_name::XorName;
+
+#[derive(PartialEq, Eq, Hash, Clone, RustcDecodable, RustcEncodable)]
+struct Detail {
+    owner_key: PublicKey,
+    version: u64,
+    allowed: BTreeSet<XorName>,
+    blocked: BTreeSet<XorName>,
+}
+
+/// The senders an account accepts messages from, signed by the account's owner.
+///
+/// Headers from blocked senders are rejected by the owner's MpidManagers.  Allowed senders are
+/// not rate limited, while all other senders may only send a limited number of headers per time
+/// window.
+#[derive(PartialEq, Eq, Hash, Clone, RustcDecodable, RustcEncodable)]
+pub struct MpidContacts {
+    detail: Detail,
+    signature: Signature,
+}
+
+impl MpidContacts {
+    /// Constructor.
+    ///
+    /// `version` must be greater than that of the lists currently held by the MpidManagers for
+    /// them to be replaced.  `public_key` and `secret_key` are the owner's signing keys, and the
+    /// owner's name is derived from `public_key`.
+    ///
+    /// An error will be returned if `allowed` and `blocked` together contain more than
+    /// [`MAX_CONTACTS`](constant.MAX_CONTACTS.html) names or if serialisation during the signing
+    /// process fails.
+    pub fn new(allowed: BTreeSet<XorName>,
+               blocked: BTreeSet<XorName>,
+               version: u64,
+               public_key: &PublicKey,
+               secret_key: &SecretKey)
+               -> Result<MpidContacts, Error> {
+        if allowed.len() + blocked.len() > MAX_CONTACTS {
+            return Err(Error::TooManyContacts);
+        }
+
+        let detail = Detail {
+            owner_key: *public_key,
+            version: version,
+            allowed: allowed,
+            blocked: blocked,
+        };
+
+        let encoded = serialise(&detail)?;
+        Ok(MpidContacts {
+            detail: detail,
+            signature: sign::sign_detached(&encoded, secret_key),
+        })
+    }
+
+    /// The public signing key of the account's owner.
+    pub fn owner_key(&self) -> &PublicKey {
+        &self.detail.owner_key
+    }
+
+    /// The name of the account, derived from the owner's public key.
+    pub fn owner(&self) -> XorName {
+        XorName(sha256::hash(&self.detail.owner_key[..]).0)
+    }
+
+    /// The version of the lists.
+    pub fn version(&self) -> u64 {
+        self.detail.version
+    }
+
+    /// The senders which are not rate limited.
+    pub fn allowed(&self) -> &BTreeSet<XorName> {
+        &self.detail.allowed
+    }
+
+    /// The senders whose messages are rejected.
+    pub fn blocked(&self) -> &BTreeSet<XorName> {
+        &self.detail.blocked
+    }
+
+    /// Whether `sender` is in the allow-list.
+    pub fn is_allowed(&self, sender: &XorName) -> bool {
+        self.detail.allowed.contains(sender)
+    }
+
+    /// Whether `sender` is in the block-list.
+    pub fn is_blocked(&self, sender: &XorName) -> bool {
+        self.detail.blocked.contains(sender)
+    }
+
+    /// Validates the signature against the owner's public key.
+    pub fn verify(&self) -> bool {
+        match serialise(&self.detail) {
+            Ok(encoded) => sign::verify_detached(&self.signature, &encoded, &self.detail.owner_key),
+            Err(_) => false,
+        }
+    }
+}
+
+impl Debug for MpidContacts {
+    fn fmt(&self, formatter: &mut Formatter) -> Result<(), fmt::Error> {
+        write!(formatter,
+               "MpidContacts {{ owner: {:?}, version: {}, allowed: {:?}, blocked: {:?}, \
+                signature: {} }}",
+               self.owner(),
+               self.detail.version,
+               self.detail.allowed,
+               self.detail.blocked,
+               utils::format_binary_array(&self.signature))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use messaging::Error;
+    use rand;
+    use rust_sodium::crypto::hash::sha256;
+    use rust_sodium::crypto::sign;
+    use std::collections::BTreeSet;
+    use super::*;
+    use xor_name::XorName;
+
+    #[test]
+    fn lists() {
+        let (public_key, secret_key) = sign::gen_keypair();
+        let friend: XorName = rand::random();
+        let spammer: XorName = rand::random();
+        let allowed = Some(friend).into_iter().collect();
+        let blocked = Some(spammer).into_iter().collect();
+        let contacts = unwrap!(MpidContacts::new(allowed, blocked, 1, &public_key, &secret_key));
+        assert_eq!(contacts.owner(), XorName(sha256::hash(&public_key[..]).0));
+        assert!(contacts.is_allowed(&friend));
+        assert!(!contacts.is_blocked(&friend));
+        assert!(contacts.is_blocked(&spammer));
+        assert!(!contacts.is_allowed(&rand::random()));
+        assert!(contacts.verify());
+
+        let (_, other_secret_key) = sign::gen_keypair();
+        let forged = unwrap!(MpidContacts::new(BTreeSet::new(),
+                                               BTreeSet::new(),
+                                               2,
+                                               &public_key,
+                                               &other_secret_key));
+        assert!(!forged.verify());
+
+        let too_many = (0..MAX_CONTACTS + 1).map(|_| rand::random()).collect();
+        match MpidContacts::new(too_many, BTreeSet::new(), 3, &public_key, &secret_key) {
+            Err(Error::TooManyContacts) => (),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,1010 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.1.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use maidsafe_utilities::serialisation::serialised_size;
+use routing_table::Authority;
+use rust_sodium::crypto::hash::sha256;
+use rust_sodium::crypto::sign::PublicKey;
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+use super::{Error, MAX_HEADERS_FROM_UNKNOWN_SENDERS, MAX_HEADERS_PER_UNKNOWN_SENDER,
+            MAX_INBOX_SIZE, MAX_OUTBOX_SIZE, MessageStatus, MpidContacts, MpidHeader, MpidMessage,
+            MpidMessageWrapper, MpidReceipt, ReceiptKind, UNKNOWN_SENDER_WINDOW_SECS};
+use types::MessageId;
+use utils;
+use xor_name::XorName;
+
+/// Salt for the message IDs of failures sent to other accounts' managers.
+const FAILURE_SALT: &'static [u8] = b"failure";
+
+/// Storage for the inboxes and outboxes of the accounts an `MpidManager` is responsible for.
+///
+/// Accounts are identified by the name of their client, and messages and headers by
+/// [`MpidHeader::name()`](struct.MpidHeader.html#method.name).
+pub trait MpidStorage {
+    /// Returns the message `name` in the outbox of `account`.
+    fn outbox_message(&self, account: &XorName, name: &XorName) -> Option<MpidMessage>;
+    /// Returns the headers of all messages in the outbox of `account`.
+    fn outbox_headers(&self, account: &XorName) -> Vec<MpidHeader>;
+    /// Returns the total serialised size of the messages in the outbox of `account`.
+    fn outbox_size(&self, account: &XorName) -> usize;
+    /// Stores `message` as `name` in the outbox of `account`, with status `Stored`.
+    fn put_outbox_message(&mut self, account: XorName, name: XorName, message: MpidMessage);
+    /// Removes and returns the message `name` from the outbox of `account`. Its status is kept
+    /// until it is removed with `remove_message_status`.
+    fn remove_outbox_message(&mut self, account: &XorName, name: &XorName) -> Option<MpidMessage>;
+    /// Returns the status of the message `name` in the outbox of `account`.
+    fn message_status(&self, account: &XorName, name: &XorName) -> Option<MessageStatus>;
+    /// Sets the status of the message `name` in the outbox of `account`, if it has one.
+    fn set_message_status(&mut self, account: &XorName, name: &XorName, status: MessageStatus);
+    /// Removes the status of the message `name` in the outbox of `account`.
+    fn remove_message_status(&mut self, account: &XorName, name: &XorName);
+    /// Returns the allow-list and block-list of `account`.
+    fn contacts(&self, account: &XorName) -> Option<MpidContacts>;
+    /// Replaces the allow-list and block-list of `account`.
+    fn put_contacts(&mut self, account: XorName, contacts: MpidContacts);
+    /// Returns the headers accepted for `account` from senders not in its allow-list.
+    fn unknown_senders(&self, account: &XorName) -> Option<UnknownSenders>;
+    /// Replaces the headers accepted for `account` from senders not in its allow-list.
+    fn put_unknown_senders(&mut self, account: XorName, senders: UnknownSenders);
+    /// Returns the header `name` in the inbox of `account`.
+    fn inbox_header(&self, account: &XorName, name: &XorName) -> Option<MpidHeader>;
+    /// Returns all headers in the inbox of `account`.
+    fn inbox_headers(&self, account: &XorName) -> Vec<MpidHeader>;
+    /// Returns the total serialised size of the headers in the inbox of `account`.
+    fn inbox_size(&self, account: &XorName) -> usize;
+    /// Stores `header` as `name` in the inbox of `account`.
+    fn put_inbox_header(&mut self, account: XorName, name: XorName, header: MpidHeader);
+    /// Removes and returns the header `name` from the inbox of `account`.
+    fn remove_inbox_header(&mut self, account: &XorName, name: &XorName) -> Option<MpidHeader>;
+}
+
+/// The numbers of headers an account has accepted from senders not in its allow-list within the
+/// current rate limiting window.
+///
+/// These are kept in the `MpidStorage`, so that they survive a restart and are handed over with
+/// the rest of the account, instead of being reset.
+#[derive(Clone, Debug, Default, PartialEq, Eq, RustcEncodable, RustcDecodable)]
+pub struct UnknownSenders {
+    /// The start of the window, in seconds since the UNIX epoch.
+    pub window_start: u64,
+    /// The number of headers accepted from each sender.
+    pub counts: BTreeMap<XorName, usize>,
+}
+
+impl UnknownSenders {
+    /// Returns the number of headers accepted from all senders.
+    pub fn total(&self) -> usize {
+        self.counts.values().sum()
+    }
+}
+
+#[derive(Default)]
+struct Account {
+    outbox: BTreeMap<XorName, MpidMessage>,
+    outbox_size: usize,
+    statuses: HashMap<XorName, MessageStatus>,
+    inbox: BTreeMap<XorName, MpidHeader>,
+    inbox_size: usize,
+    contacts: Option<MpidContacts>,
+    unknown_senders: Option<UnknownSenders>,
+}
+
+/// An `MpidStorage` that keeps all accounts in memory.
+#[derive(Default)]
+pub struct MemoryMpidStorage {
+    accounts: HashMap<XorName, Account>,
+}
+
+impl MemoryMpidStorage {
+    /// Creates a new, empty storage.
+    pub fn new() -> MemoryMpidStorage {
+        Default::default()
+    }
+
+    fn remove_if_empty(&mut self, account: &XorName) {
+        let is_empty = self.accounts
+            .get(account)
+            .map_or(false, |acc| {
+                acc.outbox.is_empty() && acc.statuses.is_empty() && acc.inbox.is_empty() &&
+                acc.contacts.is_none() && acc.unknown_senders.is_none()
+            });
+        if is_empty {
+            let _ = self.accounts.remove(account);
+        }
+    }
+}
+
+impl MpidStorage for MemoryMpidStorage {
+    fn outbox_message(&self, account: &XorName, name: &XorName) -> Option<MpidMessage> {
+        self.accounts.get(account).and_then(|acc| acc.outbox.get(name).cloned())
+    }
+
+    fn outbox_headers(&self, account: &XorName) -> Vec<MpidHeader> {
+        self.accounts.get(account).map_or_else(Vec::new, |acc| {
+            acc.outbox.values().map(|message| message.header().clone()).collect()
+        })
+    }
+
+    fn outbox_size(&self, account: &XorName) -> usize {
+        self.accounts.get(account).map_or(0, |acc| acc.outbox_size)
+    }
+
+    fn put_outbox_message(&mut self, account: XorName, name: XorName, message: MpidMessage) {
+        let acc = self.accounts.entry(account).or_insert_with(Account::default);
+        acc.outbox_size += serialised_size(&message) as usize;
+        if let Some(old) = acc.outbox.insert(name, message) {
+            acc.outbox_size -= serialised_size(&old) as usize;
+        }
+        let _ = acc.statuses.insert(name, MessageStatus::Stored);
+    }
+
+    fn remove_outbox_message(&mut self, account: &XorName, name: &XorName) -> Option<MpidMessage> {
+        let removed = self.accounts.get_mut(account).and_then(|acc| {
+            let removed = acc.outbox.remove(name);
+            if let Some(ref message) = removed {
+                acc.outbox_size -= serialised_size(message) as usize;
+            }
+            removed
+        });
+        self.remove_if_empty(account);
+        removed
+    }
+
+    fn message_status(&self, account: &XorName, name: &XorName) -> Option<MessageStatus> {
+        self.accounts.get(account).and_then(|acc| acc.statuses.get(name).cloned())
+    }
+
+    fn set_message_status(&mut self, account: &XorName, name: &XorName, status: MessageStatus) {
+        if let Some(current) = self.accounts
+            .get_mut(account)
+            .and_then(|acc| acc.statuses.get_mut(name)) {
+            *current = status;
+        }
+    }
+
+    fn remove_message_status(&mut self, account: &XorName, name: &XorName) {
+        if let Some(acc) = self.accounts.get_mut(account) {
+            let _ = acc.statuses.remove(name);
+        }
+        self.remove_if_empty(account);
+    }
+
+    fn contacts(&self, account: &XorName) -> Option<MpidContacts> {
+        self.accounts.get(account).and_then(|acc| acc.contacts.clone())
+    }
+
+    fn put_contacts(&mut self, account: XorName, contacts: MpidContacts) {
+        self.accounts.entry(account).or_insert_with(Account::default).contacts = Some(contacts);
+    }
+
+    fn unknown_senders(&self, account: &XorName) -> Option<UnknownSenders> {
+        self.accounts.get(account).and_then(|acc| acc.unknown_senders.clone())
+    }
+
+    fn put_unknown_senders(&mut self, account: XorName, senders: UnknownSenders) {
+        let acc = self.accounts.entry(account).or_insert_with(Account::default);
+        acc.unknown_senders = Some(senders);
+    }
+
+    fn inbox_header(&self, account: &XorName, name: &XorName) -> Option<MpidHeader> {
+        self.accounts.get(account).and_then(|acc| acc.inbox.get(name).cloned())
+    }
+
+    fn inbox_headers(&self, account: &XorName) -> Vec<MpidHeader> {
+        self.accounts
+            .get(account)
+            .map_or_else(Vec::new, |acc| acc.inbox.values().cloned().collect())
+    }
+
+    fn inbox_size(&self, account: &XorName) -> usize {
+        self.accounts.get(account).map_or(0, |acc| acc.inbox_size)
+    }
+
+    fn put_inbox_header(&mut self, account: XorName, name: XorName, header: MpidHeader) {
+        let acc = self.accounts.entry(account).or_insert_with(Account::default);
+        acc.inbox_size += serialised_size(&header) as usize;
+        if let Some(old) = acc.inbox.insert(name, header) {
+            acc.inbox_size -= serialised_size(&old) as usize;
+        }
+    }
+
+    fn remove_inbox_header(&mut self, account: &XorName, name: &XorName) -> Option<MpidHeader> {
+        let removed = self.accounts.get_mut(account).and_then(|acc| {
+            let removed = acc.inbox.remove(name);
+            if let Some(ref header) = removed {
+                acc.inbox_size -= serialised_size(header) as usize;
+            }
+            removed
+        });
+        self.remove_if_empty(account);
+        removed
+    }
+}
+
+/// A message that an `MpidManager` needs to have sent as a result of handling another one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MpidOutgoing {
+    /// The `ClientManager` authority of the account on whose behalf it is sent.
+    pub src: Authority<XorName>,
+    /// The account's client, or the `ClientManager` authority of another account.
+    pub dst: Authority<XorName>,
+    /// The message.
+    pub wrapper: MpidMessageWrapper,
+    /// The message ID. This is derived from the ID of the handled message, so that all managers
+    /// of the account send the same one.
+    pub id: MessageId,
+}
+
+/// The reason an `MpidManager` rejected a message, along with the failure notification that needs
+/// to be sent for it, if any.
+#[derive(Debug)]
+pub struct MpidFailure {
+    /// The reason the message was rejected.
+    pub error: Error,
+    /// A `Failure` for the client or managers that sent the message. This is `None` if the message
+    /// was not addressed to an account, or was itself a failure notification.
+    pub response: Option<MpidOutgoing>,
+}
+
+/// The manager side of MPID messaging, run by each member of a client's `ClientManager`
+/// authority.
+///
+/// A message is stored in the sender's outbox, and its header is sent to the recipient's managers,
+/// which store it in the recipient's inbox and pass it on to the recipient whenever it is online.
+/// The recipient then fetches the message via its managers from the sender's, and finally deletes
+/// it, which removes both the header from its inbox and the message from the sender's outbox.
+pub struct MpidManager<S: MpidStorage> {
+    storage: S,
+    max_inbox_size: usize,
+    max_outbox_size: usize,
+    /// The clients of the accounts that announced they are online, by account name.
+    online_clients: HashMap<XorName, Authority<XorName>>,
+    /// The maximum number of headers accepted from each sender not in the recipient's allow-list
+    /// within each window.
+    max_per_unknown_sender: usize,
+    /// The maximum number of headers accepted from all senders not in the recipient's allow-list
+    /// together within each window.
+    max_from_unknown_senders: usize,
+    /// The rate limiting window for unknown senders, in seconds.
+    unknown_sender_window: u64,
+}
+
+impl<S: MpidStorage> MpidManager<S> {
+    /// Creates a new manager using the given storage, with inbox and outbox quotas of
+    /// [`MAX_INBOX_SIZE`](constant.MAX_INBOX_SIZE.html) and
+    /// [`MAX_OUTBOX_SIZE`](constant.MAX_OUTBOX_SIZE.html).
+    pub fn new(storage: S) -> MpidManager<S> {
+        MpidManager::with_quotas(storage, MAX_INBOX_SIZE, MAX_OUTBOX_SIZE)
+    }
+
+    /// Creates a new manager using the given storage and inbox and outbox quotas, in bytes.
+    pub fn with_quotas(storage: S,
+                       max_inbox_size: usize,
+                       max_outbox_size: usize)
+                       -> MpidManager<S> {
+        MpidManager {
+            storage: storage,
+            max_inbox_size: max_inbox_size,
+            max_outbox_size: max_outbox_size,
+            online_clients: HashMap::new(),
+            max_per_unknown_sender: MAX_HEADERS_PER_UNKNOWN_SENDER,
+            max_from_unknown_senders: MAX_HEADERS_FROM_UNKNOWN_SENDERS,
+            unknown_sender_window: UNKNOWN_SENDER_WINDOW_SECS,
+        }
+    }
+
+    /// Limits senders not in a recipient's allow-list to `max_per_sender` headers each, and
+    /// `max_total` headers together, to that recipient within each `window`, instead of
+    /// [`MAX_HEADERS_PER_UNKNOWN_SENDER`](constant.MAX_HEADERS_PER_UNKNOWN_SENDER.html) and
+    /// [`MAX_HEADERS_FROM_UNKNOWN_SENDERS`](constant.MAX_HEADERS_FROM_UNKNOWN_SENDERS.html) per
+    /// [`UNKNOWN_SENDER_WINDOW_SECS`](constant.UNKNOWN_SENDER_WINDOW_SECS.html).
+    pub fn set_unknown_sender_limits(&mut self,
+                                     max_per_sender: usize,
+                                     max_total: usize,
+                                     window: Duration) {
+        self.max_per_unknown_sender = max_per_sender;
+        self.max_from_unknown_senders = max_total;
+        self.unknown_sender_window = window.as_secs();
+    }
+
+    /// Returns the storage.
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    /// Stops delivering new headers to the client of `account`, e.g. because it disconnected.
+    pub fn set_offline(&mut self, account: &XorName) {
+        let _ = self.online_clients.remove(account);
+    }
+
+    /// Handles `wrapper`, received with message ID `id` from `src` by the `ClientManager`
+    /// authority `dst`, and returns the messages that need to be sent in response.
+    ///
+    /// If the message is rejected, the returned `MpidFailure` contains the `Failure` that needs to
+    /// be sent to `src` instead.
+    pub fn handle(&mut self,
+                  src: Authority<XorName>,
+                  dst: Authority<XorName>,
+                  wrapper: MpidMessageWrapper,
+                  id: MessageId)
+                  -> Result<Vec<MpidOutgoing>, MpidFailure> {
+        let account = match dst {
+            Authority::ClientManager(name) => name,
+            _ => {
+                return Err(MpidFailure {
+                    error: Error::InvalidAuthority,
+                    response: None,
+                })
+            }
+        };
+        let is_failure = match wrapper {
+            MpidMessageWrapper::Failure { .. } => true,
+            _ => false,
+        };
+        let name = message_name(&wrapper);
+        let result = match src {
+            Authority::Client { client_key, .. } => {
+                self.handle_from_client(account, src, &client_key, wrapper, id)
+            }
+            Authority::ClientManager(src_account) => {
+                self.handle_from_managers(account, src_account, wrapper, id)
+            }
+            _ => Err(Error::InvalidAuthority),
+        };
+        result.map_err(|error| {
+            // Failures are never answered with another one, so that they can't bounce.
+            let response = if is_failure {
+                None
+            } else {
+                failure(account, src, name, &error, id)
+            };
+            MpidFailure {
+                error: error,
+                response: response,
+            }
+        })
+    }
+
+    fn handle_from_client(&mut self,
+                          account: XorName,
+                          client: Authority<XorName>,
+                          client_key: &PublicKey,
+                          wrapper: MpidMessageWrapper,
+                          id: MessageId)
+                          -> Result<Vec<MpidOutgoing>, Error> {
+        let client_name = XorName(sha256::hash(&client_key[..]).0);
+        if client_name != account {
+            // Only a message's recipient may contact the sender's managers directly.
+            return match wrapper {
+                MpidMessageWrapper::DeleteHeader(name) => {
+                    self.handle_delete_header(account, client_name, name)
+                }
+                _ => Err(Error::InvalidAuthority),
+            };
+        }
+        match wrapper {
+            MpidMessageWrapper::Online => self.handle_online(account, client, id),
+            MpidMessageWrapper::PutMessage(message) => {
+                self.handle_put_message(account, client_key, message, id)
+            }
+            MpidMessageWrapper::GetMessage(header) => self.handle_get_message(account, header, id),
+            MpidMessageWrapper::OutboxHas(names) => {
+                let headers = names.iter()
+                    .filter_map(|name| self.storage.outbox_message(&account, name))
+                    .map(|message| message.header().clone())
+                    .collect();
+                let wrapper = MpidMessageWrapper::OutboxHasResponse(headers);
+                Ok(vec![outgoing(account, client, wrapper, id)])
+            }
+            MpidMessageWrapper::GetOutboxHeaders => {
+                let headers = self.storage.outbox_headers(&account);
+                let wrapper = MpidMessageWrapper::GetOutboxHeadersResponse(headers);
+                Ok(vec![outgoing(account, client, wrapper, id)])
+            }
+            MpidMessageWrapper::DeleteMessage(name) => {
+                self.handle_delete_message(account, name, id)
+            }
+            MpidMessageWrapper::DeleteHeader(name) => {
+                self.handle_delete_header(account, client_name, name)
+            }
+            MpidMessageWrapper::DeliveryReceipt(receipt) => {
+                let kind = ReceiptKind::Delivered;
+                self.handle_receipt_from_client(account, client_key, receipt, kind, id)
+            }
+            MpidMessageWrapper::ReadReceipt(receipt) => {
+                self.handle_receipt_from_client(account, client_key, receipt, ReceiptKind::Read, id)
+            }
+            MpidMessageWrapper::GetMessageStatus(names) => {
+                let statuses = names.into_iter()
+                    .filter_map(|name| {
+                        self.storage.message_status(&account, &name).map(|status| (name, status))
+                    })
+                    .collect();
+                let wrapper = MpidMessageWrapper::GetMessageStatusResponse(statuses);
+                Ok(vec![outgoing(account, client, wrapper, id)])
+            }
+            MpidMessageWrapper::SetContacts(contacts) => {
+                self.handle_set_contacts(account, client_key, contacts)
+            }
+            MpidMessageWrapper::GetContacts => {
+                let contacts = self.storage.contacts(&account);
+                let wrapper = MpidMessageWrapper::GetContactsResponse(contacts);
+                Ok(vec![outgoing(account, client, wrapper, id)])
+            }
+            MpidMessageWrapper::PutHeader(_) => Err(Error::InvalidAuthority),
+            MpidMessageWrapper::OutboxHasResponse(_) |
+            MpidMessageWrapper::GetOutboxHeadersResponse(_) |
+            MpidMessageWrapper::GetMessageStatusResponse(_) |
+            MpidMessageWrapper::GetContactsResponse(_) |
+            MpidMessageWrapper::Failure { .. } => Err(Error::UnexpectedMessage),
+        }
+    }
+
+    fn handle_from_managers(&mut self,
+                            account: XorName,
+                            src_account: XorName,
+                            wrapper: MpidMessageWrapper,
+                            id: MessageId)
+                            -> Result<Vec<MpidOutgoing>, Error> {
+        match wrapper {
+            MpidMessageWrapper::PutHeader(header) => {
+                self.handle_put_header(account, src_account, header, id)
+            }
+            MpidMessageWrapper::GetMessage(header) => {
+                // The recipient's managers fetch a message from the sender's outbox.
+                let name = header.name()?;
+                let message = self.storage
+                    .outbox_message(&account, &name)
+                    .ok_or(Error::NoSuchMessage)?;
+                if *message.recipient() != src_account {
+                    return Err(Error::InvalidAuthority);
+                }
+                self.advance_status(&account, &name, MessageStatus::Fetched);
+                let dst = Authority::ClientManager(src_account);
+                let wrapper = MpidMessageWrapper::PutMessage(message);
+                Ok(vec![outgoing(account, dst, wrapper, id.derive(&name.0))])
+            }
+            MpidMessageWrapper::PutMessage(message) => {
+                // The sender's managers deliver a message the recipient has asked for.
+                let name = message.name()?;
+                if *message.recipient() != account || *message.header().sender() != src_account {
+                    return Err(Error::InvalidAuthority);
+                }
+                if self.storage.inbox_header(&account, &name).is_none() {
+                    return Err(Error::NoSuchMessage);
+                }
+                Ok(self.online_clients
+                    .get(&account)
+                    .map(|client| {
+                        let wrapper = MpidMessageWrapper::PutMessage(message);
+                        outgoing(account, *client, wrapper, id.derive(&name.0))
+                    })
+                    .into_iter()
+                    .collect())
+            }
+            MpidMessageWrapper::DeleteHeader(name) => {
+                self.handle_delete_header(account, src_account, name)
+            }
+            MpidMessageWrapper::DeliveryReceipt(receipt) => {
+                self.handle_receipt(account, src_account, receipt, ReceiptKind::Delivered, id)
+            }
+            MpidMessageWrapper::ReadReceipt(receipt) => {
+                self.handle_receipt(account, src_account, receipt, ReceiptKind::Read, id)
+            }
+            MpidMessageWrapper::Failure { name, reason } => {
+                // Another account's managers rejected a message we sent on our client's behalf.
+                Ok(self.online_clients
+                    .get(&account)
+                    .map(|client| {
+                        let wrapper = MpidMessageWrapper::Failure {
+                            name: name,
+                            reason: reason,
+                        };
+                        outgoing(account, *client, wrapper, id)
+                    })
+                    .into_iter()
+                    .collect())
+            }
+            MpidMessageWrapper::Online |
+            MpidMessageWrapper::OutboxHas(_) |
+            MpidMessageWrapper::GetOutboxHeaders |
+            MpidMessageWrapper::DeleteMessage(_) |
+            MpidMessageWrapper::GetMessageStatus(_) |
+            MpidMessageWrapper::SetContacts(_) |
+            MpidMessageWrapper::GetContacts => Err(Error::InvalidAuthority),
+            MpidMessageWrapper::OutboxHasResponse(_) |
+            MpidMessageWrapper::GetOutboxHeadersResponse(_) |
+            MpidMessageWrapper::GetMessageStatusResponse(_) |
+            MpidMessageWrapper::GetContactsResponse(_) => Err(Error::UnexpectedMessage),
+        }
+    }
+
+    /// Records the client as online and sends it all headers in its inbox.
+    fn handle_online(&mut self,
+                     account: XorName,
+                     client: Authority<XorName>,
+                     id: MessageId)
+                     -> Result<Vec<MpidOutgoing>, Error> {
+        let _ = self.online_clients.insert(account, client);
+        let mut result = vec![];
+        for header in self.storage.inbox_headers(&account) {
+            let name = header.name()?;
+            let wrapper = MpidMessageWrapper::PutHeader(header);
+            result.push(outgoing(account, client, wrapper, id.derive(&name.0)));
+        }
+        Ok(result)
+    }
+
+    /// Stores a client's new message in its outbox and notifies the recipient's managers.
+    fn handle_put_message(&mut self,
+                          account: XorName,
+                          client_key: &PublicKey,
+                          message: MpidMessage,
+                          id: MessageId)
+                          -> Result<Vec<MpidOutgoing>, Error> {
+        if *message.header().sender() != account {
+            return Err(Error::InvalidAuthority);
+        }
+        if !message.verify(client_key) {
+            return Err(Error::InvalidSignature);
+        }
+        let name = message.name()?;
+        if self.storage.outbox_message(&account, &name).is_none() {
+            let size = serialised_size(&message) as usize;
+            if self.storage.outbox_size(&account) + size > self.max_outbox_size {
+                return Err(Error::OutboxFull);
+            }
+            self.storage.put_outbox_message(account, name, message.clone());
+        }
+        let dst = Authority::ClientManager(*message.recipient());
+        let wrapper = MpidMessageWrapper::PutHeader(message.header().clone());
+        Ok(vec![outgoing(account, dst, wrapper, id.derive(&name.0))])
+    }
+
+    /// Stores a header sent by the sender's managers in the inbox, and passes it on to the client
+    /// if it is online.
+    ///
+    /// Headers from senders in the account's block-list are rejected, and those from senders not
+    /// in its allow-list are rate limited.
+    fn handle_put_header(&mut self,
+                         account: XorName,
+                         src_account: XorName,
+                         header: MpidHeader,
+                         id: MessageId)
+                         -> Result<Vec<MpidOutgoing>, Error> {
+        if *header.sender() != src_account {
+            return Err(Error::InvalidAuthority);
+        }
+        let name = header.name()?;
+        if self.storage.inbox_header(&account, &name).is_none() {
+            let contacts = self.storage.contacts(&account);
+            if contacts.as_ref().map_or(false, |contacts| contacts.is_blocked(&src_account)) {
+                return Err(Error::SenderBlocked);
+            }
+            let size = serialised_size(&header) as usize;
+            if self.storage.inbox_size(&account) + size > self.max_inbox_size {
+                return Err(Error::InboxFull);
+            }
+            let is_allowed = contacts.map_or(false, |contacts| contacts.is_allowed(&src_account));
+            if !is_allowed {
+                self.count_unknown_sender(account, src_account)?;
+            }
+            self.storage.put_inbox_header(account, name, header.clone());
+        }
+        Ok(self.online_clients
+            .get(&account)
+            .map(|client| {
+                let wrapper = MpidMessageWrapper::PutHeader(header);
+                outgoing(account, *client, wrapper, id.derive(&name.0))
+            })
+            .into_iter()
+            .collect())
+    }
+
+    /// Counts a header from `sender`, which is not in the allow-list of `account`, or returns
+    /// `RateLimited` if that would exceed the limit for that sender or for all unknown senders in
+    /// the current window.
+    fn count_unknown_sender(&mut self, account: XorName, sender: XorName) -> Result<(), Error> {
+        let now = utils::now_secs();
+        let window = self.unknown_sender_window;
+        let mut senders = self.storage
+            .unknown_senders(&account)
+            .and_then(|senders| if now < senders.window_start.saturating_add(window) {
+                Some(senders)
+            } else {
+                None
+            })
+            .unwrap_or_else(|| {
+                UnknownSenders {
+                    window_start: now,
+                    counts: BTreeMap::new(),
+                }
+            });
+        let count = senders.counts.get(&sender).cloned().unwrap_or(0);
+        if count >= self.max_per_unknown_sender ||
+           senders.total() >= self.max_from_unknown_senders {
+            return Err(Error::RateLimited);
+        }
+        let _ = senders.counts.insert(sender, count + 1);
+        self.storage.put_unknown_senders(account, senders);
+        Ok(())
+    }
+
+    /// Asks the sender's managers for the message belonging to a header in the inbox.
+    fn handle_get_message(&mut self,
+                          account: XorName,
+                          header: MpidHeader,
+                          id: MessageId)
+                          -> Result<Vec<MpidOutgoing>, Error> {
+        let name = header.name()?;
+        if self.storage.inbox_header(&account, &name).is_none() {
+            return Err(Error::NoSuchMessage);
+        }
+        let dst = Authority::ClientManager(*header.sender());
+        let wrapper = MpidMessageWrapper::GetMessage(header);
+        Ok(vec![outgoing(account, dst, wrapper, id.derive(&name.0))])
+    }
+
+    /// Deletes a header from the inbox, in which case the sender's managers are asked to delete
+    /// the message from their outbox, or a message and its status from the outbox.
+    fn handle_delete_message(&mut self,
+                             account: XorName,
+                             name: XorName,
+                             id: MessageId)
+                             -> Result<Vec<MpidOutgoing>, Error> {
+        if let Some(header) = self.storage.remove_inbox_header(&account, &name) {
+            let dst = Authority::ClientManager(*header.sender());
+            let wrapper = MpidMessageWrapper::DeleteHeader(name);
+            return Ok(vec![outgoing(account, dst, wrapper, id.derive(&name.0))]);
+        }
+        let had_status = self.storage.message_status(&account, &name).is_some();
+        self.storage.remove_message_status(&account, &name);
+        match self.storage.remove_outbox_message(&account, &name) {
+            Some(_) => Ok(vec![]),
+            // The recipient already deleted the message, and only its status was left.
+            None if had_status => Ok(vec![]),
+            None => Err(Error::NoSuchMessage),
+        }
+    }
+
+    /// Deletes a message from the outbox on behalf of its recipient `requester`. Its status is
+    /// kept, so that the recipient can still confirm reading it.
+    fn handle_delete_header(&mut self,
+                            account: XorName,
+                            requester: XorName,
+                            name: XorName)
+                            -> Result<Vec<MpidOutgoing>, Error> {
+        let message = self.storage.outbox_message(&account, &name).ok_or(Error::NoSuchMessage)?;
+        if *message.recipient() != requester {
+            return Err(Error::InvalidAuthority);
+        }
+        let _ = self.storage.remove_outbox_message(&account, &name);
+        Ok(vec![])
+    }
+
+    /// Passes a receipt signed by the account's client on to the sender's managers.
+    fn handle_receipt_from_client(&mut self,
+                                  account: XorName,
+                                  client_key: &PublicKey,
+                                  receipt: MpidReceipt,
+                                  kind: ReceiptKind,
+                                  id: MessageId)
+                                  -> Result<Vec<MpidOutgoing>, Error> {
+        if receipt.kind() != kind || receipt.recipient_key() != client_key {
+            return Err(Error::InvalidAuthority);
+        }
+        if !receipt.verify() {
+            return Err(Error::InvalidSignature);
+        }
+        let dst = Authority::ClientManager(*receipt.sender());
+        let id = id.derive(&receipt.message_name().0);
+        let wrapper = match kind {
+            ReceiptKind::Delivered => MpidMessageWrapper::DeliveryReceipt(receipt),
+            ReceiptKind::Read => MpidMessageWrapper::ReadReceipt(receipt),
+        };
+        Ok(vec![outgoing(account, dst, wrapper, id)])
+    }
+
+    /// Updates the status of a message in the outbox according to a receipt sent by the
+    /// recipient's managers, and passes the receipt on to the client if it is online.
+    fn handle_receipt(&mut self,
+                      account: XorName,
+                      src_account: XorName,
+                      receipt: MpidReceipt,
+                      kind: ReceiptKind,
+                      id: MessageId)
+                      -> Result<Vec<MpidOutgoing>, Error> {
+        if receipt.kind() != kind || receipt.recipient() != src_account ||
+           *receipt.sender() != account {
+            return Err(Error::InvalidAuthority);
+        }
+        if !receipt.verify() {
+            return Err(Error::InvalidSignature);
+        }
+        let name = *receipt.message_name();
+        match self.storage.outbox_message(&account, &name) {
+            Some(message) => {
+                if *message.recipient() != src_account {
+                    return Err(Error::InvalidAuthority);
+                }
+            }
+            None => {
+                // If the recipient deleted the message already, only its status is left.
+                if self.storage.message_status(&account, &name).is_none() {
+                    return Err(Error::NoSuchMessage);
+                }
+            }
+        }
+        self.advance_status(&account, &name, kind.status());
+        Ok(self.online_clients
+            .get(&account)
+            .map(|client| {
+                let wrapper = match kind {
+                    ReceiptKind::Delivered => MpidMessageWrapper::DeliveryReceipt(receipt),
+                    ReceiptKind::Read => MpidMessageWrapper::ReadReceipt(receipt),
+                };
+                outgoing(account, *client, wrapper, id.derive(&name.0))
+            })
+            .into_iter()
+            .collect())
+    }
+
+    /// Replaces the account's allow-list and block-list with the ones signed by its client.
+    fn handle_set_contacts(&mut self,
+                           account: XorName,
+                           client_key: &PublicKey,
+                           contacts: MpidContacts)
+                           -> Result<Vec<MpidOutgoing>, Error> {
+        if contacts.owner_key() != client_key {
+            return Err(Error::InvalidAuthority);
+        }
+        if !contacts.verify() {
+            return Err(Error::InvalidSignature);
+        }
+        if let Some(current) = self.storage.contacts(&account) {
+            if current.version() >= contacts.version() {
+                return Err(Error::OutdatedContacts);
+            }
+        }
+        self.storage.put_contacts(account, contacts);
+        Ok(vec![])
+    }
+
+    /// Sets the status of a message in the outbox to `status`, unless it is already further
+    /// along.
+    fn advance_status(&mut self, account: &XorName, name: &XorName, status: MessageStatus) {
+        if let Some(current) = self.storage.message_status(account, name) {
+            if current < status {
+                self.storage.set_message_status(account, name, status);
+            }
+        }
+    }
+}
+
+fn outgoing(account: XorName,
+            dst: Authority<XorName>,
+            wrapper: MpidMessageWrapper,
+            id: MessageId)
+            -> MpidOutgoing {
+    MpidOutgoing {
+        src: Authority::ClientManager(account),
+        dst: dst,
+        wrapper: wrapper,
+        id: id,
+    }
+}
+
+/// Returns the `Failure` to send to `src` for the message with ID `id` that was rejected with
+/// `error`, if `src` is a client or another account's managers.
+fn failure(account: XorName,
+           src: Authority<XorName>,
+           name: Option<XorName>,
+           error: &Error,
+           id: MessageId)
+           -> Option<MpidOutgoing> {
+    let id = match src {
+        // The client recognises the failure by its request's ID.
+        Authority::Client { .. } => id,
+        Authority::ClientManager(_) => id.derive(FAILURE_SALT),
+        _ => return None,
+    };
+    let wrapper = MpidMessageWrapper::Failure {
+        name: name,
+        reason: error.to_string(),
+    };
+    Some(outgoing(account, src, wrapper, id))
+}
+
+/// Returns the name of the message `wrapper` concerns, if any.
+fn message_name(wrapper: &MpidMessageWrapper) -> Option<XorName> {
+    match *wrapper {
+        MpidMessageWrapper::PutMessage(ref message) => message.name().ok(),
+        MpidMessageWrapper::PutHeader(ref header) |
+        MpidMessageWrapper::GetMessage(ref header) => header.name().ok(),
+        MpidMessageWrapper::DeleteMessage(name) |
+        MpidMessageWrapper::DeleteHeader(name) => Some(name),
+        MpidMessageWrapper::DeliveryReceipt(ref receipt) |
+        MpidMessageWrapper::ReadReceipt(ref receipt) => Some(*receipt.message_name()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(not(feature = "use-mock-crust"))]
+    use crust::PeerId;
+    use id::FullId;
+    use maidsafe_utilities::serialisation::serialised_size;
+    use messaging::{Error, MpidContacts, MpidMessage, MpidMessageWrapper};
+    #[cfg(feature = "use-mock-crust")]
+    use mock_crust::crust::PeerId;
+    use rand;
+    use routing_table::Authority;
+    use rust_sodium::crypto::sign;
+    use std::time::Duration;
+    use super::*;
+    use types::MessageId;
+    use xor_name::XorName;
+
+    #[cfg(not(feature = "use-mock-crust"))]
+    fn make_peer_id() -> PeerId {
+        PeerId(*FullId::new().public_id().encrypting_public_key())
+    }
+    #[cfg(feature = "use-mock-crust")]
+    fn make_peer_id() -> PeerId {
+        PeerId(0)
+    }
+
+    #[test]
+    fn inbox_quota() {
+        let (_, secret_key) = sign::gen_keypair();
+        let sender: XorName = rand::random();
+        let recipient: XorName = rand::random();
+        let headers: Vec<_> = (0..3)
+            .map(|_| {
+                let message =
+                    unwrap!(MpidMessage::new(sender, vec![], recipient, vec![], &secret_key));
+                message.header().clone()
+            })
+            .collect();
+        // Room for two headers only.
+        let quota = 2 * serialised_size(&headers[0]) as usize;
+        let mut manager = MpidManager::with_quotas(MemoryMpidStorage::new(), quota, quota);
+
+        for (i, header) in headers.into_iter().enumerate() {
+            let name = unwrap!(header.name());
+            let result = manager.handle(Authority::ClientManager(sender),
+                                        Authority::ClientManager(recipient),
+                                        MpidMessageWrapper::PutHeader(header),
+                                        MessageId::new());
+            match result {
+                Ok(ref outgoing) if i < 2 => assert!(outgoing.is_empty()),
+                Err(MpidFailure { error: Error::InboxFull, response: Some(ref response) })
+                    if i == 2 => {
+                    // The sender's managers are notified, to pass it on to the sender.
+                    assert_eq!(response.dst, Authority::ClientManager(sender));
+                    match response.wrapper {
+                        MpidMessageWrapper::Failure { name: failed, .. } => {
+                            assert_eq!(failed, Some(name))
+                        }
+                        ref wrapper => panic!("Unexpected response: {:?}", wrapper),
+                    }
+                }
+                result => panic!("Unexpected result: {:?}", result),
+            }
+        }
+        assert_eq!(manager.storage().inbox_size(&recipient), quota);
+        assert_eq!(manager.storage().inbox_headers(&recipient).len(), 2);
+    }
+
+    #[test]
+    fn header_from_wrong_sender() {
+        let (_, secret_key) = sign::gen_keypair();
+        let sender: XorName = rand::random();
+        let recipient: XorName = rand::random();
+        let mut manager = MpidManager::new(MemoryMpidStorage::new());
+        let message = unwrap!(MpidMessage::new(sender, vec![], recipient, vec![], &secret_key));
+        let wrapper = MpidMessageWrapper::PutHeader(message.header().clone());
+        match manager.handle(Authority::ClientManager(rand::random()),
+                             Authority::ClientManager(recipient),
+                             wrapper,
+                             MessageId::new()) {
+            Err(MpidFailure { error: Error::InvalidAuthority, .. }) => (),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+        assert_eq!(manager.storage().inbox_size(&recipient), 0);
+    }
+
+    #[test]
+    fn contacts() {
+        let full_id = FullId::new();
+        let public_key = *full_id.public_id().signing_public_key();
+        let recipient = *full_id.public_id().name();
+        let client = Authority::Client {
+            client_key: public_key,
+            proxy_node_name: rand::random(),
+            peer_id: make_peer_id(),
+        };
+        let dst = Authority::ClientManager(recipient);
+        let (friend, spammer, stranger): (XorName, XorName, XorName) =
+            (rand::random(), rand::random(), rand::random());
+        let mut manager = MpidManager::new(MemoryMpidStorage::new());
+        manager.set_unknown_sender_limits(1, 2, Duration::from_secs(60));
+
+        let allowed = Some(friend).into_iter().collect();
+        let blocked = Some(spammer).into_iter().collect();
+        let contacts = unwrap!(MpidContacts::new(allowed,
+                                                 blocked,
+                                                 1,
+                                                 &public_key,
+                                                 full_id.signing_private_key()));
+        let wrapper = MpidMessageWrapper::SetContacts(contacts.clone());
+        assert!(unwrap!(manager.handle(client, dst, wrapper.clone(), MessageId::new())).is_empty());
+        assert_eq!(manager.storage().contacts(&recipient), Some(contacts));
+        // The same version can't be set again, and the client is told so.
+        let id = MessageId::new();
+        match manager.handle(client, dst, wrapper, id) {
+            Err(MpidFailure { error: Error::OutdatedContacts, response: Some(response) }) => {
+                assert_eq!(response.dst, client);
+                assert_eq!(response.id, id);
+                match response.wrapper {
+                    MpidMessageWrapper::Failure { name: None, .. } => (),
+                    wrapper => panic!("Unexpected response: {:?}", wrapper),
+                }
+            }
+            result => panic!("Unexpected result: {:?}", result),
+        }
+
+        let put_header = |manager: &mut MpidManager<MemoryMpidStorage>, sender: XorName| {
+            let (_, sender_secret_key) = sign::gen_keypair();
+            let message =
+                unwrap!(MpidMessage::new(sender, vec![], recipient, vec![], &sender_secret_key));
+            manager.handle(Authority::ClientManager(sender),
+                           dst,
+                           MpidMessageWrapper::PutHeader(message.header().clone()),
+                           MessageId::new())
+        };
+        match put_header(&mut manager, spammer) {
+            Err(MpidFailure { error: Error::SenderBlocked, .. }) => (),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+        // Unknown senders are rate limited, allowed ones aren't.
+        assert!(put_header(&mut manager, stranger).is_ok());
+        match put_header(&mut manager, stranger) {
+            Err(MpidFailure { error: Error::RateLimited, .. }) => (),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+        assert!(put_header(&mut manager, friend).is_ok());
+        assert!(put_header(&mut manager, friend).is_ok());
+        assert_eq!(manager.storage().inbox_headers(&recipient).len(), 3);
+
+        // Rotating through unknown senders is limited by the total for the recipient.
+        assert!(put_header(&mut manager, rand::random()).is_ok());
+        match put_header(&mut manager, rand::random()) {
+            Err(MpidFailure { error: Error::RateLimited, .. }) => (),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+        assert_eq!(manager.storage().inbox_headers(&recipient).len(), 4);
+        // The counts are kept in the storage.
+        assert_eq!(unwrap!(manager.storage().unknown_senders(&recipient)).total(), 2);
+
+        // Once the window has passed, unknown senders are accepted again.
+        manager.set_unknown_sender_limits(1, 2, Duration::from_secs(0));
+        assert!(put_header(&mut manager, stranger).is_ok());
+    }
+}
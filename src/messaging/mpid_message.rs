@@ -20,12 +20,17 @@
 pub const MAX_BODY_SIZE: usize = 102400 - 512 - super::MAX_HEADER_METADATA_SIZE;
 
 use maidsafe_utilities::serialisation::serialise;
+use rust_sodium::crypto::box_;
 use rust_sodium::crypto::sign::{self, PublicKey, SecretKey, Signature};
 use std::fmt::{self, Debug, Formatter};
 use super::{Error, MpidHeader};
 use utils;
 use xor_name::XorName;
 
+/// Appended to the signed content of messages with an encrypted body, so that plaintext messages
+/// are signed and serialised exactly as before encryption was supported.
+const ENCRYPTED_TAG: &'static [u8] = b"encrypted";
+
 #[derive(PartialEq, Eq, Hash, Clone, RustcDecodable, RustcEncodable)]
 struct Detail {
     recipient: XorName,
@@ -65,14 +70,52 @@ impl MpidMessage {
             return Err(Error::BodyTooLarge);
         }
 
-        let header = MpidHeader::new(sender, metadata, secret_key)?;
-
         let detail = Detail {
             recipient: recipient,
             body: body,
         };
+        MpidMessage::with_detail(sender, metadata, detail, false, secret_key)
+    }
+
+    /// Constructs a message whose body is encrypted, so that only the recipient can read it.
+    ///
+    /// The body is encrypted to the recipient's `recipient_encrypt_key` using the sender's
+    /// `sender_encrypt_key`, which also authenticates the sender to the recipient. The header's
+    /// `metadata` is not encrypted. The arguments are otherwise as for [`new()`](#method.new).
+    ///
+    /// The stored body is the nonce followed by the ciphertext, so `body` must be at least
+    /// `box_::NONCEBYTES + box_::MACBYTES` shorter than
+    /// [`MAX_BODY_SIZE`](constant.MAX_BODY_SIZE.html).
+    pub fn new_encrypted(sender: XorName,
+                         metadata: Vec<u8>,
+                         recipient: XorName,
+                         body: &[u8],
+                         recipient_encrypt_key: &box_::PublicKey,
+                         sender_encrypt_key: &box_::SecretKey,
+                         secret_key: &SecretKey)
+                         -> Result<MpidMessage, Error> {
+        if body.len() + box_::NONCEBYTES + box_::MACBYTES > MAX_BODY_SIZE {
+            return Err(Error::BodyTooLarge);
+        }
+
+        let nonce = box_::gen_nonce();
+        let mut encrypted_body = nonce.0.to_vec();
+        encrypted_body.extend(box_::seal(body, &nonce, recipient_encrypt_key, sender_encrypt_key));
+        let detail = Detail {
+            recipient: recipient,
+            body: encrypted_body,
+        };
+        MpidMessage::with_detail(sender, metadata, detail, true, secret_key)
+    }
 
-        let recipient_and_body = serialise(&detail)?;
+    fn with_detail(sender: XorName,
+                   metadata: Vec<u8>,
+                   detail: Detail,
+                   encrypted: bool,
+                   secret_key: &SecretKey)
+                   -> Result<MpidMessage, Error> {
+        let header = MpidHeader::new(sender, metadata, secret_key)?;
+        let recipient_and_body = signed_bytes(&detail, encrypted)?;
         Ok(MpidMessage {
             header: header,
             detail: detail,
@@ -90,11 +133,40 @@ impl MpidMessage {
         &self.detail.recipient
     }
 
-    /// Arbitrary, user-supplied data representing the main portion of the message.
+    /// Arbitrary, user-supplied data representing the main portion of the message.  If the body
+    /// [is encrypted](#method.is_encrypted), this is the nonce followed by the ciphertext.
     pub fn body(&self) -> &Vec<u8> {
         &self.detail.body
     }
 
+    /// Whether the message is validly signed by the sender's `public_key` and its body encrypted
+    /// to the recipient.
+    pub fn is_encrypted(&self, public_key: &PublicKey) -> bool {
+        self.verify_detail(public_key, true) && self.header.verify(public_key)
+    }
+
+    /// Validates the message against the sender's `public_key` and returns its decrypted body.
+    ///
+    /// `sender_encrypt_key` is the sender's public encryption key and `recipient_encrypt_key` the
+    /// recipient's secret one.  An error is returned if the signatures are invalid, if the body is
+    /// not encrypted or if it can't be decrypted with the given keys.
+    pub fn decrypt_body(&self,
+                        public_key: &PublicKey,
+                        sender_encrypt_key: &box_::PublicKey,
+                        recipient_encrypt_key: &box_::SecretKey)
+                        -> Result<Vec<u8>, Error> {
+        if !self.verify(public_key) {
+            return Err(Error::InvalidSignature);
+        }
+        if !self.verify_detail(public_key, true) || self.detail.body.len() < box_::NONCEBYTES {
+            return Err(Error::NotEncrypted);
+        }
+        let (nonce, ciphertext) = self.detail.body.split_at(box_::NONCEBYTES);
+        let nonce = box_::Nonce::from_slice(nonce).ok_or(Error::NotEncrypted)?;
+        box_::open(ciphertext, &nonce, sender_encrypt_key, recipient_encrypt_key)
+            .map_err(|()| Error::DecryptionFailed)
+    }
+
     /// The name of the message, equivalent to the
     /// [`MpidHeader::name()`](../struct.MpidHeader.html#method.name).  As per that getter, this is
     /// relatively expensive, so its use should be minimised.
@@ -104,16 +176,28 @@ impl MpidMessage {
 
     /// Validates the message and header signatures against the provided `PublicKey`.
     pub fn verify(&self, public_key: &PublicKey) -> bool {
-        match serialise(&self.detail) {
+        (self.verify_detail(public_key, false) || self.verify_detail(public_key, true)) &&
+        self.header.verify(public_key)
+    }
+
+    fn verify_detail(&self, public_key: &PublicKey, encrypted: bool) -> bool {
+        match signed_bytes(&self.detail, encrypted) {
             Ok(recipient_and_body) => {
-                sign::verify_detached(&self.signature, &recipient_and_body, public_key) &&
-                self.header.verify(public_key)
+                sign::verify_detached(&self.signature, &recipient_and_body, public_key)
             }
             Err(_) => false,
         }
     }
 }
 
+fn signed_bytes(detail: &Detail, encrypted: bool) -> Result<Vec<u8>, Error> {
+    let mut bytes = serialise(detail)?;
+    if encrypted {
+        bytes.extend_from_slice(ENCRYPTED_TAG);
+    }
+    Ok(bytes)
+}
+
 impl Debug for MpidMessage {
     fn fmt(&self, formatter: &mut Formatter) -> Result<(), fmt::Error> {
         write!(formatter,
@@ -127,9 +211,9 @@ impl Debug for MpidMessage {
 
 #[cfg(test)]
 mod tests {
-    use messaging;
+    use messaging::{self, Error};
     use rand;
-    use rust_sodium::crypto::sign;
+    use rust_sodium::crypto::{box_, sign};
     use super::*;
     use xor_name::XorName;
 
@@ -174,4 +258,70 @@ mod tests {
         }
         assert!(!message.verify(&public_key));
     }
+
+    #[test]
+    fn encrypted() {
+        let (public_key, secret_key) = sign::gen_keypair();
+        let (sender_public_key, sender_secret_key) = box_::gen_keypair();
+        let (recipient_public_key, recipient_secret_key) = box_::gen_keypair();
+        let sender: XorName = rand::random();
+        let recipient: XorName = rand::random();
+        let metadata = messaging::generate_random_bytes(10);
+        let max_size = MAX_BODY_SIZE - box_::NONCEBYTES - box_::MACBYTES;
+        let mut body = messaging::generate_random_bytes(max_size + 1);
+
+        // The encrypted body must not exceed the size limit either.
+        match MpidMessage::new_encrypted(sender,
+                                         metadata.clone(),
+                                         recipient,
+                                         &body,
+                                         &recipient_public_key,
+                                         &sender_secret_key,
+                                         &secret_key) {
+            Err(Error::BodyTooLarge) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        let _ = body.pop();
+
+        let message = unwrap!(MpidMessage::new_encrypted(sender,
+                                                         metadata.clone(),
+                                                         recipient,
+                                                         &body,
+                                                         &recipient_public_key,
+                                                         &sender_secret_key,
+                                                         &secret_key));
+        assert!(message.is_encrypted(&public_key));
+        assert_eq!(message.body().len(), MAX_BODY_SIZE);
+        assert!(*message.body() != body);
+        assert_eq!(*message.header().metadata(), metadata);
+        assert!(message.verify(&public_key));
+        assert_eq!(unwrap!(message.decrypt_body(&public_key,
+                                                &sender_public_key,
+                                                &recipient_secret_key)),
+                   body);
+
+        // Decryption fails with the wrong keys.
+        let (other_public_key, other_secret_key) = box_::gen_keypair();
+        match message.decrypt_body(&public_key, &other_public_key, &recipient_secret_key) {
+            Err(Error::DecryptionFailed) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        match message.decrypt_body(&public_key, &sender_public_key, &other_secret_key) {
+            Err(Error::DecryptionFailed) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        let (other_sign_key, _) = sign::gen_keypair();
+        match message.decrypt_body(&other_sign_key, &sender_public_key, &recipient_secret_key) {
+            Err(Error::InvalidSignature) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+
+        // A plaintext body can't be decrypted.
+        let plain = unwrap!(MpidMessage::new(sender, metadata, recipient, body, &secret_key));
+        assert!(!plain.is_encrypted(&public_key));
+        match plain.decrypt_body(&public_key, &sender_public_key, &recipient_secret_key) {
+            Err(Error::NotEncrypted) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
 }
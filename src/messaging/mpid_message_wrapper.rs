@@ -15,7 +15,7 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
-use super::{MpidHeader, MpidMessage};
+use super::{MessageStatus, MpidContacts, MpidHeader, MpidMessage, MpidReceipt};
 use xor_name::XorName;
 
 /// A serialisable wrapper to allow multiplexing all MPID message types and actions via a single
@@ -50,4 +50,31 @@ pub enum MpidMessageWrapper {
     /// Sent by a receiving Client to the sender's MpidManagers to delete the named message's header
     /// from the sender's outbox.
     DeleteHeader(XorName),
+    /// Sent by a receiving Client to its MpidManagers once it has received a header, and by them
+    /// to the sender's MpidManagers, which pass it on to the sender if it is online.
+    DeliveryReceipt(MpidReceipt),
+    /// Sent by a receiving Client to its MpidManagers once it has read a message, and passed on to
+    /// the sender like a `DeliveryReceipt`.
+    ReadReceipt(MpidReceipt),
+    /// Sent by a Client to its MpidManagers to query the status of the provided vector of messages
+    /// in its outbox.
+    GetMessageStatus(Vec<XorName>),
+    /// Sent by MpidManagers to the Client as a response to a `GetMessageStatus`.  The contents is
+    /// the status of each of the queried messages which are still in the outbox.
+    GetMessageStatusResponse(Vec<(XorName, MessageStatus)>),
+    /// Sent by a Client to its MpidManagers to replace its allow-list and block-list.
+    SetContacts(MpidContacts),
+    /// Sent by a Client to its MpidManagers to retrieve its allow-list and block-list.
+    GetContacts,
+    /// Sent by MpidManagers to the Client as a response to a `GetContacts`.  The contents is
+    /// `None` if no lists have been set.
+    GetContactsResponse(Option<MpidContacts>),
+    /// Sent by MpidManagers that rejected a message: to the Client that sent it, or to the
+    /// MpidManagers that sent it, which pass it on to their Client if it is online.
+    Failure {
+        /// The name of the message the rejected one concerned, if any.
+        name: Option<XorName>,
+        /// The reason it was rejected.
+        reason: String,
+    },
 }
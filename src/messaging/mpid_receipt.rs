@@ -0,0 +1,188 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.1.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use maidsafe_utilities::serialisation::serialise;
+use rust_sodium::crypto::hash::sha256;
+use rust_sodium::crypto::sign::{self, PublicKey, SecretKey, Signature};
+use std::fmt::{self, Debug, Formatter};
+use super::{Error, MpidHeader};
+use utils;
+use xor_name::XorName;
+
+/// What a receipt confirms.
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug, RustcDecodable, RustcEncodable)]
+pub enum ReceiptKind {
+    /// The recipient has been notified of the message, i.e. has received its header.
+    Delivered,
+    /// The recipient has read the message.
+    Read,
+}
+
+/// The delivery status of a message in the sender's outbox, as known to the sender's
+/// MpidManagers.  Each status implies all the previous ones.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug, RustcDecodable,
+         RustcEncodable)]
+pub enum MessageStatus {
+    /// The message is stored in the outbox.
+    Stored,
+    /// The recipient has confirmed receiving the header.
+    Notified,
+    /// The recipient's MpidManagers have fetched the message.
+    Fetched,
+    /// The recipient has confirmed reading the message.
+    Read,
+}
+
+impl ReceiptKind {
+    /// The message status confirmed by a receipt of this kind.
+    pub fn status(&self) -> MessageStatus {
+        match *self {
+            ReceiptKind::Delivered => MessageStatus::Notified,
+            ReceiptKind::Read => MessageStatus::Read,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, RustcDecodable, RustcEncodable)]
+struct Detail {
+    message_name: XorName,
+    sender: XorName,
+    kind: ReceiptKind,
+    recipient_key: PublicKey,
+}
+
+/// A confirmation, signed by the recipient, that a message has been delivered or read.
+#[derive(PartialEq, Eq, Hash, Clone, RustcDecodable, RustcEncodable)]
+pub struct MpidReceipt {
+    detail: Detail,
+    signature: Signature,
+}
+
+impl MpidReceipt {
+    /// Constructor.
+    ///
+    /// `header` is the header of the received message, and `public_key` and `secret_key` are the
+    /// recipient's signing keys.  The recipient's name is derived from `public_key`.
+    ///
+    /// An error will be returned if serialisation during the signing process fails.
+    pub fn new(header: &MpidHeader,
+               kind: ReceiptKind,
+               public_key: &PublicKey,
+               secret_key: &SecretKey)
+               -> Result<MpidReceipt, Error> {
+        let detail = Detail {
+            message_name: header.name()?,
+            sender: *header.sender(),
+            kind: kind,
+            recipient_key: *public_key,
+        };
+
+        let encoded = serialise(&detail)?;
+        Ok(MpidReceipt {
+            detail: detail,
+            signature: sign::sign_detached(&encoded, secret_key),
+        })
+    }
+
+    /// The name of the message this receipt is for.
+    pub fn message_name(&self) -> &XorName {
+        &self.detail.message_name
+    }
+
+    /// The name of the message's sender.
+    pub fn sender(&self) -> &XorName {
+        &self.detail.sender
+    }
+
+    /// What this receipt confirms.
+    pub fn kind(&self) -> ReceiptKind {
+        self.detail.kind
+    }
+
+    /// The public signing key of the message's recipient.
+    pub fn recipient_key(&self) -> &PublicKey {
+        &self.detail.recipient_key
+    }
+
+    /// The name of the message's recipient, derived from its public key.
+    pub fn recipient(&self) -> XorName {
+        XorName(sha256::hash(&self.detail.recipient_key[..]).0)
+    }
+
+    /// Validates the receipt's signature against the recipient's public key it contains.
+    pub fn verify(&self) -> bool {
+        match serialise(&self.detail) {
+            Ok(encoded) => {
+                sign::verify_detached(&self.signature, &encoded, &self.detail.recipient_key)
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+impl Debug for MpidReceipt {
+    fn fmt(&self, formatter: &mut Formatter) -> Result<(), fmt::Error> {
+        write!(formatter,
+               "MpidReceipt {{ message_name: {:?}, sender: {:?}, kind: {:?}, recipient: {:?}, \
+                signature: {} }}",
+               self.detail.message_name,
+               self.detail.sender,
+               self.detail.kind,
+               self.recipient(),
+               utils::format_binary_array(&self.signature))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use messaging::MpidMessage;
+    use rand;
+    use rust_sodium::crypto::hash::sha256;
+    use rust_sodium::crypto::sign;
+    use super::*;
+    use xor_name::XorName;
+
+    #[test]
+    fn sign_and_verify() {
+        let (sender_public_key, sender_secret_key) = sign::gen_keypair();
+        let (public_key, secret_key) = sign::gen_keypair();
+        let sender = XorName(sha256::hash(&sender_public_key[..]).0);
+        let recipient = XorName(sha256::hash(&public_key[..]).0);
+        let message =
+            unwrap!(MpidMessage::new(sender, vec![], recipient, vec![], &sender_secret_key));
+
+        let receipt = unwrap!(MpidReceipt::new(message.header(),
+                                               ReceiptKind::Read,
+                                               &public_key,
+                                               &secret_key));
+        assert_eq!(*receipt.message_name(), unwrap!(message.name()));
+        assert_eq!(*receipt.sender(), sender);
+        assert_eq!(receipt.recipient(), recipient);
+        assert_eq!(receipt.kind().status(), MessageStatus::Read);
+        assert!(receipt.verify());
+
+        // A receipt signed with another key is invalid.
+        let (_, other_secret_key) = sign::gen_keypair();
+        let forged = unwrap!(MpidReceipt::new(message.header(),
+                                              ReceiptKind::Read,
+                                              &public_key,
+                                              &other_secret_key));
+        assert!(!forged.verify());
+        assert!(MessageStatus::Stored < MessageStatus::Notified);
+        assert!(MessageStatus::Fetched < MessageStatus::Read);
+    }
+}
@@ -17,16 +17,17 @@
 
 use action::Action;
 use cache::{Cache, NullCache};
-use data::{Data, DataIdentifier};
+use compression::Compression;
+use data::{AppendedItems, ContentHash, Data, DataIdentifier, DataProof, DataValidator,
+           DataValidators, ExpiryTracker, MerkleHash, MerkleProof, SectionKey};
 use error::{InterfaceError, RoutingError};
 use event::Event;
 use event_stream::{EventStepper, EventStream};
 use evented::{Evented, ToEvented};
-use id::FullId;
-#[cfg(feature = "use-mock-crust")]
-use id::PublicId;
-use messages::{CLIENT_GET_PRIORITY, DEFAULT_PRIORITY, RELOCATE_PRIORITY, Request, Response,
-               UserMessage};
+use id::{FullId, PublicId};
+use messages::{BatchItemResult, CLIENT_GET_PRIORITY, DEFAULT_PRIORITY, RELOCATE_PRIORITY,
+               Request, Response, UserMessage};
+use messaging::MpidOutgoing;
 #[cfg(feature = "use-mock-crust")]
 use routing_table::{Prefix, RoutingTable};
 use routing_table::Authority;
@@ -36,6 +37,7 @@ use rust_sodium;
 use rust_sodium::crypto::sign;
 use state_machine::{State, StateMachine};
 use states;
+use stats::CacheMetrics;
 #[cfg(feature = "use-mock-crust")]
 use std::collections::BTreeMap;
 use std::collections::VecDeque;
@@ -43,12 +45,17 @@ use std::collections::VecDeque;
 use std::fmt::{self, Debug, Formatter};
 use std::sync::mpsc::{Receiver, RecvError, Sender, TryRecvError, channel};
 use types::{MessageId, RoutingActionSender};
+use utils;
 use xor_name::XorName;
 
 /// A builder to configure and create a new `Node`.
 pub struct NodeBuilder {
     cache: Box<Cache>,
+    compression: Compression,
+    data_validators: DataValidators,
+    expiry_tracker: ExpiryTracker,
     first: bool,
+    peer_bytes_per_sec: Option<usize>,
     deny_other_local_nodes: bool,
 }
 
@@ -58,6 +65,40 @@ impl NodeBuilder {
         NodeBuilder { cache: cache, ..self }
     }
 
+    /// Configures the node to compress the messages it sends using the given codec.
+    ///
+    /// Compression is negotiated separately with each directly connected peer and applied to
+    /// messages after they have been signed: peers that don't announce support for the codec, and
+    /// messages that don't get smaller, are sent uncompressed.
+    pub fn compression(self, compression: Compression) -> NodeBuilder {
+        NodeBuilder { compression: compression, ..self }
+    }
+
+    /// Limits the bulk user data sent to each directly connected peer to `bytes_per_sec`.
+    ///
+    /// User messages beyond that are queued, and rejected with `SendQueueFull` once the queue is
+    /// full. By default, user data is not rate limited.
+    pub fn peer_bytes_per_sec(self, bytes_per_sec: usize) -> NodeBuilder {
+        NodeBuilder { peer_bytes_per_sec: Some(bytes_per_sec), ..self }
+    }
+
+    /// Registers a validator for application-defined data of the validator's type ID.
+    ///
+    /// `Put`, `Post` and `Delete` requests carrying `Data::Custom` of that type are checked against
+    /// it before they are delivered, and rejected with the validation error otherwise. Requests
+    /// for unregistered types are rejected too. Upper layers check a `Post` against the stored
+    /// version with `Node::validate_successor`.
+    pub fn data_validator(mut self, validator: Box<DataValidator>) -> NodeBuilder {
+        self.data_validators.register(validator);
+        self
+    }
+
+    /// Configures the node to resume tracking the expiry times in `tracker`, as previously
+    /// returned by `Node::expiry_tracker`, e.g. after a restart or relocation.
+    pub fn expiry_tracker(self, tracker: ExpiryTracker) -> NodeBuilder {
+        NodeBuilder { expiry_tracker: tracker, ..self }
+    }
+
     /// Configures the node to start a new network instead of joining an existing one.
     pub fn first(self, first: bool) -> NodeBuilder {
         NodeBuilder { first: first, ..self }
@@ -104,6 +145,10 @@ impl NodeBuilder {
 
         StateMachine::new(move |crust_service, timer| if self.first {
             if let Some(state) = states::Node::first(self.cache,
+                                                     self.compression,
+                                                     self.peer_bytes_per_sec,
+                                                     self.data_validators,
+                                                     self.expiry_tracker,
                                                      crust_service,
                                                      full_id,
                                                      min_section_size,
@@ -122,6 +167,10 @@ impl NodeBuilder {
         } else {
             states::Bootstrapping::new(self.cache,
                                         false,
+                                        self.compression,
+                                        self.peer_bytes_per_sec,
+                                        self.data_validators,
+                                        self.expiry_tracker,
                                         crust_service,
                                         full_id,
                                         min_section_size,
@@ -150,7 +199,11 @@ impl Node {
     pub fn builder() -> NodeBuilder {
         NodeBuilder {
             cache: Box::new(NullCache),
+            compression: Compression::None,
+            data_validators: DataValidators::new(),
+            expiry_tracker: ExpiryTracker::new(),
             first: false,
+            peer_bytes_per_sec: None,
             deny_other_local_nodes: false,
         }
     }
@@ -163,7 +216,7 @@ impl Node {
                             id: MessageId)
                             -> Result<(), InterfaceError> {
         let user_msg = UserMessage::Request(Request::Get(data_request, id));
-        self.send_action(src, dst, user_msg, RELOCATE_PRIORITY)
+        self.send_action(src, dst, user_msg, RELOCATE_PRIORITY, None)
     }
 
     /// Send a `Put` request to `dst` to store data on the network.
@@ -174,7 +227,7 @@ impl Node {
                             id: MessageId)
                             -> Result<(), InterfaceError> {
         let user_msg = UserMessage::Request(Request::Put(data, id));
-        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY)
+        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY, None)
     }
 
     /// Send a `Post` request to `dst` to modify data on the network.
@@ -185,7 +238,7 @@ impl Node {
                              id: MessageId)
                              -> Result<(), InterfaceError> {
         let user_msg = UserMessage::Request(Request::Post(data, id));
-        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY)
+        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY, None)
     }
 
     /// Send a `Delete` request to `dst` to remove data from the network.
@@ -196,23 +249,55 @@ impl Node {
                                id: MessageId)
                                -> Result<(), InterfaceError> {
         let user_msg = UserMessage::Request(Request::Delete(data, id));
-        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY)
+        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY, None)
+    }
+
+    /// Send a message produced by an `MpidManager`: as a `Response` if it is for a client,
+    /// otherwise as a `Request` to another account's managers.
+    pub fn send_mpid_message(&mut self, outgoing: MpidOutgoing) -> Result<(), InterfaceError> {
+        let user_msg = if outgoing.dst.is_client() {
+            UserMessage::Response(Response::Messaging(outgoing.wrapper, outgoing.id))
+        } else {
+            UserMessage::Request(Request::Messaging(outgoing.wrapper, outgoing.id))
+        };
+        self.send_action(outgoing.src, outgoing.dst, user_msg, DEFAULT_PRIORITY, None)
     }
 
     /// Respond to a `Get` request indicating success and sending the requested data.
+    ///
+    /// The retained previous versions of `StructuredData` are only sent to other nodes. Clients
+    /// request them individually with `DataIdentifier::StructuredVersion`.
     pub fn send_get_success(&mut self,
                             src: Authority<XorName>,
                             dst: Authority<XorName>,
                             data: Data,
                             id: MessageId)
                             -> Result<(), InterfaceError> {
+        let (data, priority) = if dst.is_client() {
+            (data.without_history(), CLIENT_GET_PRIORITY)
+        } else {
+            (data, RELOCATE_PRIORITY)
+        };
         let user_msg = UserMessage::Response(Response::GetSuccess(data, id));
+        self.send_action(src, dst, user_msg, priority, None)
+    }
+
+    /// Respond to a `GetWithProof` request with the requested data and a proof, obtained from
+    /// `data_proof`, that our section holds it.
+    pub fn send_get_success_with_proof(&mut self,
+                                       src: Authority<XorName>,
+                                       dst: Authority<XorName>,
+                                       data: Data,
+                                       proof: DataProof,
+                                       id: MessageId)
+                                       -> Result<(), InterfaceError> {
+        let user_msg = UserMessage::Response(Response::GetSuccessWithProof(data, proof, id));
         let priority = if dst.is_client() {
             CLIENT_GET_PRIORITY
         } else {
             RELOCATE_PRIORITY
         };
-        self.send_action(src, dst, user_msg, priority)
+        self.send_action(src, dst, user_msg, priority, None)
     }
 
     /// Respond to a `Get` request indicating failure.
@@ -233,7 +318,7 @@ impl Node {
         } else {
             RELOCATE_PRIORITY
         };
-        self.send_action(src, dst, user_msg, priority)
+        self.send_action(src, dst, user_msg, priority, None)
     }
 
     /// Respond to a `Put` request indicating success.
@@ -244,7 +329,7 @@ impl Node {
                             id: MessageId)
                             -> Result<(), InterfaceError> {
         let user_msg = UserMessage::Response(Response::PutSuccess(name, id));
-        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY)
+        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY, None)
     }
 
     /// Respond to a `Put` request indicating failure.
@@ -260,7 +345,7 @@ impl Node {
             data_id: data_id,
             external_error_indicator: external_error_indicator,
         });
-        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY)
+        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY, None)
     }
 
     /// Respond to a `Post` request indicating success.
@@ -271,7 +356,7 @@ impl Node {
                              id: MessageId)
                              -> Result<(), InterfaceError> {
         let user_msg = UserMessage::Response(Response::PostSuccess(name, id));
-        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY)
+        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY, None)
     }
 
     /// Respond to a `Post` request indicating failure.
@@ -287,7 +372,7 @@ impl Node {
             data_id: data_id,
             external_error_indicator: external_error_indicator,
         });
-        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY)
+        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY, None)
     }
 
     /// Respond to a `Delete` request indicating success.
@@ -298,7 +383,7 @@ impl Node {
                                id: MessageId)
                                -> Result<(), InterfaceError> {
         let user_msg = UserMessage::Response(Response::DeleteSuccess(name, id));
-        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY)
+        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY, None)
     }
 
     /// Respond to a `Delete` request indicating failure.
@@ -314,7 +399,27 @@ impl Node {
             data_id: data_id,
             external_error_indicator: external_error_indicator,
         });
-        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY)
+        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY, None)
+    }
+
+    /// Respond to a `ConditionalPost` or `ConditionalDelete` request whose condition is not met,
+    /// with the current version and hash of the stored data, as returned by `Data::version` and
+    /// `content_hash`.
+    pub fn send_condition_not_met(&mut self,
+                                  src: Authority<XorName>,
+                                  dst: Authority<XorName>,
+                                  data_id: DataIdentifier,
+                                  current_version: Option<u64>,
+                                  current_hash: ContentHash,
+                                  id: MessageId)
+                                  -> Result<(), InterfaceError> {
+        let user_msg = UserMessage::Response(Response::ConditionNotMet {
+            id: id,
+            data_id: data_id,
+            current_version: current_version,
+            current_hash: current_hash,
+        });
+        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY, None)
     }
 
     /// Respond to an `Append` request indicating success.
@@ -325,7 +430,7 @@ impl Node {
                                id: MessageId)
                                -> Result<(), InterfaceError> {
         let user_msg = UserMessage::Response(Response::AppendSuccess(name, id));
-        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY)
+        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY, None)
     }
 
     /// Respond to an `Append` request indicating failure.
@@ -341,7 +446,96 @@ impl Node {
             data_id: data_id,
             external_error_indicator: external_error_indicator,
         });
-        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY)
+        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY, None)
+    }
+
+    /// Respond to a `MutateEntries` request indicating success.
+    pub fn send_mutate_entries_success(&mut self,
+                                       src: Authority<XorName>,
+                                       dst: Authority<XorName>,
+                                       name: DataIdentifier,
+                                       id: MessageId)
+                                       -> Result<(), InterfaceError> {
+        let user_msg = UserMessage::Response(Response::MutateEntriesSuccess(name, id));
+        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY, None)
+    }
+
+    /// Respond to a `MutateEntries` request indicating failure.
+    pub fn send_mutate_entries_failure(&mut self,
+                                       src: Authority<XorName>,
+                                       dst: Authority<XorName>,
+                                       data_id: DataIdentifier,
+                                       external_error_indicator: Vec<u8>,
+                                       id: MessageId)
+                                       -> Result<(), InterfaceError> {
+        let user_msg = UserMessage::Response(Response::MutateEntriesFailure {
+            id: id,
+            data_id: data_id,
+            external_error_indicator: external_error_indicator,
+        });
+        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY, None)
+    }
+
+    /// Respond to a `SetUserPermissions` request indicating success.
+    pub fn send_set_user_permissions_success(&mut self,
+                                             src: Authority<XorName>,
+                                             dst: Authority<XorName>,
+                                             name: DataIdentifier,
+                                             id: MessageId)
+                                             -> Result<(), InterfaceError> {
+        let user_msg = UserMessage::Response(Response::SetUserPermissionsSuccess(name, id));
+        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY, None)
+    }
+
+    /// Respond to a `SetUserPermissions` request indicating failure.
+    pub fn send_set_user_permissions_failure(&mut self,
+                                             src: Authority<XorName>,
+                                             dst: Authority<XorName>,
+                                             data_id: DataIdentifier,
+                                             external_error_indicator: Vec<u8>,
+                                             id: MessageId)
+                                             -> Result<(), InterfaceError> {
+        let user_msg = UserMessage::Response(Response::SetUserPermissionsFailure {
+            id: id,
+            data_id: data_id,
+            external_error_indicator: external_error_indicator,
+        });
+        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY, None)
+    }
+
+    /// Respond to a `GetAppendedData` request with the requested range of appended `items`, and
+    /// the `total` number of items held in the appendable data.
+    pub fn send_get_appended_data_success(&mut self,
+                                          src: Authority<XorName>,
+                                          dst: Authority<XorName>,
+                                          data_id: DataIdentifier,
+                                          total: u64,
+                                          items: AppendedItems,
+                                          id: MessageId)
+                                          -> Result<(), InterfaceError> {
+        let user_msg = UserMessage::Response(Response::GetAppendedDataSuccess {
+            id: id,
+            data_id: data_id,
+            total: total,
+            items: items,
+        });
+        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY, None)
+    }
+
+    /// Respond to a `GetAppendedData` request indicating failure.
+    pub fn send_get_appended_data_failure(&mut self,
+                                          src: Authority<XorName>,
+                                          dst: Authority<XorName>,
+                                          data_id: DataIdentifier,
+                                          external_error_indicator: Vec<u8>,
+                                          id: MessageId)
+                                          -> Result<(), InterfaceError> {
+        let user_msg = UserMessage::Response(Response::GetAppendedDataFailure {
+            id: id,
+            data_id: data_id,
+            external_error_indicator: external_error_indicator,
+        });
+        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY, None)
     }
 
     /// Respond to a `GetAccountInfo` request indicating success.
@@ -357,7 +551,7 @@ impl Node {
             data_stored: data_stored,
             space_available: space_available,
         });
-        self.send_action(src, dst, user_msg, CLIENT_GET_PRIORITY)
+        self.send_action(src, dst, user_msg, CLIENT_GET_PRIORITY, None)
     }
 
     /// Respond to a `GetAccountInfo` request indicating failure.
@@ -371,7 +565,42 @@ impl Node {
             id: id,
             external_error_indicator: external_error_indicator,
         });
-        self.send_action(src, dst, user_msg, CLIENT_GET_PRIORITY)
+        self.send_action(src, dst, user_msg, CLIENT_GET_PRIORITY, None)
+    }
+
+    /// Respond to a `GetBatch` request with the result for each requested item, in the order of
+    /// the request.
+    pub fn send_get_batch_response(&mut self,
+                                   src: Authority<XorName>,
+                                   dst: Authority<XorName>,
+                                   results: Vec<BatchItemResult<Data>>,
+                                   id: MessageId)
+                                   -> Result<(), InterfaceError> {
+        let user_msg = UserMessage::Response(Response::GetBatchResponse {
+            id: id,
+            results: results,
+        });
+        let priority = if dst.is_client() {
+            CLIENT_GET_PRIORITY
+        } else {
+            RELOCATE_PRIORITY
+        };
+        self.send_action(src, dst, user_msg, priority, None)
+    }
+
+    /// Respond to a `PutBatch` request with the result for each item, in the order of the
+    /// request.
+    pub fn send_put_batch_response(&mut self,
+                                   src: Authority<XorName>,
+                                   dst: Authority<XorName>,
+                                   results: Vec<BatchItemResult<DataIdentifier>>,
+                                   id: MessageId)
+                                   -> Result<(), InterfaceError> {
+        let user_msg = UserMessage::Response(Response::PutBatchResponse {
+            id: id,
+            results: results,
+        });
+        self.send_action(src, dst, user_msg, DEFAULT_PRIORITY, None)
     }
 
     /// Send a `Refresh` request from `src` to `dst` to trigger churn.
@@ -382,7 +611,88 @@ impl Node {
                                 id: MessageId)
                                 -> Result<(), InterfaceError> {
         let user_msg = UserMessage::Request(Request::Refresh(content, id));
-        self.send_action(src, dst, user_msg, RELOCATE_PRIORITY)
+        self.send_action(src, dst, user_msg, RELOCATE_PRIORITY, None)
+    }
+
+    /// Send `request` from `src` to the single node or client `dst`, encrypted with the `box_`
+    /// public key of `recipient`, so that only `dst` and none of the nodes relaying it can read
+    /// it.
+    ///
+    /// `src` must be a single node, i.e. a `ManagedNode` authority, and `dst` must be a
+    /// `ManagedNode` or `Client` authority whose public ID is `recipient`.
+    pub fn send_encrypted_request(&mut self,
+                                  src: Authority<XorName>,
+                                  dst: Authority<XorName>,
+                                  recipient: &PublicId,
+                                  request: Request)
+                                  -> Result<(), InterfaceError> {
+        if !src.is_single() {
+            return Err(InterfaceError::InvalidSource);
+        }
+        if !utils::is_single_node_authority_of(&dst, recipient) {
+            return Err(InterfaceError::InvalidRecipient);
+        }
+        let priority = request.priority();
+        let user_msg = UserMessage::Request(request);
+        self.send_action(src, dst, user_msg, priority, Some(*recipient))
+    }
+
+    /// Send `response` from `src` to the single node or client `dst`, encrypted with the `box_`
+    /// public key of `recipient`, so that only `dst` and none of the nodes relaying it can read
+    /// it.
+    ///
+    /// `src` must be a single node, i.e. a `ManagedNode` authority, and `dst` must be a
+    /// `ManagedNode` or `Client` authority whose public ID is `recipient`.
+    pub fn send_encrypted_response(&mut self,
+                                   src: Authority<XorName>,
+                                   dst: Authority<XorName>,
+                                   recipient: &PublicId,
+                                   response: Response)
+                                   -> Result<(), InterfaceError> {
+        if !src.is_single() {
+            return Err(InterfaceError::InvalidSource);
+        }
+        if !utils::is_single_node_authority_of(&dst, recipient) {
+            return Err(InterfaceError::InvalidRecipient);
+        }
+        let priority = response.priority();
+        let user_msg = UserMessage::Response(response);
+        self.send_action(src, dst, user_msg, priority, Some(*recipient))
+    }
+
+    /// Send a broadcast with the given `content` to every node in the network.
+    ///
+    /// The content is limited to 20 KiB, and each node will only accept a limited number of
+    /// broadcasts from the same origin per minute. All nodes, including this one, raise an
+    /// `Event::Broadcast` on receipt.
+    pub fn send_broadcast(&mut self, content: Vec<u8>) -> Result<(), InterfaceError> {
+        let action = Action::NodeSendBroadcast {
+            content: content,
+            result_tx: self.interface_result_tx.clone(),
+        };
+        self.perform_action(action)
+    }
+
+    /// Send `content` to the node `dst` as a stream of chunks.
+    ///
+    /// Only a limited window of chunks is in flight at any time, and it advances as the recipient
+    /// acknowledges them. Chunks whose routes all failed are periodically resent, so the stream
+    /// resumes where it stalled. The recipient raises an `Event::StreamChunk` for each chunk, in
+    /// order, and we raise `Event::StreamSent` with the returned stream ID once all chunks have
+    /// been acknowledged, or `Event::StreamFailed` if a chunk still isn't acknowledged after
+    /// being resent several times.
+    pub fn send_stream(&mut self,
+                       dst: XorName,
+                       content: Vec<u8>)
+                       -> Result<MessageId, InterfaceError> {
+        let stream_id = MessageId::new();
+        let action = Action::NodeSendStream {
+            dst: dst,
+            stream_id: stream_id,
+            content: content,
+            result_tx: self.interface_result_tx.clone(),
+        };
+        self.perform_action(action).map(|()| stream_id)
     }
 
     /// Returns the first `count` names of the nodes in the routing table which are closest
@@ -391,6 +701,88 @@ impl Node {
         self.machine.close_group(name, count)
     }
 
+    /// Signs `root`, the root of a `MerkleTree` over all data this node holds as a member of its
+    /// section's `NaeManager`, and sends the signature to the section.
+    ///
+    /// This needs to be called whenever the held data changes, with a `version` that increases
+    /// with every change and that the whole section agrees on, e.g. the number of mutations
+    /// applied so far. Only then can `data_proof` find the signatures of every member for the
+    /// current root.
+    pub fn publish_data_root(&mut self,
+                             version: u64,
+                             root: MerkleHash)
+                             -> Result<(), InterfaceError> {
+        let action = Action::NodeSignDataRoot {
+            version: version,
+            root: root,
+            result_tx: self.interface_result_tx.clone(),
+        };
+        self.perform_action(action)
+    }
+
+    /// Tracks the expiry time of `data`, which this node holds, replacing any previous one. Once
+    /// it has passed, an `Event::DataExpired` is raised for it.
+    ///
+    /// Data without an expiry time is no longer tracked.
+    pub fn track_expiry(&mut self, data: &Data) -> Result<(), InterfaceError> {
+        let action = Action::NodeSetExpiry {
+            data_id: data.identifier(),
+            expiry: data.expiry(),
+            result_tx: self.interface_result_tx.clone(),
+        };
+        self.perform_action(action)
+    }
+
+    /// Stops tracking the expiry time of the data item `data_id`, e.g. because it was deleted.
+    pub fn untrack_expiry(&mut self, data_id: DataIdentifier) -> Result<(), InterfaceError> {
+        let action = Action::NodeSetExpiry {
+            data_id: data_id,
+            expiry: None,
+            result_tx: self.interface_result_tx.clone(),
+        };
+        self.perform_action(action)
+    }
+
+    /// Returns a proof that the data item with the given `merkle_proof` is held by our section
+    /// in the given `version`, or `NotEnoughSignatures` if not every member of our section has
+    /// signed `root` for it yet.
+    pub fn data_proof(&self,
+                      merkle_proof: MerkleProof,
+                      version: u64,
+                      root: MerkleHash)
+                      -> Result<DataProof, RoutingError> {
+        self.machine.data_proof(merkle_proof, version, root)
+    }
+
+    /// Validates `successor` as the new version of `current`, e.g. when handling a `Post`, using
+    /// the built-in rules and the validators registered via `NodeBuilder::data_validator`.
+    ///
+    /// Returns a `RoutingError::Validation` with the violated rule if it is invalid.
+    pub fn validate_successor(&self, current: &Data, successor: &Data) -> Result<(), RoutingError> {
+        self.machine.validate_successor(current, successor)
+    }
+
+    /// Returns the key clients need to verify our section's data proofs, or
+    /// `InvalidStateForOperation` if this isn't a full node.
+    pub fn section_key(&self) -> Result<SectionKey, RoutingError> {
+        self.machine.section_key()
+    }
+
+    /// Returns the counts of requests answered from this node's response cache, and of the
+    /// responses it passed to the cache, or `InvalidStateForOperation` if it isn't a full node.
+    pub fn cache_metrics(&self) -> Result<CacheMetrics, RoutingError> {
+        self.machine.cache_metrics()
+    }
+
+    /// Returns a copy of the expiry times this node is tracking, or `InvalidStateForOperation` if
+    /// it is neither bootstrapping nor a full node.
+    ///
+    /// The tracker is only held in memory: to keep it across a restart or relocation, persist it
+    /// (e.g. on `Event::RestartRequired`) and pass it to `NodeBuilder::expiry_tracker`.
+    pub fn expiry_tracker(&self) -> Result<ExpiryTracker, RoutingError> {
+        self.machine.expiry_tracker()
+    }
+
     /// Returns the name of this node.
     pub fn name(&self) -> Result<XorName, RoutingError> {
         self.machine.name().ok_or(RoutingError::Terminated)
@@ -400,18 +792,24 @@ impl Node {
                    src: Authority<XorName>,
                    dst: Authority<XorName>,
                    user_msg: UserMessage,
-                   priority: u8)
+                   priority: u8,
+                   recipient: Option<PublicId>)
                    -> Result<(), InterfaceError> {
-        // Make sure the state machine has processed any outstanding crust events.
-        self.poll();
-
         let action = Action::NodeSendMessage {
             src: src,
             dst: dst,
             content: user_msg,
             priority: priority,
+            recipient: recipient,
             result_tx: self.interface_result_tx.clone(),
         };
+        self.perform_action(action)
+    }
+
+    fn perform_action(&mut self, action: Action) -> Result<(), InterfaceError> {
+        // Make sure the state machine has processed any outstanding crust events.
+        self.poll();
+
         let events = self.machine
             .current_mut()
             .handle_action(action)
@@ -497,6 +895,14 @@ impl Node {
     pub fn clear_next_node_name(&mut self) {
         self.machine.current_mut().set_next_node_name(None)
     }
+
+    /// Raises an `Event::DataExpired` for the tracked data that has expired at time `now`, as the
+    /// tick timer would, which the mock timer never raises.
+    pub fn expire_data(&mut self, now: u64) {
+        if let Some(event) = self.machine.current_mut().expire_data(now) {
+            self.event_buffer.push_back(event);
+        }
+    }
 }
 
 #[cfg(feature = "use-mock-crust")]
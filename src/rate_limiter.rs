@@ -0,0 +1,115 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.1.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// The maximum number of keys whose counts are tracked at the same time.
+///
+/// While that many keys are within their window, events for any further key are rejected. A live
+/// count is never evicted to make room, so rotating through many keys can't reset a key's limit.
+const MAX_TRACKED_KEYS: usize = 1000;
+
+/// Limits how often an event keyed by `K` is allowed to happen within a time window.
+pub struct RateLimiter<K> {
+    counts: BTreeMap<K, (Instant, usize)>,
+    max_count: usize,
+    window: Duration,
+}
+
+impl<K: Ord + Clone> RateLimiter<K> {
+    /// Creates a limiter allowing at most `max_count` events per key within each `window`.
+    pub fn new(max_count: usize, window: Duration) -> Self {
+        RateLimiter {
+            counts: BTreeMap::new(),
+            max_count: max_count,
+            window: window,
+        }
+    }
+
+    /// Records an event for `key` and returns `true`, or returns `false` if the limit for `key`
+    /// has already been reached in the current window, or if `key` is new and too many other keys
+    /// are being tracked.
+    pub fn allow(&mut self, key: K) -> bool {
+        let now = Instant::now();
+        if !self.counts.contains_key(&key) && self.counts.len() >= MAX_TRACKED_KEYS {
+            self.remove_expired(now);
+            if self.counts.len() >= MAX_TRACKED_KEYS {
+                return false;
+            }
+        }
+        let window = self.window;
+        let entry = self.counts.entry(key).or_insert((now, 0));
+        if now.duration_since(entry.0) > window {
+            *entry = (now, 0);
+        }
+        if entry.1 >= self.max_count {
+            return false;
+        }
+        entry.1 += 1;
+        true
+    }
+
+    fn remove_expired(&mut self, now: Instant) {
+        let window = self.window;
+        let expired: Vec<K> = self.counts
+            .iter()
+            .filter(|&(_, &(start, _))| now.duration_since(start) > window)
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in expired {
+            let _ = self.counts.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+    use super::{MAX_TRACKED_KEYS, RateLimiter};
+
+    #[test]
+    fn limit_per_key() {
+        let mut limiter = RateLimiter::new(2, Duration::from_millis(100));
+        assert!(limiter.allow(0));
+        assert!(limiter.allow(0));
+        assert!(!limiter.allow(0));
+        assert!(limiter.allow(1));
+
+        thread::sleep(Duration::from_millis(150));
+        assert!(limiter.allow(0));
+    }
+
+    #[test]
+    fn no_eviction_of_live_counts() {
+        let mut limiter = RateLimiter::new(1, Duration::from_millis(100));
+        assert!(limiter.allow(0));
+        for key in 1..MAX_TRACKED_KEYS {
+            assert!(limiter.allow(key));
+        }
+
+        // New keys are rejected instead of evicting the count of key `0`.
+        assert!(!limiter.allow(MAX_TRACKED_KEYS));
+        assert!(!limiter.allow(0));
+
+        // Once the counts have expired, there is room for new keys again.
+        thread::sleep(Duration::from_millis(150));
+        assert!(limiter.allow(MAX_TRACKED_KEYS));
+        assert!(limiter.allow(0));
+    }
+}
@@ -0,0 +1,234 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.1.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use event::Event;
+use messages::Response;
+use routing_table::{Authority, Prefix, PrefixSet};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use types::MessageId;
+use xor_name::XorName;
+
+/// Time (in seconds) after which the responses received so far to a request sent to multiple
+/// sections are raised, even if some of the sections haven't responded.
+pub const RESPONSE_AGGREGATION_TIMEOUT_SECS: u64 = 60;
+
+struct PendingRequest {
+    /// The `Sections` authority the request was sent to.
+    src: Authority<XorName>,
+    /// Our authority that sent the request and receives the responses.
+    dst: Authority<XorName>,
+    set: PrefixSet<XorName>,
+    /// The responses by the prefix of the section that sent them. If a section has split since the
+    /// request was sent, this may be a sub-prefix of a prefix in `set`.
+    responses: Vec<(Prefix<XorName>, Response)>,
+    timestamp: Instant,
+}
+
+impl PendingRequest {
+    /// Returns the prefix of the responding section, if it belongs to a prefix in `set`.
+    fn responder_prefix(&self, src: &Authority<XorName>) -> Option<Prefix<XorName>> {
+        match *src {
+            Authority::PrefixSection(prefix) => {
+                if self.set.prefixes().iter().any(|pfx| pfx.is_compatible(&prefix)) {
+                    Some(prefix)
+                } else {
+                    None
+                }
+            }
+            _ => self.set.find(&src.name()),
+        }
+    }
+
+    /// Returns whether `prefix` is already covered by the responses received so far.
+    fn is_covered(&self, prefix: &Prefix<XorName>) -> bool {
+        let responders: Vec<_> = self.responses.iter().map(|&(pfx, _)| pfx).collect();
+        prefix.is_covered_by(&responders)
+    }
+
+    fn is_complete(&self) -> bool {
+        self.set.prefixes().iter().all(|prefix| self.is_covered(prefix))
+    }
+
+    fn into_event(self, msg_id: MessageId) -> Event {
+        Event::SectionsResponse {
+            msg_id: msg_id,
+            responses: self.responses,
+            src: self.src,
+            dst: self.dst,
+        }
+    }
+}
+
+/// Collects the responses to requests sent to an `Authority::Sections`, one per responding
+/// section, until the responses cover all the requested prefixes.
+pub struct ResponseAggregator {
+    pending: HashMap<MessageId, PendingRequest>,
+}
+
+impl ResponseAggregator {
+    pub fn new() -> Self {
+        ResponseAggregator { pending: HashMap::new() }
+    }
+
+    /// Registers a request sent from `src` to `dst`, if `dst` is an `Authority::Sections`.
+    pub fn expect(&mut self,
+                  msg_id: MessageId,
+                  src: Authority<XorName>,
+                  dst: Authority<XorName>) {
+        if let Authority::Sections(set) = dst {
+            let _ = self.pending.insert(msg_id,
+                                        PendingRequest {
+                                            src: dst,
+                                            dst: src,
+                                            set: set,
+                                            responses: Vec::new(),
+                                            timestamp: Instant::now(),
+                                        });
+        }
+    }
+
+    /// Adds a response from `src`. If it doesn't belong to a pending request, it is returned as
+    /// `Err`. Otherwise, if it was the last missing response, returns the aggregated event.
+    pub fn add(&mut self,
+               src: &Authority<XorName>,
+               response: Response)
+               -> Result<Option<Event>, Response> {
+        let msg_id = response.message_id();
+        let complete = if let Some(pending) = self.pending.get_mut(&msg_id) {
+            let prefix = match pending.responder_prefix(src) {
+                Some(prefix) => prefix,
+                None => return Err(response),
+            };
+            if !pending.is_covered(&prefix) {
+                pending.responses.push((prefix, response));
+            }
+            pending.is_complete()
+        } else {
+            return Err(response);
+        };
+        if complete {
+            Ok(self.pending.remove(&msg_id).map(|pending| pending.into_event(msg_id)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Returns `true` if no requests are awaiting responses.
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Removes the requests that timed out and returns events with their partial responses.
+    pub fn remove_expired(&mut self) -> Vec<Event> {
+        let timeout = Duration::from_secs(RESPONSE_AGGREGATION_TIMEOUT_SECS);
+        let expired_ids: Vec<MessageId> = self.pending
+            .iter()
+            .filter(|&(_, pending)| pending.timestamp.elapsed() > timeout)
+            .map(|(msg_id, _)| *msg_id)
+            .collect();
+        expired_ids.into_iter()
+            .filter_map(|msg_id| {
+                self.pending.remove(&msg_id).map(|pending| pending.into_event(msg_id))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use messages::Response;
+    use rand;
+    use routing_table::{Authority, Prefix, PrefixSet};
+    use super::*;
+    use types::MessageId;
+
+    #[test]
+    fn responses_aggregated_per_prefix() {
+        let base = Prefix::new(2, rand::random());
+        let set = PrefixSet::new(base).with_flipped_bit(0);
+        let src = Authority::ManagedNode(rand::random());
+        let dst = Authority::Sections(set);
+        let msg_id = MessageId::new();
+        let mut aggregator = ResponseAggregator::new();
+        aggregator.expect(msg_id, src, dst);
+
+        let response = Response::GetAccountInfoFailure {
+            id: msg_id,
+            external_error_indicator: vec![],
+        };
+        let other = Response::GetAccountInfoFailure {
+            id: MessageId::new(),
+            external_error_indicator: vec![],
+        };
+        let from_base = Authority::PrefixSection(base);
+        let from_flipped = Authority::PrefixSection(base.with_flipped_bit(0));
+        let from_outside = Authority::PrefixSection(base.with_flipped_bit(1));
+
+        assert_eq!(Err(other.clone()), aggregator.add(&from_base, other));
+        assert_eq!(Err(response.clone()),
+                   aggregator.add(&from_outside, response.clone()));
+        assert_eq!(Ok(None), aggregator.add(&from_base, response.clone()));
+        // A second response from the same prefix is ignored.
+        assert_eq!(Ok(None), aggregator.add(&from_base, response.clone()));
+        match aggregator.add(&from_flipped, response.clone()) {
+            Ok(Some(Event::SectionsResponse { msg_id: id, responses, src: s, dst: d })) => {
+                assert_eq!(msg_id, id);
+                assert_eq!(dst, s);
+                assert_eq!(src, d);
+                assert_eq!(vec![(base, response.clone()),
+                                (base.with_flipped_bit(0), response.clone())],
+                           responses);
+            }
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert_eq!(Err(response.clone()), aggregator.add(&from_base, response));
+        assert!(aggregator.remove_expired().is_empty());
+    }
+
+    #[test]
+    fn responses_from_split_sections() {
+        let base = Prefix::new(2, rand::random());
+        let set = PrefixSet::new(base);
+        let src = Authority::ManagedNode(rand::random());
+        let dst = Authority::Sections(set);
+        let msg_id = MessageId::new();
+        let mut aggregator = ResponseAggregator::new();
+        aggregator.expect(msg_id, src, dst);
+
+        let response = Response::GetAccountInfoFailure {
+            id: msg_id,
+            external_error_indicator: vec![],
+        };
+        let (sub0, sub1) = (base.pushed(false), base.pushed(true));
+
+        // Each half of the split section responds, and both responses are kept.
+        assert_eq!(Ok(None),
+                   aggregator.add(&Authority::PrefixSection(sub0), response.clone()));
+        assert_eq!(Ok(None),
+                   aggregator.add(&Authority::PrefixSection(sub0), response.clone()));
+        assert!(!aggregator.is_empty());
+        match aggregator.add(&Authority::PrefixSection(sub1), response.clone()) {
+            Ok(Some(Event::SectionsResponse { responses, .. })) => {
+                assert_eq!(vec![(sub0, response.clone()), (sub1, response.clone())],
+                           responses);
+            }
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert!(aggregator.is_empty());
+    }
+}
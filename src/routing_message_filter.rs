@@ -16,11 +16,13 @@
 // relating to use of the SAFE Network Software.
 
 use crust::PeerId;
+use id::PublicId;
 use lru_time_cache::LruCache;
 use maidsafe_utilities;
 use message_filter::MessageFilter;
 use messages::RoutingMessage;
 use std::time::Duration;
+use types::MessageId;
 
 const INCOMING_EXPIRY_DURATION_SECS: u64 = 60 * 20;
 const OUTGOING_EXPIRY_DURATION_SECS: u64 = 60 * 10;
@@ -40,6 +42,7 @@ pub struct RoutingMessageFilter {
     incoming: MessageFilter<RoutingMessage>,
     incoming_route: MessageFilter<(RoutingMessage, u8)>,
     outgoing: LruCache<(u64, PeerId, u8), ()>,
+    broadcasts: MessageFilter<(PublicId, MessageId)>,
 }
 
 impl RoutingMessageFilter {
@@ -51,6 +54,7 @@ impl RoutingMessageFilter {
             incoming: MessageFilter::with_expiry_duration(incoming_duration),
             incoming_route: MessageFilter::with_expiry_duration(incoming_duration),
             outgoing: LruCache::with_expiry_duration(outgoing_duration),
+            broadcasts: MessageFilter::with_expiry_duration(incoming_duration),
         }
     }
 
@@ -74,10 +78,18 @@ impl RoutingMessageFilter {
         self.outgoing.insert((hash, *peer_id, route), ()).is_some()
     }
 
+    // Filter incoming broadcasts. Return whether a broadcast with this origin and ID has been seen
+    // recently, possibly via a different sender (and thus should be neither delivered nor
+    // forwarded again).
+    pub fn filter_broadcast(&mut self, origin: &PublicId, msg_id: &MessageId) -> bool {
+        self.broadcasts.insert(&(*origin, *msg_id)) > 1
+    }
+
     #[cfg(feature = "use-mock-crust")]
     pub fn clear(&mut self) {
         self.incoming.clear();
         self.incoming_route.clear();
         self.outgoing.clear();
+        self.broadcasts.clear();
     }
 }
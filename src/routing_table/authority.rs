@@ -18,7 +18,7 @@
 use crust::PeerId;
 use rust_sodium::crypto::{hash, sign};
 use std::fmt::{self, Binary, Debug, Display, Formatter};
-use super::{Prefix, Xorable};
+use super::{Prefix, PrefixSet, Xorable};
 
 /// An entity that can act as a source or destination of a message.
 ///
@@ -28,6 +28,8 @@ use super::{Prefix, Xorable};
 /// require quorum agreement from the group of nodes closest to the source, while `Section` and
 /// `PrefixSection` use _section_ verification: the set from which a quorum is required is all
 /// members of the section (`Section`) or of all sections matching the prefix (`PrefixSection`).
+/// `Sections` names several prefixes at once: a message to it is delivered and accumulated
+/// separately in each of them, as if it was sent to the `PrefixSection`s.
 #[derive(RustcEncodable, RustcDecodable, PartialEq, PartialOrd, Eq, Ord, Clone, Copy, Hash)]
 pub enum Authority<N: Xorable + Clone + Copy + Binary + Default> {
     /// Manager of a Client.  XorName is the hash of the Client's `client_key`.
@@ -56,6 +58,8 @@ pub enum Authority<N: Xorable + Clone + Copy + Binary + Default> {
         /// through.
         proxy_node_name: N,
     },
+    /// The sets of nodes matching any of the given prefixes, e.g. a section and its neighbours.
+    Sections(PrefixSet<N>),
 }
 
 impl<N: Xorable + Clone + Copy + Binary + Default> Authority<N> {
@@ -64,6 +68,7 @@ impl<N: Xorable + Clone + Copy + Binary + Default> Authority<N> {
         match *self {
             Authority::Section(_) |
             Authority::PrefixSection(_) |
+            Authority::Sections(_) |
             Authority::ClientManager(_) |
             Authority::NaeManager(_) |
             Authority::NodeManager(_) => true,
@@ -79,6 +84,7 @@ impl<N: Xorable + Clone + Copy + Binary + Default> Authority<N> {
             Authority::NaeManager(_) |
             Authority::Section(_) |
             Authority::PrefixSection(_) |
+            Authority::Sections(_) |
             Authority::NodeManager(_) => false,
             Authority::ManagedNode(_) |
             Authority::Client { .. } => true,
@@ -103,9 +109,21 @@ impl<N: Xorable + Clone + Copy + Binary + Default> Authority<N> {
             Authority::Section(ref name) |
             Authority::ManagedNode(ref name) => *name,
             Authority::PrefixSection(ref prefix) => prefix.lower_bound(),
+            Authority::Sections(ref set) => set.base().lower_bound(),
             Authority::Client { ref proxy_node_name, .. } => *proxy_node_name,
         }
     }
+
+    /// Returns the authorities a message to this one is actually sent to: a `PrefixSection` for
+    /// each prefix of a `Sections` authority, and only the authority itself otherwise.
+    pub fn delivery_authorities(&self) -> Vec<Authority<N>> {
+        match *self {
+            Authority::Sections(ref set) => {
+                set.prefixes().into_iter().map(Authority::PrefixSection).collect()
+            }
+            _ => vec![*self],
+        }
+    }
 }
 
 impl<N: Xorable + Clone + Copy + Binary + Default + Display> Debug for Authority<N> {
@@ -120,6 +138,7 @@ impl<N: Xorable + Clone + Copy + Binary + Default + Display> Debug for Authority
             Authority::PrefixSection(ref prefix) => {
                 write!(formatter, "PrefixSection(prefix: {:?})", prefix)
             }
+            Authority::Sections(ref set) => write!(formatter, "Sections({:?})", set),
             Authority::ManagedNode(ref name) => write!(formatter, "ManagedNode(name: {})", name),
             Authority::Client { ref client_key, ref proxy_node_name, ref peer_id } => {
                 write!(formatter,
@@ -111,6 +111,7 @@ mod authority;
 mod error;
 mod network_tests;
 mod prefix;
+mod prefix_set;
 mod xorable;
 
 use itertools::Itertools;
@@ -119,6 +120,7 @@ pub use self::error::Error;
 #[cfg(any(test, feature = "use-mock-crust"))]
 pub use self::network_tests::verify_network_invariant;
 pub use self::prefix::Prefix;
+pub use self::prefix_set::PrefixSet;
 pub use self::xorable::Xorable;
 use std::{iter, mem};
 use std::cmp::Ordering;
@@ -727,6 +729,9 @@ impl<T: Binary + Clone + Copy + Debug + Default + Hash + Xorable> RoutingTable<T
     ///     - returns the `route`-th closest member of the RT to the lower bound of the target
     ///       prefix
     ///
+    /// * If the destination is an `Authority::Sections`, returns `Err(Error::CannotRoute)`: such
+    ///   messages need to be sent to each of its `PrefixSection`s instead.
+    ///
     /// * If the destination is a group (`ClientManager`, `NaeManager` or `NodeManager`):
     ///     - if our section is the closest on the network (i.e. our section's prefix is a prefix of
     ///       the destination), returns all other members of our section; otherwise
@@ -794,6 +799,8 @@ impl<T: Binary + Clone + Copy + Debug + Default + Hash + Xorable> RoutingTable<T
                 }
                 candidates(&prefix.lower_bound())
             }
+            // Messages to multiple sections are sent to each `PrefixSection` separately.
+            Authority::Sections(_) => return Err(Error::CannotRoute),
         };
         Ok(iter::once(self.get_routeth_node(&closest_section, dst.name(), Some(exclude), route)?)
             .collect())
@@ -810,6 +817,7 @@ impl<T: Binary + Clone + Copy + Debug + Default + Hash + Xorable> RoutingTable<T
             Authority::NodeManager(ref name) => self.is_closest(name, self.min_section_size),
             Authority::Section(ref name) => self.our_prefix.matches(name),
             Authority::PrefixSection(ref prefix) => prefix.matches(&self.our_name),
+            Authority::Sections(ref set) => set.matches(&self.our_name),
         }
     }
 
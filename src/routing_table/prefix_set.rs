@@ -0,0 +1,180 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.1.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use std::cmp;
+use std::fmt::{Binary, Debug, Formatter};
+use std::fmt::Result as FmtResult;
+use super::{Prefix, Xorable};
+
+/// The maximal number of leading bits in which neighbours can differ from the base prefix.
+pub const MAX_FLIPPED_BITS: usize = 32;
+
+/// A set of prefixes: a base prefix, and any of its neighbours, i.e. the prefixes that differ from
+/// it in exactly one bit.
+///
+/// The neighbours don't need to have the same bit count as the base prefix: for each bit, the set
+/// can hold a neighbour that is shorter than the base prefix or of equal length. A neighbour that
+/// is longer, e.g. because that section has split, is covered by its ancestor of the base prefix's
+/// length, which matches the neighbour and all its siblings.
+///
+/// Unlike a collection of arbitrary prefixes, this is `Copy`, so that it can be used in an
+/// `Authority`, e.g. to name a section together with all its neighbours.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, RustcEncodable, RustcDecodable)]
+pub struct PrefixSet<T: Clone + Copy + Default + Binary + Xorable> {
+    base: Prefix<T>,
+    /// The `i`-th entry is the bit count of the included neighbour that differs from the base
+    /// prefix in the `i`-th bit, or `0` if there is none.
+    neighbour_bit_counts: [u16; MAX_FLIPPED_BITS],
+}
+
+impl<T: Clone + Copy + Default + Binary + Xorable> PrefixSet<T> {
+    /// Creates a set containing only `base`.
+    pub fn new(base: Prefix<T>) -> PrefixSet<T> {
+        PrefixSet {
+            base: base,
+            neighbour_bit_counts: [0; MAX_FLIPPED_BITS],
+        }
+    }
+
+    /// Creates a set containing `base` and all its neighbours of the same length that differ from
+    /// it in one of the first `MAX_FLIPPED_BITS` bits.
+    pub fn with_neighbours(base: Prefix<T>) -> PrefixSet<T> {
+        (0..cmp::min(base.bit_count(), MAX_FLIPPED_BITS))
+            .fold(PrefixSet::new(base), |set, i| set.with_flipped_bit(i))
+    }
+
+    /// Returns the set with the neighbour differing from the base prefix in the `i`-th bit added.
+    /// If `i` is not less than both the base prefix's bit count and `MAX_FLIPPED_BITS`, the set is
+    /// returned unchanged.
+    pub fn with_flipped_bit(self, i: usize) -> PrefixSet<T> {
+        let neighbour = self.base.with_flipped_bit(i);
+        self.with_neighbour(neighbour)
+    }
+
+    /// Returns the set with `prefix` added, if it is a neighbour of the base prefix, i.e. differs
+    /// from it in exactly one of the first `MAX_FLIPPED_BITS` bits.
+    ///
+    /// If `prefix` is longer than the base prefix, its ancestor with the base prefix's bit count is
+    /// added instead. If the set already contains a neighbour differing in the same bit, the
+    /// shorter one of the two is kept, as it covers the other. Otherwise the set is returned
+    /// unchanged.
+    pub fn with_neighbour(mut self, prefix: Prefix<T>) -> PrefixSet<T> {
+        let bit_count = cmp::min(prefix.bit_count(), self.base.bit_count());
+        let prefix = Prefix::new(bit_count, prefix.lower_bound());
+        let i = self.base.common_prefix(&prefix.lower_bound());
+        if i >= cmp::min(bit_count, MAX_FLIPPED_BITS) || !self.base.is_neighbour(&prefix) {
+            return self;
+        }
+        {
+            let entry = &mut self.neighbour_bit_counts[i];
+            if *entry == 0 || bit_count < *entry as usize {
+                *entry = bit_count as u16;
+            }
+        }
+        self
+    }
+
+    /// Returns the base prefix.
+    pub fn base(&self) -> &Prefix<T> {
+        &self.base
+    }
+
+    /// Returns the prefixes in the set: the base prefix first, then the neighbours in the order of
+    /// the bits in which they differ from it.
+    pub fn prefixes(&self) -> Vec<Prefix<T>> {
+        let base = self.base;
+        let neighbours = self.neighbour_bit_counts
+            .iter()
+            .enumerate()
+            .filter(|&(_, &bit_count)| bit_count != 0)
+            .map(move |(i, &bit_count)| {
+                Prefix::new(bit_count as usize, base.lower_bound().with_flipped_bit(i))
+            });
+        Some(self.base).into_iter().chain(neighbours).collect()
+    }
+
+    /// Returns the prefix in the set matching `name`, if any.
+    pub fn find(&self, name: &T) -> Option<Prefix<T>> {
+        self.prefixes().into_iter().find(|prefix| prefix.matches(name))
+    }
+
+    /// Returns `true` if `name` matches any of the prefixes in the set.
+    pub fn matches(&self, name: &T) -> bool {
+        self.find(name).is_some()
+    }
+}
+
+impl<T: Clone + Copy + Default + Binary + Xorable> Debug for PrefixSet<T> {
+    fn fmt(&self, formatter: &mut Formatter) -> FmtResult {
+        write!(formatter, "PrefixSet({:?})", self.prefixes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use routing_table::Prefix;
+    use super::PrefixSet;
+
+    #[test]
+    fn prefix_set() {
+        let base = Prefix::from_str("101");
+        assert_eq!(PrefixSet::new(base).prefixes(), vec![base]);
+
+        let set = PrefixSet::with_neighbours(base);
+        assert_eq!(set.prefixes(),
+                   vec![base, Prefix::from_str("001"), Prefix::from_str("111"),
+                        Prefix::from_str("100")]);
+        assert_eq!(set.find(&0b11100000), Some(Prefix::from_str("111")));
+        assert!(!set.matches(&0b11000000));
+
+        // Flipping a bit beyond the base prefix's length doesn't add anything.
+        assert_eq!(PrefixSet::new(base).with_flipped_bit(3), PrefixSet::new(base));
+    }
+
+    #[test]
+    fn prefix_set_differing_lengths() {
+        let base = Prefix::from_str("101");
+
+        // The neighbour in bit 0 has split: both halves are covered by their parent.
+        let set = PrefixSet::new(base)
+            .with_neighbour(Prefix::from_str("0010"))
+            .with_neighbour(Prefix::from_str("0011"))
+            .with_neighbour(Prefix::from_str("11"))
+            .with_neighbour(Prefix::from_str("100"));
+        assert_eq!(set.prefixes(),
+                   vec![base, Prefix::from_str("001"), Prefix::from_str("11"),
+                        Prefix::from_str("100")]);
+        assert_eq!(set.find(&0b00100000), Some(Prefix::from_str("001")));
+        assert_eq!(set.find(&0b00110000), Some(Prefix::from_str("001")));
+        assert_eq!(set.find(&0b11000000), Some(Prefix::from_str("11")));
+        assert!(!set.matches(&0b00000000));
+
+        // A shorter neighbour replaces a longer one differing in the same bit, not vice versa.
+        let set = PrefixSet::new(base)
+            .with_flipped_bit(1)
+            .with_neighbour(Prefix::from_str("11"))
+            .with_neighbour(Prefix::from_str("111"));
+        assert_eq!(set.prefixes(), vec![base, Prefix::from_str("11")]);
+
+        // Prefixes that aren't neighbours of the base aren't added.
+        assert_eq!(PrefixSet::new(base).with_neighbour(Prefix::from_str("01")),
+                   PrefixSet::new(base));
+        assert_eq!(PrefixSet::new(base).with_neighbour(Prefix::from_str("1")),
+                   PrefixSet::new(base));
+        assert_eq!(PrefixSet::new(base).with_neighbour(base), PrefixSet::new(base));
+    }
+}
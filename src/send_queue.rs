@@ -0,0 +1,276 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.1.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use crust::PeerId;
+use error::{InterfaceError, RoutingError};
+use std::cmp;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::time::Instant;
+
+/// Interval (in milliseconds) at which queued messages are sent, as budgets allow.
+pub const SEND_QUEUE_FLUSH_INTERVAL_MS: u64 = 100;
+/// The maximal number of bytes queued for a single peer. Further messages are rejected.
+const MAX_QUEUED_BYTES_PER_PEER: usize = 4 * 1024 * 1024;
+/// The maximal number of bytes queued for all peers. New user messages are rejected beyond that.
+const MAX_TOTAL_QUEUED_BYTES: usize = 16 * 1024 * 1024;
+
+struct PeerQueue {
+    /// The number of bytes that may be sent to the peer per second. This is also the maximal
+    /// budget the peer can accumulate while idle.
+    bytes_per_sec: usize,
+    /// The number of bytes that can currently be sent to the peer.
+    budget: usize,
+    refilled_at: Instant,
+    /// The queued messages by priority. Lower values have higher priority.
+    msgs: BTreeMap<u8, VecDeque<Vec<u8>>>,
+    queued_bytes: usize,
+}
+
+impl PeerQueue {
+    fn new(bytes_per_sec: usize, now: Instant) -> Self {
+        PeerQueue {
+            bytes_per_sec: bytes_per_sec,
+            budget: bytes_per_sec,
+            refilled_at: now,
+            msgs: BTreeMap::new(),
+            queued_bytes: 0,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.refilled_at);
+        let millis = elapsed.as_secs() * 1000 + elapsed.subsec_nanos() as u64 / 1_000_000;
+        let added = (self.bytes_per_sec as u64).saturating_mul(millis) / 1000;
+        let added = cmp::min(added, self.bytes_per_sec as u64) as usize;
+        if added > 0 {
+            self.budget = cmp::min(self.bytes_per_sec, self.budget.saturating_add(added));
+            self.refilled_at = now;
+        }
+    }
+
+    /// Deducts `len` from the budget and returns `true` if the budget allows sending it. A message
+    /// larger than the maximal budget can be sent whenever the budget is full.
+    fn consume(&mut self, len: usize) -> bool {
+        if len > self.budget && self.budget < self.bytes_per_sec {
+            return false;
+        }
+        self.budget = self.budget.saturating_sub(len);
+        true
+    }
+
+    /// Removes and returns the first message with the highest priority, if the budget allows
+    /// sending it.
+    fn pop(&mut self) -> Option<(Vec<u8>, u8)> {
+        let priority = match self.msgs.keys().next() {
+            Some(&priority) => priority,
+            None => return None,
+        };
+        let len = match self.msgs.get(&priority).and_then(VecDeque::front) {
+            Some(bytes) => bytes.len(),
+            None => 0,
+        };
+        if !self.consume(len) {
+            return None;
+        }
+        let (bytes, now_empty) = match self.msgs.get_mut(&priority) {
+            Some(queue) => (queue.pop_front(), queue.is_empty()),
+            None => (None, true),
+        };
+        if now_empty {
+            let _ = self.msgs.remove(&priority);
+        }
+        bytes.map(|bytes| {
+            self.queued_bytes -= bytes.len();
+            (bytes, priority)
+        })
+    }
+}
+
+/// Per-peer queues for outgoing bulk user data, drained by priority within per-peer byte budgets.
+///
+/// Only user data is ever queued: Routing messages are sent right away, so that they never wait
+/// behind bulk user data. Without a byte rate limit, nothing is queued at all.
+pub struct SendQueue {
+    /// The number of bytes of bulk user data that may be sent to a single peer per second.
+    peer_bytes_per_sec: Option<usize>,
+    peers: HashMap<PeerId, PeerQueue>,
+    total_bytes: usize,
+    flush_timer_token: Option<u64>,
+}
+
+impl SendQueue {
+    /// Creates a new, empty queue limiting the bulk user data sent to each peer to
+    /// `peer_bytes_per_sec`, if given.
+    pub fn new(peer_bytes_per_sec: Option<usize>) -> Self {
+        SendQueue {
+            peer_bytes_per_sec: peer_bytes_per_sec,
+            peers: HashMap::new(),
+            total_bytes: 0,
+            flush_timer_token: None,
+        }
+    }
+
+    /// Returns `true` if a user message of `len` bytes can be accepted for sending.
+    pub fn has_capacity(&self, len: usize) -> bool {
+        self.total_bytes + len <= MAX_TOTAL_QUEUED_BYTES
+    }
+
+    /// Returns `true` if no messages are queued.
+    pub fn is_empty(&self) -> bool {
+        self.total_bytes == 0
+    }
+
+    /// Handles `bytes` to be sent to `peer_id`. Returns them if they should be sent right away,
+    /// or `None` if they have been queued. Returns `SendQueueFull` if the peer's queue is full.
+    pub fn push(&mut self,
+                peer_id: PeerId,
+                bytes: Vec<u8>,
+                priority: u8)
+                -> Result<Option<Vec<u8>>, RoutingError> {
+        let bytes_per_sec = match self.peer_bytes_per_sec {
+            Some(bytes_per_sec) => bytes_per_sec,
+            None => return Ok(Some(bytes)),
+        };
+        let now = Instant::now();
+        let queue = self.peers.entry(peer_id).or_insert_with(|| PeerQueue::new(bytes_per_sec, now));
+        queue.refill(now);
+        if queue.msgs.is_empty() && queue.consume(bytes.len()) {
+            return Ok(Some(bytes));
+        }
+        if queue.queued_bytes + bytes.len() > MAX_QUEUED_BYTES_PER_PEER {
+            debug!("Send queue for {:?} is full. Rejecting message.", peer_id);
+            return Err(RoutingError::Interface(InterfaceError::SendQueueFull));
+        }
+        queue.queued_bytes += bytes.len();
+        self.total_bytes += bytes.len();
+        queue.msgs.entry(priority).or_insert_with(VecDeque::new).push_back(bytes);
+        Ok(None)
+    }
+
+    /// Returns the queued messages that fit into the peers' budgets, highest priority first.
+    pub fn flush(&mut self) -> Vec<(PeerId, Vec<u8>, u8)> {
+        let now = Instant::now();
+        let mut msgs = vec![];
+        for (peer_id, queue) in &mut self.peers {
+            queue.refill(now);
+            while let Some((bytes, priority)) = queue.pop() {
+                self.total_bytes -= bytes.len();
+                msgs.push((*peer_id, bytes, priority));
+            }
+        }
+        msgs
+    }
+
+    /// Drops all messages queued for `peer_id`.
+    pub fn remove_peer(&mut self, peer_id: &PeerId) {
+        if let Some(queue) = self.peers.remove(peer_id) {
+            self.total_bytes -= queue.queued_bytes;
+        }
+    }
+
+    /// Returns the token of the timer scheduled to flush the queue, if any.
+    pub fn flush_timer_token(&self) -> Option<u64> {
+        self.flush_timer_token
+    }
+
+    /// Sets or clears the token of the timer scheduled to flush the queue.
+    pub fn set_flush_timer_token(&mut self, token: Option<u64>) {
+        self.flush_timer_token = token;
+    }
+}
+
+#[cfg(all(test, feature = "use-mock-crust"))]
+mod tests {
+    use crust::PeerId;
+    use error::{InterfaceError, RoutingError};
+    use std::time::{Duration, Instant};
+    use super::{MAX_QUEUED_BYTES_PER_PEER, PeerQueue, SendQueue};
+
+    const BYTES_PER_SEC: usize = 1024 * 1024;
+
+    #[test]
+    fn queue_beyond_budget() {
+        let mut queue = SendQueue::new(Some(BYTES_PER_SEC));
+        let peer_id = PeerId(0);
+        let len = BYTES_PER_SEC / 4;
+
+        // The first message exhausts the budget, so the next ones are queued.
+        assert!(unwrap!(queue.push(peer_id, vec![0; BYTES_PER_SEC], 2)).is_some());
+        assert!(unwrap!(queue.push(peer_id, vec![3; len], 3)).is_none());
+        assert!(unwrap!(queue.push(peer_id, vec![2; len], 2)).is_none());
+        assert!(!queue.is_empty());
+
+        // Messages to other peers are not affected.
+        assert!(unwrap!(queue.push(PeerId(1), vec![0; len], 3)).is_some());
+
+        // Once the budget is refilled, they are sent by priority.
+        if let Some(peer_queue) = queue.peers.get_mut(&peer_id) {
+            peer_queue.budget = BYTES_PER_SEC;
+        }
+        let msgs = queue.flush();
+        assert_eq!(msgs,
+                   vec![(peer_id, vec![2; len], 2), (peer_id, vec![3; len], 3)]);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn reject_when_full() {
+        let mut queue = SendQueue::new(Some(BYTES_PER_SEC));
+        let peer_id = PeerId(0);
+        assert!(unwrap!(queue.push(peer_id, vec![0; BYTES_PER_SEC], 2)).is_some());
+        assert!(unwrap!(queue.push(peer_id, vec![0; MAX_QUEUED_BYTES_PER_PEER], 2)).is_none());
+        match queue.push(peer_id, vec![0; 1], 2) {
+            Err(RoutingError::Interface(InterfaceError::SendQueueFull)) => (),
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert!(!queue.has_capacity(MAX_QUEUED_BYTES_PER_PEER * 3 + 1));
+
+        queue.remove_peer(&peer_id);
+        assert!(queue.is_empty());
+        assert!(queue.has_capacity(MAX_QUEUED_BYTES_PER_PEER * 3 + 1));
+    }
+
+    #[test]
+    fn refill_slow_rate() {
+        let start = Instant::now();
+
+        // Rates below 1000 bytes per second still refill the budget.
+        let mut queue = PeerQueue::new(500, start);
+        assert!(queue.consume(500));
+        assert_eq!(queue.budget, 0);
+        queue.refill(start + Duration::from_millis(100));
+        assert_eq!(queue.budget, 50);
+        queue.refill(start + Duration::from_secs(10));
+        assert_eq!(queue.budget, 500);
+
+        // And rates between multiples of 1000 are not rounded down.
+        let mut queue = PeerQueue::new(1999, start);
+        assert!(queue.consume(1999));
+        queue.refill(start + Duration::from_millis(500));
+        assert_eq!(queue.budget, 999);
+    }
+
+    #[test]
+    fn unlimited() {
+        let mut queue = SendQueue::new(None);
+        let peer_id = PeerId(0);
+        for _ in 0..10 {
+            assert!(unwrap!(queue.push(peer_id, vec![0; MAX_QUEUED_BYTES_PER_PEER], 2)).is_some());
+        }
+        assert!(queue.is_empty());
+    }
+}
@@ -18,6 +18,8 @@
 use action::Action;
 use crust::{CrustEventSender, PeerId, Service};
 use crust::Event as CrustEvent;
+use data::{Data, DataProof, ExpiryTracker, MerkleHash, MerkleProof, SectionKey};
+use error::RoutingError;
 use event::Event;
 use evented::{Evented, ToEvented};
 use id::PublicId;
@@ -28,6 +30,7 @@ use routing_table::{Prefix, RoutingTable};
 use rust_sodium::crypto::sign;
 use states::{Bootstrapping, Client, Node};
 use states::common::Base;
+use stats::CacheMetrics;
 #[cfg(feature = "use-mock-crust")]
 use std::collections::BTreeMap;
 use std::fmt::{self, Debug, Formatter};
@@ -57,7 +60,7 @@ impl State {
     pub fn handle_action(&mut self, action: Action) -> Evented<Transition> {
         match *self {
             State::Bootstrapping(ref mut state) => state.handle_action(action),
-            State::Client(ref mut state) => state.handle_action(action).to_evented(),
+            State::Client(ref mut state) => state.handle_action(action),
             State::Node(ref mut state) => state.handle_action(action),
             State::Terminated => Transition::Terminate.to_evented(),
         }
@@ -95,6 +98,48 @@ impl State {
         self.base_state().and_then(|state| state.close_group(name, count))
     }
 
+    fn data_proof(&self,
+                  merkle_proof: MerkleProof,
+                  version: u64,
+                  root: MerkleHash)
+                  -> Result<DataProof, RoutingError> {
+        match *self {
+            State::Node(ref state) => state.data_proof(merkle_proof, version, root),
+            _ => Err(RoutingError::InvalidStateForOperation),
+        }
+    }
+
+    fn validate_successor(&self, current: &Data, successor: &Data) -> Result<(), RoutingError> {
+        match *self {
+            State::Node(ref state) => {
+                state.validate_successor(current, successor).map_err(RoutingError::from)
+            }
+            _ => Err(RoutingError::InvalidStateForOperation),
+        }
+    }
+
+    fn section_key(&self) -> Result<SectionKey, RoutingError> {
+        match *self {
+            State::Node(ref state) => Ok(state.section_key()),
+            _ => Err(RoutingError::InvalidStateForOperation),
+        }
+    }
+
+    fn cache_metrics(&self) -> Result<CacheMetrics, RoutingError> {
+        match *self {
+            State::Node(ref state) => Ok(state.cache_metrics()),
+            _ => Err(RoutingError::InvalidStateForOperation),
+        }
+    }
+
+    fn expiry_tracker(&self) -> Result<ExpiryTracker, RoutingError> {
+        match *self {
+            State::Bootstrapping(ref state) => Ok(state.expiry_tracker().clone()),
+            State::Node(ref state) => Ok(state.expiry_tracker().clone()),
+            _ => Err(RoutingError::InvalidStateForOperation),
+        }
+    }
+
     fn base_state(&self) -> Option<&Base> {
         match *self {
             State::Node(ref node) => Some(node),
@@ -166,6 +211,13 @@ impl State {
             state.set_next_node_name(relocation_name);
         }
     }
+
+    pub fn expire_data(&mut self, now: u64) -> Option<Event> {
+        match *self {
+            State::Node(ref mut state) => state.expire_data(now),
+            _ => None,
+        }
+    }
 }
 
 /// Enum returned from many message handlers
@@ -309,6 +361,33 @@ impl StateMachine {
         self.state.close_group(name, count)
     }
 
+    pub fn data_proof(&self,
+                      merkle_proof: MerkleProof,
+                      version: u64,
+                      root: MerkleHash)
+                      -> Result<DataProof, RoutingError> {
+        self.state.data_proof(merkle_proof, version, root)
+    }
+
+    pub fn validate_successor(&self,
+                              current: &Data,
+                              successor: &Data)
+                              -> Result<(), RoutingError> {
+        self.state.validate_successor(current, successor)
+    }
+
+    pub fn section_key(&self) -> Result<SectionKey, RoutingError> {
+        self.state.section_key()
+    }
+
+    pub fn cache_metrics(&self) -> Result<CacheMetrics, RoutingError> {
+        self.state.cache_metrics()
+    }
+
+    pub fn expiry_tracker(&self) -> Result<ExpiryTracker, RoutingError> {
+        self.state.expiry_tracker()
+    }
+
     #[cfg(feature = "use-mock-crust")]
     /// Get reference to the current state.
     pub fn current(&self) -> &State {
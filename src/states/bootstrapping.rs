@@ -17,8 +17,10 @@
 
 use action::Action;
 use cache::Cache;
+use compression::{Compression, PeerCompression};
 use crust::{CrustUser, PeerId, Service};
 use crust::Event as CrustEvent;
+use data::{DataValidators, ExpiryTracker};
 use error::RoutingError;
 use event::Event;
 use evented::{Evented, ToEvented};
@@ -48,16 +50,25 @@ pub struct Bootstrapping {
     bootstrap_connection: Option<(PeerId, u64)>,
     cache: Box<Cache>,
     client_restriction: bool,
+    compression: Compression,
     crust_service: Service,
+    data_validators: DataValidators,
+    expiry_tracker: ExpiryTracker,
     full_id: FullId,
     min_section_size: usize,
+    peer_bytes_per_sec: Option<usize>,
     stats: Stats,
     timer: Timer,
 }
 
 impl Bootstrapping {
+    #[cfg_attr(feature = "cargo-clippy", allow(too_many_arguments))]
     pub fn new(cache: Box<Cache>,
                client_restriction: bool,
+               compression: Compression,
+               peer_bytes_per_sec: Option<usize>,
+               data_validators: DataValidators,
+               expiry_tracker: ExpiryTracker,
                mut crust_service: Service,
                full_id: FullId,
                min_section_size: usize,
@@ -73,9 +84,13 @@ impl Bootstrapping {
             bootstrap_connection: None,
             cache: cache,
             client_restriction: client_restriction,
+            compression: compression,
             crust_service: crust_service,
+            data_validators: data_validators,
+            expiry_tracker: expiry_tracker,
             full_id: full_id,
             min_section_size: min_section_size,
+            peer_bytes_per_sec: peer_bytes_per_sec,
             stats: Stats::new(),
             timer: timer,
         })
@@ -84,12 +99,19 @@ impl Bootstrapping {
     pub fn handle_action(&mut self, action: Action) -> Evented<Transition> {
         match action {
             Action::ClientSendRequest { ref result_tx, .. } |
-            Action::NodeSendMessage { ref result_tx, .. } => {
+            Action::NodeSendMessage { ref result_tx, .. } |
+            Action::NodeSendBroadcast { ref result_tx, .. } |
+            Action::NodeSendStream { ref result_tx, .. } |
+            Action::NodeSignDataRoot { ref result_tx, .. } => {
                 warn!("{:?} Cannot handle {:?} - not bootstrapped", self, action);
                 // TODO: return Err here eventually. Returning Ok for now to
                 // preserve the pre-refactor behaviour.
                 let _ = result_tx.send(Ok(()));
             }
+            Action::NodeSetExpiry { data_id, expiry, result_tx } => {
+                self.expiry_tracker.set_expiry(data_id, expiry);
+                let _ = result_tx.send(Ok(()));
+            }
             Action::Name { result_tx } => {
                 let _ = result_tx.send(*self.name());
             }
@@ -140,7 +162,9 @@ impl Bootstrapping {
     }
 
     pub fn into_client(self, proxy_peer_id: PeerId, proxy_public_id: PublicId) -> Evented<Client> {
-        Client::from_bootstrapping(self.crust_service,
+        Client::from_bootstrapping(self.compression,
+                                   self.peer_bytes_per_sec,
+                                   self.crust_service,
                                    self.full_id,
                                    self.min_section_size,
                                    proxy_peer_id,
@@ -151,6 +175,10 @@ impl Bootstrapping {
 
     pub fn into_node(self, proxy_peer_id: PeerId, proxy_public_id: PublicId) -> Option<Node> {
         Node::from_bootstrapping(self.cache,
+                                 self.compression,
+                                 self.peer_bytes_per_sec,
+                                 self.data_validators,
+                                 self.expiry_tracker,
                                  self.crust_service,
                                  self.full_id,
                                  self.min_section_size,
@@ -160,6 +188,10 @@ impl Bootstrapping {
                                  self.timer)
     }
 
+    pub fn expiry_tracker(&self) -> &ExpiryTracker {
+        &self.expiry_tracker
+    }
+
     pub fn client_restriction(&self) -> bool {
         self.client_restriction
     }
@@ -271,6 +303,11 @@ impl Bootstrapping {
             client_restriction: self.client_restriction,
         };
 
+        self.stats().count_direct_message(&direct_message);
+        self.send_message(&peer_id, Message::Direct(direct_message))?;
+
+        let direct_message =
+            DirectMessage::Capabilities { compression: PeerCompression::supported() };
         self.stats().count_direct_message(&direct_message);
         self.send_message(&peer_id, Message::Direct(direct_message))
     }
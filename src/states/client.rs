@@ -17,6 +17,7 @@
 
 use ack_manager::{Ack, AckManager};
 use action::Action;
+use compression::{Compression, PeerCompression};
 use crust::{PeerId, Service};
 use crust::Event as CrustEvent;
 use error::{InterfaceError, RoutingError};
@@ -24,10 +25,12 @@ use event::Event;
 use evented::{Evented, ToEvented};
 use id::{FullId, PublicId};
 use maidsafe_utilities::serialisation;
-use messages::{HopMessage, Message, MessageContent, RoutingMessage, SignedMessage, UserMessage,
-               UserMessageCache};
+use messages::{DirectMessage, HopMessage, Message, MessageContent, RoutingMessage, SignedMessage,
+               UserMessage, UserMessageCache};
+use response_aggregator::{RESPONSE_AGGREGATION_TIMEOUT_SECS, ResponseAggregator};
 use routing_message_filter::{FilteringResult, RoutingMessageFilter};
 use routing_table::Authority;
+use send_queue::SendQueue;
 use state_machine::Transition;
 use stats::Stats;
 use std::collections::BTreeSet;
@@ -45,9 +48,15 @@ pub struct Client {
     crust_service: Service,
     full_id: FullId,
     min_section_size: usize,
+    /// The codecs our proxy supports, to compress the messages we send it.
+    peer_compression: PeerCompression,
     proxy_peer_id: PeerId,
     proxy_public_id: PublicId,
+    response_aggregator: ResponseAggregator,
+    /// The token of the timer scheduled to raise timed out aggregated responses, if any.
+    response_aggregator_timer_token: Option<u64>,
     routing_msg_filter: RoutingMessageFilter,
+    send_queue: SendQueue,
     stats: Stats,
     timer: Timer,
     user_msg_cache: UserMessageCache,
@@ -55,7 +64,9 @@ pub struct Client {
 
 impl Client {
     #[cfg_attr(feature = "cargo-clippy", allow(too_many_arguments))]
-    pub fn from_bootstrapping(crust_service: Service,
+    pub fn from_bootstrapping(compression: Compression,
+                              peer_bytes_per_sec: Option<usize>,
+                              crust_service: Service,
                               full_id: FullId,
                               min_section_size: usize,
                               proxy_peer_id: PeerId,
@@ -68,9 +79,13 @@ impl Client {
             crust_service: crust_service,
             full_id: full_id,
             min_section_size: min_section_size,
+            peer_compression: PeerCompression::new(compression),
             proxy_peer_id: proxy_peer_id,
             proxy_public_id: proxy_public_id,
+            response_aggregator: ResponseAggregator::new(),
+            response_aggregator_timer_token: None,
             routing_msg_filter: RoutingMessageFilter::new(),
+            send_queue: SendQueue::new(peer_bytes_per_sec),
             stats: stats,
             timer: timer,
             user_msg_cache: UserMessageCache::with_expiry_duration(
@@ -82,9 +97,10 @@ impl Client {
         Evented::single(Event::Connected, client)
     }
 
-    pub fn handle_action(&mut self, action: Action) -> Transition {
+    pub fn handle_action(&mut self, action: Action) -> Evented<Transition> {
+        let mut events = Evented::empty();
         match action {
-            Action::ClientSendRequest { content, dst, priority, result_tx } => {
+            Action::ClientSendRequest { content, dst, priority, recipient, result_tx } => {
                 let src = Authority::Client {
                     client_key: *self.full_id.public_id().signing_public_key(),
                     proxy_node_name: *self.proxy_public_id.name(),
@@ -92,26 +108,34 @@ impl Client {
                 };
 
                 let user_msg = UserMessage::Request(content);
-                let result = match self.send_user_message(src, dst, user_msg, priority) {
+                let result = match self.send_user_message(src,
+                                                          dst,
+                                                          user_msg,
+                                                          priority,
+                                                          recipient.as_ref()) {
                     Err(RoutingError::Interface(err)) => Err(err),
                     Err(_) | Ok(_) => Ok(()),
                 };
 
                 let _ = result_tx.send(result);
             }
-            Action::NodeSendMessage { result_tx, .. } => {
+            Action::NodeSendMessage { result_tx, .. } |
+            Action::NodeSendBroadcast { result_tx, .. } |
+            Action::NodeSendStream { result_tx, .. } |
+            Action::NodeSignDataRoot { result_tx, .. } |
+            Action::NodeSetExpiry { result_tx, .. } => {
                 let _ = result_tx.send(Err(InterfaceError::InvalidState));
             }
             Action::Name { result_tx } => {
                 let _ = result_tx.send(*self.name());
             }
-            Action::Timeout(token) => self.handle_timeout(token),
+            Action::Timeout(token) => events.add_events(self.handle_timeout(token)),
             Action::Terminate => {
-                return Transition::Terminate;
+                return events.with_value(Transition::Terminate);
             }
         }
 
-        Transition::Stay
+        events.with_value(Transition::Stay)
     }
 
     pub fn handle_crust_event(&mut self, crust_event: CrustEvent) -> Evented<Transition> {
@@ -130,21 +154,41 @@ impl Client {
         Transition::Stay.to_evented()
     }
 
-    fn handle_timeout(&mut self, token: u64) {
-        self.resend_unacknowledged_timed_out_msgs(token)
+    fn handle_timeout(&mut self, token: u64) -> Vec<Event> {
+        if self.send_queue.flush_timer_token() == Some(token) {
+            self.flush_send_queue();
+        } else if self.response_aggregator_timer_token == Some(token) {
+            self.response_aggregator_timer_token = None;
+            let events = self.response_aggregator.remove_expired();
+            self.schedule_response_aggregator_timeout();
+            return events;
+        } else {
+            self.resend_unacknowledged_timed_out_msgs(token)
+        }
+        vec![]
+    }
+
+    /// Schedules a timer to raise the aggregated responses that timed out, unless there are no
+    /// pending requests or a timer is already scheduled.
+    fn schedule_response_aggregator_timeout(&mut self) {
+        if self.response_aggregator.is_empty() || self.response_aggregator_timer_token.is_some() {
+            return;
+        }
+        let timeout = Duration::from_secs(RESPONSE_AGGREGATION_TIMEOUT_SECS);
+        self.response_aggregator_timer_token = Some(self.timer.schedule(timeout));
     }
 
     fn handle_new_message(&mut self, peer_id: PeerId, bytes: Vec<u8>) -> Evented<Transition> {
         let mut result = Evented::empty();
 
         let transition = match serialisation::deserialise(&bytes) {
-            Ok(Message::Hop(hop_msg)) => {
-                self.handle_hop_message(hop_msg, peer_id).extract(&mut result)
-            }
-            Ok(message) => {
-                debug!("{:?} - Unhandled new message: {:?}", self, message);
-                Ok(Transition::Stay)
+            Ok(Message::Compressed { compression, payload }) => {
+                match Message::decompress(compression, payload) {
+                    Ok((message, _)) => self.handle_message(message, peer_id).extract(&mut result),
+                    Err(error) => Err(error),
+                }
             }
+            Ok(message) => self.handle_message(message, peer_id).extract(&mut result),
             Err(error) => Err(RoutingError::SerialisationError(error)),
         };
 
@@ -158,6 +202,27 @@ impl Client {
         }
     }
 
+    fn handle_message(&mut self,
+                      message: Message,
+                      peer_id: PeerId)
+                      -> Evented<Result<Transition, RoutingError>> {
+        match message {
+            Message::Hop(hop_msg) => self.handle_hop_message(hop_msg, peer_id),
+            Message::Direct(DirectMessage::Capabilities { compression }) => {
+                if self.proxy_peer_id == peer_id {
+                    self.peer_compression.insert_peer(peer_id, compression);
+                    Ok(Transition::Stay).to_evented()
+                } else {
+                    Err(RoutingError::UnknownConnection(peer_id)).to_evented()
+                }
+            }
+            message => {
+                debug!("{:?} - Unhandled new message: {:?}", self, message);
+                Ok(Transition::Stay).to_evented()
+            }
+        }
+    }
+
     fn handle_hop_message(&mut self,
                           hop_msg: HopMessage,
                           peer_id: PeerId)
@@ -202,7 +267,12 @@ impl Client {
                 let transition = self.handle_ack_response(ack).extract(&mut result);
                 result.with_value(transition)
             }
-            MessageContent::UserMessagePart { hash, part_count, part_index, payload, .. } => {
+            MessageContent::UserMessagePart { hash,
+                                              part_count,
+                                              part_index,
+                                              encrypted,
+                                              payload,
+                                              .. } => {
                 trace!("{:?} Got UserMessagePart {:x}, {}/{} from {:?} to {:?}.",
                        self,
                        hash,
@@ -211,12 +281,35 @@ impl Client {
                        routing_msg.src,
                        routing_msg.dst);
                 let mut result = Evented::empty();
-                if let Some(msg) = self.user_msg_cache.add(hash, part_count, part_index, payload) {
+                let msg = {
+                    let secret_key = if encrypted {
+                        Some(self.full_id.encrypting_private_key())
+                    } else {
+                        None
+                    };
+                    self.user_msg_cache.add(hash, part_count, part_index, payload, secret_key)
+                };
+                if let Some(msg) = msg {
                     self.stats().count_user_message(&msg);
-                    result.add_event(msg.into_event(routing_msg.src, routing_msg.dst));
+                    let event = match msg {
+                        UserMessage::Response(response) => {
+                            match self.response_aggregator.add(&routing_msg.src, response) {
+                                Ok(event) => event,
+                                Err(response) => {
+                                    Some(UserMessage::Response(response)
+                                        .into_event(routing_msg.src, routing_msg.dst))
+                                }
+                            }
+                        }
+                        msg => Some(msg.into_event(routing_msg.src, routing_msg.dst)),
+                    };
+                    if let Some(event) = event {
+                        result.add_event(event);
+                    }
                 }
                 result.with_value(Transition::Stay)
             }
+            MessageContent::CacheInvalidation(_) => Transition::Stay.to_evented(),
             content => {
                 debug!("{:?} - Unhandled routing message: {:?} from {:?} to {:?}",
                        self,
@@ -233,11 +326,30 @@ impl Client {
                          src: Authority<XorName>,
                          dst: Authority<XorName>,
                          user_msg: UserMessage,
-                         priority: u8)
+                         priority: u8,
+                         recipient: Option<&PublicId>)
                          -> Result<(), RoutingError> {
+        let recipient_key = recipient.map(PublicId::encrypting_public_key);
+        let parts = user_msg.to_parts(priority, recipient_key)?;
+        let parts_len: usize = parts.iter()
+            .map(|part| match *part {
+                MessageContent::UserMessagePart { ref payload, .. } => payload.len(),
+                _ => 0,
+            })
+            .sum();
+        let delivery_dsts = dst.delivery_authorities();
+        if !self.send_queue.has_capacity(parts_len * delivery_dsts.len()) {
+            return Err(RoutingError::Interface(InterfaceError::SendQueueFull));
+        }
         self.stats.count_user_message(&user_msg);
-        for part in user_msg.to_parts(priority)? {
-            self.send_routing_message(src, dst, part)?;
+        if let UserMessage::Request(ref request) = user_msg {
+            self.response_aggregator.expect(request.message_id(), src, dst);
+            self.schedule_response_aggregator_timeout();
+        }
+        for delivery_dst in delivery_dsts {
+            for part in &parts {
+                self.send_routing_message(src, delivery_dst, part.clone())?;
+            }
         }
         Ok(())
     }
@@ -269,6 +381,9 @@ impl Base for Client {
 
         debug!("{:?} Received LostPeer - {:?}", self, peer_id);
 
+        self.send_queue.remove_peer(&peer_id);
+        self.peer_compression.remove_peer(&peer_id);
+
         if self.proxy_peer_id == peer_id {
             debug!("{:?} Lost bootstrap connection to {:?} ({:?}).",
                    self,
@@ -349,16 +464,25 @@ impl Bootstrapped for Client {
         if self.add_to_pending_acks(&signed_msg, route) &&
            !self.filter_outgoing_routing_msg(signed_msg.routing_message(), &proxy_peer_id, route) {
             let bytes = self.to_hop_bytes(signed_msg.clone(), route, BTreeSet::new())?;
-            self.send_or_drop(&proxy_peer_id, bytes, signed_msg.priority());
+            let bulk = signed_msg.routing_message().content.is_bulk();
+            self.send_or_queue(&proxy_peer_id, bytes, signed_msg.priority(), bulk)?;
         }
 
         Ok(())
     }
 
+    fn peer_compression(&self) -> &PeerCompression {
+        &self.peer_compression
+    }
+
     fn routing_msg_filter(&mut self) -> &mut RoutingMessageFilter {
         &mut self.routing_msg_filter
     }
 
+    fn send_queue(&mut self) -> &mut SendQueue {
+        &mut self.send_queue
+    }
+
     fn timer(&mut self) -> &mut Timer {
         &mut self.timer
     }
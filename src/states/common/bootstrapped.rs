@@ -16,12 +16,14 @@
 // relating to use of the SAFE Network Software.
 
 use ack_manager::{ACK_TIMEOUT_SECS, Ack, AckManager, UnacknowledgedMessage};
+use compression::{self, Compression, PeerCompression};
 use crust::PeerId;
 use error::RoutingError;
 use maidsafe_utilities::serialisation;
 use messages::{HopMessage, Message, MessageContent, RoutingMessage, SignedMessage};
 use routing_message_filter::RoutingMessageFilter;
 use routing_table::Authority;
+use send_queue::{SEND_QUEUE_FLUSH_INTERVAL_MS, SendQueue};
 use std::collections::BTreeSet;
 use std::time::Duration;
 use super::Base;
@@ -40,7 +42,9 @@ pub trait Bootstrapped: Base {
                                       route: u8)
                                       -> Result<(), RoutingError>;
 
+    fn peer_compression(&self) -> &PeerCompression;
     fn routing_msg_filter(&mut self) -> &mut RoutingMessageFilter;
+    fn send_queue(&mut self) -> &mut SendQueue;
     fn timer(&mut self) -> &mut Timer;
 
     /// Examines a message, and possibly adds a pending ack. Returns true unless
@@ -98,6 +102,87 @@ pub trait Bootstrapped: Base {
         false
     }
 
+    /// Sends the given `bytes` to the peer, compressed if the peer supports it. If they are bulk
+    /// user data, they are queued instead while the peer's byte budget is exhausted, and rejected
+    /// with `SendQueueFull` if the peer's queue is full.
+    fn send_or_queue(&mut self,
+                     peer_id: &PeerId,
+                     bytes: Vec<u8>,
+                     priority: u8,
+                     bulk: bool)
+                     -> Result<(), RoutingError> {
+        let bytes = self.compress_for(peer_id, bytes);
+        if !bulk {
+            self.send_or_drop(peer_id, bytes, priority);
+            return Ok(());
+        }
+        if let Some(bytes) = self.send_queue().push(*peer_id, bytes, priority)? {
+            self.send_or_drop(peer_id, bytes, priority);
+        }
+        self.schedule_send_queue_flush();
+        Ok(())
+    }
+
+    /// Wraps the serialised message `bytes` in a `Message::Compressed` if the peer supports our
+    /// codec and compressing makes the message smaller. Otherwise returns `bytes` unchanged.
+    fn compress_for(&mut self, peer_id: &PeerId, bytes: Vec<u8>) -> Vec<u8> {
+        let compression = self.peer_compression().codec_for(peer_id);
+        if compression == Compression::None {
+            return bytes;
+        }
+        let uncompressed_len = bytes.len();
+        let message = match compression::compress(compression, bytes.clone()) {
+            Ok((Compression::None, _)) => {
+                self.stats().count_compression(uncompressed_len, uncompressed_len);
+                return bytes;
+            }
+            Ok((compression, payload)) => {
+                Message::Compressed {
+                    compression: compression,
+                    payload: payload,
+                }
+            }
+            Err(error) => {
+                debug!("{:?} Failed to compress message to {:?}: {:?}",
+                       self,
+                       peer_id,
+                       error);
+                return bytes;
+            }
+        };
+        match serialisation::serialise(&message) {
+            Ok(compressed) => {
+                self.stats().count_compression(uncompressed_len, compressed.len());
+                compressed
+            }
+            Err(error) => {
+                debug!("{:?} Failed to serialise compressed message to {:?}: {:?}",
+                       self,
+                       peer_id,
+                       error);
+                bytes
+            }
+        }
+    }
+
+    /// Sends the queued messages that fit into the peers' byte budgets by now.
+    fn flush_send_queue(&mut self) {
+        self.send_queue().set_flush_timer_token(None);
+        let msgs = self.send_queue().flush();
+        for (peer_id, bytes, priority) in msgs {
+            self.send_or_drop(&peer_id, bytes, priority);
+        }
+        self.schedule_send_queue_flush();
+    }
+
+    fn schedule_send_queue_flush(&mut self) {
+        if self.send_queue().is_empty() || self.send_queue().flush_timer_token().is_some() {
+            return;
+        }
+        let token = self.timer().schedule(Duration::from_millis(SEND_QUEUE_FLUSH_INTERVAL_MS));
+        self.send_queue().set_flush_timer_token(Some(token));
+    }
+
     fn resend_unacknowledged_timed_out_msgs(&mut self, token: u64) {
         if let Some((unacked_msg, ack)) = self.ack_mgr_mut().find_timed_out(token) {
             trace!("{:?} Timed out waiting for {:?}: {:?}",
@@ -18,9 +18,13 @@
 use ack_manager::{ACK_TIMEOUT_SECS, Ack, AckManager};
 use action::Action;
 use cache::Cache;
+use client_errors::MutationError;
+use compression::{Compression, PeerCompression};
 use crust::{ConnectionInfoResult, CrustError, PeerId, PrivConnectionInfo, PubConnectionInfo,
             Service};
 use crust::Event as CrustEvent;
+use data::{self, Data, DataProof, DataValidators, ExpiryTracker, MerkleHash, MerkleProof,
+           SectionKey, ValidationError};
 use error::{InterfaceError, RoutingError};
 use event::Event;
 use evented::{Evented, ToEvented};
@@ -28,12 +32,15 @@ use id::{FullId, PublicId};
 use itertools::Itertools;
 use log::LogLevel;
 use maidsafe_utilities::serialisation;
-use messages::{DEFAULT_PRIORITY, DirectMessage, HopMessage, MAX_PART_LEN, Message, MessageContent,
-               RoutingMessage, SectionList, SignedMessage, UserMessage, UserMessageCache};
+use messages::{BatchItemResult, DEFAULT_PRIORITY, DirectMessage, HopMessage, MAX_BROADCAST_LEN,
+               MAX_PART_LEN, Message, MessageContent, Request, Response, RoutingMessage,
+               SectionList, SignedBroadcast, SignedMessage, UserMessage, UserMessageCache};
 use peer_manager::{ConnectionInfoPreparedResult, PeerManager, PeerState,
                    RESOURCE_PROOF_DURATION_SECS, SectionMap};
 use rand::{self, Rng};
+use rate_limiter::RateLimiter;
 use resource_proof::ResourceProof;
+use response_aggregator::ResponseAggregator;
 use routing_message_filter::{FilteringResult, RoutingMessageFilter};
 use routing_table::{Authority, OtherMergeDetails, OwnMergeState, Prefix, RemovalDetails, Xorable};
 use routing_table::Error as RoutingTableError;
@@ -42,21 +49,21 @@ use routing_table::RoutingTable;
 use rust_sodium::crypto::{box_, sign};
 use rust_sodium::crypto::hash::sha256;
 use section_list_cache::SectionListCache;
+use send_queue::SendQueue;
 use signature_accumulator::{ACCUMULATION_TIMEOUT_SECS, SignatureAccumulator};
 use state_machine::Transition;
-use stats::Stats;
+use stats::{CacheMetrics, Stats};
 use std::{cmp, fmt, iter, mem};
-use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
-#[cfg(feature = "use-mock-crust")]
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::fmt::{Debug, Formatter};
 use std::time::{Duration, Instant};
+use stream::StreamManager;
 use super::common::{Base, Bootstrapped, USER_MSG_CACHE_EXPIRY_DURATION_SECS};
 use timer::Timer;
 use tunnels::Tunnels;
 use types::MessageId;
 use utils;
-use xor_name::XorName;
+use xor_name::{XOR_NAME_BITS, XorName};
 
 /// Time (in seconds) after which a `Tick` event is sent.
 const TICK_TIMEOUT_SECS: u64 = 60;
@@ -79,11 +86,24 @@ const APPROVAL_TIMEOUT_SECS: u64 = RESOURCE_PROOF_DURATION_SECS + ACCUMULATION_T
 const APPROVAL_PROGRESS_INTERVAL_SECS: u64 = 30;
 /// Interval between displaying info about current candidate, in seconds.
 const CANDIDATE_STATUS_INTERVAL_SECS: u64 = 60;
+/// Maximum number of broadcasts we accept from a single origin per `BROADCAST_RATE_WINDOW_SECS`.
+const MAX_BROADCASTS_PER_WINDOW: usize = 10;
+const BROADCAST_RATE_WINDOW_SECS: u64 = 60;
+/// Maximum number of broadcasts we accept forwarded by a single node per
+/// `BROADCAST_RATE_WINDOW_SECS`.
+const MAX_FORWARDED_BROADCASTS_PER_WINDOW: usize = 100;
+/// The number of members of a section that forward each broadcast to a sibling section.
+const BROADCAST_FORWARDERS: usize = 3;
 
 pub struct Node {
     ack_mgr: AckManager,
+    broadcast_limiter: RateLimiter<XorName>,
+    broadcast_forwarder_limiter: RateLimiter<XorName>,
     cacheable_user_msg_cache: UserMessageCache,
     crust_service: Service,
+    data_validators: DataValidators,
+    /// The expiry times of the data items held by the upper layer.
+    expiry_tracker: ExpiryTracker,
     full_id: FullId,
     get_approval_timer_token: Option<u64>,
     approval_progress_timer_token: Option<u64>,
@@ -93,12 +113,19 @@ pub struct Node {
     /// The queue of routing messages addressed to us. These do not themselves need
     /// forwarding, although they may wrap a message which needs forwarding.
     msg_queue: VecDeque<RoutingMessage>,
+    /// The codecs our peers support, to compress the messages we send them.
+    peer_compression: PeerCompression,
     peer_mgr: PeerManager,
     response_cache: Box<Cache>,
     routing_msg_filter: RoutingMessageFilter,
     sig_accumulator: SignatureAccumulator,
     section_list_sigs: SectionListCache,
+    /// The latest signed version and Merkle root of the held data of each member of our section.
+    data_root_sigs: HashMap<PublicId, (Prefix<XorName>, u64, MerkleHash, sign::Signature)>,
+    send_queue: SendQueue,
     stats: Stats,
+    stream_mgr: StreamManager,
+    response_aggregator: ResponseAggregator,
     tick_timer_token: u64,
     timer: Timer,
     tunnels: Tunnels,
@@ -128,7 +155,12 @@ pub struct Node {
 }
 
 impl Node {
+    #[cfg_attr(feature = "cargo-clippy", allow(too_many_arguments))]
     pub fn first(cache: Box<Cache>,
+                 compression: Compression,
+                 peer_bytes_per_sec: Option<usize>,
+                 data_validators: DataValidators,
+                 expiry_tracker: ExpiryTracker,
                  crust_service: Service,
                  mut full_id: FullId,
                  min_section_size: usize,
@@ -138,6 +170,10 @@ impl Node {
         full_id.public_id_mut().set_name(name);
 
         let mut node = Self::new(cache,
+                                 compression,
+                                 peer_bytes_per_sec,
+                                 data_validators,
+                                 expiry_tracker,
                                  crust_service,
                                  true,
                                  full_id,
@@ -156,6 +192,10 @@ impl Node {
 
     #[cfg_attr(feature = "cargo-clippy", allow(too_many_arguments))]
     pub fn from_bootstrapping(cache: Box<Cache>,
+                              compression: Compression,
+                              peer_bytes_per_sec: Option<usize>,
+                              data_validators: DataValidators,
+                              expiry_tracker: ExpiryTracker,
                               crust_service: Service,
                               full_id: FullId,
                               min_section_size: usize,
@@ -165,6 +205,10 @@ impl Node {
                               timer: Timer)
                               -> Option<Self> {
         let mut node = Self::new(cache,
+                                 compression,
+                                 peer_bytes_per_sec,
+                                 data_validators,
+                                 expiry_tracker,
                                  crust_service,
                                  false,
                                  full_id,
@@ -184,6 +228,10 @@ impl Node {
 
     #[cfg_attr(feature = "cargo-clippy", allow(too_many_arguments))]
     fn new(cache: Box<Cache>,
+           compression: Compression,
+           peer_bytes_per_sec: Option<usize>,
+           data_validators: DataValidators,
+           expiry_tracker: ExpiryTracker,
            crust_service: Service,
            first_node: bool,
            full_id: FullId,
@@ -197,9 +245,17 @@ impl Node {
         let user_msg_cache_duration = Duration::from_secs(USER_MSG_CACHE_EXPIRY_DURATION_SECS);
         Node {
             ack_mgr: AckManager::new(),
+            broadcast_limiter:
+                RateLimiter::new(MAX_BROADCASTS_PER_WINDOW,
+                                 Duration::from_secs(BROADCAST_RATE_WINDOW_SECS)),
+            broadcast_forwarder_limiter:
+                RateLimiter::new(MAX_FORWARDED_BROADCASTS_PER_WINDOW,
+                                 Duration::from_secs(BROADCAST_RATE_WINDOW_SECS)),
             cacheable_user_msg_cache:
                 UserMessageCache::with_expiry_duration(user_msg_cache_duration),
             crust_service: crust_service,
+            data_validators: data_validators,
+            expiry_tracker: expiry_tracker,
             full_id: full_id,
             get_approval_timer_token: None,
             approval_progress_timer_token: None,
@@ -207,12 +263,17 @@ impl Node {
             is_first_node: first_node,
             is_approved: first_node,
             msg_queue: VecDeque::new(),
+            peer_compression: PeerCompression::new(compression),
             peer_mgr: PeerManager::new(min_section_size, public_id),
             response_cache: cache,
             routing_msg_filter: RoutingMessageFilter::new(),
             sig_accumulator: Default::default(),
             section_list_sigs: SectionListCache::new(),
+            data_root_sigs: HashMap::new(),
+            send_queue: SendQueue::new(peer_bytes_per_sec),
             stats: stats,
+            stream_mgr: StreamManager::new(),
+            response_aggregator: ResponseAggregator::new(),
             tick_timer_token: tick_timer_token,
             timer: timer,
             tunnels: Default::default(),
@@ -312,14 +373,46 @@ impl Node {
             Action::ClientSendRequest { result_tx, .. } => {
                 let _ = result_tx.send(Err(InterfaceError::InvalidState));
             }
-            Action::NodeSendMessage { src, dst, content, priority, result_tx } => {
-                let result = match self.send_user_message(src, dst, content, priority) {
+            Action::NodeSendMessage { src, dst, content, priority, recipient, result_tx } => {
+                let result = match self.send_user_message(src,
+                                                          dst,
+                                                          content,
+                                                          priority,
+                                                          recipient.as_ref()) {
                     Err(RoutingError::Interface(err)) => Err(err),
                     Err(_) | Ok(()) => Ok(()),
                 };
 
                 let _ = result_tx.send(result);
             }
+            Action::NodeSendBroadcast { content, result_tx } => {
+                let result = match self.send_broadcast(content) {
+                    Err(RoutingError::Interface(err)) => Err(err),
+                    Err(_) | Ok(()) => Ok(()),
+                };
+
+                let _ = result_tx.send(result);
+            }
+            Action::NodeSendStream { dst, stream_id, content, result_tx } => {
+                let result = match self.send_stream(dst, stream_id, content) {
+                    Err(RoutingError::Interface(err)) => Err(err),
+                    Err(_) | Ok(()) => Ok(()),
+                };
+
+                let _ = result_tx.send(result);
+            }
+            Action::NodeSignDataRoot { version, root, result_tx } => {
+                let result = match self.sign_data_root(version, root) {
+                    Err(RoutingError::Interface(err)) => Err(err),
+                    Err(_) | Ok(()) => Ok(()),
+                };
+
+                let _ = result_tx.send(result);
+            }
+            Action::NodeSetExpiry { data_id, expiry, result_tx } => {
+                self.expiry_tracker.set_expiry(data_id, expiry);
+                let _ = result_tx.send(Ok(()));
+            }
             Action::Name { result_tx } => {
                 let _ = result_tx.send(*self.name());
             }
@@ -487,9 +580,27 @@ impl Node {
                           bytes: Vec<u8>)
                           -> Evented<Result<(), RoutingError>> {
         match serialisation::deserialise(&bytes) {
-            Ok(Message::Hop(hop_msg)) => self.handle_hop_message(hop_msg, peer_id).to_evented(),
-            Ok(Message::Direct(direct_msg)) => self.handle_direct_message(direct_msg, peer_id),
-            Ok(Message::TunnelDirect { content, src, dst }) => {
+            Ok(Message::Compressed { compression, payload }) => {
+                match Message::decompress(compression, payload) {
+                    Ok((message, bytes)) => self.handle_message(peer_id, message, bytes),
+                    Err(error) => Err(error).to_evented(),
+                }
+            }
+            Ok(message) => self.handle_message(peer_id, message, bytes),
+            Err(error) => Err(RoutingError::SerialisationError(error)).to_evented(),
+        }
+    }
+
+    // Handles a message received from `peer_id`, whose serialised form is `bytes`.
+    fn handle_message(&mut self,
+                      peer_id: PeerId,
+                      message: Message,
+                      bytes: Vec<u8>)
+                      -> Evented<Result<(), RoutingError>> {
+        match message {
+            Message::Hop(hop_msg) => self.handle_hop_message(hop_msg, peer_id).to_evented(),
+            Message::Direct(direct_msg) => self.handle_direct_message(direct_msg, peer_id),
+            Message::TunnelDirect { content, src, dst } => {
                 if dst == self.crust_service.id() &&
                    self.tunnels.tunnel_for(&src) == Some(&peer_id) {
                     self.handle_direct_message(content, src)
@@ -512,13 +623,13 @@ impl Node {
                     Err(RoutingError::InvalidDestination).to_evented()
                 }
             }
-            Ok(Message::TunnelHop { content, src, dst }) => {
+            Message::TunnelHop { content, src, dst } => {
                 if dst == self.crust_service.id() &&
                    self.tunnels.tunnel_for(&src) == Some(&peer_id) {
                     self.handle_hop_message(content, src).to_evented()
                 } else if self.tunnels.has_clients(src, dst) {
-                    self.send_or_drop(&dst, bytes, content.content.priority());
-                    Ok(()).to_evented()
+                    let bulk = content.content.routing_message().content.is_bulk();
+                    self.send_or_queue(&dst, bytes, content.content.priority(), bulk).to_evented()
                 } else {
                     debug!("{:?} Invalid TunnelHop message received via {:?}: {:?} -> {:?} {:?}",
                            self,
@@ -529,7 +640,7 @@ impl Node {
                     Err(RoutingError::InvalidDestination).to_evented()
                 }
             }
-            Err(error) => Err(RoutingError::SerialisationError(error)).to_evented(),
+            Message::Compressed { .. } => Err(RoutingError::NestedCompression).to_evented(),
         }
     }
 
@@ -545,6 +656,9 @@ impl Node {
             SectionListSignature(section_list, sig) => {
                 self.handle_section_list_signature(peer_id, section_list, sig).to_evented()
             }
+            DataRootSignature(prefix, version, root, sig) => {
+                self.handle_data_root_signature(peer_id, prefix, version, root, sig).to_evented()
+            }
             ClientIdentify { ref serialised_public_id, ref signature, client_restriction } => {
                 if let Ok(public_id) = verify_signed_public_id(serialised_public_id, signature) {
                     self.handle_client_identify(public_id, peer_id, client_restriction).to_evented()
@@ -600,6 +714,10 @@ impl Node {
                                                     leading_zero_bytes);
                 Ok(()).to_evented()
             }
+            Capabilities { compression } => {
+                self.peer_compression.insert_peer(peer_id, compression);
+                Ok(()).to_evented()
+            }
             msg @ BootstrapIdentify { .. } |
             msg @ BootstrapDeny => {
                 debug!("{:?} Unhandled direct message: {:?}", self, msg);
@@ -723,6 +841,120 @@ impl Node {
         }
     }
 
+    /// Signs the version and Merkle root of the data we hold as `NaeManager` and sends the
+    /// signature to our section, replacing our previous one.
+    fn sign_data_root(&mut self, version: u64, root: MerkleHash) -> Result<(), RoutingError> {
+        let prefix = *self.peer_mgr.routing_table().our_prefix();
+        let serialised = data::root_signed_bytes(&prefix, version, &root)?;
+        let sig = sign::sign_detached(&serialised, self.full_id.signing_private_key());
+        let _ = self.data_root_sigs
+            .insert(*self.full_id.public_id(), (prefix, version, root, sig));
+
+        let peers = self.peer_mgr
+            .routing_table()
+            .our_section()
+            .into_iter()
+            .filter(|&x| *x != *self.name())
+            .filter_map(|x| self.peer_mgr.get_peer_id(x))
+            .cloned()
+            .collect_vec();
+        for peer_id in peers {
+            let msg = DirectMessage::DataRootSignature(prefix, version, root, sig);
+            if let Err(error) = self.send_direct_message(peer_id, msg) {
+                warn!("{:?} Error sending data root signature to {:?}: {:?}",
+                      self,
+                      peer_id,
+                      error);
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_data_root_signature(&mut self,
+                                  peer_id: PeerId,
+                                  prefix: Prefix<XorName>,
+                                  version: u64,
+                                  root: MerkleHash,
+                                  sig: sign::Signature)
+                                  -> Result<(), RoutingError> {
+        let src_pub_id =
+            *self.peer_mgr.get_routing_peer(&peer_id).ok_or(RoutingError::InvalidSource)?;
+        if prefix != *self.peer_mgr.routing_table().our_prefix() ||
+           !self.peer_mgr.routing_table().our_section().contains(src_pub_id.name()) {
+            return Err(RoutingError::InvalidSource);
+        }
+        let serialised = data::root_signed_bytes(&prefix, version, &root)?;
+        if !sign::verify_detached(&sig, &serialised, src_pub_id.signing_public_key()) {
+            return Err(RoutingError::FailedSignature);
+        }
+        let _ = self.data_root_sigs.insert(src_pub_id, (prefix, version, root, sig));
+        Ok(())
+    }
+
+    /// Returns the counts of the response cache usage.
+    pub fn cache_metrics(&self) -> CacheMetrics {
+        self.stats.cache_metrics()
+    }
+
+    /// Returns the expiry times we are tracking.
+    pub fn expiry_tracker(&self) -> &ExpiryTracker {
+        &self.expiry_tracker
+    }
+
+    /// Stops tracking the data that has expired at time `now`, and returns the event to notify
+    /// the upper layer of it, if any.
+    fn remove_expired_data(&mut self, now: u64) -> Option<Event> {
+        let expired = self.expiry_tracker.remove_expired(now);
+        if expired.is_empty() {
+            None
+        } else {
+            Some(Event::DataExpired(expired))
+        }
+    }
+
+    /// Validates `successor` as the new version of `current` using our registered validators.
+    pub fn validate_successor(&self,
+                              current: &Data,
+                              successor: &Data)
+                              -> Result<(), ValidationError> {
+        self.data_validators.validate_successor(current, successor)
+    }
+
+    /// Returns the key clients can use to verify the data proofs of our section: its prefix and
+    /// its members' public IDs.
+    pub fn section_key(&self) -> SectionKey {
+        let prefix = *self.peer_mgr.routing_table().our_prefix();
+        SectionKey::new(prefix,
+                        self.peer_mgr.get_pub_ids(self.peer_mgr.routing_table().our_section()))
+    }
+
+    /// Returns a proof that the data with the given `merkle_proof` is part of the given `version`
+    /// of the data our section holds, if every member of our section has signed `root` for it.
+    ///
+    /// Only signatures the whole section agrees on are included, so that all members build the
+    /// same proof and their responses accumulate.
+    pub fn data_proof(&self,
+                      merkle_proof: MerkleProof,
+                      version: u64,
+                      root: MerkleHash)
+                      -> Result<DataProof, RoutingError> {
+        let section_key = self.section_key();
+        let mut root_sigs = BTreeMap::new();
+        for pub_id in section_key.members() {
+            match self.data_root_sigs.get(pub_id) {
+                Some(&(sig_prefix, sig_version, sig_root, sig))
+                    if sig_prefix == *section_key.prefix() && sig_version == version &&
+                       sig_root == root => {
+                    let _ = root_sigs.insert(*pub_id, sig);
+                }
+                _ => return Err(RoutingError::NotEnoughSignatures),
+            }
+        }
+        let proof = DataProof::new(*section_key.prefix(), version, merkle_proof, root, root_sigs);
+        proof.verify_signatures(&section_key)?;
+        Ok(proof)
+    }
+
     fn handle_hop_message(&mut self,
                           hop_msg: HopMessage,
                           peer_id: PeerId)
@@ -823,7 +1055,8 @@ impl Node {
                 ConnectionInfoRequest { .. } |
                 SectionUpdate { .. } |
                 RoutingTableRequest(..) |
-                RoutingTableResponse { .. } => {
+                RoutingTableResponse { .. } |
+                Broadcast { .. } => {
                     trace!("{:?} Not approved yet. Delaying message handling: {:?}",
                            self,
                            routing_msg);
@@ -915,14 +1148,59 @@ impl Node {
             (OtherSectionMerge(section), PrefixSection(merge_prefix), PrefixSection(_)) => {
                 self.handle_other_section_merge(merge_prefix, section)
             }
-            (Ack(ack, _), _, _) => self.handle_ack_response(ack).to_evented(),
-            (UserMessagePart { hash, part_count, part_index, payload, .. }, src, dst) => {
-                if let Some(msg) = self.user_msg_cache.add(hash, part_count, part_index, payload) {
-                    self.stats().count_user_message(&msg);
-                    Evented::single(msg.into_event(src, dst), Ok(()))
-                } else {
-                    Ok(()).to_evented()
-                }
+            (Ack(ack, _), _, _) => self.handle_ack_response(ack),
+            (Broadcast { broadcast, depth }, ManagedNode(src_name), Section(_)) => {
+                self.handle_broadcast(broadcast, depth, src_name)
+            }
+            (StreamChunk { stream_id, chunk_count, index, payload, .. },
+             ManagedNode(src_name),
+             ManagedNode(_)) => {
+                let mut result = Evented::empty();
+                result.add_events(self.stream_mgr
+                    .receive(src_name, stream_id, chunk_count, index, payload));
+                result.with_value(Ok(()))
+            }
+            (CacheInvalidation(data_id), _, _) => {
+                self.response_cache.invalidate(&data_id);
+                Ok(()).to_evented()
+            }
+            (UserMessagePart { hash, part_count, part_index, encrypted, payload, .. },
+             src,
+             dst) => {
+                let msg = {
+                    let secret_key = if encrypted {
+                        Some(self.full_id.encrypting_private_key())
+                    } else {
+                        None
+                    };
+                    match self.user_msg_cache
+                        .add(hash, part_count, part_index, payload, secret_key) {
+                        Some(msg) => msg,
+                        None => return Ok(()).to_evented(),
+                    }
+                };
+                self.stats().count_user_message(&msg);
+                // Responses to requests sent to multiple sections are raised together.
+                let event = match msg {
+                    UserMessage::Response(response) => {
+                        match self.response_aggregator.add(&src, response) {
+                            Ok(Some(event)) => event,
+                            Ok(None) => return Ok(()).to_evented(),
+                            Err(response) => UserMessage::Response(response).into_event(src, dst),
+                        }
+                    }
+                    UserMessage::Request(request) => {
+                        if let Some(response) = self.validate_request(&request) {
+                            let user_msg = UserMessage::Response(response);
+                            let _ =
+                                self.send_user_message(dst, src, user_msg, DEFAULT_PRIORITY, None);
+                            return Ok(()).to_evented();
+                        }
+                        UserMessage::Request(request).into_event(src, dst)
+                    }
+                    msg => msg.into_event(src, dst),
+                };
+                Evented::single(event, Ok(()))
             }
             (content, src, dst) => {
                 debug!("{:?} Unhandled routing message {:?} from {:?} to {:?}",
@@ -935,6 +1213,79 @@ impl Node {
         }
     }
 
+    /// Checks the data carried by a `Put`, `Post`, `Delete` or `PutBatch` request, or their
+    /// conditional variants, against the registered data validators. Returns the failure response
+    /// to send back if it is rejected.
+    fn validate_request(&self, request: &Request) -> Option<Response> {
+        let (data, id) = match *request {
+            Request::Put(ref data, id) |
+            Request::Post(ref data, id) |
+            Request::Delete(ref data, id) |
+            Request::ConditionalPost(ref data, _, id) |
+            Request::ConditionalDelete(ref data, _, id) => (data, id),
+            Request::PutBatch(ref items, id) => return self.validate_batch(items, id),
+            _ => return None,
+        };
+        let error = match self.data_validators.validate(data) {
+            Ok(()) => return None,
+            Err(error) => error,
+        };
+        debug!("{:?} Rejecting {:?}: {}", self, request, error);
+        let data_id = data.identifier();
+        let external_error_indicator = serialisation::serialise(&MutationError::from(error))
+            .unwrap_or_else(|_| vec![]);
+        Some(match *request {
+            Request::Put(..) => {
+                Response::PutFailure {
+                    id: id,
+                    data_id: data_id,
+                    external_error_indicator: external_error_indicator,
+                }
+            }
+            Request::Post(..) |
+            Request::ConditionalPost(..) => {
+                Response::PostFailure {
+                    id: id,
+                    data_id: data_id,
+                    external_error_indicator: external_error_indicator,
+                }
+            }
+            _ => {
+                Response::DeleteFailure {
+                    id: id,
+                    data_id: data_id,
+                    external_error_indicator: external_error_indicator,
+                }
+            }
+        })
+    }
+
+    /// Rejects the whole batch if any of its items is invalid, so it is never partially delivered.
+    fn validate_batch(&self, items: &[Data], id: MessageId) -> Option<Response> {
+        let errors: Vec<_> = items.iter().map(|data| self.data_validators.validate(data)).collect();
+        if errors.iter().all(Result::is_ok) {
+            return None;
+        }
+        debug!("{:?} Rejecting PutBatch({:?}): invalid items", self, id);
+        let results = items.iter()
+            .zip(errors)
+            .map(|(data, result)| {
+                let error = result.err().unwrap_or_else(|| {
+                    ValidationError::Rejected("another item in the batch is invalid".to_owned())
+                });
+                BatchItemResult::Failure {
+                    data_id: data.identifier(),
+                    external_error_indicator: serialisation::serialise(&MutationError::from(error))
+                        .unwrap_or_else(|_| vec![]),
+                }
+            })
+            .collect();
+        Some(Response::PutBatchResponse {
+            id: id,
+            results: results,
+        })
+    }
+
     fn handle_candidate_approval(&mut self,
                                  candidate_id: PublicId,
                                  client_auth: Authority<XorName>,
@@ -1194,24 +1545,137 @@ impl Node {
         }
     }
 
+    /// Delivers the broadcast unless we have seen it before, and forwards it to one section in
+    /// each sibling subtree of our prefix we are responsible for, i.e. at positions `depth` and
+    /// higher.
+    ///
+    /// A broadcast with depth `0` must come directly from its origin, which must be a member of
+    /// our section. A broadcast with a higher depth must come from a node in our routing table
+    /// which is in the sibling subtree at position `depth - 1`, i.e. the one that forwards to us
+    /// at that depth. Besides per origin, broadcasts are also rate limited per forwarding node,
+    /// so that a forwarder can't get around the limit by claiming fresh origins.
+    fn handle_broadcast(&mut self,
+                        broadcast: SignedBroadcast,
+                        depth: u16,
+                        src_name: XorName)
+                        -> Evented<Result<(), RoutingError>> {
+        if !broadcast.verify() {
+            debug!("{:?} Invalid broadcast {:?}.", self, broadcast);
+            return Err(RoutingError::FailedSignature).to_evented();
+        }
+        if depth == 0 &&
+           (src_name != *broadcast.origin().name() || !self.is_section_member(broadcast.origin())) {
+            debug!("{:?} Broadcast {:?} not sent by its origin {:?}.",
+                   self,
+                   broadcast,
+                   src_name);
+            return Err(RoutingError::InvalidSource).to_evented();
+        }
+        if depth > 0 && !self.is_expected_broadcast_forwarder(depth as usize, &src_name) {
+            debug!("{:?} Broadcast {:?} with depth {} forwarded by unexpected node {:?}.",
+                   self,
+                   broadcast,
+                   depth,
+                   src_name);
+            return Err(RoutingError::InvalidSource).to_evented();
+        }
+        if self.routing_msg_filter.filter_broadcast(broadcast.origin(), broadcast.msg_id()) {
+            return Ok(()).to_evented();
+        }
+        if depth > 0 && !self.broadcast_forwarder_limiter.allow(src_name) {
+            debug!("{:?} Too many broadcasts forwarded by {:?}. Dropping {:?}.",
+                   self,
+                   src_name,
+                   broadcast);
+            return Ok(()).to_evented();
+        }
+        let origin = *broadcast.origin().name();
+        if origin != *self.name() && !self.broadcast_limiter.allow(origin) {
+            debug!("{:?} Too many broadcasts from {:?}. Dropping {:?}.",
+                   self,
+                   origin,
+                   broadcast);
+            return Ok(()).to_evented();
+        }
+
+        let our_prefix = *self.peer_mgr.routing_table().our_prefix();
+        let src = Authority::ManagedNode(*self.name());
+        for bit_count in depth as usize..our_prefix.bit_count() {
+            let sibling = Prefix::new(bit_count + 1, *self.name()).with_flipped_bit(bit_count);
+            if !self.is_broadcast_forwarder(&sibling.lower_bound()) {
+                continue;
+            }
+            let content = MessageContent::Broadcast {
+                broadcast: broadcast.clone(),
+                depth: sibling.bit_count() as u16,
+            };
+            let dst = Authority::Section(sibling.lower_bound());
+            if let Err(error) = self.send_routing_message(src, dst, content) {
+                debug!("{:?} Failed to forward broadcast to {:?}: {:?}", self, sibling, error);
+            }
+        }
+
+        Evented::single(broadcast.into_event(), Ok(()))
+    }
+
+    /// Returns whether `pub_id` is us or a member of our section.
+    fn is_section_member(&self, pub_id: &PublicId) -> bool {
+        if !self.peer_mgr.routing_table().is_in_our_section(pub_id.name()) {
+            return false;
+        }
+        let names = iter::once(*pub_id.name()).collect();
+        self.peer_mgr.get_pub_ids(&names).contains(pub_id)
+    }
+
+    /// Returns whether `name` is in our routing table and shares the first `depth - 1` bits with
+    /// us but not bit `depth - 1`, i.e. whether it is in the section that forwards broadcasts
+    /// with the given depth to us.
+    fn is_expected_broadcast_forwarder(&self, depth: usize, name: &XorName) -> bool {
+        if depth > XOR_NAME_BITS || !self.peer_mgr.routing_table().has(name) {
+            return false;
+        }
+        Prefix::new(depth, *self.name()).with_flipped_bit(depth - 1).matches(name)
+    }
+
+    /// Returns whether we are one of the `BROADCAST_FORWARDERS` members of our section closest to
+    /// `target`. Only those forward broadcasts towards `target`, so that a section doesn't send
+    /// every broadcast to each member of the next one once per own member.
+    fn is_broadcast_forwarder(&self, target: &XorName) -> bool {
+        let mut section = self.peer_mgr.routing_table().our_section().iter().collect_vec();
+        section.sort_by(|lhs, rhs| target.cmp_distance(lhs, rhs));
+        section.into_iter().take(BROADCAST_FORWARDERS).any(|name| name == self.name())
+    }
+
     fn respond_from_cache(&mut self,
                           routing_msg: &RoutingMessage,
                           route: u8)
                           -> Result<bool, RoutingError> {
+        if let MessageContent::CacheInvalidation(ref data_id) = routing_msg.content {
+            debug!("{:?} Invalidating cached {:?}", self, data_id);
+            self.response_cache.invalidate(data_id);
+            return Ok(false);
+        }
+
         if let MessageContent::UserMessagePart { hash,
                                                  part_count,
                                                  part_index,
                                                  cacheable,
                                                  ref payload,
                                                  .. } = routing_msg.content {
-            if !cacheable {
+            if !cacheable || !self.response_cache.is_enabled() {
                 return Ok(false);
             }
 
-            match self.cacheable_user_msg_cache.add(hash, part_count, part_index, payload.clone()) {
+            match self.cacheable_user_msg_cache
+                .add(hash, part_count, part_index, payload.clone(), None) {
                 Some(UserMessage::Request(request)) => {
+                    if !request.is_cacheable() && !self.response_cache.caches_structured_data() {
+                        return Ok(false);
+                    }
                     if let Some(response) = self.response_cache.get(&request) {
                         debug!("{:?} Found cached response to {:?}", self, request);
+                        let len = serialisation::serialised_size(&response) as usize;
+                        self.stats.count_cache_hit(&request, len);
 
                         let priority = response.priority();
                         let src = Authority::ManagedNode(*self.name());
@@ -1219,15 +1683,28 @@ impl Node {
                         let msg = UserMessage::Response(response);
 
                         self.send_ack_from(routing_msg, route, src);
-                        self.send_user_message(src, dst, msg, priority)?;
+                        self.send_user_message(src, dst, msg, priority, None)?;
 
                         return Ok(true);
                     }
+                    self.stats.count_cache_miss(&request);
                 }
 
                 Some(UserMessage::Response(response)) => {
-                    debug!("{:?} Putting {:?} in cache", self, response);
-                    self.response_cache.put(response);
+                    if let Some(data_id) = response.invalidated_data_id() {
+                        debug!("{:?} Invalidating cached {:?}", self, data_id);
+                        self.response_cache.invalidate(&data_id);
+                    } else if response.is_cacheable() ||
+                              self.response_cache.caches_structured_data() {
+                        let data_id = match response {
+                            Response::GetSuccess(ref data, _) => data.identifier(),
+                            _ => return Ok(false),
+                        };
+                        debug!("{:?} Putting {:?} in cache", self, response);
+                        if self.response_cache.put(response) {
+                            self.stats.count_cache_put(&data_id);
+                        }
+                    }
                 }
 
                 None => (),
@@ -1268,6 +1745,13 @@ impl Node {
     fn send_bootstrap_identify(&mut self, peer_id: PeerId) -> Result<(), RoutingError> {
         let direct_message =
             DirectMessage::BootstrapIdentify { public_id: *self.full_id.public_id() };
+        self.send_direct_message(peer_id, direct_message)?;
+        self.send_capabilities(peer_id)
+    }
+
+    fn send_capabilities(&mut self, peer_id: PeerId) -> Result<(), RoutingError> {
+        let direct_message =
+            DirectMessage::Capabilities { compression: PeerCompression::supported() };
         self.send_direct_message(peer_id, direct_message)
     }
 
@@ -2251,9 +2735,17 @@ impl Node {
         result.with_value(Ok(()))
     }
 
-    fn handle_ack_response(&mut self, ack: Ack) -> Result<(), RoutingError> {
+    fn handle_ack_response(&mut self, ack: Ack) -> Evented<Result<(), RoutingError>> {
         self.ack_mgr.receive(ack);
-        Ok(())
+        let (msgs, complete) = match self.stream_mgr.handle_ack(&ack) {
+            Ok(result) => result,
+            Err(error) => return Err(error).to_evented(),
+        };
+        self.send_stream_chunks(msgs);
+        match complete {
+            Some(stream_id) => Evented::single(Event::StreamSent(stream_id), Ok(())),
+            None => Ok(()).to_evented(),
+        }
     }
 
     /// Returns true if the calling node should keep running, false for terminate or restart.
@@ -2262,6 +2754,11 @@ impl Node {
             return self.handle_approval_timeout();
         }
 
+        if self.send_queue.flush_timer_token() == Some(token) {
+            self.flush_send_queue();
+            return true.to_evented();
+        }
+
         let mut events = Evented::empty();
         if self.tick_timer_token == token {
             let tick_period = Duration::from_secs(TICK_TIMEOUT_SECS);
@@ -2272,6 +2769,18 @@ impl Node {
                 let _ = self.crust_service.disconnect(peer_id);
             }
             self.merge_if_necessary();
+            match self.stream_mgr.resume_stalled() {
+                Ok((msgs, failed)) => {
+                    self.send_stream_chunks(msgs);
+                    events.add_events(failed);
+                }
+                Err(error) => debug!("{:?} Failed to resume streams: {:?}", self, error),
+            }
+            events.add_events(self.stream_mgr.remove_expired());
+            events.add_events(self.response_aggregator.remove_expired());
+            if let Some(event) = self.remove_expired_data(utils::now_secs()) {
+                events.add_event(event);
+            }
 
             events.add_event(Event::Tick);
             return events.with_value(true);
@@ -2426,15 +2935,68 @@ impl Node {
     }
 
     // ----- Send Functions -----------------------------------------------------------------------
+    fn send_broadcast(&mut self, payload: Vec<u8>) -> Result<(), RoutingError> {
+        if payload.len() > MAX_BROADCAST_LEN {
+            return Err(RoutingError::Interface(InterfaceError::PayloadTooLarge));
+        }
+        if !self.broadcast_limiter.allow(*self.name()) {
+            return Err(RoutingError::Interface(InterfaceError::RateLimitExceeded));
+        }
+        let broadcast = SignedBroadcast::new(&self.full_id, MessageId::new(), payload)?;
+        let src = Authority::ManagedNode(*self.name());
+        let dst = Authority::Section(*self.name());
+        let content = MessageContent::Broadcast {
+            broadcast: broadcast,
+            depth: 0,
+        };
+        self.send_routing_message(src, dst, content)
+    }
+
+    fn send_stream(&mut self,
+                   dst: XorName,
+                   stream_id: MessageId,
+                   payload: Vec<u8>)
+                   -> Result<(), RoutingError> {
+        let msgs = self.stream_mgr.start(stream_id, *self.name(), dst, &payload)?;
+        self.send_stream_chunks(msgs);
+        Ok(())
+    }
+
+    fn send_stream_chunks(&mut self, msgs: Vec<RoutingMessage>) {
+        for msg in msgs {
+            if let Err(error) = self.send_routing_message_via_route(msg, 0) {
+                debug!("{:?} Failed to send stream chunk: {:?}", self, error);
+            }
+        }
+    }
+
     fn send_user_message(&mut self,
                          src: Authority<XorName>,
                          dst: Authority<XorName>,
                          user_msg: UserMessage,
-                         priority: u8)
+                         priority: u8,
+                         recipient: Option<&PublicId>)
                          -> Result<(), RoutingError> {
+        let recipient_key = recipient.map(PublicId::encrypting_public_key);
+        let parts = user_msg.to_parts(priority, recipient_key)?;
+        let parts_len: usize = parts.iter()
+            .map(|part| match *part {
+                MessageContent::UserMessagePart { ref payload, .. } => payload.len(),
+                _ => 0,
+            })
+            .sum();
+        let delivery_dsts = dst.delivery_authorities();
+        if !self.send_queue.has_capacity(parts_len * delivery_dsts.len()) {
+            return Err(RoutingError::Interface(InterfaceError::SendQueueFull));
+        }
         self.stats.count_user_message(&user_msg);
-        for part in user_msg.to_parts(priority)? {
-            self.send_routing_message(src, dst, part)?;
+        if let UserMessage::Request(ref request) = user_msg {
+            self.response_aggregator.expect(request.message_id(), src, dst);
+        }
+        for delivery_dst in delivery_dsts {
+            for part in &parts {
+                self.send_routing_message(src, delivery_dst, part.clone())?;
+            }
         }
         Ok(())
     }
@@ -2489,7 +3051,8 @@ impl Node {
             return Ok(());
         };
         if !self.filter_outgoing_routing_msg(signed_msg.routing_message(), &target, route) {
-            self.send_or_drop(&peer_id, bytes, signed_msg.priority());
+            let bulk = signed_msg.routing_message().content.is_bulk();
+            self.send_or_queue(&peer_id, bytes, signed_msg.priority(), bulk)?;
         }
         Ok(())
     }
@@ -2499,6 +3062,7 @@ impl Node {
                        peer_id: &PeerId)
                        -> Result<(), RoutingError> {
         let priority = signed_msg.priority();
+        let bulk = signed_msg.routing_message().content.is_bulk();
 
         if self.peer_mgr.get_connected_peer(peer_id).is_some() {
             if self.filter_outgoing_routing_msg(signed_msg.routing_message(), peer_id, 0) {
@@ -2510,8 +3074,7 @@ impl Node {
                                           self.full_id.signing_private_key())?;
             let message = Message::Hop(hop_msg);
             let raw_bytes = serialisation::serialise(&message)?;
-            self.send_or_drop(peer_id, raw_bytes, priority);
-            Ok(())
+            self.send_or_queue(peer_id, raw_bytes, priority, bulk)
         } else {
             // Acknowledge the message so that the sender doesn't retry.
             let hop = *self.name();
@@ -2553,6 +3116,7 @@ impl Node {
                     .sorted_by(|&lhs, &rhs| src.name().cmp_distance(lhs, rhs))
             }
             ManagedNode(_) | Client { .. } => return Some(*self.name()),
+            Sections(_) => return None,
         };
 
         if !list.contains(&self.name()) {
@@ -2646,8 +3210,7 @@ impl Node {
             }
         };
 
-        let result = self.send_direct_message(peer_id, direct_message);
-        if let Err(ref error) = result {
+        if let Err(error) = self.send_direct_message(peer_id, direct_message) {
             let id_type = if self.is_approved {
                 "NodeIdentify"
             } else {
@@ -2658,8 +3221,9 @@ impl Node {
                   id_type,
                   peer_id,
                   error);
+            return Err(error);
         }
-        result
+        self.send_capabilities(peer_id)
     }
 
     fn send_connection_info_request(&mut self,
@@ -2768,6 +3332,7 @@ impl Node {
             self.reset_rt_timer();
             self.section_list_sigs
                 .remove_signatures_by(*pub_id, self.peer_mgr.routing_table().our_section().len());
+            let _ = self.data_root_sigs.remove(pub_id);
         }
 
         if self.peer_mgr.routing_table().is_empty() {
@@ -2960,6 +3525,8 @@ impl Base for Node {
 
         let mut result = Evented::empty();
 
+        self.send_queue.remove_peer(&peer_id);
+        self.peer_compression.remove_peer(&peer_id);
         self.dropped_tunnel_client(&peer_id);
         self.dropped_tunnel_node(&peer_id).extract(&mut result);
 
@@ -3019,6 +3586,11 @@ impl Node {
     pub fn set_next_node_name(&mut self, relocation_name: Option<XorName>) {
         self.next_node_name = relocation_name;
     }
+
+    /// Removes the data that has expired at time `now`, as the tick timer does.
+    pub fn expire_data(&mut self, now: u64) -> Option<Event> {
+        self.remove_expired_data(now)
+    }
 }
 
 impl Bootstrapped for Node {
@@ -3075,6 +3647,12 @@ impl Bootstrapped for Node {
                     .collect()
             }
             Client { .. } => vec![],
+            Sections(_) => {
+                debug!("{:?} Multiple sections can't be the source of message {:?}.",
+                       self,
+                       routing_msg);
+                return Err(RoutingError::BadAuthority);
+            }
         };
 
         let signed_msg = SignedMessage::new(routing_msg, &self.full_id, sending_names)?;
@@ -3111,10 +3689,18 @@ impl Bootstrapped for Node {
         }
     }
 
+    fn peer_compression(&self) -> &PeerCompression {
+        &self.peer_compression
+    }
+
     fn routing_msg_filter(&mut self) -> &mut RoutingMessageFilter {
         &mut self.routing_msg_filter
     }
 
+    fn send_queue(&mut self) -> &mut SendQueue {
+        &mut self.send_queue
+    }
+
     fn timer(&mut self) -> &mut Timer {
         &mut self.timer
     }
@@ -15,11 +15,55 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
+use data::DataIdentifier;
 use messages::{DirectMessage, MessageContent, Request, Response, RoutingMessage, UserMessage};
 
 /// The number of messages after which the message statistics should be printed.
 const MSG_LOG_COUNT: usize = 5000;
 
+/// Counts of a node's response cache usage for one kind of data.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheCounts {
+    /// The number of requests answered from the cache.
+    pub hits: u64,
+    /// The number of cacheable requests the cache had no response to.
+    pub misses: u64,
+    /// The serialised size of the responses sent from the cache.
+    pub bytes_served: u64,
+    /// The number of relayed responses stored in the cache.
+    pub puts: u64,
+}
+
+/// Counts of a node's response cache usage, per kind of cacheable data.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheMetrics {
+    /// Counts for `ImmutableData`.
+    pub immutable: CacheCounts,
+    /// Counts for `StructuredData`.
+    pub structured: CacheCounts,
+}
+
+impl CacheMetrics {
+    fn counts_mut(&mut self, data_id: &DataIdentifier) -> Option<&mut CacheCounts> {
+        match *data_id {
+            DataIdentifier::Immutable(..) => Some(&mut self.immutable),
+            DataIdentifier::Structured(..) => Some(&mut self.structured),
+            _ => None,
+        }
+    }
+
+    fn request_counts_mut(&mut self, request: &Request) -> Option<&mut CacheCounts> {
+        match *request {
+            Request::Get(ref data_id, _) => self.counts_mut(data_id),
+            _ => None,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == CacheMetrics::default()
+    }
+}
+
 /// A collection of counters to gather Routing statistics.
 #[derive(Default)]
 pub struct Stats {
@@ -42,13 +86,22 @@ pub struct Stats {
     msg_direct_resource_proof_rsp: usize,
     msg_direct_resource_proof_rsp_receipt: usize,
     msg_direct_sls: usize,
+    msg_direct_data_root_sig: usize,
 
     msg_get: usize,
     msg_put: usize,
     msg_post: usize,
     msg_delete: usize,
+    msg_conditional_post: usize,
+    msg_conditional_delete: usize,
     msg_append: usize,
+    msg_mutate_entries: usize,
+    msg_set_user_permissions: usize,
+    msg_get_appended_data: usize,
     msg_get_account_info: usize,
+    msg_get_batch: usize,
+    msg_put_batch: usize,
+    msg_messaging: usize,
     msg_get_node_name: usize,
     msg_expect_candidate: usize,
     msg_accept_as_candidate: usize,
@@ -63,10 +116,20 @@ pub struct Stats {
     msg_post_failure: usize,
     msg_delete_success: usize,
     msg_delete_failure: usize,
+    msg_condition_not_met: usize,
     msg_append_success: usize,
     msg_append_failure: usize,
+    msg_mutate_entries_success: usize,
+    msg_mutate_entries_failure: usize,
+    msg_set_user_permissions_success: usize,
+    msg_set_user_permissions_failure: usize,
+    msg_get_appended_data_success: usize,
+    msg_get_appended_data_failure: usize,
     msg_get_account_info_success: usize,
     msg_get_account_info_failure: usize,
+    msg_get_batch_rsp: usize,
+    msg_put_batch_rsp: usize,
+    msg_messaging_rsp: usize,
     msg_section_update: usize,
     msg_section_split: usize,
     msg_own_section_merge: usize,
@@ -77,12 +140,26 @@ pub struct Stats {
     msg_candidate_approval: usize,
     msg_node_approval: usize,
     msg_ack: usize,
+    msg_broadcast: usize,
+    msg_stream_chunk: usize,
+    msg_cache_invalidation: usize,
 
     msg_other: usize,
 
     msg_total: usize,
     msg_total_bytes: u64,
 
+    /// Messages we sent to peers with which we negotiated compression, and how many of them
+    /// actually shrank.
+    compression_msgs: usize,
+    compression_shrunk_msgs: usize,
+    /// Serialised size of those messages before and after compression.
+    compression_bytes_in: u64,
+    compression_bytes_out: u64,
+
+    /// Usage of the response cache.
+    cache: CacheMetrics,
+
     should_log: bool,
 }
 
@@ -110,17 +187,27 @@ impl Stats {
             UserMessage::Request(ref request) => {
                 match *request {
                     Request::Refresh(..) => self.msg_refresh += 1,
-                    Request::Get(..) => self.msg_get += 1,
+                    Request::Get(..) |
+                    Request::GetWithProof(..) => self.msg_get += 1,
                     Request::Put(..) => self.msg_put += 1,
                     Request::Post(..) => self.msg_post += 1,
                     Request::Delete(..) => self.msg_delete += 1,
+                    Request::ConditionalPost(..) => self.msg_conditional_post += 1,
+                    Request::ConditionalDelete(..) => self.msg_conditional_delete += 1,
                     Request::Append(..) => self.msg_append += 1,
                     Request::GetAccountInfo(..) => self.msg_get_account_info += 1,
+                    Request::GetBatch(..) => self.msg_get_batch += 1,
+                    Request::PutBatch(..) => self.msg_put_batch += 1,
+                    Request::MutateEntries(..) => self.msg_mutate_entries += 1,
+                    Request::SetUserPermissions(..) => self.msg_set_user_permissions += 1,
+                    Request::GetAppendedData(..) => self.msg_get_appended_data += 1,
+                    Request::Messaging(..) => self.msg_messaging += 1,
                 }
             }
             UserMessage::Response(ref response) => {
                 match *response {
-                    Response::GetSuccess(..) => self.msg_get_success += 1,
+                    Response::GetSuccess(..) |
+                    Response::GetSuccessWithProof(..) => self.msg_get_success += 1,
                     Response::GetFailure { .. } => self.msg_get_failure += 1,
                     Response::PutSuccess(..) => self.msg_put_success += 1,
                     Response::PutFailure { .. } => self.msg_put_failure += 1,
@@ -128,14 +215,34 @@ impl Stats {
                     Response::PostFailure { .. } => self.msg_post_failure += 1,
                     Response::DeleteSuccess(..) => self.msg_delete_success += 1,
                     Response::DeleteFailure { .. } => self.msg_delete_failure += 1,
+                    Response::ConditionNotMet { .. } => self.msg_condition_not_met += 1,
                     Response::AppendSuccess(..) => self.msg_append_success += 1,
                     Response::AppendFailure { .. } => self.msg_append_failure += 1,
+                    Response::MutateEntriesSuccess(..) => self.msg_mutate_entries_success += 1,
+                    Response::MutateEntriesFailure { .. } => {
+                        self.msg_mutate_entries_failure += 1
+                    }
+                    Response::SetUserPermissionsSuccess(..) => {
+                        self.msg_set_user_permissions_success += 1
+                    }
+                    Response::SetUserPermissionsFailure { .. } => {
+                        self.msg_set_user_permissions_failure += 1
+                    }
+                    Response::GetAppendedDataSuccess { .. } => {
+                        self.msg_get_appended_data_success += 1
+                    }
+                    Response::GetAppendedDataFailure { .. } => {
+                        self.msg_get_appended_data_failure += 1
+                    }
                     Response::GetAccountInfoSuccess { .. } => {
                         self.msg_get_account_info_success += 1
                     }
                     Response::GetAccountInfoFailure { .. } => {
                         self.msg_get_account_info_failure += 1
                     }
+                    Response::GetBatchResponse { .. } => self.msg_get_batch_rsp += 1,
+                    Response::PutBatchResponse { .. } => self.msg_put_batch_rsp += 1,
+                    Response::Messaging(..) => self.msg_messaging_rsp += 1,
                 }
             }
         }
@@ -160,6 +267,9 @@ impl Stats {
             MessageContent::Ack(..) => self.msg_ack += 1,
             MessageContent::CandidateApproval { .. } => self.msg_candidate_approval += 1,
             MessageContent::NodeApproval { .. } => self.msg_node_approval += 1,
+            MessageContent::Broadcast { .. } => self.msg_broadcast += 1,
+            MessageContent::StreamChunk { .. } => self.msg_stream_chunk += 1,
+            MessageContent::CacheInvalidation(..) => self.msg_cache_invalidation += 1,
             MessageContent::UserMessagePart { .. } => return, // Counted as request/response.
         }
         self.increment_msg_total();
@@ -173,6 +283,7 @@ impl Stats {
             CandidateIdentify { .. } => self.msg_direct_candidate_identify += 1,
             MessageSignature(..) => self.msg_direct_sig += 1,
             SectionListSignature(..) => self.msg_direct_sls += 1,
+            DataRootSignature(..) => self.msg_direct_data_root_sig += 1,
             ResourceProof { .. } => self.msg_direct_resource_proof += 1,
             ResourceProofResponse { .. } => self.msg_direct_resource_proof_rsp += 1,
             ResourceProofResponseReceipt => self.msg_direct_resource_proof_rsp_receipt += 1,
@@ -182,11 +293,49 @@ impl Stats {
             TunnelRequest(_) |
             TunnelSuccess(_) |
             TunnelClosed(_) |
-            TunnelDisconnect(_) => self.msg_other += 1,
+            TunnelDisconnect(_) |
+            Capabilities { .. } => self.msg_other += 1,
         }
         self.increment_msg_total();
     }
 
+    /// Records the size of a serialised message before and after compression.
+    pub fn count_compression(&mut self, uncompressed_len: usize, compressed_len: usize) {
+        self.compression_msgs += 1;
+        if compressed_len < uncompressed_len {
+            self.compression_shrunk_msgs += 1;
+        }
+        self.compression_bytes_in += uncompressed_len as u64;
+        self.compression_bytes_out += compressed_len as u64;
+    }
+
+    /// Records that the response to `request`, of serialised size `len`, was sent from the cache.
+    pub fn count_cache_hit(&mut self, request: &Request, len: usize) {
+        if let Some(counts) = self.cache.request_counts_mut(request) {
+            counts.hits += 1;
+            counts.bytes_served += len as u64;
+        }
+    }
+
+    /// Records that the cache had no response to `request`.
+    pub fn count_cache_miss(&mut self, request: &Request) {
+        if let Some(counts) = self.cache.request_counts_mut(request) {
+            counts.misses += 1;
+        }
+    }
+
+    /// Records that a response containing the data `data_id` was stored in the cache.
+    pub fn count_cache_put(&mut self, data_id: &DataIdentifier) {
+        if let Some(counts) = self.cache.counts_mut(data_id) {
+            counts.puts += 1;
+        }
+    }
+
+    /// Returns the counts of the response cache usage.
+    pub fn cache_metrics(&self) -> CacheMetrics {
+        self.cache
+    }
+
     pub fn count_bytes(&mut self, len: usize) {
         self.msg_total_bytes += len as u64;
     }
@@ -210,19 +359,22 @@ impl Stats {
                   self.unacked_msgs);
             info!(target: "routing_stats",
                   "Stats - Direct - NodeIdentify: {}, CandidateIdentify: {}, \
-                   MessageSignature: {}, ResourceProof: {}/{}/{}, SectionListSignature: {}",
+                   MessageSignature: {}, ResourceProof: {}/{}/{}, SectionListSignature: {}, \
+                   DataRootSignature: {}",
                   self.msg_direct_node_identify,
                   self.msg_direct_candidate_identify,
                   self.msg_direct_sig,
                   self.msg_direct_resource_proof,
                   self.msg_direct_resource_proof_rsp,
                   self.msg_direct_resource_proof_rsp_receipt,
-                  self.msg_direct_sls);
+                  self.msg_direct_sls,
+                  self.msg_direct_data_root_sig);
             info!(target: "routing_stats",
                   "Stats - Hops (Request/Response) - GetNodeName: {}/{}, ExpectCandidate: {}, \
                    AcceptAsCandidate: {}, SectionUpdate: {}, SectionSplit: {}, \
                    OwnSectionMerge: {}, OtherSectionMerge: {}, RoutingTable: {}/{}, \
-                   ConnectionInfo: {}/{}, CandidateApproval: {}, NodeApproval: {}, Ack: {}",
+                   ConnectionInfo: {}/{}, CandidateApproval: {}, NodeApproval: {}, Ack: {}, \
+                   Broadcast: {}, StreamChunk: {}, CacheInvalidation: {}",
                   self.msg_get_node_name,
                   self.msg_get_node_name_rsp,
                   self.msg_expect_candidate,
@@ -237,11 +389,17 @@ impl Stats {
                   self.msg_connection_info_rsp,
                   self.msg_candidate_approval,
                   self.msg_node_approval,
-                  self.msg_ack);
+                  self.msg_ack,
+                  self.msg_broadcast,
+                  self.msg_stream_chunk,
+                  self.msg_cache_invalidation);
             info!(target: "routing_stats",
                   "Stats - User (Request/Success/Failure) - Get: {}/{}/{}, Put: {}/{}/{}, \
                    Post: {}/{}/{}, Delete: {}/{}/{}, Append: {}/{}/{}, GetAccountInfo: {}/{}/{}, \
-                   Refresh: {}",
+                   MutateEntries: {}/{}/{}, SetUserPermissions: {}/{}/{}, \
+                   GetAppendedData: {}/{}/{}, ConditionalPost/Delete (Request): {}/{}, \
+                   ConditionNotMet: {}, Refresh: {}, GetBatch (Request/Response): {}/{}, \
+                   PutBatch (Request/Response): {}/{}, Messaging (Request/Response): {}/{}",
                   self.msg_get,
                   self.msg_get_success,
                   self.msg_get_failure,
@@ -260,7 +418,57 @@ impl Stats {
                   self.msg_get_account_info,
                   self.msg_get_account_info_success,
                   self.msg_get_account_info_failure,
-                  self.msg_refresh);
+                  self.msg_mutate_entries,
+                  self.msg_mutate_entries_success,
+                  self.msg_mutate_entries_failure,
+                  self.msg_set_user_permissions,
+                  self.msg_set_user_permissions_success,
+                  self.msg_set_user_permissions_failure,
+                  self.msg_get_appended_data,
+                  self.msg_get_appended_data_success,
+                  self.msg_get_appended_data_failure,
+                  self.msg_conditional_post,
+                  self.msg_conditional_delete,
+                  self.msg_condition_not_met,
+                  self.msg_refresh,
+                  self.msg_get_batch,
+                  self.msg_get_batch_rsp,
+                  self.msg_put_batch,
+                  self.msg_put_batch_rsp,
+                  self.msg_messaging,
+                  self.msg_messaging_rsp);
+            if self.compression_msgs > 0 {
+                info!(target: "routing_stats",
+                      "Stats - Compression - Messages (Total/Shrunk): {}/{}, \
+                       Bytes (In/Out): {}/{}, Ratio: {:.3}",
+                      self.compression_msgs,
+                      self.compression_shrunk_msgs,
+                      self.compression_bytes_in,
+                      self.compression_bytes_out,
+                      self.compression_ratio());
+            }
+            if !self.cache.is_empty() {
+                info!(target: "routing_stats",
+                      "Stats - Cache (Hits/Misses/Bytes served/Puts) - Immutable: {}/{}/{}/{}, \
+                       Structured: {}/{}/{}/{}",
+                      self.cache.immutable.hits,
+                      self.cache.immutable.misses,
+                      self.cache.immutable.bytes_served,
+                      self.cache.immutable.puts,
+                      self.cache.structured.hits,
+                      self.cache.structured.misses,
+                      self.cache.structured.bytes_served,
+                      self.cache.structured.puts);
+            }
+        }
+    }
+
+    /// Returns the ratio of compressed to uncompressed bytes of the messages we sent to peers with
+    /// which we negotiated compression.
+    fn compression_ratio(&self) -> f64 {
+        if self.compression_bytes_in == 0 {
+            return 1.0;
         }
+        self.compression_bytes_out as f64 / self.compression_bytes_in as f64
     }
 }
@@ -0,0 +1,562 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.1.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use ack_manager::Ack;
+use error::RoutingError;
+use event::Event;
+use lru_time_cache::LruCache;
+use messages::{MAX_PART_LEN, MessageContent, RoutingMessage};
+use routing_table::Authority;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+use types::MessageId;
+use xor_name::XorName;
+
+/// The maximal length of a stream chunk's payload, in bytes.
+pub const MAX_STREAM_CHUNK_LEN: usize = MAX_PART_LEN;
+/// The maximal number of chunks of a stream that are sent but not yet acknowledged.
+pub const STREAM_WINDOW: u32 = 8;
+/// Time (in seconds) after which an unacknowledged chunk is resent as a new attempt. This is
+/// longer than it takes the `AckManager` to try all routes, so the chunk's routes have failed.
+const STREAM_RESUME_TIMEOUT_SECS: u64 = 5 * 60;
+/// The maximal number of times a chunk is resent before its stream is dropped as failed.
+const MAX_STREAM_RESUME_ATTEMPTS: u32 = 3;
+/// Time (in seconds) after which an incoming stream that received no chunks is aborted.
+const INCOMING_STREAM_EXPIRY_SECS: u64 = 20 * 60;
+/// The maximal number of incomplete incoming streams from a single sender.
+pub const MAX_INCOMING_STREAMS_PER_PEER: usize = 16;
+
+/// A chunk that has been sent but not acknowledged yet.
+struct InFlightChunk {
+    sent_at: Instant,
+    attempt: u32,
+}
+
+struct OutgoingStream {
+    src: XorName,
+    dst: XorName,
+    chunks: Vec<Vec<u8>>,
+    /// The index of the next chunk to be sent for the first time.
+    next_index: u32,
+    in_flight: BTreeMap<u32, InFlightChunk>,
+}
+
+impl OutgoingStream {
+    /// The index of the first chunk that hasn't been acknowledged yet.
+    fn first_unacked(&self) -> u32 {
+        self.in_flight.keys().next().cloned().unwrap_or(self.next_index)
+    }
+
+    fn is_complete(&self) -> bool {
+        self.in_flight.is_empty() && self.next_index as usize == self.chunks.len()
+    }
+
+    fn chunk_msg(&self, stream_id: MessageId, index: u32, attempt: u32) -> RoutingMessage {
+        RoutingMessage {
+            src: Authority::ManagedNode(self.src),
+            dst: Authority::ManagedNode(self.dst),
+            content: MessageContent::StreamChunk {
+                stream_id: stream_id,
+                chunk_count: self.chunks.len() as u32,
+                index: index,
+                attempt: attempt,
+                payload: self.chunks[index as usize].clone(),
+            },
+        }
+    }
+}
+
+struct IncomingStream {
+    received_at: Instant,
+    chunk_count: u32,
+    /// The index of the next chunk to be delivered.
+    next_index: u32,
+    /// Chunks received ahead of `next_index`.
+    buffered: BTreeMap<u32, Vec<u8>>,
+}
+
+/// Splits large payloads into streams of chunks sent between two nodes, with windowed flow
+/// control, and reassembles incoming streams in order.
+///
+/// Each chunk is an ordinary routing message, acknowledged via the `AckManager`. The window
+/// advances as the acks arrive. Chunks which have not been acknowledged after all their routes
+/// failed are resent periodically, so that the stream resumes where it stalled. If a chunk is
+/// still not acknowledged after `MAX_STREAM_RESUME_ATTEMPTS` resends, the stream is dropped with
+/// an `Event::StreamFailed`.
+///
+/// Since the chunks have already been acknowledged when they are received, an incoming stream
+/// that can't be completed is aborted with an `Event::StreamAborted` instead of being dropped.
+pub struct StreamManager {
+    outgoing: HashMap<MessageId, OutgoingStream>,
+    /// The stream and chunk index each pending ack belongs to.
+    acks: HashMap<Ack, (MessageId, u32)>,
+    incoming: HashMap<(XorName, MessageId), IncomingStream>,
+    /// Recently completed or aborted incoming streams, whose remaining chunks are ignored.
+    finished: LruCache<(XorName, MessageId), ()>,
+}
+
+impl StreamManager {
+    /// Creates a new manager without any streams.
+    pub fn new() -> Self {
+        let expiry_duration = Duration::from_secs(INCOMING_STREAM_EXPIRY_SECS);
+        StreamManager {
+            outgoing: HashMap::new(),
+            acks: HashMap::new(),
+            incoming: HashMap::new(),
+            finished: LruCache::with_expiry_duration(expiry_duration),
+        }
+    }
+
+    /// Starts a stream of `payload` from `src` to `dst` and returns the messages with the first
+    /// window of chunks.
+    pub fn start(&mut self,
+                 stream_id: MessageId,
+                 src: XorName,
+                 dst: XorName,
+                 payload: &[u8])
+                 -> Result<Vec<RoutingMessage>, RoutingError> {
+        let chunks = if payload.is_empty() {
+            vec![vec![]]
+        } else {
+            payload.chunks(MAX_STREAM_CHUNK_LEN).map(|chunk| chunk.to_vec()).collect()
+        };
+        let stream = OutgoingStream {
+            src: src,
+            dst: dst,
+            chunks: chunks,
+            next_index: 0,
+            in_flight: BTreeMap::new(),
+        };
+        let _ = self.outgoing.insert(stream_id, stream);
+        self.fill_window(stream_id)
+    }
+
+    /// Handles an ack for a message we sent. If it acknowledges a stream chunk, returns the
+    /// messages with the chunks the window advanced to, and the stream's ID if it is now
+    /// complete.
+    pub fn handle_ack(&mut self,
+                      ack: &Ack)
+                      -> Result<(Vec<RoutingMessage>, Option<MessageId>), RoutingError> {
+        let (stream_id, index) = match self.acks.remove(ack) {
+            Some(entry) => entry,
+            None => return Ok((vec![], None)),
+        };
+        let complete = match self.outgoing.get_mut(&stream_id) {
+            Some(stream) => {
+                let _ = stream.in_flight.remove(&index);
+                stream.is_complete()
+            }
+            None => return Ok((vec![], None)),
+        };
+        if complete {
+            self.remove_outgoing(stream_id);
+            return Ok((vec![], Some(stream_id)));
+        }
+        Ok((self.fill_window(stream_id)?, None))
+    }
+
+    /// Returns the messages to resend the chunks that have not been acknowledged for too long,
+    /// and an `Event::StreamFailed` for each stream that is dropped because one of its chunks has
+    /// already been resent `MAX_STREAM_RESUME_ATTEMPTS` times.
+    ///
+    /// They are sent as new attempts, so they are neither filtered as duplicates nor share their
+    /// ack with the failed messages. An ack for any attempt acknowledges the chunk.
+    pub fn resume_stalled(&mut self) -> Result<(Vec<RoutingMessage>, Vec<Event>), RoutingError> {
+        self.resume_stalled_at(Instant::now())
+    }
+
+    fn resume_stalled_at(&mut self,
+                         now: Instant)
+                         -> Result<(Vec<RoutingMessage>, Vec<Event>), RoutingError> {
+        let timeout = Duration::from_secs(STREAM_RESUME_TIMEOUT_SECS);
+        let mut msgs = vec![];
+        let mut failed = vec![];
+        for (stream_id, stream) in &mut self.outgoing {
+            let stalled: Vec<u32> = stream.in_flight
+                .iter()
+                .filter(|&(_, chunk)| now.duration_since(chunk.sent_at) > timeout)
+                .map(|(index, _)| *index)
+                .collect();
+            if stalled.iter()
+                .any(|index| stream.in_flight[index].attempt >= MAX_STREAM_RESUME_ATTEMPTS) {
+                failed.push(*stream_id);
+                continue;
+            }
+            for index in stalled {
+                let attempt = match stream.in_flight.get_mut(&index) {
+                    Some(chunk) => {
+                        chunk.attempt += 1;
+                        chunk.sent_at = now;
+                        chunk.attempt
+                    }
+                    None => continue,
+                };
+                let msg = stream.chunk_msg(*stream_id, index, attempt);
+                let _ = self.acks.insert(Ack::compute(&msg)?, (*stream_id, index));
+                msgs.push(msg);
+            }
+        }
+        let events = failed.into_iter()
+            .map(|stream_id| {
+                self.remove_outgoing(stream_id);
+                Event::StreamFailed(stream_id)
+            })
+            .collect();
+        Ok((msgs, events))
+    }
+
+    /// Handles a received chunk, and returns the events for all chunks of that stream that can
+    /// now be delivered in order.
+    ///
+    /// If the chunk doesn't fit the stream, i.e. its index is out of range or beyond the window,
+    /// or its chunk count differs from the stream's, or if the sender already has
+    /// `MAX_INCOMING_STREAMS_PER_PEER` incomplete streams, the stream is aborted instead.
+    pub fn receive(&mut self,
+                   src: XorName,
+                   stream_id: MessageId,
+                   chunk_count: u32,
+                   index: u32,
+                   payload: Vec<u8>)
+                   -> Vec<Event> {
+        let key = (src, stream_id);
+        if self.finished.contains_key(&key) {
+            return vec![];
+        }
+        if index >= chunk_count {
+            return self.abort(key);
+        }
+        if !self.incoming.contains_key(&key) {
+            let stream_count = self.incoming.keys().filter(|&&(name, _)| name == src).count();
+            if stream_count >= MAX_INCOMING_STREAMS_PER_PEER {
+                return self.abort(key);
+            }
+            let stream = IncomingStream {
+                received_at: Instant::now(),
+                chunk_count: chunk_count,
+                next_index: 0,
+                buffered: BTreeMap::new(),
+            };
+            let _ = self.incoming.insert(key, stream);
+        }
+        let valid = match self.incoming.get(&key) {
+            Some(stream) => {
+                chunk_count == stream.chunk_count && index < stream.next_index + STREAM_WINDOW
+            }
+            None => false,
+        };
+        if !valid {
+            return self.abort(key);
+        }
+
+        let mut events = vec![];
+        let complete = {
+            let stream = match self.incoming.get_mut(&key) {
+                Some(stream) => stream,
+                None => return events,
+            };
+            stream.received_at = Instant::now();
+            // Chunks that have already been delivered are resent attempts: ignore them.
+            if index < stream.next_index {
+                return events;
+            }
+            let _ = stream.buffered.insert(index, payload);
+            Self::deliver_buffered(src, stream_id, stream, &mut events);
+            stream.next_index == stream.chunk_count
+        };
+        if complete {
+            let _ = self.incoming.remove(&key);
+            let _ = self.finished.insert(key, ());
+        }
+        events
+    }
+
+    /// Aborts the incoming streams that haven't received any chunks for
+    /// `INCOMING_STREAM_EXPIRY_SECS`, and returns the corresponding events.
+    pub fn remove_expired(&mut self) -> Vec<Event> {
+        let expiry_duration = Duration::from_secs(INCOMING_STREAM_EXPIRY_SECS);
+        let expired = self.incoming
+            .iter()
+            .filter(|&(_, stream)| stream.received_at.elapsed() > expiry_duration)
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+        expired.into_iter().flat_map(|key| self.abort(key)).collect()
+    }
+
+    /// Drops the given outgoing stream and its pending acks.
+    fn remove_outgoing(&mut self, stream_id: MessageId) {
+        let _ = self.outgoing.remove(&stream_id);
+        let stale_acks: Vec<Ack> = self.acks
+            .iter()
+            .filter(|&(_, &(id, _))| id == stream_id)
+            .map(|(ack, _)| *ack)
+            .collect();
+        for ack in stale_acks {
+            let _ = self.acks.remove(&ack);
+        }
+    }
+
+    /// Drops the state of the given incoming stream, and ignores its chunks from now on.
+    fn abort(&mut self, key: (XorName, MessageId)) -> Vec<Event> {
+        let _ = self.incoming.remove(&key);
+        let _ = self.finished.insert(key, ());
+        vec![Event::StreamAborted {
+                 src: key.0,
+                 stream_id: key.1,
+             }]
+    }
+
+    /// Moves the chunks that can now be delivered in order from `buffered` into `events`.
+    fn deliver_buffered(src: XorName,
+                        stream_id: MessageId,
+                        stream: &mut IncomingStream,
+                        events: &mut Vec<Event>) {
+        let chunk_count = stream.chunk_count;
+        loop {
+            let next_index = stream.next_index;
+            match stream.buffered.remove(&next_index) {
+                Some(payload) => {
+                    events.push(Event::StreamChunk {
+                        src: src,
+                        stream_id: stream_id,
+                        index: next_index,
+                        chunk_count: chunk_count,
+                        payload: payload,
+                    });
+                    stream.next_index += 1;
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// Sends the chunks of the given stream that fit into its window.
+    fn fill_window(&mut self, stream_id: MessageId) -> Result<Vec<RoutingMessage>, RoutingError> {
+        let mut msgs = vec![];
+        if let Some(stream) = self.outgoing.get_mut(&stream_id) {
+            while (stream.next_index as usize) < stream.chunks.len() &&
+                  stream.next_index < stream.first_unacked() + STREAM_WINDOW {
+                let index = stream.next_index;
+                let msg = stream.chunk_msg(stream_id, index, 0);
+                let _ = self.acks.insert(Ack::compute(&msg)?, (stream_id, index));
+                let chunk = InFlightChunk {
+                    sent_at: Instant::now(),
+                    attempt: 0,
+                };
+                let _ = stream.in_flight.insert(index, chunk);
+                stream.next_index += 1;
+                msgs.push(msg);
+            }
+        }
+        Ok(msgs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ack_manager::Ack;
+    use event::Event;
+    use messages::{MessageContent, RoutingMessage};
+    use rand;
+    use std::time::{Duration, Instant};
+    use super::{MAX_INCOMING_STREAMS_PER_PEER, MAX_STREAM_CHUNK_LEN, MAX_STREAM_RESUME_ATTEMPTS,
+                STREAM_RESUME_TIMEOUT_SECS, STREAM_WINDOW, StreamManager};
+    use types::MessageId;
+    use xor_name::XorName;
+
+    fn chunk_index(msg: &RoutingMessage) -> u32 {
+        match msg.content {
+            MessageContent::StreamChunk { index, .. } => index,
+            ref content => panic!("Unexpected content {:?}", content),
+        }
+    }
+
+    fn deliver(receiver: &mut StreamManager, src: XorName, msg: RoutingMessage) -> Vec<Event> {
+        match msg.content {
+            MessageContent::StreamChunk { stream_id, chunk_count, index, payload, .. } => {
+                receiver.receive(src, stream_id, chunk_count, index, payload)
+            }
+            content => panic!("Unexpected content {:?}", content),
+        }
+    }
+
+    #[test]
+    fn window_advances_with_acks() {
+        let (src, dst) = (rand::random(), rand::random());
+        let stream_id = MessageId::new();
+        let chunk_count = STREAM_WINDOW as usize + 3;
+        let payload = vec![7u8; MAX_STREAM_CHUNK_LEN * (chunk_count - 1) + 1];
+        let mut sender = StreamManager::new();
+
+        let msgs = unwrap!(sender.start(stream_id, src, dst, &payload));
+        assert_eq!(msgs.len(), STREAM_WINDOW as usize);
+        assert_eq!(msgs.iter().map(chunk_index).collect::<Vec<_>>(),
+                   (0..STREAM_WINDOW).collect::<Vec<_>>());
+
+        // Acknowledging a chunk other than the first one doesn't advance the window.
+        let (next, complete) = unwrap!(sender.handle_ack(&unwrap!(Ack::compute(&msgs[1]))));
+        assert!(next.is_empty());
+        assert_eq!(complete, None);
+
+        // Acknowledging the first one does, past the already acknowledged second chunk.
+        let (next, complete) = unwrap!(sender.handle_ack(&unwrap!(Ack::compute(&msgs[0]))));
+        assert_eq!(next.iter().map(chunk_index).collect::<Vec<_>>(),
+                   vec![STREAM_WINDOW, STREAM_WINDOW + 1]);
+        assert_eq!(complete, None);
+
+        let mut remaining = msgs[2..].iter().chain(next.iter()).cloned().collect::<Vec<_>>();
+        let mut completed = None;
+        while let Some(msg) = remaining.pop() {
+            let (next, complete) = unwrap!(sender.handle_ack(&unwrap!(Ack::compute(&msg))));
+            remaining.extend(next);
+            if complete.is_some() {
+                completed = complete;
+            }
+        }
+        assert_eq!(completed, Some(stream_id));
+    }
+
+    #[test]
+    fn chunks_delivered_in_order_once() {
+        let (src, dst) = (rand::random(), rand::random());
+        let stream_id = MessageId::new();
+        let payload = (0..(3 * MAX_STREAM_CHUNK_LEN)).map(|i| i as u8).collect::<Vec<_>>();
+        let mut sender = StreamManager::new();
+        let mut receiver = StreamManager::new();
+
+        let msgs = unwrap!(sender.start(stream_id, src, dst, &payload));
+        assert_eq!(msgs.len(), 3);
+
+        assert!(deliver(&mut receiver, src, msgs[2].clone()).is_empty());
+        assert!(deliver(&mut receiver, src, msgs[1].clone()).is_empty());
+        let events = deliver(&mut receiver, src, msgs[0].clone());
+        // A duplicate of an already delivered chunk is ignored.
+        assert!(deliver(&mut receiver, src, msgs[1].clone()).is_empty());
+
+        let mut received = vec![];
+        for (expected_index, event) in events.into_iter().enumerate() {
+            match event {
+                Event::StreamChunk { src: ev_src, index, chunk_count, payload, .. } => {
+                    assert_eq!(ev_src, src);
+                    assert_eq!(index, expected_index as u32);
+                    assert_eq!(chunk_count, 3);
+                    received.extend(payload);
+                }
+                event => panic!("Unexpected event {:?}", event),
+            }
+        }
+        assert_eq!(received, payload);
+    }
+
+    fn is_aborted(events: &[Event], src: XorName, stream_id: MessageId) -> bool {
+        match events.first() {
+            Some(&Event::StreamAborted { src: ev_src, stream_id: ev_stream_id }) => {
+                events.len() == 1 && ev_src == src && ev_stream_id == stream_id
+            }
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn invalid_chunks_abort_stream() {
+        let src = rand::random();
+        let mut receiver = StreamManager::new();
+
+        // A chunk beyond the window aborts the stream, and its later chunks are ignored.
+        let stream_id = MessageId::new();
+        let events = receiver.receive(src, stream_id, STREAM_WINDOW + 1, STREAM_WINDOW, vec![]);
+        assert!(is_aborted(&events, src, stream_id));
+        assert!(receiver.receive(src, stream_id, STREAM_WINDOW + 1, 0, vec![]).is_empty());
+
+        // So does a chunk whose chunk count differs from the stream's.
+        let stream_id = MessageId::new();
+        assert!(receiver.receive(src, stream_id, 3, 1, vec![]).is_empty());
+        let events = receiver.receive(src, stream_id, 4, 0, vec![]);
+        assert!(is_aborted(&events, src, stream_id));
+
+        // And a chunk whose index is out of range.
+        let stream_id = MessageId::new();
+        let events = receiver.receive(src, stream_id, 3, 3, vec![]);
+        assert!(is_aborted(&events, src, stream_id));
+    }
+
+    #[test]
+    fn incoming_streams_per_peer_limit() {
+        let (src, other_src) = (rand::random(), rand::random());
+        let mut receiver = StreamManager::new();
+
+        for _ in 0..MAX_INCOMING_STREAMS_PER_PEER {
+            assert!(receiver.receive(src, MessageId::new(), 2, 1, vec![]).is_empty());
+        }
+        let stream_id = MessageId::new();
+        let events = receiver.receive(src, stream_id, 2, 1, vec![]);
+        assert!(is_aborted(&events, src, stream_id));
+        // Other senders are unaffected.
+        assert!(receiver.receive(other_src, MessageId::new(), 2, 1, vec![]).is_empty());
+        // Completed streams are not delivered twice.
+        let stream_id = MessageId::new();
+        assert_eq!(receiver.receive(other_src, stream_id, 1, 0, vec![]).len(), 1);
+        assert!(receiver.receive(other_src, stream_id, 1, 0, vec![]).is_empty());
+    }
+
+    #[test]
+    fn stalled_stream_fails() {
+        let (src, dst) = (rand::random(), rand::random());
+        let stream_id = MessageId::new();
+        let mut sender = StreamManager::new();
+        let msgs = unwrap!(sender.start(stream_id, src, dst, &[1, 2, 3]));
+        assert_eq!(msgs.len(), 1);
+
+        // Nothing is resent before the timeout.
+        let (resent, events) = unwrap!(sender.resume_stalled());
+        assert!(resent.is_empty());
+        assert!(events.is_empty());
+
+        // The stalled chunk is resent up to the maximum number of attempts.
+        let timeout = Duration::from_secs(STREAM_RESUME_TIMEOUT_SECS + 1);
+        let mut now = Instant::now();
+        let mut last_msg = msgs[0].clone();
+        for _ in 0..MAX_STREAM_RESUME_ATTEMPTS {
+            now += timeout;
+            let (resent, events) = unwrap!(sender.resume_stalled_at(now));
+            assert_eq!(resent.len(), 1);
+            assert_eq!(chunk_index(&resent[0]), 0);
+            assert!(events.is_empty());
+            last_msg = resent[0].clone();
+        }
+
+        // Then the stream is dropped and reported as failed.
+        now += timeout;
+        let (resent, events) = unwrap!(sender.resume_stalled_at(now));
+        assert!(resent.is_empty());
+        match events.first() {
+            Some(&Event::StreamFailed(id)) => assert_eq!(id, stream_id),
+            event => panic!("Unexpected event {:?}", event),
+        }
+        assert_eq!(events.len(), 1);
+        assert!(sender.outgoing.is_empty());
+        assert!(sender.acks.is_empty());
+
+        // A late ack is ignored.
+        let (next, complete) = unwrap!(sender.handle_ack(&unwrap!(Ack::compute(&last_msg))));
+        assert!(next.is_empty());
+        assert_eq!(complete, None);
+        now += timeout;
+        let (resent, events) = unwrap!(sender.resume_stalled_at(now));
+        assert!(resent.is_empty());
+        assert!(events.is_empty());
+    }
+}
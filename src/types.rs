@@ -17,6 +17,7 @@
 
 use maidsafe_utilities::event_sender::MaidSafeObserver;
 use rand::random;
+use rust_sodium::crypto::hash::sha256;
 use xor_name::XorName;
 
 pub type RoutingActionSender = MaidSafeObserver<::action::Action>;
@@ -60,6 +61,15 @@ impl MessageId {
         MessageId(XorName(name_mut))
     }
 
+    /// Generate a `MessageId` from the hash of this one and `salt`. All nodes that derive an ID
+    /// from the same message get the same result, so their messages accumulate.
+    pub fn derive(&self, salt: &[u8]) -> MessageId {
+        let MessageId(XorName(ref id)) = *self;
+        let mut bytes = id.to_vec();
+        bytes.extend_from_slice(salt);
+        MessageId(XorName(sha256::hash(&bytes).0))
+    }
+
     /// Generate the increment (on the MSB only) of the given `MessageId`.
     pub fn increment_first_byte(message_id: &MessageId) -> MessageId {
         let MessageId(XorName(mut vec_mut)) = *message_id;
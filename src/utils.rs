@@ -15,10 +15,12 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
-use routing_table::Xorable;
+use id::PublicId;
+use routing_table::{Authority, Xorable};
 use rust_sodium::crypto::hash::sha256;
 use std::fmt::Write;
 use std::iter;
+use std::time::{SystemTime, UNIX_EPOCH};
 use xor_name::XorName;
 
 /// Format a vector of bytes as a hexadecimal number, ellipsising all but the first and last three.
@@ -68,6 +70,20 @@ pub fn calculate_relocated_name(mut close_nodes: Vec<XorName>, original_name: &X
     XorName(sha256::hash(&combined).0)
 }
 
+/// Returns whether `pub_id` is the single node or client `authority` refers to.
+pub fn is_single_node_authority_of(authority: &Authority<XorName>, pub_id: &PublicId) -> bool {
+    match *authority {
+        Authority::ManagedNode(ref name) => name == pub_id.name(),
+        Authority::Client { ref client_key, .. } => client_key == pub_id.signing_public_key(),
+        _ => false,
+    }
+}
+
+/// Returns the current time in seconds since the UNIX epoch.
+pub fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0)
+}
+
 #[cfg(test)]
 mod tests {
     use rand;
@@ -15,11 +15,12 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
-use routing::{Authority, Event, EventStream, MessageId, QUORUM, Response, XorName};
+use routing::{Authority, Compression, Data, Event, EventStream, ImmutableData, MessageId, QUORUM,
+              Response, XorName};
 use routing::mock_crust::Network;
 use std::sync::mpsc;
-use super::{TestNode, create_connected_nodes, gen_immutable_data, poll_all,
-            sort_nodes_by_distance_to};
+use super::{TestNode, create_connected_nodes, create_connected_nodes_with_compression,
+            gen_immutable_data, poll_all, sort_nodes_by_distance_to};
 
 #[test]
 fn messages_accumulate_with_quorum() {
@@ -119,3 +120,34 @@ fn messages_accumulate_with_quorum() {
         expect_no_event!(node);
     }
 }
+
+#[test]
+fn compressed_messages_accumulate() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+    // Only every other node compresses the messages it sends.
+    let mut nodes = create_connected_nodes_with_compression(&network, min_section_size, |i| {
+        if i % 2 == 0 {
+            Compression::Deflate
+        } else {
+            Compression::None
+        }
+    });
+
+    // A large, highly compressible message, sent from the whole section to itself: the signed
+    // parts must be identical whatever the senders' compression settings are.
+    let data = Data::Immutable(ImmutableData::new(vec![7; 100 * 1024]));
+    let src = Authority::NaeManager(*data.name());
+    let dst = Authority::Section(src.name());
+    let message_id = MessageId::new();
+    for node in &mut *nodes {
+        unwrap!(node.inner.send_get_success(src, dst, data.clone(), message_id));
+    }
+    let _ = poll_all(&mut nodes, &mut []);
+    for node in &mut *nodes {
+        expect_any_event!(node, Event::Response {
+            response: Response::GetSuccess(ref got, id), ..
+        } if *got == data && id == message_id);
+        expect_no_event!(node);
+    }
+}
@@ -0,0 +1,65 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.1.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use routing::{Event, EventStream, InterfaceError};
+use routing::mock_crust::Network;
+use super::{create_connected_nodes_until_split, poll_all};
+
+#[test]
+fn broadcast_reaches_all_nodes_once() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+    let mut nodes = create_connected_nodes_until_split(&network, vec![1, 2, 2], false);
+    for node in &mut *nodes {
+        while let Ok(_) = node.try_next_ev() {}
+    }
+
+    let origin = nodes[0].name();
+    let content = b"protocol upgrade".to_vec();
+    unwrap!(nodes[0].inner.send_broadcast(content.clone()));
+    let _ = poll_all(&mut nodes, &mut []);
+
+    for node in &mut *nodes {
+        expect_any_event!(node,
+                          Event::Broadcast { origin: ref src, ref payload, .. }
+                              if *src == origin && *payload == content);
+        while let Ok(event) = node.try_next_ev() {
+            if let Event::Broadcast { .. } = event {
+                panic!("{} received the broadcast more than once.", node.name());
+            }
+        }
+    }
+}
+
+#[test]
+fn broadcast_rate_limit() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+    let mut nodes = create_connected_nodes_until_split(&network, vec![1, 1], false);
+
+    let mut sent = 0;
+    loop {
+        match nodes[0].inner.send_broadcast(vec![sent as u8]) {
+            Ok(()) => sent += 1,
+            Err(InterfaceError::RateLimitExceeded) => break,
+            Err(error) => panic!("Unexpected error {:?}", error),
+        }
+        assert!(sent <= 100, "Broadcasts are not rate limited.");
+    }
+    assert!(sent > 0);
+    let _ = poll_all(&mut nodes, &mut []);
+}
@@ -15,11 +15,14 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
+use maidsafe_utilities::serialisation::serialised_size;
 use rand::Rng;
-use routing::{Authority, Data, Event, EventStream, MessageId, Prefix, Request, Response};
+use routing::{Authority, CacheMetrics, Data, Event, EventStream, MessageId, Prefix, Request,
+              Response, StructuredData, XorName};
 use routing::mock_crust::Network;
+use std::collections::BTreeSet;
 use std::sync::mpsc;
-use super::{TestNode, create_connected_clients, create_connected_nodes_until_split,
+use super::{TestClient, TestNode, create_connected_clients, create_connected_nodes_until_split,
             gen_immutable_data, poll_all};
 
 // Generate random immutable data, but make sure the first node in the given
@@ -39,6 +42,39 @@ fn gen_immutable_data_not_in_first_node_section<T: Rng>(rng: &mut T, nodes: &[Te
     }
 }
 
+// Sends a `Get` request for `data` from the first client, has the nodes answer it if it reaches
+// them and returns the source of the response the client receives.
+fn get_data(nodes: &mut [TestNode],
+            clients: &mut [TestClient],
+            data: &Data,
+            message_id: MessageId)
+            -> Authority<XorName> {
+    let dst = Authority::NaeManager(*data.name());
+    unwrap!(clients[0].inner.send_get_request(dst, data.identifier(), message_id));
+    poll_all(nodes, clients);
+    for node in nodes.iter_mut() {
+        while let Ok(event) = node.try_next_ev() {
+            if let Event::Request { request: Request::Get(_, req_message_id), src, dst } = event {
+                unwrap!(node.inner.send_get_success(dst, src, data.clone(), req_message_id));
+            }
+        }
+    }
+    poll_all(nodes, clients);
+
+    let mut response_src = None;
+    while let Ok(event) = clients[0].inner.try_next_ev() {
+        if let Event::Response { response: Response::GetSuccess(ref res_data, res_message_id),
+                                 ref src,
+                                 .. } = event {
+            if res_data == data && res_message_id == message_id {
+                assert!(response_src.is_none());
+                response_src = Some(*src);
+            }
+        }
+    }
+    unwrap!(response_src)
+}
+
 #[test]
 fn response_caching() {
     let min_section_size = 8;
@@ -131,3 +167,161 @@ fn response_caching() {
         expect_no_event!(node);
     }
 }
+
+#[test]
+fn cache_invalidation() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes_until_split(&network, vec![1, 1], true);
+    let mut clients = create_connected_clients(&network, &mut nodes, 1);
+
+    // As in `response_caching`, the data must not be in the proxy node's section.
+    let prefix = Prefix::new(1, nodes[0].name());
+    let mut name: XorName = rng.gen();
+    while prefix.matches(&name) {
+        name = rng.gen();
+    }
+    let data = Data::Structured(unwrap!(StructuredData::new(0, name, 0, vec![], BTreeSet::new())));
+    let data_id = data.identifier();
+    let dst = Authority::NaeManager(name);
+
+    // The proxy node caches the response to the first request.
+    unwrap!(clients[0].inner.send_get_request(dst, data_id, MessageId::new()));
+    poll_all(&mut nodes, &mut clients);
+    for node in &mut *nodes {
+        while let Ok(event) = node.try_next_ev() {
+            if let Event::Request { request: Request::Get(_, message_id), src, dst } = event {
+                unwrap!(node.inner.send_get_success(dst, src, data.clone(), message_id));
+            }
+        }
+    }
+    poll_all(&mut nodes, &mut clients);
+    expect_any_event!(clients[0], Event::Response { response: Response::GetSuccess(..), .. });
+    while let Ok(_) = clients[0].inner.try_next_ev() {}
+
+    let proxy_node_name = nodes[0].name();
+    unwrap!(clients[0].inner.send_get_request(dst, data_id, MessageId::new()));
+    poll_all(&mut nodes, &mut clients);
+    expect_any_event!(
+        clients[0],
+        Event::Response {
+            response: Response::GetSuccess(..),
+            src: Authority::ManagedNode(src_name),
+            ..
+        } if src_name == proxy_node_name
+    );
+    while let Ok(_) = clients[0].inner.try_next_ev() {}
+
+    // Updating the data removes it from the proxy node's cache, even if the success response is
+    // encrypted so that the proxy node can't read it.
+    let client_id = *clients[0].full_id.public_id();
+    unwrap!(clients[0].inner.send_post_request(dst, data.clone(), MessageId::new()));
+    poll_all(&mut nodes, &mut clients);
+    let mut responded = false;
+    for node in &mut *nodes {
+        while let Ok(event) = node.try_next_ev() {
+            if let Event::Request { request: Request::Post(_, message_id), src, .. } = event {
+                if !responded {
+                    let node_auth = Authority::ManagedNode(node.name());
+                    let response = Response::PostSuccess(data_id, message_id);
+                    unwrap!(node.inner
+                        .send_encrypted_response(node_auth, src, &client_id, response));
+                    responded = true;
+                }
+            }
+        }
+    }
+    assert!(responded);
+    poll_all(&mut nodes, &mut clients);
+    expect_any_event!(clients[0], Event::Response { response: Response::PostSuccess(..), .. });
+    while let Ok(_) = clients[0].inner.try_next_ev() {}
+
+    // So the next request reaches the data's section again.
+    unwrap!(clients[0].inner.send_get_request(dst, data_id, MessageId::new()));
+    poll_all(&mut nodes, &mut clients);
+    let mut request_count = 0;
+    for node in &mut *nodes {
+        while let Ok(event) = node.try_next_ev() {
+            if let Event::Request { request: Request::Get(..), .. } = event {
+                request_count += 1;
+            }
+        }
+    }
+    assert!(request_count > 0);
+}
+
+#[test]
+fn cache_metrics() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes_until_split(&network, vec![1, 1], true);
+    let mut clients = create_connected_clients(&network, &mut nodes, 1);
+    let proxy_node_name = nodes[0].name();
+
+    // As in `response_caching`, the data must not be in the proxy node's section.
+    let immutable = gen_immutable_data_not_in_first_node_section(&mut rng, &nodes);
+    let prefix = Prefix::new(1, proxy_node_name);
+    let mut name: XorName = rng.gen();
+    while prefix.matches(&name) {
+        name = rng.gen();
+    }
+    let structured =
+        Data::Structured(unwrap!(StructuredData::new(0, name, 0, vec![], BTreeSet::new())));
+
+    let mut expected = CacheMetrics::default();
+    assert_eq!(unwrap!(nodes[0].inner.cache_metrics()), expected);
+
+    // The first request misses and is answered by the data's section, whose response the proxy
+    // node caches.
+    let src = get_data(&mut nodes, &mut clients, &immutable, MessageId::new());
+    assert_eq!(src, Authority::NaeManager(*immutable.name()));
+    expected.immutable.misses = 1;
+    expected.immutable.puts = 1;
+    assert_eq!(unwrap!(nodes[0].inner.cache_metrics()), expected);
+
+    // The second one is answered from the cache, counting the serialised size of the response.
+    let message_id = MessageId::new();
+    let src = get_data(&mut nodes, &mut clients, &immutable, message_id);
+    assert_eq!(src, Authority::ManagedNode(proxy_node_name));
+    expected.immutable.hits = 1;
+    expected.immutable.bytes_served =
+        serialised_size(&Response::GetSuccess(immutable.clone(), message_id));
+    assert_eq!(unwrap!(nodes[0].inner.cache_metrics()), expected);
+
+    // Structured data is counted separately.
+    let src = get_data(&mut nodes, &mut clients, &structured, MessageId::new());
+    assert_eq!(src, Authority::NaeManager(name));
+    expected.structured.misses = 1;
+    expected.structured.puts = 1;
+    assert_eq!(unwrap!(nodes[0].inner.cache_metrics()), expected);
+
+    let message_id = MessageId::new();
+    let src = get_data(&mut nodes, &mut clients, &structured, message_id);
+    assert_eq!(src, Authority::ManagedNode(proxy_node_name));
+    expected.structured.hits = 1;
+    expected.structured.bytes_served =
+        serialised_size(&Response::GetSuccess(structured.clone(), message_id));
+    assert_eq!(unwrap!(nodes[0].inner.cache_metrics()), expected);
+}
+
+#[test]
+fn cache_metrics_without_cache() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes_until_split(&network, vec![1, 1], false);
+    let mut clients = create_connected_clients(&network, &mut nodes, 1);
+
+    // Nothing is counted if the nodes don't have a cache.
+    let data = gen_immutable_data_not_in_first_node_section(&mut rng, &nodes);
+    for _ in 0..2 {
+        let src = get_data(&mut nodes, &mut clients, &data, MessageId::new());
+        assert_eq!(src, Authority::NaeManager(*data.name()));
+    }
+    assert_eq!(unwrap!(nodes[0].inner.cache_metrics()), CacheMetrics::default());
+}
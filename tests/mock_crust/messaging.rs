@@ -0,0 +1,374 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.1.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use routing::{Authority, Event, EventStream, MessageId, Request, Response, XorName};
+use routing::messaging::{MAX_INBOX_SIZE, MAX_OUTBOX_SIZE, MemoryMpidStorage, MessageStatus,
+                         MpidManager, MpidMessage, MpidMessageWrapper, MpidReceipt, MpidStorage,
+                         ReceiptKind};
+use routing::mock_crust::Network;
+use super::{TestClient, TestNode, create_connected_clients, create_connected_nodes, gen_bytes,
+            poll_all};
+
+/// Polls the network and lets each node's manager handle the messaging requests it receives,
+/// until no more are sent.
+fn poll_and_handle(nodes: &mut [TestNode],
+                   clients: &mut [TestClient],
+                   managers: &mut [MpidManager<MemoryMpidStorage>]) {
+    poll_and_handle_with(nodes, clients, managers, false)
+}
+
+/// Like `poll_and_handle`, but sends the failure notifications for rejected requests instead of
+/// panicking.
+fn poll_and_handle_failures(nodes: &mut [TestNode],
+                            clients: &mut [TestClient],
+                            managers: &mut [MpidManager<MemoryMpidStorage>]) {
+    poll_and_handle_with(nodes, clients, managers, true)
+}
+
+fn poll_and_handle_with(nodes: &mut [TestNode],
+                        clients: &mut [TestClient],
+                        managers: &mut [MpidManager<MemoryMpidStorage>],
+                        allow_failures: bool) {
+    loop {
+        let _ = poll_all(nodes, clients);
+        let mut handled = false;
+        for (node, manager) in nodes.iter_mut().zip(managers.iter_mut()) {
+            while let Ok(event) = node.try_next_ev() {
+                if let Event::Request { request: Request::Messaging(wrapper, id), src, dst } =
+                    event {
+                    handled = true;
+                    let outgoing = match manager.handle(src, dst, wrapper, id) {
+                        Ok(outgoing) => outgoing,
+                        Err(failure) => {
+                            assert!(allow_failures, "Unexpected failure: {:?}", failure);
+                            failure.response.into_iter().collect()
+                        }
+                    };
+                    for outgoing in outgoing {
+                        unwrap!(node.inner.send_mpid_message(outgoing));
+                    }
+                }
+            }
+        }
+        if !handled {
+            return;
+        }
+    }
+}
+
+/// Returns the next messaging response received by the client.
+fn expect_messaging_response(client: &mut TestClient) -> MpidMessageWrapper {
+    loop {
+        match client.inner.try_next_ev() {
+            Ok(Event::Response { response: Response::Messaging(wrapper, _), .. }) => {
+                return wrapper
+            }
+            Ok(_) => (),
+            other => panic!("Expected a messaging response, got {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn send_fetch_and_delete_message() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes(&network, min_section_size + 1);
+    let mut clients = create_connected_clients(&network, &mut nodes, 2);
+    let mut managers: Vec<_> = (0..nodes.len())
+        .map(|_| MpidManager::new(MemoryMpidStorage::new()))
+        .collect();
+
+    let sender = clients[0].name();
+    let recipient = clients[1].name();
+    let body = gen_bytes(&mut rng, 100);
+    let message = unwrap!(MpidMessage::new(sender,
+                                           vec![],
+                                           recipient,
+                                           body.clone(),
+                                           clients[0].full_id.signing_private_key()));
+    let name = unwrap!(message.name());
+
+    // The sender stores the message in its outbox, and the header is passed on to the
+    // recipient's inbox.
+    unwrap!(clients[0].inner.send_messaging_request(Authority::ClientManager(sender),
+                                                    MpidMessageWrapper::PutMessage(message),
+                                                    MessageId::new()));
+    poll_and_handle(&mut nodes, &mut clients, &mut managers);
+
+    // Once online, the recipient is notified of the header.
+    unwrap!(clients[1].inner.send_messaging_request(Authority::ClientManager(recipient),
+                                                    MpidMessageWrapper::Online,
+                                                    MessageId::new()));
+    poll_and_handle(&mut nodes, &mut clients, &mut managers);
+    let header = match expect_messaging_response(&mut clients[1]) {
+        MpidMessageWrapper::PutHeader(header) => header,
+        wrapper => panic!("Expected a header, got {:?}", wrapper),
+    };
+    assert_eq!(*header.sender(), sender);
+    assert_eq!(unwrap!(header.name()), name);
+
+    // The recipient fetches the message from the sender's outbox.
+    unwrap!(clients[1].inner.send_messaging_request(Authority::ClientManager(recipient),
+                                                    MpidMessageWrapper::GetMessage(header),
+                                                    MessageId::new()));
+    poll_and_handle(&mut nodes, &mut clients, &mut managers);
+    match expect_messaging_response(&mut clients[1]) {
+        MpidMessageWrapper::PutMessage(received) => {
+            assert!(received.verify(clients[0].full_id.public_id().signing_public_key()));
+            assert_eq!(*received.body(), body);
+        }
+        wrapper => panic!("Expected a message, got {:?}", wrapper),
+    }
+
+    // Deleting it removes both the header from the inbox and the message from the outbox.
+    unwrap!(clients[1].inner.send_messaging_request(Authority::ClientManager(recipient),
+                                                    MpidMessageWrapper::DeleteMessage(name),
+                                                    MessageId::new()));
+    poll_and_handle(&mut nodes, &mut clients, &mut managers);
+    unwrap!(clients[0].inner.send_messaging_request(Authority::ClientManager(sender),
+                                                    MpidMessageWrapper::OutboxHas(vec![name]),
+                                                    MessageId::new()));
+    poll_and_handle(&mut nodes, &mut clients, &mut managers);
+    match expect_messaging_response(&mut clients[0]) {
+        MpidMessageWrapper::OutboxHasResponse(headers) => assert!(headers.is_empty()),
+        wrapper => panic!("Expected an outbox response, got {:?}", wrapper),
+    }
+    for manager in &managers {
+        assert!(manager.storage().inbox_headers(&recipient).is_empty());
+        assert!(manager.storage().outbox_headers(&sender).is_empty());
+    }
+}
+
+/// Sends `wrapper` from the client to its own managers and lets them handle it.
+fn send_to_managers(nodes: &mut [TestNode],
+                    clients: &mut [TestClient],
+                    managers: &mut [MpidManager<MemoryMpidStorage>],
+                    index: usize,
+                    wrapper: MpidMessageWrapper) {
+    let dst = Authority::ClientManager(clients[index].name());
+    unwrap!(clients[index].inner.send_messaging_request(dst, wrapper, MessageId::new()));
+    poll_and_handle(nodes, clients, managers);
+}
+
+/// Returns the status of the message `name` in the outbox of the client `index`.
+fn message_status(nodes: &mut [TestNode],
+                  clients: &mut [TestClient],
+                  managers: &mut [MpidManager<MemoryMpidStorage>],
+                  index: usize,
+                  name: XorName)
+                  -> MessageStatus {
+    let wrapper = MpidMessageWrapper::GetMessageStatus(vec![name]);
+    send_to_managers(nodes, clients, managers, index, wrapper);
+    match expect_messaging_response(&mut clients[index]) {
+        MpidMessageWrapper::GetMessageStatusResponse(ref statuses) if statuses.len() == 1 => {
+            assert_eq!(statuses[0].0, name);
+            statuses[0].1
+        }
+        wrapper => panic!("Expected a status response, got {:?}", wrapper),
+    }
+}
+
+#[test]
+fn delivery_and_read_receipts() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+    let mut nodes = create_connected_nodes(&network, min_section_size + 1);
+    let mut clients = create_connected_clients(&network, &mut nodes, 2);
+    let mut managers: Vec<_> = (0..nodes.len())
+        .map(|_| MpidManager::new(MemoryMpidStorage::new()))
+        .collect();
+
+    let sender = clients[0].name();
+    let recipient = clients[1].name();
+    let message = unwrap!(MpidMessage::new(sender,
+                                           vec![],
+                                           recipient,
+                                           vec![1, 2, 3],
+                                           clients[0].full_id.signing_private_key()));
+    let name = unwrap!(message.name());
+
+    // The sender is online to receive the receipts.
+    send_to_managers(&mut nodes, &mut clients, &mut managers, 0, MpidMessageWrapper::Online);
+    let wrapper = MpidMessageWrapper::PutMessage(message);
+    send_to_managers(&mut nodes, &mut clients, &mut managers, 0, wrapper);
+    assert_eq!(message_status(&mut nodes, &mut clients, &mut managers, 0, name),
+               MessageStatus::Stored);
+
+    // The recipient confirms receiving the header.
+    send_to_managers(&mut nodes, &mut clients, &mut managers, 1, MpidMessageWrapper::Online);
+    let header = match expect_messaging_response(&mut clients[1]) {
+        MpidMessageWrapper::PutHeader(header) => header,
+        wrapper => panic!("Expected a header, got {:?}", wrapper),
+    };
+    let receipt = unwrap!(MpidReceipt::new(&header,
+                                           ReceiptKind::Delivered,
+                                           clients[1].full_id.public_id().signing_public_key(),
+                                           clients[1].full_id.signing_private_key()));
+    let wrapper = MpidMessageWrapper::DeliveryReceipt(receipt.clone());
+    send_to_managers(&mut nodes, &mut clients, &mut managers, 1, wrapper);
+    match expect_messaging_response(&mut clients[0]) {
+        MpidMessageWrapper::DeliveryReceipt(received) => assert_eq!(received, receipt),
+        wrapper => panic!("Expected a delivery receipt, got {:?}", wrapper),
+    }
+    assert_eq!(message_status(&mut nodes, &mut clients, &mut managers, 0, name),
+               MessageStatus::Notified);
+
+    // The recipient fetches the message.
+    let wrapper = MpidMessageWrapper::GetMessage(header.clone());
+    send_to_managers(&mut nodes, &mut clients, &mut managers, 1, wrapper);
+    match expect_messaging_response(&mut clients[1]) {
+        MpidMessageWrapper::PutMessage(_) => (),
+        wrapper => panic!("Expected a message, got {:?}", wrapper),
+    }
+    assert_eq!(message_status(&mut nodes, &mut clients, &mut managers, 0, name),
+               MessageStatus::Fetched);
+
+    // The recipient confirms reading it.
+    let receipt = unwrap!(MpidReceipt::new(&header,
+                                           ReceiptKind::Read,
+                                           clients[1].full_id.public_id().signing_public_key(),
+                                           clients[1].full_id.signing_private_key()));
+    let wrapper = MpidMessageWrapper::ReadReceipt(receipt.clone());
+    send_to_managers(&mut nodes, &mut clients, &mut managers, 1, wrapper);
+    match expect_messaging_response(&mut clients[0]) {
+        MpidMessageWrapper::ReadReceipt(received) => assert_eq!(received, receipt),
+        wrapper => panic!("Expected a read receipt, got {:?}", wrapper),
+    }
+    assert_eq!(message_status(&mut nodes, &mut clients, &mut managers, 0, name),
+               MessageStatus::Read);
+    for manager in &managers {
+        assert_eq!(manager.storage().message_status(&sender, &name),
+                   Some(MessageStatus::Read));
+    }
+}
+
+#[test]
+fn read_receipt_after_delete() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+    let mut nodes = create_connected_nodes(&network, min_section_size + 1);
+    let mut clients = create_connected_clients(&network, &mut nodes, 2);
+    let mut managers: Vec<_> = (0..nodes.len())
+        .map(|_| MpidManager::new(MemoryMpidStorage::new()))
+        .collect();
+
+    let sender = clients[0].name();
+    let recipient = clients[1].name();
+    let message = unwrap!(MpidMessage::new(sender,
+                                           vec![],
+                                           recipient,
+                                           vec![1, 2, 3],
+                                           clients[0].full_id.signing_private_key()));
+    let name = unwrap!(message.name());
+
+    send_to_managers(&mut nodes, &mut clients, &mut managers, 0, MpidMessageWrapper::Online);
+    let wrapper = MpidMessageWrapper::PutMessage(message);
+    send_to_managers(&mut nodes, &mut clients, &mut managers, 0, wrapper);
+
+    // The recipient fetches the message and deletes it before confirming reading it.
+    send_to_managers(&mut nodes, &mut clients, &mut managers, 1, MpidMessageWrapper::Online);
+    let header = match expect_messaging_response(&mut clients[1]) {
+        MpidMessageWrapper::PutHeader(header) => header,
+        wrapper => panic!("Expected a header, got {:?}", wrapper),
+    };
+    let wrapper = MpidMessageWrapper::GetMessage(header.clone());
+    send_to_managers(&mut nodes, &mut clients, &mut managers, 1, wrapper);
+    match expect_messaging_response(&mut clients[1]) {
+        MpidMessageWrapper::PutMessage(_) => (),
+        wrapper => panic!("Expected a message, got {:?}", wrapper),
+    }
+    let wrapper = MpidMessageWrapper::DeleteMessage(name);
+    send_to_managers(&mut nodes, &mut clients, &mut managers, 1, wrapper);
+    for manager in &managers {
+        assert!(manager.storage().outbox_headers(&sender).is_empty());
+    }
+    assert_eq!(message_status(&mut nodes, &mut clients, &mut managers, 0, name),
+               MessageStatus::Fetched);
+
+    // The receipt still reaches the sender.
+    let receipt = unwrap!(MpidReceipt::new(&header,
+                                           ReceiptKind::Read,
+                                           clients[1].full_id.public_id().signing_public_key(),
+                                           clients[1].full_id.signing_private_key()));
+    let wrapper = MpidMessageWrapper::ReadReceipt(receipt.clone());
+    send_to_managers(&mut nodes, &mut clients, &mut managers, 1, wrapper);
+    match expect_messaging_response(&mut clients[0]) {
+        MpidMessageWrapper::ReadReceipt(received) => assert_eq!(received, receipt),
+        wrapper => panic!("Expected a read receipt, got {:?}", wrapper),
+    }
+    assert_eq!(message_status(&mut nodes, &mut clients, &mut managers, 0, name),
+               MessageStatus::Read);
+
+    // Once the sender deletes it too, the status is gone.
+    let wrapper = MpidMessageWrapper::DeleteMessage(name);
+    send_to_managers(&mut nodes, &mut clients, &mut managers, 0, wrapper);
+    for manager in &managers {
+        assert_eq!(manager.storage().message_status(&sender, &name), None);
+    }
+}
+
+#[test]
+fn failures_reach_sender() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+    let mut nodes = create_connected_nodes(&network, min_section_size + 1);
+    let mut clients = create_connected_clients(&network, &mut nodes, 2);
+
+    let sender = clients[0].name();
+    let recipient = clients[1].name();
+    let message = unwrap!(MpidMessage::new(sender,
+                                           vec![],
+                                           recipient,
+                                           vec![],
+                                           clients[0].full_id.signing_private_key()));
+    let name = unwrap!(message.name());
+
+    // The recipient's inbox is full: its managers notify the sender's, which pass it on to the
+    // online sender.
+    let mut managers: Vec<_> = (0..nodes.len())
+        .map(|_| MpidManager::with_quotas(MemoryMpidStorage::new(), 1, MAX_OUTBOX_SIZE))
+        .collect();
+    unwrap!(clients[0].inner.send_messaging_request(Authority::ClientManager(sender),
+                                                    MpidMessageWrapper::Online,
+                                                    MessageId::new()));
+    unwrap!(clients[0].inner.send_messaging_request(Authority::ClientManager(sender),
+                                                    MpidMessageWrapper::PutMessage(message.clone()),
+                                                    MessageId::new()));
+    poll_and_handle_failures(&mut nodes, &mut clients, &mut managers);
+    match expect_messaging_response(&mut clients[0]) {
+        MpidMessageWrapper::Failure { name: failed, .. } => assert_eq!(failed, Some(name)),
+        wrapper => panic!("Expected a failure, got {:?}", wrapper),
+    }
+
+    // The sender's outbox is full: its managers reply to the request directly.
+    let mut managers: Vec<_> = (0..nodes.len())
+        .map(|_| MpidManager::with_quotas(MemoryMpidStorage::new(), MAX_INBOX_SIZE, 1))
+        .collect();
+    let message_id = MessageId::new();
+    unwrap!(clients[0].inner.send_messaging_request(Authority::ClientManager(sender),
+                                                    MpidMessageWrapper::PutMessage(message),
+                                                    message_id));
+    poll_and_handle_failures(&mut nodes, &mut clients, &mut managers);
+    expect_any_event!(clients[0], Event::Response {
+        response: Response::Messaging(MpidMessageWrapper::Failure { name: failed, .. }, id), ..
+    } if id == message_id && failed == Some(name));
+    for manager in &managers {
+        assert!(manager.storage().outbox_headers(&sender).is_empty());
+    }
+}
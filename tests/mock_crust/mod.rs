@@ -16,11 +16,14 @@
 // relating to use of the SAFE Network Software.
 
 mod accumulate;
+mod broadcast;
 mod cache;
 mod churn;
 mod drop;
 mod merge;
+mod messaging;
 mod requests;
+mod stream;
 mod utils;
 
 use itertools::Itertools;
@@ -28,7 +31,8 @@ use routing::{Event, EventStream, Prefix, XOR_NAME_LEN, XorName};
 use routing::mock_crust::{Config, Endpoint, Network};
 use routing::mock_crust::crust::PeerId;
 pub use self::utils::{Nodes, TestClient, TestNode, create_connected_clients,
-                      create_connected_nodes, create_connected_nodes_until_split, gen_bytes,
+                      create_connected_nodes, create_connected_nodes_until_split,
+                      create_connected_nodes_with_compression, gen_bytes,
                       gen_immutable_data, gen_range_except, poll_all, poll_and_resend,
                       sort_nodes_by_distance_to, verify_invariant_for_all_nodes};
 
@@ -15,11 +15,17 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
-use routing::{Authority, Data, DataIdentifier, Event, EventStream, ImmutableData, MessageId,
-              Request, Response};
-use routing::mock_crust::Network;
-use super::{create_connected_clients, create_connected_nodes, gen_bytes, gen_immutable_data,
-            poll_all};
+use rand::Rng;
+use routing::{Authority, BatchItemResult, Condition, Data, DataIdentifier, Event, EventStream,
+              FullId, ImmutableData, InterfaceError, MerkleTree, MessageId, PrefixSet, Request,
+              Response, StructuredData, content_hash};
+use routing::mock_crust::{Config, Endpoint, Network};
+use std::collections::BTreeSet;
+use std::iter;
+use std::sync::mpsc;
+use super::{TestNode, create_connected_clients, create_connected_nodes,
+            create_connected_nodes_until_split, gen_bytes, gen_immutable_data, poll_all,
+            poll_and_resend};
 
 #[test]
 fn successful_put_request() {
@@ -129,6 +135,180 @@ fn successful_get_request() {
     assert!(response_received_count == 1);
 }
 
+#[test]
+fn encrypted_get_response() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes(&network, min_section_size + 1);
+    let mut clients = create_connected_clients(&network, &mut nodes, 1);
+    let client_id = *clients[0].full_id.public_id();
+
+    let data = gen_immutable_data(&mut rng, 1024);
+    let node_name = nodes[1].name();
+    let message_id = MessageId::new();
+    unwrap!(clients[0].inner.send_get_request(Authority::ManagedNode(node_name),
+                                              data.identifier(),
+                                              message_id));
+    let _ = poll_all(&mut nodes, &mut clients);
+
+    let mut request_auths = None;
+    while request_auths.is_none() {
+        match nodes[1].try_next_ev() {
+            Ok(Event::Request { request: Request::Get(_, id), src, dst }) => {
+                if message_id == id {
+                    request_auths = Some((src, dst));
+                }
+            }
+            Ok(_) => (),
+            _ => panic!("Event::Request not received"),
+        }
+    }
+    let (src, dst) = unwrap!(request_auths);
+    let response = Response::GetSuccess(data.clone(), message_id);
+
+    // A section can't send an encrypted response, as its members' encryptions would differ.
+    match nodes[1].inner.send_encrypted_response(Authority::NaeManager(*data.name()),
+                                                 src,
+                                                 &client_id,
+                                                 response.clone()) {
+        Err(InterfaceError::InvalidSource) => (),
+        result => panic!("Unexpected result {:?}", result),
+    }
+
+    unwrap!(nodes[1].inner.send_encrypted_response(dst, src, &client_id, response));
+    let _ = poll_all(&mut nodes, &mut clients);
+    expect_any_event!(clients[0], Event::Response {
+        response: Response::GetSuccess(ref got, id),
+        src: Authority::ManagedNode(src_name),
+        ..
+    } if *got == data && id == message_id && src_name == node_name);
+    expect_no_event!(clients[0]);
+}
+
+#[test]
+fn successful_get_request_with_proof() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes(&network, min_section_size + 1);
+    let mut clients = create_connected_clients(&network, &mut nodes, 1);
+
+    let data = gen_immutable_data(&mut rng, 1024);
+    let other_data = gen_immutable_data(&mut rng, 1024);
+    let tree = unwrap!(MerkleTree::new(vec![&data, &other_data]));
+    let dst = Authority::NaeManager(*data.name());
+    let version = 7;
+    for node in nodes.iter_mut().filter(|n| n.is_recipient(&dst)) {
+        unwrap!(node.inner.publish_data_root(version, tree.root()));
+    }
+    let _ = poll_all(&mut nodes, &mut clients);
+    let section_key = unwrap!(unwrap!(nodes.iter().find(|n| n.is_recipient(&dst)))
+        .inner
+        .section_key());
+
+    let message_id = MessageId::new();
+    unwrap!(clients[0].inner.send_get_with_proof_request(dst, data.identifier(), message_id));
+    let _ = poll_all(&mut nodes, &mut clients);
+
+    for node in nodes.iter_mut().filter(|n| n.is_recipient(&dst)) {
+        loop {
+            match node.try_next_ev() {
+                Ok(Event::Request { request: Request::GetWithProof(data_id, id), src, dst }) => {
+                    let merkle_proof = unwrap!(tree.proof(&data_id));
+                    let proof =
+                        unwrap!(node.inner.data_proof(merkle_proof, version, tree.root()));
+                    unwrap!(node.inner.send_get_success_with_proof(dst,
+                                                                   src,
+                                                                   data.clone(),
+                                                                   proof,
+                                                                   id));
+                    break;
+                }
+                Ok(_) => (),
+                _ => panic!("Event::Request not received"),
+            }
+        }
+    }
+    let _ = poll_all(&mut nodes, &mut clients);
+
+    loop {
+        match clients[0].inner.try_next_ev() {
+            Ok(Event::Response {
+                response: Response::GetSuccessWithProof(ref received, ref proof, id), ..
+            }) => {
+                assert_eq!(message_id, id);
+                assert_eq!(data, *received);
+                unwrap!(proof.verify(received, &section_key, version));
+                assert!(proof.verify(&other_data, &section_key, version).is_err());
+                assert!(proof.verify(received, &section_key, version + 1).is_err());
+                break;
+            }
+            Ok(_) => (),
+            _ => panic!("Event::Response not received"),
+        }
+    }
+}
+
+#[test]
+fn successful_get_batch_request() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes(&network, min_section_size + 1);
+    let mut clients = create_connected_clients(&network, &mut nodes, 1);
+
+    let data = gen_immutable_data(&mut rng, 1024);
+    let missing_data = gen_immutable_data(&mut rng, 1024);
+    let dst = Authority::NaeManager(*data.name());
+    let data_ids = vec![data.identifier(), missing_data.identifier()];
+    let results = vec![BatchItemResult::Success(data.clone()),
+                       BatchItemResult::Failure {
+                           data_id: missing_data.identifier(),
+                           external_error_indicator: vec![],
+                       }];
+    let message_id = MessageId::new();
+
+    assert!(clients[0]
+        .inner
+        .send_get_batch_request(dst, data_ids.clone(), message_id)
+        .is_ok());
+
+    let _ = poll_all(&mut nodes, &mut clients);
+
+    let mut request_received_count = 0;
+
+    for node in nodes.iter_mut().filter(|n| n.is_recipient(&dst)) {
+        loop {
+            match node.try_next_ev() {
+                Ok(Event::Request { request: Request::GetBatch(ref request, id), src, dst }) => {
+                    request_received_count += 1;
+                    if data_ids == *request && message_id == id {
+                        if let Err(err) = node.inner
+                            .send_get_batch_response(dst, src, results.clone(), id) {
+                            trace!("Failed to send GetBatchResponse: {:?}", err);
+                        }
+                        break;
+                    }
+                }
+                Ok(_) => (),
+                _ => panic!("Event::Request not received"),
+            }
+        }
+    }
+
+    // TODO: Assert a quorum here.
+    assert!(2 * request_received_count > min_section_size);
+
+    let _ = poll_all(&mut nodes, &mut clients);
+
+    expect_any_event!(clients[0],
+                      Event::Response {
+                          response: Response::GetBatchResponse { id, results: ref received },
+                          ..
+                      } if id == message_id && *received == results);
+}
+
 #[test]
 fn failed_get_request() {
     let min_section_size = 8;
@@ -251,3 +431,236 @@ fn disconnect_on_get_request() {
         }
     }
 }
+
+#[test]
+fn get_request_to_multiple_sections() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes_until_split(&network, vec![1, 1], false);
+    let mut clients = create_connected_clients(&network, &mut nodes, 1);
+
+    let data = gen_immutable_data(&mut rng, 1024);
+    let prefix = *nodes[0].routing_table().our_prefix();
+    let dst = Authority::Sections(PrefixSet::with_neighbours(prefix));
+    let message_id = MessageId::new();
+
+    unwrap!(clients[0].inner.send_get_request(dst, data.identifier(), message_id));
+    let _ = poll_all(&mut nodes, &mut clients);
+
+    // Every node receives the request, addressed to its own section.
+    for node in nodes.iter_mut() {
+        assert!(node.is_recipient(&dst));
+        loop {
+            match node.try_next_ev() {
+                Ok(Event::Request { request: Request::Get(_, id), src, dst }) => {
+                    if id == message_id {
+                        assert!(node.routing_table().in_authority(&dst));
+                        unwrap!(node.inner.send_get_success(dst, src, data.clone(), id));
+                        break;
+                    }
+                }
+                Ok(_) => (),
+                _ => panic!("Event::Request not received"),
+            }
+        }
+    }
+
+    let _ = poll_all(&mut nodes, &mut clients);
+
+    // The client receives a single event with one response from each section.
+    loop {
+        match clients[0].inner.try_next_ev() {
+            Ok(Event::SectionsResponse { msg_id, responses, src, .. }) => {
+                assert_eq!(message_id, msg_id);
+                assert_eq!(dst, src);
+                assert_eq!(2, responses.len());
+                let expected = Response::GetSuccess(data.clone(), msg_id);
+                assert!(responses.iter().all(|&(_, ref response)| *response == expected));
+                break;
+            }
+            Ok(Event::Response { .. }) => panic!("Unaggregated response received"),
+            Ok(_) => (),
+            _ => panic!("Event::SectionsResponse not received"),
+        }
+    }
+}
+
+#[test]
+fn get_structured_data_version() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes(&network, min_section_size + 1);
+    let mut clients = create_connected_clients(&network, &mut nodes, 1);
+
+    // Three versions, of which the two previous ones are retained.
+    let full_id = FullId::new();
+    let keys = (*full_id.public_id().signing_public_key(), full_id.signing_private_key().clone());
+    let owners = iter::once(keys.0).collect::<BTreeSet<_>>();
+    let (name, tag) = (rng.gen(), 10000);
+    let mut sd = unwrap!(StructuredData::with_history(tag, name, 0, vec![0], owners.clone(), 2));
+    for version in 1..3 {
+        let data = vec![version as u8];
+        let mut sd_new =
+            unwrap!(StructuredData::with_history(tag, name, version, data, owners.clone(), 2));
+        let _ = unwrap!(sd_new.add_signature(&keys));
+        unwrap!(sd.replace_with_other(sd_new));
+    }
+
+    let dst = Authority::NaeManager(name);
+    for &(data_id, version) in &[(DataIdentifier::StructuredVersion(name, tag, 1), 1),
+                                 (DataIdentifier::Structured(name, tag), 2)] {
+        let message_id = MessageId::new();
+        unwrap!(clients[0].inner.send_get_request(dst, data_id, message_id));
+        let _ = poll_all(&mut nodes, &mut clients);
+
+        for node in nodes.iter_mut().filter(|node| node.is_recipient(&dst)) {
+            loop {
+                match node.try_next_ev() {
+                    Ok(Event::Request { request: Request::Get(req_data_id, id), src, dst }) => {
+                        assert_eq!(data_id, req_data_id);
+                        let data = match req_data_id {
+                            DataIdentifier::StructuredVersion(_, _, version) => {
+                                unwrap!(sd.get_historic_version(version))
+                            }
+                            _ => sd.clone(),
+                        };
+                        unwrap!(node.inner.send_get_success(dst, src, Data::Structured(data), id));
+                        break;
+                    }
+                    Ok(_) => (),
+                    _ => panic!("Event::Request not received"),
+                }
+            }
+        }
+        let _ = poll_all(&mut nodes, &mut clients);
+
+        // The client receives the requested version, without the retained previous ones.
+        expect_any_event!(clients[0],
+                          Event::Response {
+                              response: Response::GetSuccess(Data::Structured(ref data), id),
+                              ..
+                          } if id == message_id && data.get_version() == version &&
+                               *data.get_data() == vec![version as u8] &&
+                               data.get_history().is_empty());
+    }
+}
+
+#[test]
+fn conditional_post_request() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes(&network, min_section_size + 1);
+    let mut clients = create_connected_clients(&network, &mut nodes, 1);
+
+    let full_id = FullId::new();
+    let keys = (*full_id.public_id().signing_public_key(), full_id.signing_private_key().clone());
+    let owners = iter::once(keys.0).collect::<BTreeSet<_>>();
+    let (name, tag) = (rng.gen(), 10000);
+    let sd = unwrap!(StructuredData::new(tag, name, 0, vec![0], owners.clone()));
+    let stored = Data::Structured(sd);
+    let stored_hash = unwrap!(content_hash(&stored));
+    let mut sd_new = unwrap!(StructuredData::new(tag, name, 1, vec![1], owners));
+    let _ = unwrap!(sd_new.add_signature(&keys));
+    let data_id = DataIdentifier::Structured(name, tag);
+
+    // The first condition expects a version that isn't stored, the second one the stored version.
+    let dst = Authority::NaeManager(name);
+    for &(condition, met) in &[(Condition::Version(1), false), (Condition::Version(0), true)] {
+        let message_id = MessageId::new();
+        unwrap!(clients[0].inner.send_conditional_post_request(dst,
+                                                               Data::Structured(sd_new.clone()),
+                                                               condition,
+                                                               message_id));
+        let _ = poll_all(&mut nodes, &mut clients);
+
+        for node in nodes.iter_mut().filter(|node| node.is_recipient(&dst)) {
+            loop {
+                match node.try_next_ev() {
+                    Ok(Event::Request {
+                        request: Request::ConditionalPost(_, req_condition, id), src, dst
+                    }) => {
+                        assert_eq!(condition, req_condition);
+                        if req_condition.is_met_by(&stored) {
+                            unwrap!(node.inner.send_post_success(dst, src, data_id, id));
+                        } else {
+                            unwrap!(node.inner.send_condition_not_met(dst,
+                                                                      src,
+                                                                      data_id,
+                                                                      stored.version(),
+                                                                      stored_hash,
+                                                                      id));
+                        }
+                        break;
+                    }
+                    Ok(_) => (),
+                    _ => panic!("Event::Request not received"),
+                }
+            }
+        }
+        let _ = poll_all(&mut nodes, &mut clients);
+
+        if met {
+            expect_any_event!(clients[0], Event::Response {
+                response: Response::PostSuccess(got_data_id, id), ..
+            } if id == message_id && got_data_id == data_id);
+        } else {
+            expect_any_event!(clients[0], Event::Response {
+                response: Response::ConditionNotMet {
+                    id, data_id: got_data_id, current_version, current_hash
+                }, ..
+            } if id == message_id && got_data_id == data_id && current_version == Some(0) &&
+                 current_hash == stored_hash);
+        }
+    }
+}
+
+#[test]
+fn data_expiry() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes(&network, min_section_size);
+
+    let mut expiring = |expiry| {
+        let mut sd = unwrap!(StructuredData::new(10000, rng.gen(), 0, vec![], BTreeSet::new()));
+        sd.set_expiry(Some(expiry));
+        Data::Structured(sd)
+    };
+    let (early, late) = (expiring(100), expiring(200));
+    let immutable = Data::Immutable(ImmutableData::new(vec![1, 2, 3]));
+    for data in &[&early, &late, &immutable] {
+        unwrap!(nodes[1].inner.track_expiry(data));
+    }
+
+    // Nothing has expired yet.
+    nodes[1].inner.expire_data(99);
+    expect_no_event!(nodes[1]);
+
+    let early_id = early.identifier();
+    nodes[1].inner.expire_data(150);
+    expect_any_event!(nodes[1], Event::DataExpired(ref ids) if *ids == vec![early_id]);
+    expect_no_event!(nodes[1]);
+
+    // The tracker survives a restart of the node.
+    let tracker = unwrap!(nodes[1].inner.expiry_tracker());
+    assert_eq!(tracker.len(), 1);
+    let _ = nodes.remove(1);
+    let _ = poll_all(&mut nodes, &mut []);
+
+    let config = Config::with_contacts(&[nodes[0].handle.endpoint()]);
+    nodes.push(TestNode::builder(&network)
+        .config(config)
+        .endpoint(Endpoint(min_section_size))
+        .expiry_tracker(tracker)
+        .create());
+    poll_and_resend(&mut nodes, &mut []);
+
+    let late_id = late.identifier();
+    let restarted = nodes.len() - 1;
+    expect_any_event!(nodes[restarted], Event::Connected);
+    nodes[restarted].inner.expire_data(250);
+    expect_any_event!(nodes[restarted], Event::DataExpired(ref ids) if *ids == vec![late_id]);
+}
@@ -0,0 +1,56 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement, version 1.1.  This, along with the
+// Licenses can be found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use routing::{Event, EventStream};
+use routing::mock_crust::Network;
+use super::{create_connected_nodes_until_split, gen_bytes, poll_all};
+
+#[test]
+fn stream_between_sections() {
+    let min_section_size = 8;
+    let network = Network::new(min_section_size, None);
+    let mut rng = network.new_rng();
+    let mut nodes = create_connected_nodes_until_split(&network, vec![1, 1], false);
+    for node in &mut *nodes {
+        while let Ok(_) = node.try_next_ev() {}
+    }
+
+    // Find a recipient in the other section, so the chunks are relayed.
+    let src = nodes[0].name();
+    let dst_index = unwrap!(nodes.iter()
+        .position(|node| !node.routing_table().our_prefix().matches(&src)));
+    let dst = nodes[dst_index].name();
+
+    // Large enough to need several windows of chunks.
+    let content = gen_bytes(&mut rng, 250 * 1024);
+    let stream_id = unwrap!(nodes[0].inner.send_stream(dst, content.clone()));
+    let _ = poll_all(&mut nodes, &mut []);
+
+    let mut received = vec![];
+    let mut next_index = 0;
+    while let Ok(event) = nodes[dst_index].try_next_ev() {
+        if let Event::StreamChunk { src: chunk_src, stream_id: id, index, payload, .. } = event {
+            assert_eq!(chunk_src, src);
+            assert_eq!(id, stream_id);
+            assert_eq!(index, next_index);
+            next_index += 1;
+            received.extend(payload);
+        }
+    }
+    assert_eq!(received, content);
+    expect_any_event!(nodes[0], Event::StreamSent(id) if id == stream_id);
+}
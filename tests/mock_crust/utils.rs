@@ -17,9 +17,9 @@
 
 use itertools::Itertools;
 use rand::Rng;
-use routing::{Authority, Cache, Client, Data, DataIdentifier, Event, EventStream, FullId,
-              ImmutableData, Node, NullCache, Prefix, Request, Response, RoutingTable, XorName,
-              Xorable, verify_network_invariant};
+use routing::{Authority, Cache, Client, Compression, Data, DataIdentifier, Event, EventStream,
+              ExpiryTracker, FullId, ImmutableData, Node, NullCache, Prefix, Request, Response,
+              RoutingTable, XorName, Xorable, verify_network_invariant};
 use routing::mock_crust::{self, Config, Endpoint, Network, ServiceHandle};
 use std::{cmp, thread};
 use std::cell::RefCell;
@@ -118,6 +118,8 @@ impl TestNode {
             config: None,
             endpoint: None,
             cache: Box::new(NullCache),
+            compression: Compression::None,
+            expiry_tracker: ExpiryTracker::new(),
         }
     }
 
@@ -125,12 +127,16 @@ impl TestNode {
                first_node: bool,
                config: Option<Config>,
                endpoint: Option<Endpoint>,
-               cache: Box<Cache>)
+               cache: Box<Cache>,
+               compression: Compression,
+               expiry_tracker: ExpiryTracker)
                -> Self {
         let handle = network.new_service_handle(config, endpoint);
         let node = mock_crust::make_current(&handle, || {
             unwrap!(Node::builder()
                 .cache(cache)
+                .compression(compression)
+                .expiry_tracker(expiry_tracker)
                 .first(first_node)
                 .create(network.min_section_size()))
         });
@@ -164,6 +170,8 @@ pub struct TestNodeBuilder<'a> {
     config: Option<Config>,
     endpoint: Option<Endpoint>,
     cache: Box<Cache>,
+    compression: Compression,
+    expiry_tracker: ExpiryTracker,
 }
 
 impl<'a> TestNodeBuilder<'a> {
@@ -192,12 +200,24 @@ impl<'a> TestNodeBuilder<'a> {
         self
     }
 
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn expiry_tracker(mut self, expiry_tracker: ExpiryTracker) -> Self {
+        self.expiry_tracker = expiry_tracker;
+        self
+    }
+
     pub fn create(self) -> TestNode {
         TestNode::new(self.network,
                       self.first_node,
                       self.config,
                       self.endpoint,
-                      self.cache)
+                      self.cache,
+                      self.compression,
+                      self.expiry_tracker)
     }
 }
 
@@ -253,11 +273,22 @@ impl Cache for TestCache {
         }
     }
 
-    fn put(&self, response: Response) {
+    fn put(&self, response: Response) -> bool {
         if let Response::GetSuccess(data, _) = response {
             let _ = self.0.borrow_mut().insert(data.identifier(), data);
+            true
+        } else {
+            false
         }
     }
+
+    fn invalidate(&self, data_id: &DataIdentifier) {
+        let _ = self.0.borrow_mut().remove(data_id);
+    }
+
+    fn caches_structured_data(&self) -> bool {
+        true
+    }
 }
 
 
@@ -296,6 +327,27 @@ pub fn create_connected_nodes(network: &Network, size: usize) -> Nodes {
 }
 
 pub fn create_connected_nodes_with_cache(network: &Network, size: usize, use_cache: bool) -> Nodes {
+    create_connected_nodes_with(network, size, use_cache, |_| Compression::None)
+}
+
+/// Creates connected nodes, the `i`-th of which compresses the messages it sends using
+/// `compression(i)`.
+pub fn create_connected_nodes_with_compression<F>(network: &Network,
+                                                  size: usize,
+                                                  compression: F)
+                                                  -> Nodes
+    where F: Fn(usize) -> Compression
+{
+    create_connected_nodes_with(network, size, false, compression)
+}
+
+fn create_connected_nodes_with<F>(network: &Network,
+                                  size: usize,
+                                  use_cache: bool,
+                                  compression: F)
+                                  -> Nodes
+    where F: Fn(usize) -> Compression
+{
     let mut nodes = Vec::new();
 
     // Create the seed node.
@@ -303,6 +355,7 @@ pub fn create_connected_nodes_with_cache(network: &Network, size: usize, use_cac
         .first()
         .endpoint(Endpoint(0))
         .cache(use_cache)
+        .compression(compression(0))
         .create());
     nodes[0].poll();
 
@@ -314,6 +367,7 @@ pub fn create_connected_nodes_with_cache(network: &Network, size: usize, use_cac
             .config(config.clone())
             .endpoint(Endpoint(i))
             .cache(use_cache)
+            .compression(compression(i))
             .create());
         poll_and_resend(&mut nodes, &mut []);
         verify_invariant_for_all_nodes(&nodes);